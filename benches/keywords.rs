@@ -0,0 +1,35 @@
+//! Measures how long it takes to parse the keywords of the test file.
+//!
+//! Run it with `cargo bench --bench keywords`.
+
+extern crate fits_rs;
+
+use std::hint::black_box;
+use std::str::FromStr;
+use std::time::Instant;
+use fits_rs::parser::parse;
+use fits_rs::types::Keyword;
+
+const ROUNDS: u32 = 20000;
+
+fn main() {
+    let data = include_bytes!("../assets/images/k2-trappist1-unofficial-tpf-long-cadence.fits");
+    let fits = parse(data).expect("the test file parses");
+    let names: Vec<String> = fits.iter_hdus()
+        .flat_map(|hdu| hdu.header.keyword_records.iter().map(|record| format!("{:8}", record.keyword())))
+        .collect();
+
+    // Warm up, so that the first round does not pay for cold caches.
+    for name in &names {
+        black_box(Keyword::from_str(black_box(name)).ok());
+    }
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        for name in &names {
+            black_box(Keyword::from_str(black_box(name)).ok());
+        }
+    }
+    let elapsed = start.elapsed();
+    let count = ROUNDS * names.len() as u32;
+    println!("keywords: {} parsed in {:?}, {:?} per keyword", count, elapsed, elapsed / count);
+}
@@ -0,0 +1,19 @@
+//! Parse arbitrary input strictly and leniently, and decode what was parsed.
+//!
+//! Run it with the files in `assets` as the initial corpus:
+//!
+//! `cargo +nightly fuzz run parse fuzz/corpus/parse assets/images`
+#![no_main]
+
+use fits_rs::types::Fits;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Fits::parse_strict(data);
+    if let Ok(fits) = Fits::parse_lenient(data) {
+        for hdu in fits.iter_hdus() {
+            let _ = hdu.header.axes();
+            let _ = hdu.data_array().map(|array| array.to_f64());
+        }
+    }
+});
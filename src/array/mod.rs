@@ -0,0 +1,196 @@
+//! The array module converts data arrays into `ndarray` arrays. It is only
+//! available with the `ndarray` feature.
+//!
+//! FITS stores `NAXIS1` fastest, like the last axis of a row-major array. The
+//! shape of an array is therefore the axes in reverse order, i.e. the value of
+//! pixel `(x, y)` of an image is at index `[y, x]`, counted from 0.
+//!
+//! Array-valued columns of a binary table convert likewise. The first axis of
+//! the array is the row, followed by the dimensions of a field, `TDIMn`, in
+//! reverse order.
+
+use ndarray::{ArrayD, ArrayViewD, IxDyn, ShapeBuilder};
+use super::table::{BinaryTable, DataType, TableError};
+use super::types::{HDU, DataArray, DataArrayError, Pixel};
+
+impl<'a> DataArray<'a> {
+    /// Decode the values of this data array into an array shaped by `NAXISn`,
+    /// `NAXIS1` being the last axis. The type `T` should correspond with `BITPIX`.
+    pub fn to_ndarray<T: Pixel>(&self) -> Result<ArrayD<T>, DataArrayError> {
+        let shape: Vec<usize> = self.axes().iter().rev().cloned().collect();
+        let values = self.values::<T>()?;
+        ArrayD::from_shape_vec(IxDyn(&shape), values).map_err(|_| DataArrayError::IncompleteValue)
+    }
+}
+
+impl<'a> HDU<'a> {
+    /// Decode the data array of this HDU into an array shaped by `NAXISn`,
+    /// `NAXIS1` being the last axis. An HDU without a data array gives an empty array.
+    pub fn to_ndarray<T: Pixel>(&self) -> Result<ArrayD<T>, DataArrayError> {
+        match self.data_array() {
+            Some(data_array) => data_array.to_ndarray(),
+            None => Ok(ArrayD::from_shape_vec(IxDyn(&[0]), vec!()).expect("an empty shape fits no values")),
+        }
+    }
+}
+
+impl<'t> BinaryTable<'t> {
+    /// Decode the column called `name` into an array with one row per row of
+    /// the table, followed by the dimensions of a field in reverse order. The
+    /// type `T` should correspond with the type of the column, e.g. `f32` for `E`.
+    pub fn column_ndarray<T: Pixel>(&self, name: &str) -> Result<ArrayD<T>, TableError> {
+        let (column, shape) = self.column_shape(name)?;
+        let data_type = self.columns()[column].format.data_type;
+        if data_type.bitpix() != Some(T::BITPIX) {
+            return Err(TableError::TypeMismatch { column, actual: data_type })
+        }
+        let mut values = Vec::with_capacity(shape.iter().product());
+        for row in 0..self.rows() {
+            values.extend(self.field(row, column)?.chunks(T::BITPIX.bytes()).map(T::from_big_endian));
+        }
+        Ok(ArrayD::from_shape_vec(IxDyn(&shape), values).expect("the shape fits the fields"))
+    }
+
+    /// A view of the column called `name`, shaped like `column_ndarray`,
+    /// without copying. Only byte columns, i.e. `B`, can be viewed: other
+    /// values are stored big-endian.
+    pub fn column_view(&self, name: &str) -> Result<ArrayViewD<'t, u8>, TableError> {
+        let (column, shape) = self.column_shape(name)?;
+        let format = self.columns()[column].format;
+        if format.data_type != DataType::Byte {
+            return Err(TableError::TypeMismatch { column, actual: format.data_type })
+        }
+        let mut strides = vec![0usize; shape.len()];
+        let mut stride = 1;
+        for axis in (1..shape.len()).rev() {
+            strides[axis] = stride;
+            stride *= shape[axis];
+        }
+        strides[0] = self.row_width();
+        let offset = self.columns()[column].offset.min(self.bytes().len());
+        Ok(ArrayViewD::from_shape(IxDyn(&shape).strides(IxDyn(&strides)), &self.bytes()[offset..]).expect("the fields lie within the rows"))
+    }
+
+    // The index of the column called `name`, and the shape of its array.
+    fn column_shape(&self, name: &str) -> Result<(usize, Vec<usize>), TableError> {
+        let column = self.column_index(name).ok_or_else(|| TableError::MissingColumn(name.to_string()))?;
+        let format = self.columns()[column].format;
+        if format.descriptor.is_some() {
+            return Err(TableError::NotNumeric(column))
+        }
+        let mut shape = vec!(self.rows());
+        match self.columns()[column].dimensions {
+            Some(ref dimensions) => shape.extend(dimensions.iter().rev()),
+            None => shape.push(format.repeat),
+        }
+        Ok((column, shape))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use super::super::parser::parse;
+    use super::super::table::{BinaryTable, DataType, TableError};
+    use super::super::types::{Bitpix, DataArray, DataArrayError, HDU, Header, KeywordRecord, Keyword, Value};
+
+    #[test]
+    fn to_ndarray_should_put_naxis1_last() {
+        let data = [1u8, 2u8, 3u8, 4u8, 5u8, 6u8];
+        let data_array = DataArray::new(Bitpix::U8, vec!(3usize, 2usize), &data);
+
+        let array = data_array.to_ndarray::<u8>().unwrap();
+
+        assert_eq!(array.shape(), &[2usize, 3usize]);
+        assert_eq!(array[[1, 0]], 4u8);
+        assert_eq!(array[[0, 2]], 3u8);
+    }
+
+    #[test]
+    fn to_ndarray_should_refuse_a_type_that_does_not_match_bitpix() {
+        let data = [0u8; 4];
+        let data_array = DataArray::new(Bitpix::I32, vec!(1usize), &data);
+
+        assert_eq!(data_array.to_ndarray::<f32>(), Err(DataArrayError::BitpixMismatch { expected: Bitpix::F32, actual: Bitpix::I32 }));
+        assert_eq!(HDU::new(Header::new(vec!())).to_ndarray::<u8>().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn to_ndarray_should_shape_a_k2_image() {
+        let data = include_bytes!("../../assets/images/k2-trappist1-unofficial-tpf-long-cadence.fits");
+        let fits = parse(data).unwrap();
+        let hdu = &fits.extensions[1];
+
+        let array = hdu.to_ndarray::<i32>().unwrap();
+
+        let axes = hdu.data_array().unwrap().axes();
+        assert_eq!(array.shape(), &[axes[1], axes[0]]);
+    }
+
+    #[test]
+    fn column_ndarray_should_shape_a_k2_column_by_tdim() {
+        let data = include_bytes!("../../assets/images/k2-trappist1-unofficial-tpf-long-cadence.fits");
+        let fits = parse(data).unwrap();
+        let table = BinaryTable::from_hdu(fits.hdu_by_name("TARGETTABLES").unwrap()).unwrap();
+
+        let flux = table.column_ndarray::<f32>("FLUX").unwrap();
+        let raw = table.column_ndarray::<i32>("RAW_CNTS").unwrap();
+        let field = table.field(5, 3).unwrap();
+        let index = (2 * 11 + 3) * 4;
+
+        assert_eq!(flux.shape(), &[table.rows(), 11usize, 11usize]);
+        assert_eq!(raw[[5, 2, 3]], i32::from_be_bytes([field[index], field[index + 1], field[index + 2], field[index + 3]]));
+        assert_eq!(table.column_ndarray::<f64>("FLUX"), Err(TableError::TypeMismatch { column: 4, actual: DataType::Float }));
+        assert_eq!(table.column_ndarray::<f32>("SPECTRUM"), Err(TableError::MissingColumn("SPECTRUM".to_string())));
+    }
+
+    #[test]
+    fn column_view_should_not_copy_byte_columns() {
+        let data = [1u8, 2u8, 3u8, 4u8, 5u8, 6u8, 7u8, 8u8, 9u8, 10u8, 11u8, 12u8, 13u8, 14u8];
+        let header = Header::new(vec!(
+            KeywordRecord::new(Keyword::XTENSION, Value::CharacterString(Cow::Borrowed("BINTABLE")), None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(2i64), None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(7i64), None),
+            KeywordRecord::new(Keyword::NAXISn(2u16), Value::Integer(2i64), None),
+            KeywordRecord::new(Keyword::TFIELDS, Value::Integer(2i64), None),
+            KeywordRecord::new(Keyword::TTYPEn(1u16), Value::CharacterString(Cow::Borrowed("ID")), None),
+            KeywordRecord::new(Keyword::TFORMn(1u16), Value::CharacterString(Cow::Borrowed("1B")), None),
+            KeywordRecord::new(Keyword::TTYPEn(2u16), Value::CharacterString(Cow::Borrowed("STAMP")), None),
+            KeywordRecord::new(Keyword::TFORMn(2u16), Value::CharacterString(Cow::Borrowed("6B")), None),
+            KeywordRecord::new(Keyword::TDIMn(2u16), Value::CharacterString(Cow::Borrowed("(3,2)")), None),
+        ));
+        let hdu = HDU::with_data(header, &data);
+        let table = BinaryTable::from_hdu(&hdu).unwrap();
+
+        let view = table.column_view("STAMP").unwrap();
+
+        assert_eq!(view.shape(), &[2usize, 2usize, 3usize]);
+        assert_eq!(view[[0, 1, 0]], 5u8);
+        assert_eq!(view[[1, 0, 2]], 11u8);
+        assert_eq!(view.as_ptr(), data[1..].as_ptr());
+        assert_eq!(table.column_ndarray::<u8>("STAMP").unwrap(), view);
+    }
+
+    #[test]
+    fn columns_with_a_repeat_count_of_zero_should_give_empty_arrays() {
+        let data = [1u8, 2u8];
+        let header = Header::new(vec!(
+            KeywordRecord::new(Keyword::XTENSION, Value::CharacterString(Cow::Borrowed("BINTABLE")), None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(2i64), None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(1i64), None),
+            KeywordRecord::new(Keyword::NAXISn(2u16), Value::Integer(2i64), None),
+            KeywordRecord::new(Keyword::TFIELDS, Value::Integer(2i64), None),
+            KeywordRecord::new(Keyword::TTYPEn(1u16), Value::CharacterString(Cow::Borrowed("ID")), None),
+            KeywordRecord::new(Keyword::TFORMn(1u16), Value::CharacterString(Cow::Borrowed("1B")), None),
+            KeywordRecord::new(Keyword::TTYPEn(2u16), Value::CharacterString(Cow::Borrowed("EMPTY")), None),
+            KeywordRecord::new(Keyword::TFORMn(2u16), Value::CharacterString(Cow::Borrowed("0B")), None),
+        ));
+        let hdu = HDU::with_data(header, &data);
+        let table = BinaryTable::from_hdu(&hdu).unwrap();
+
+        assert_eq!(table.column_view("EMPTY").unwrap().shape(), &[2usize, 0usize]);
+        assert_eq!(table.column_ndarray::<u8>("EMPTY").unwrap().len(), 0);
+    }
+}
@@ -0,0 +1,200 @@
+//! Argument handling, error reporting and exit codes shared by the binaries.
+//!
+//! The binaries exit with one of the following codes, so that batch scripts
+//! can tell problems apart.
+//!
+//! | code | meaning                                                         |
+//! |------|-----------------------------------------------------------------|
+//! | 0    | success                                                         |
+//! | 1    | the file could not be parsed as FITS                            |
+//! | 2    | the command line arguments are wrong                            |
+//! | 3    | the file could not be read                                      |
+//! | 4    | the file parses, but violates the standard, e.g. a bad `BITPIX` |
+
+// Not every binary uses every helper.
+#![allow(dead_code)]
+
+use std::env;
+use std::fmt::Display;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::Path;
+use std::process;
+use std::ptr;
+use std::str::FromStr;
+use fits_rs::error::Error;
+use fits_rs::types::{Fits, NameMatching};
+
+/// Exit code when the file could not be parsed as FITS.
+pub const PARSE_ERROR: i32 = 1;
+/// Exit code when the command line arguments are wrong.
+pub const USAGE_ERROR: i32 = 2;
+/// Exit code when the file could not be read.
+pub const IO_ERROR: i32 = 3;
+/// Exit code when the file parses, but violates the standard.
+pub const VALIDATION_FAILURE: i32 = 4;
+
+/// The command line arguments of a binary, with the flags and options
+/// separated from the positional arguments.
+pub struct Arguments {
+    /// The arguments that are not a flag or an option, without the name of the binary.
+    pub positional: Vec<String>,
+    /// Whether `--json` is given, i.e. output should be JSON lines.
+    pub json: bool,
+    /// The options, e.g. `-k NAXIS*`, with their values in the order they are given.
+    pub options: Vec<(String, String)>,
+}
+
+impl Arguments {
+    /// Collect the arguments the binary was started with.
+    pub fn from_env() -> Arguments {
+        Arguments::from(env::args().skip(1))
+    }
+
+    /// Separate `arguments`. Every argument that starts with `-`, except for
+    /// `--json`, is an option and takes the argument after it as its value.
+    pub fn from<I: Iterator<Item = String>>(mut arguments: I) -> Arguments {
+        let mut positional = vec!();
+        let mut json = false;
+        let mut options = vec!();
+        while let Some(argument) = arguments.next() {
+            if argument == "--json" {
+                json = true;
+            } else if argument.starts_with('-') && argument.len() > 1 {
+                let value = arguments.next().unwrap_or_default();
+                options.push((argument, value));
+            } else {
+                positional.push(argument);
+            }
+        }
+        Arguments { positional, json, options }
+    }
+
+    /// The values of the option with one of `names`, e.g. `["-k", "--keyword"]`.
+    pub fn values(&self, names: &[&str]) -> Vec<&str> {
+        self.options.iter()
+            .filter(|(name, _)| names.contains(&name.as_str()))
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+
+    /// The last value of the option with one of `names`, if it is given.
+    pub fn value(&self, names: &[&str]) -> Option<&str> {
+        self.values(names).pop()
+    }
+
+    /// Refuse options that are not one of `names`.
+    pub fn only_options(&self, names: &[&str]) -> Result<(), Failure> {
+        match self.options.iter().find(|(name, _)| !names.contains(&name.as_str())) {
+            Some((name, _)) => Err(Failure::usage(format!("unknown option: {}", name))),
+            None => Ok(()),
+        }
+    }
+
+    /// The positional argument at `index`, described by `name` when it is missing.
+    pub fn get(&self, index: usize, name: &str) -> Result<&str, Failure> {
+        self.positional.get(index)
+            .map(|argument| argument.as_str())
+            .ok_or_else(|| Failure::usage(format!("missing argument: {}", name)))
+    }
+
+    /// The positional argument at `index`, parsed as a `T`.
+    pub fn parse<T: FromStr>(&self, index: usize, name: &str) -> Result<T, Failure> {
+        let argument = self.get(index, name)?;
+        T::from_str(argument)
+            .map_err(|_| Failure::usage(format!("{} should be a number, got \"{}\"", name, argument)))
+    }
+}
+
+/// The reason a binary stops, with the code it exits with.
+#[derive(Debug)]
+pub struct Failure {
+    /// The exit code, one of the constants of this module.
+    pub code: i32,
+    /// The message printed on standard error.
+    pub message: String,
+}
+
+impl Failure {
+    /// A failure with a given exit code.
+    pub fn new<M: Display>(code: i32, message: M) -> Failure {
+        Failure { code, message: message.to_string() }
+    }
+
+    /// The command line arguments are wrong.
+    pub fn usage<M: Display>(message: M) -> Failure {
+        Failure::new(USAGE_ERROR, message)
+    }
+
+    /// Reading `filename` failed.
+    pub fn io(filename: &str, error: &io::Error) -> Failure {
+        Failure::new(IO_ERROR, format!("{}: {}", filename, error))
+    }
+
+    /// Parsing `filename` failed. Problems with the structure of a header that
+    /// parses are validation failures, the other problems are parse errors.
+    pub fn fits(filename: &str, error: &Error) -> Failure {
+        let code = match *error {
+            Error::Io(_) => IO_ERROR,
+            Error::InvalidBitpix { .. } | Error::InvalidAxes { .. } | Error::NotConforming { .. } | Error::Value(_) => VALIDATION_FAILURE,
+            _ => PARSE_ERROR,
+        };
+        Failure::new(code, format!("{}: {}", filename, error))
+    }
+}
+
+/// Split a reference to an HDU, e.g. `file.fits[EXTNAME]` or `file.fits[2]`,
+/// into the name of the file and the HDU. An argument that names an existing
+/// file is taken as it is, brackets and all.
+pub fn hdu_reference(argument: &str) -> (&str, Option<&str>) {
+    match argument.rfind('[') {
+        Some(open) if argument.ends_with(']') && open > 0 && !Path::new(argument).exists() =>
+            (&argument[..open], Some(&argument[(open + 1)..(argument.len() - 1)])),
+        _ => (argument, None),
+    }
+}
+
+/// Split the name of an HDU into the name and how to match it: a name that
+/// starts with `~`, e.g. `~events`, matches regardless of case.
+pub fn hdu_name(selector: &str) -> (&str, NameMatching) {
+    match selector.strip_prefix('~') {
+        Some(name) => (name, NameMatching::CaseInsensitive),
+        None => (selector, NameMatching::CaseSensitive),
+    }
+}
+
+/// The index of the HDU of `fits` that `selector` selects: an index, or an
+/// `EXTNAME` as `hdu_name` splits it. A selector without an HDU is a usage
+/// failure.
+pub fn select_hdu(filename: &str, fits: &Fits, selector: &str) -> Result<usize, Failure> {
+    let count = fits.iter_hdus().count();
+    let index = match selector.parse::<usize>() {
+        Ok(index) if index < count => Some(index),
+        Ok(_) => None,
+        Err(_) => {
+            let (name, matching) = hdu_name(selector);
+            fits.hdu_by_name_matching(name, matching)
+                .and_then(|found| fits.iter_hdus().position(|candidate| ptr::eq(candidate, found)))
+        },
+    };
+    index.ok_or_else(|| Failure::usage(format!("{}: there is no header {}, the file has {} headers", filename, selector, count)))
+}
+
+/// Read the complete contents of `filename`.
+pub fn read_file(filename: &str) -> Result<Vec<u8>, Failure> {
+    let mut buffer: Vec<u8> = vec!();
+    File::open(filename)
+        .and_then(|mut f| f.read_to_end(&mut buffer))
+        .map_err(|error| Failure::io(filename, &error))?;
+    Ok(buffer)
+}
+
+/// Run a binary, printing a failure on standard error and exiting with its code.
+pub fn run<F>(execute: F) where F: FnOnce(&Arguments) -> Result<(), Failure> {
+    let args = Arguments::from_env();
+    if let Err(failure) = execute(&args) {
+        eprintln!("{}", failure.message);
+        process::exit(failure.code);
+    }
+}
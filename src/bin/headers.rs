@@ -1,37 +1,269 @@
-extern crate nom;
 extern crate fits_rs;
 
-use std::env;
-use std::fs::File;
-use std::io::Read;
+mod common;
+
+use std::cmp::Ordering;
+use std::io;
+use std::io::IsTerminal;
+use std::ptr;
 use std::str::FromStr;
-use nom::IResult;
-use fits_rs::parser::fits;
-use fits_rs::types::Header;
+use fits_rs::json;
+use fits_rs::parser::parse;
+use fits_rs::pretty::PrettyOptions;
+use fits_rs::types::{Fits, Header, KeywordRecord, Keyword, Value};
+use common::{Arguments, Failure};
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    let filename = &args[1];
-    let header_index = u64::from_str(&args[2]).expect("second argument should be a non-negative number");
+const KEYWORD: [&str; 2] = ["-k", "--keyword"];
+const FORMAT: [&str; 2] = ["-f", "--format"];
+const WHERE: [&str; 2] = ["-w", "--where"];
+
+/// The ways the selected records can be printed.
+enum Format {
+    Pretty,
+    Cards,
+    Csv,
+    Json,
+}
+
+/// How a condition tests the value of a keyword.
+#[derive(Clone, Copy)]
+enum Test {
+    Present,
+    Absent,
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
 
-    let mut f = File::open(filename).expect("file not found");
-    let mut buffer: Vec<u8> = vec!();
-    let _ = f.read_to_end(&mut buffer);
+/// A condition of `--where`, e.g. `NAXIS>=2`.
+struct Condition<'t> {
+    keyword: Keyword,
+    test: Test,
+    expected: &'t str,
+}
+
+fn main() {
+    common::run(headers);
+}
 
-    let result = fits(&buffer);
+/// Print the headers of a file.
+///
+/// `headers FILE[HDU] [HDU...] [-k PATTERN...] [-f pretty|cards|csv|json]`
+///
+/// An HDU is selected by its index or its `EXTNAME`, after the file or as a
+/// reference like `file.fits[EXTNAME]`; without one, every HDU is printed. A
+/// name is matched case sensitively, unless it starts with `~`, e.g. `~events`.
+/// A pattern selects the keywords it matches, where `*` matches any text and
+/// `?` a single character, e.g. `-k 'NAXIS*'`.
+///
+/// `headers FILE... -w CONDITION...`
+///
+/// With conditions, the HDUs of the files whose headers meet all of them are
+/// listed instead, as references like `file.fits[EXTNAME]` that the binaries
+/// accept, or `file.fits[2]` for an HDU without a name of its own. A
+/// condition compares the value of a keyword with `=`, `!=`, `<`, `<=`, `>`
+/// or `>=`, e.g. `NAXIS>=2`, numbers by their value and other values as text.
+/// A bare `KEYWORD` asks for the keyword to be present, `!KEYWORD` for it to
+/// be missing. A file that can not be read or parsed is reported on standard
+/// error and skipped.
+fn headers(args: &Arguments) -> Result<(), Failure> {
+    args.only_options(&[KEYWORD[0], KEYWORD[1], FORMAT[0], FORMAT[1], WHERE[0], WHERE[1]])?;
+    if !args.values(&WHERE).is_empty() {
+        return find(args)
+    }
+    let (filename, reference) = common::hdu_reference(args.get(0, "filename")?);
+    let hdus: Vec<String> = reference.map(str::to_string).into_iter().chain(args.positional[1..].iter().cloned()).collect();
+    let format = format(args)?;
+    let patterns: Vec<String> = args.values(&KEYWORD).iter().map(|pattern| pattern.to_uppercase()).collect();
 
-    match result {
-        IResult::Done(_, trappist1) => {
-            let header: &Header = if header_index == 0 {
-                &trappist1.primary_hdu.header
-            } else {
-                &trappist1.extensions[0].header
-            };
+    let buffer = common::read_file(filename)?;
+    let fits = parse(&buffer).map_err(|error| Failure::fits(filename, &error))?;
+    let selected = select(filename, &fits, &hdus)?;
 
-            for ref record in &header.keyword_records {
+    if let Format::Csv = format {
+        println!("hdu,keyword,value,comment");
+    }
+    for &(index, header) in &selected {
+        let records: Vec<&KeywordRecord> = header.keyword_records.iter()
+            .filter(|record| patterns.is_empty() || patterns.iter().any(|pattern| matches(pattern, &record.keyword().to_string())))
+            .collect();
+        match format {
+            Format::Pretty | Format::Cards if selected.len() > 1 => println!("# HDU {}", index),
+            _ => (),
+        }
+        match format {
+            Format::Pretty => {
+                let filtered = Header::new(records.into_iter().cloned().collect());
+                let options = PrettyOptions { color: io::stdout().is_terminal(), ..PrettyOptions::default() };
+                print!("{}", filtered.pretty(&options));
+            },
+            Format::Cards => for record in records {
                 println!("{}", record);
-            }
-        },
-        _ => panic!("Whoops, something went wrong")
+            },
+            Format::Csv => for record in records {
+                println!("{},{},{},{}", index, csv(&record.keyword().to_string()), csv(&text(record.value())), csv(record.comment().unwrap_or("")));
+            },
+            Format::Json => for record in records {
+                println!("{}", json::record(index, record));
+            },
+        }
+    }
+    Ok(())
+}
+
+// Print references to the HDUs whose headers meet the conditions. Files that
+// fail are reported and skipped; the first failure decides the exit code.
+fn find(args: &Arguments) -> Result<(), Failure> {
+    args.get(0, "filename")?;
+    let conditions = args.values(&WHERE).into_iter()
+        .map(condition)
+        .collect::<Result<Vec<Condition>, Failure>>()?;
+
+    let mut failures = vec!();
+    for filename in &args.positional {
+        if let Err(failure) = find_in(filename, &conditions) {
+            eprintln!("{}", failure.message);
+            failures.push(failure);
+        }
+    }
+    match failures.first() {
+        Some(failure) => Err(Failure::new(failure.code, format!("{} of {} files were skipped", failures.len(), args.positional.len()))),
+        None => Ok(()),
+    }
+}
+
+// Print references to the HDUs of a file whose headers meet the conditions.
+fn find_in(filename: &str, conditions: &[Condition]) -> Result<(), Failure> {
+    let buffer = common::read_file(filename)?;
+    let fits = parse(&buffer).map_err(|error| Failure::fits(filename, &error))?;
+    for (index, hdu) in fits.iter_hdus().enumerate() {
+        if !conditions.iter().all(|condition| meets(&hdu.header, condition)) {
+            continue
+        }
+        match hdu.name() {
+            Some(name) if fits.hdu_by_name(name).is_some_and(|found| ptr::eq(found, hdu)) => println!("{}[{}]", filename, name),
+            _ => println!("{}[{}]", filename, index),
+        }
+    }
+    Ok(())
+}
+
+// Parse a condition like `TELESCOP=Kepler`, `NAXIS>=2`, `EXTNAME` or `!BLANK`.
+fn condition(text: &str) -> Result<Condition<'_>, Failure> {
+    let usage = || Failure::usage(format!("condition \"{}\" should look like KEYWORD=VALUE, KEYWORD<VALUE, KEYWORD or !KEYWORD", text));
+    let keyword = |name: &str| match name.trim() {
+        "" => Err(usage()),
+        name => Keyword::from_str(&name.to_uppercase()).map_err(|_| usage()),
+    };
+    if let Some(name) = text.strip_prefix('!') {
+        return Ok(Condition { keyword: keyword(name)?, test: Test::Absent, expected: "" })
+    }
+    let start = match text.find(['=', '!', '<', '>']) {
+        Some(start) => start,
+        None => return Ok(Condition { keyword: keyword(text)?, test: Test::Present, expected: "" }),
+    };
+    let (name, rest) = text.split_at(start);
+    let operators = [
+        ("!=", Test::NotEqual), ("<=", Test::LessOrEqual), (">=", Test::GreaterOrEqual),
+        ("=", Test::Equal), ("<", Test::Less), (">", Test::Greater),
+    ];
+    let &(operator, test) = operators.iter()
+        .find(|(operator, _)| rest.starts_with(operator))
+        .ok_or_else(usage)?;
+    Ok(Condition { keyword: keyword(name)?, test, expected: rest[operator.len()..].trim() })
+}
+
+// Whether a header meets a condition. A missing keyword only meets `!KEYWORD`.
+fn meets(header: &Header, condition: &Condition) -> bool {
+    let value = header.get(&condition.keyword);
+    let ordering = || value.map(|value| compare(value, condition.expected));
+    match condition.test {
+        Test::Present => value.is_some(),
+        Test::Absent => value.is_none(),
+        Test::Equal => ordering() == Some(Ordering::Equal),
+        Test::NotEqual => ordering().is_some_and(|ordering| ordering != Ordering::Equal),
+        Test::Less => ordering() == Some(Ordering::Less),
+        Test::LessOrEqual => ordering().is_some_and(|ordering| ordering != Ordering::Greater),
+        Test::Greater => ordering() == Some(Ordering::Greater),
+        Test::GreaterOrEqual => ordering().is_some_and(|ordering| ordering != Ordering::Less),
+    }
+}
+
+// Compare a value with the expected text, numbers by their value and other values as text.
+fn compare(value: &Value, expected: &str) -> Ordering {
+    let actual = text(value);
+    match (actual.parse::<f64>(), expected.parse::<f64>()) {
+        (Ok(actual), Ok(expected)) => actual.total_cmp(&expected),
+        _ => actual.as_str().cmp(expected),
+    }
+}
+
+fn format(args: &Arguments) -> Result<Format, Failure> {
+    if args.json {
+        return Ok(Format::Json)
+    }
+    match args.value(&FORMAT) {
+        None | Some("pretty") => Ok(Format::Pretty),
+        Some("cards") => Ok(Format::Cards),
+        Some("csv") => Ok(Format::Csv),
+        Some("json") => Ok(Format::Json),
+        Some(other) => Err(Failure::usage(format!("unknown format \"{}\", expected pretty, cards, csv or json", other))),
+    }
+}
+
+// The headers with their index that `hdus` select, or every header without a selection.
+fn select<'f, 'a>(filename: &str, fits: &'f Fits<'a>, hdus: &[String]) -> Result<Vec<(usize, &'f Header<'a>)>, Failure> {
+    if hdus.is_empty() {
+        return Ok(fits.iter_hdus().map(|hdu| &hdu.header).enumerate().collect())
+    }
+    hdus.iter()
+        .map(|hdu| {
+            let index = common::select_hdu(filename, fits, hdu)?;
+            Ok((index, &fits.hdu(index).expect("the index exists").header))
+        })
+        .collect()
+}
+
+// Whether `name` matches `pattern`, in which `*` matches any text and `?` a single character.
+fn matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            // Let the last `*` match one more character and try again.
+            star = Some((star_p, star_n + 1));
+            p = star_p + 1;
+            n = star_n + 1;
+        } else {
+            return false
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+// The text of a value as it appears in CSV, i.e. strings without quotes and trailing spaces.
+fn text(value: &Value) -> String {
+    match value.as_trimmed_str() {
+        Some(s) => s.to_string(),
+        None => value.to_string(),
+    }
+}
+
+fn csv(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
     }
 }
@@ -1,21 +1,50 @@
-use std::env;
-use std::fs::File;
-use std::io::Read;
+extern crate fits_rs;
+
+mod common;
+
 use std::str;
-use std::str::FromStr;
+use fits_rs::json;
+use fits_rs::parser::parse_with_info;
+use common::{Arguments, Failure};
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    let filename = &args[1];
-    let low = usize::from_str(&args[2]).expect("second argument should be a number");
-    let high = usize::from_str(&args[3]).expect("thirs argument should be a number");
+    common::run(inspect);
+}
 
-    let mut f = File::open(filename).expect("file not found");
-    let mut buffer: Vec<u8> = vec!();
-    let _ = f.read_to_end(&mut buffer);
+/// Print the bytes of a file from one offset up to another as text.
+///
+/// `inspect FILE[HDU] LOW HIGH`
+///
+/// With a reference like `file.fits[EXTNAME]` or `file.fits[2]`, as `headers`
+/// prints them, the offsets count from the start of the header of that HDU,
+/// and the range should lie within the HDU.
+fn inspect(args: &Arguments) -> Result<(), Failure> {
+    let (filename, reference) = common::hdu_reference(args.get(0, "filename")?);
+    let low: usize = args.parse(1, "low offset")?;
+    let high: usize = args.parse(2, "high offset")?;
 
-    let result: &[u8]= &buffer;
+    let buffer = common::read_file(filename)?;
+    let (bytes, extent) = match reference {
+        Some(hdu) => {
+            let (fits, infos) = parse_with_info(&buffer).map_err(|error| Failure::fits(filename, &error))?;
+            let index = common::select_hdu(filename, &fits, hdu)?;
+            let info = &infos[index];
+            (&buffer[info.header_range.start..info.data_range.end], format!("HDU {}", index))
+        },
+        None => (&buffer[..], "the file".to_string()),
+    };
+    if low > high || high > bytes.len() {
+        return Err(Failure::usage(format!(
+            "{}: range {}..{} does not lie within the {} bytes of {}",
+            filename, low, high, bytes.len(), extent)))
+    }
+    let text = str::from_utf8(&bytes[low..high])
+        .map_err(|error| Failure::new(common::PARSE_ERROR, format!("{}: {}", filename, error)))?;
 
-    println!("{:?}", str::from_utf8(&result[low..high]).expect("should be utf8"));
+    if args.json {
+        println!("{{\"offset\":{},\"length\":{},\"text\":{}}}", low, high - low, json::string(text));
+    } else {
+        println!("{:?}", text);
+    }
+    Ok(())
 }
-
@@ -1,28 +1,42 @@
-extern crate nom;
 extern crate fits_rs;
 
-use std::env;
-use std::fs::File;
-use std::io::Read;
-use nom::IResult;
-use fits_rs::parser::fits;
+mod common;
+
+use std::io;
+use std::io::IsTerminal;
+use fits_rs::json;
+use fits_rs::parser::parse;
+use fits_rs::pretty::PrettyOptions;
+use common::{Arguments, Failure};
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    let filename = &args[1];
+    common::run(primary_header);
+}
 
-    let mut f = File::open(filename).expect("file not found");
-    let mut buffer: Vec<u8> = vec!();
-    let _ = f.read_to_end(&mut buffer);
+/// Print the primary header of a file.
+///
+/// `primary_header FILE[HDU]`
+///
+/// A reference like `file.fits[EXTNAME]` or `file.fits[2]`, as `headers`
+/// prints them, prints the header of that HDU instead.
+fn primary_header(args: &Arguments) -> Result<(), Failure> {
+    let (filename, reference) = common::hdu_reference(args.get(0, "filename")?);
 
-    let result = fits(&buffer);
+    let buffer = common::read_file(filename)?;
+    let trappist1 = parse(&buffer).map_err(|error| Failure::fits(filename, &error))?;
+    let index = match reference {
+        Some(hdu) => common::select_hdu(filename, &trappist1, hdu)?,
+        None => 0,
+    };
 
-    match result {
-        IResult::Done(_, trappist1) => {
-            for record in trappist1.primary_hdu.header.keyword_records {
-                println!("{}", record);
-            }
-        },
-        _ => panic!("Whoops, something went wrong")
+    let header = &trappist1.hdu(index).expect("the index exists").header;
+    if args.json {
+        for record in &header.keyword_records {
+            println!("{}", json::record(index, record));
+        }
+    } else {
+        let options = PrettyOptions { color: io::stdout().is_terminal(), ..PrettyOptions::default() };
+        print!("{}", header.pretty(&options));
     }
+    Ok(())
 }
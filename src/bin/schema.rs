@@ -0,0 +1,59 @@
+extern crate fits_rs;
+
+mod common;
+
+use fits_rs::json;
+use fits_rs::parser::parse;
+use fits_rs::schema::HeaderSchema;
+use fits_rs::types::Header;
+use common::{Arguments, Failure};
+
+const THRESHOLD: [&str; 2] = ["-t", "--threshold"];
+const HDU: [&str; 1] = ["--hdu"];
+
+fn main() {
+    common::run(schema);
+}
+
+/// Print a draft schema of the headers of many files.
+///
+/// `schema FILE[HDU]... [-t PERCENT] [--hdu INDEX|EXTNAME] [--json]`
+///
+/// The schema lists the keywords that occur in at least `PERCENT` of the
+/// files, 50 by default, with the type and range of their values. The primary
+/// header is scanned, unless an HDU is selected; files without it are skipped.
+/// A reference like `file.fits[EXTNAME]`, as `headers` prints them, selects
+/// the HDU of that file. A name that starts with `~`, e.g. `~events`, matches
+/// regardless of case. Files are read one at a time, only their selected
+/// header is kept.
+fn schema(args: &Arguments) -> Result<(), Failure> {
+    args.only_options(&[THRESHOLD[0], THRESHOLD[1], HDU[0]])?;
+    args.get(0, "filename")?;
+    let threshold = match args.value(&THRESHOLD) {
+        None => 50f64,
+        Some(percent) => percent.parse::<f64>()
+            .map_err(|_| Failure::usage(format!("threshold \"{}\" is not a percentage", percent)))?,
+    };
+    let hdu = args.value(&HDU).unwrap_or("0");
+
+    let mut headers: Vec<Header<'static>> = vec!();
+    for argument in &args.positional {
+        let (filename, reference) = common::hdu_reference(argument);
+        let buffer = common::read_file(filename)?;
+        let fits = parse(&buffer).map_err(|error| Failure::fits(filename, &error))?;
+        let selected = common::select_hdu(filename, &fits, reference.unwrap_or(hdu)).ok().and_then(|index| fits.hdu(index));
+        if let Some(selected) = selected {
+            headers.push(selected.header.clone().into_owned());
+        }
+    }
+
+    let schema = HeaderSchema::infer(&headers, threshold / 100f64);
+    if args.json {
+        for keyword in &schema.keywords {
+            println!("{}", json::keyword_schema(keyword));
+        }
+    } else {
+        print!("{}", schema);
+    }
+    Ok(())
+}
@@ -0,0 +1,44 @@
+extern crate fits_rs;
+
+mod common;
+
+use fits_rs::json;
+use fits_rs::parser::parse;
+use fits_rs::validator::{verify_strict, Severity};
+use common::{Arguments, Failure};
+
+fn main() {
+    common::run(verify);
+}
+
+/// Verify a file against the standard.
+///
+/// `verify FILE[HDU]`
+///
+/// A reference like `file.fits[EXTNAME]` or `file.fits[2]`, as `headers`
+/// prints them, only reports the findings about that HDU.
+fn verify(args: &Arguments) -> Result<(), Failure> {
+    let (filename, reference) = common::hdu_reference(args.get(0, "filename")?);
+
+    let buffer = common::read_file(filename)?;
+    let mut report = verify_strict(&buffer).map_err(|error| Failure::fits(filename, &error))?;
+    if let Some(hdu) = reference {
+        let fits = parse(&buffer).map_err(|error| Failure::fits(filename, &error))?;
+        let index = common::select_hdu(filename, &fits, hdu)?;
+        report.hdus.retain(|report| report.index == index);
+    }
+
+    for (hdu, finding) in report.findings() {
+        if args.json {
+            println!("{}", json::finding(hdu, finding));
+        } else {
+            println!("{}: HDU {}: {}", filename, hdu, finding);
+        }
+    }
+    if report.passes(Severity::Error) {
+        Ok(())
+    } else {
+        Err(Failure::new(common::VALIDATION_FAILURE, format!(
+            "{}: {} errors, {} warnings", filename, report.count(Severity::Error), report.count(Severity::Warning))))
+    }
+}
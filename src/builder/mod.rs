@@ -0,0 +1,182 @@
+//! The builder module constructs headers for writing.
+//!
+//! A `HeaderBuilder` places the mandatory keywords itself, in the order the
+//! standard prescribes, and ends the header with `END`. Other records follow
+//! the mandatory ones in the order they are added, and are checked to fit in
+//! fixed-format cards.
+//!
+//! `Fits::minimal` starts a file with an empty primary HDU, to which image or
+//! table extensions can be added.
+
+use std::borrow::Cow;
+use super::types::{Bitpix, Fits, HDU, Header, KeywordRecord, Keyword, Value};
+use super::writer::{cards, WriteError};
+
+/// Builds a `Header`, starting with the mandatory keywords of a primary HDU or
+/// of an extension.
+#[derive(Debug, Clone)]
+pub struct HeaderBuilder<'a> {
+    mandatory: Vec<KeywordRecord<'a>>,
+    records: Vec<KeywordRecord<'a>>,
+}
+
+impl<'a> HeaderBuilder<'a> {
+    /// Start the header of a primary HDU, i.e. with `SIMPLE`, `BITPIX`, `NAXIS`
+    /// and `NAXISn`.
+    pub fn primary(bitpix: Bitpix, axes: &[usize]) -> HeaderBuilder<'a> {
+        let mut mandatory = vec!(KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::Some("conforms to FITS standard")));
+        mandatory.append(&mut array_records(bitpix, axes));
+        HeaderBuilder { mandatory, records: vec!() }
+    }
+
+    /// Start the header of an extension of type `xtension`, e.g. `IMAGE`, i.e.
+    /// with `XTENSION`, `BITPIX`, `NAXIS`, `NAXISn`, `PCOUNT` and `GCOUNT`.
+    pub fn extension(xtension: &'a str, bitpix: Bitpix, axes: &[usize], pcount: usize, gcount: usize) -> HeaderBuilder<'a> {
+        let mut mandatory = vec!(KeywordRecord::new(Keyword::XTENSION, Value::CharacterString(Cow::Borrowed(xtension)), Option::Some("extension type")));
+        mandatory.append(&mut array_records(bitpix, axes));
+        mandatory.push(KeywordRecord::new(Keyword::PCOUNT, Value::Integer(pcount as i64), Option::Some("number of parameters")));
+        mandatory.push(KeywordRecord::new(Keyword::GCOUNT, Value::Integer(gcount as i64), Option::Some("number of groups")));
+        HeaderBuilder { mandatory, records: vec!() }
+    }
+
+    /// Add a record after the mandatory ones.
+    pub fn record(self, keyword: Keyword, value: Value<'a>, comment: Option<&'a str>) -> HeaderBuilder<'a> {
+        self.keyword_record(KeywordRecord::new(keyword, value, comment))
+    }
+
+    /// Add a record that is already constructed after the mandatory ones.
+    pub fn keyword_record(mut self, record: KeywordRecord<'a>) -> HeaderBuilder<'a> {
+        self.records.push(record);
+        self
+    }
+
+    /// Add a `COMMENT` record. Long text is wrapped over several cards.
+    pub fn comment(self, text: &'a str) -> HeaderBuilder<'a> {
+        self.record(Keyword::COMMENT, Value::CommentText(Cow::Borrowed(text)), Option::None)
+    }
+
+    /// Add a `HISTORY` record. Long text is wrapped over several cards.
+    pub fn history(self, text: &'a str) -> HeaderBuilder<'a> {
+        self.record(Keyword::HISTORY, Value::CommentText(Cow::Borrowed(text)), Option::None)
+    }
+
+    /// Build the header, ending it with `END`.
+    ///
+    /// Fails when a record repeats a mandatory keyword, when the name of a
+    /// keyword is longer than 8 characters, or when a value does not fit in a card.
+    pub fn build(self) -> Result<Header<'a>, WriteError> {
+        for record in &self.records {
+            if self.is_mandatory(record.keyword()) {
+                return Err(WriteError::MisplacedKeyword(record.keyword().clone()))
+            }
+            cards(record)?;
+        }
+        let mut keyword_records = self.mandatory;
+        keyword_records.extend(self.records);
+        keyword_records.push(KeywordRecord::new(Keyword::END, Value::Undefined, Option::None));
+        Ok(Header::new(keyword_records))
+    }
+
+    fn is_mandatory(&self, keyword: &Keyword) -> bool {
+        *keyword == Keyword::END ||
+            matches!(*keyword, Keyword::NAXISn(_)) ||
+            self.mandatory.iter().any(|record| record.keyword() == keyword)
+    }
+}
+
+impl<'a> HDU<'a> {
+    /// A primary HDU without data, i.e. with only `SIMPLE`, `BITPIX = 8`,
+    /// `NAXIS = 0` and `END`.
+    pub fn empty_primary() -> HDU<'a> {
+        HDU::new(HeaderBuilder::primary(Bitpix::U8, &[]).build().expect("mandatory records fit in cards"))
+    }
+}
+
+impl<'a> Fits<'a> {
+    /// A file with an empty primary HDU and no extensions yet.
+    pub fn minimal() -> Fits<'a> {
+        Fits::new(HDU::empty_primary(), vec!())
+    }
+}
+
+fn array_records<'a>(bitpix: Bitpix, axes: &[usize]) -> Vec<KeywordRecord<'a>> {
+    let mut records = vec!(
+        KeywordRecord::new(Keyword::BITPIX, Value::Integer(bitpix.value()), Option::Some("array data type")),
+        KeywordRecord::new(Keyword::NAXIS, Value::Integer(axes.len() as i64), Option::Some("number of array dimensions")),
+    );
+    for (index, axis) in axes.iter().enumerate() {
+        records.push(KeywordRecord::new(Keyword::NAXISn((index + 1) as u16), Value::Integer(*axis as i64), Option::None));
+    }
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use super::super::parser::parse;
+    use super::super::types::{Bitpix, Fits, HDU, KeywordRecord, Keyword, Value};
+    use super::super::validator::{verify_strict, Severity};
+    use super::super::writer::{write_fits, WriteError};
+    use super::HeaderBuilder;
+
+    #[test]
+    fn primary_header_should_start_with_the_mandatory_keywords() {
+        let header = HeaderBuilder::primary(Bitpix::I16, &[3, 2])
+            .record(Keyword::OBJECT, Value::CharacterString(Cow::Borrowed("TRAPPIST-1")), Option::None)
+            .history("created")
+            .build()
+            .unwrap();
+
+        let keywords: Vec<Keyword> = header.keyword_records.iter().map(|record| record.keyword().clone()).collect();
+        assert_eq!(keywords, vec!(
+            Keyword::SIMPLE, Keyword::BITPIX, Keyword::NAXIS, Keyword::NAXISn(1u16), Keyword::NAXISn(2u16),
+            Keyword::OBJECT, Keyword::HISTORY, Keyword::END,
+        ));
+        assert_eq!(header.data_array_size(), 2880 * 8);
+    }
+
+    #[test]
+    fn extension_header_should_include_pcount_and_gcount() {
+        let header = HeaderBuilder::extension("IMAGE", Bitpix::F32, &[10], 0, 1).build().unwrap();
+
+        assert_eq!(header.keyword_records[0], KeywordRecord::new(Keyword::XTENSION, Value::CharacterString(Cow::Borrowed("IMAGE")), Option::Some("extension type")));
+        assert_eq!(header.get_int(&Keyword::PCOUNT), Ok(0i64));
+        assert_eq!(header.get_int(&Keyword::GCOUNT), Ok(1i64));
+    }
+
+    #[test]
+    fn build_should_refuse_records_that_can_not_be_written() {
+        let mandatory = HeaderBuilder::primary(Bitpix::U8, &[])
+            .record(Keyword::NAXIS, Value::Integer(1i64), Option::None)
+            .build();
+        let long_name = HeaderBuilder::primary(Bitpix::U8, &[])
+            .record(Keyword::Custom("LONGKEYWORD".to_string()), Value::Integer(1i64), Option::None)
+            .build();
+
+        match mandatory {
+            Err(WriteError::MisplacedKeyword(keyword)) => assert_eq!(keyword, Keyword::NAXIS),
+            _ => panic!("Expected a misplaced keyword error"),
+        }
+        match long_name {
+            Err(WriteError::KeywordTooLong(name)) => assert_eq!(name, "LONGKEYWORD"),
+            _ => panic!("Expected a keyword too long error"),
+        }
+    }
+
+    #[test]
+    fn minimal_fits_should_be_a_valid_empty_primary() {
+        let mut fits = Fits::minimal();
+        let extension = HeaderBuilder::extension("IMAGE", Bitpix::U8, &[], 0, 1).build().unwrap();
+        fits.extensions.push(HDU::new(extension));
+        let mut buffer: Vec<u8> = vec!();
+
+        write_fits(&mut buffer, &fits).unwrap();
+
+        let keywords: Vec<&Keyword> = fits.primary_hdu.header.keyword_records.iter().map(|record| record.keyword()).collect();
+        assert_eq!(keywords, vec!(&Keyword::SIMPLE, &Keyword::BITPIX, &Keyword::NAXIS, &Keyword::END));
+        assert_eq!(fits.primary_hdu.data_array(), None);
+        assert_eq!(buffer.len(), 2 * 2880);
+        assert_eq!(parse(&buffer).unwrap().extensions.len(), 1);
+        assert!(verify_strict(&buffer).unwrap().passes(Severity::Warning));
+    }
+}
@@ -0,0 +1,199 @@
+//! The ccd module provides basic CCD reductions driven by section keywords.
+//!
+//! `TRIMSEC` or `DATASEC` describe the part of an image that holds data, and
+//! `BIASSEC` the overscan region that measures the bias level of every row.
+
+use super::section::{header_section, Interval, Section, ParseSectionError};
+use super::types::{HDU, Keyword, DataArrayError};
+
+/// A two dimensional image of `f64` values.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Image {
+    /// The number of pixels along the first axis, i.e. `NAXIS1`.
+    pub width: usize,
+    /// The number of pixels along the second axis, i.e. `NAXIS2`.
+    pub height: usize,
+    /// The pixel values, `NAXIS1` varying fastest.
+    pub values: Vec<f64>,
+}
+
+impl Image {
+    /// Create an `Image` from its dimensions and values.
+    pub fn new(width: usize, height: usize, values: Vec<f64>) -> Image {
+        Image { width, height, values }
+    }
+
+    /// The value at pixel `(x, y)`, counted from 1 like section coordinates.
+    pub fn value(&self, x: usize, y: usize) -> f64 {
+        self.values[(y - 1) * self.width + x - 1]
+    }
+
+    /// Whether `section` lies within this image.
+    pub fn contains(&self, section: &Section) -> bool {
+        section.x.upper() <= self.width && section.y.upper() <= self.height
+    }
+
+    /// Cut `section` out of this image, flipping reversed intervals. Returns
+    /// `None` when the section does not lie within this image.
+    pub fn cut(&self, section: &Section) -> Option<Image> {
+        if !self.contains(section) {
+            return None
+        }
+        let mut values = Vec::with_capacity(section.width() * section.height());
+        for j in 0..section.height() {
+            for i in 0..section.width() {
+                values.push(self.value(section.x.at(i), section.y.at(j)));
+            }
+        }
+        Some(Image::new(section.width(), section.height(), values))
+    }
+}
+
+/// Problems that could occur when reducing an image.
+#[derive(Debug, PartialEq)]
+pub enum CcdError {
+    /// The HDU does not hold a two dimensional data array.
+    NotAnImage,
+    /// A section keyword that is needed is not present.
+    MissingSection(Keyword),
+    /// A section keyword could not be parsed.
+    InvalidSection {
+        /// The keyword holding the section.
+        keyword: Keyword,
+        /// What is wrong with the section.
+        error: ParseSectionError,
+    },
+    /// The section of a keyword lies outside the image.
+    SectionOutOfBounds(Keyword),
+    /// The data array could not be decoded.
+    Data(DataArrayError),
+}
+
+/// The data array of an HDU as an image.
+pub fn image(hdu: &HDU) -> Result<Image, CcdError> {
+    let data_array = match hdu.data_array() {
+        Some(data_array) if data_array.axes().len() == 2 => data_array,
+        _ => return Err(CcdError::NotAnImage),
+    };
+    let values = data_array.to_f64().map_err(CcdError::Data)?;
+    Ok(Image::new(data_array.axes()[0], data_array.axes()[1], values))
+}
+
+// The section of the image that holds data, taken from `TRIMSEC`, or from
+// `DATASEC` when `TRIMSEC` is not present. Without either, this is the whole image.
+fn trim_section(hdu: &HDU, image: &Image) -> Result<(Keyword, Section), CcdError> {
+    for keyword in [Keyword::TRIMSEC, Keyword::DATASEC] {
+        if let Some(section) = section(hdu, &keyword)? {
+            return Ok((keyword, section))
+        }
+    }
+    Ok((Keyword::NAXIS, Section::new(Interval::new(1, image.width), Interval::new(1, image.height))))
+}
+
+/// Trim an image to the section that holds data, i.e. `TRIMSEC`, or `DATASEC`
+/// when `TRIMSEC` is not present. Without either, the whole image is kept.
+pub fn trim(hdu: &HDU) -> Result<Image, CcdError> {
+    let image = image(hdu)?;
+    let (keyword, section) = trim_section(hdu, &image)?;
+    image.cut(&section).ok_or(CcdError::SectionOutOfBounds(keyword))
+}
+
+/// The bias level of every row of an image, i.e. the mean of the row within
+/// `BIASSEC`. Rows not covered by `BIASSEC` get the mean of the whole section.
+pub fn overscan_levels(hdu: &HDU) -> Result<Vec<f64>, CcdError> {
+    let image = image(hdu)?;
+    overscan_levels_of(hdu, &image)
+}
+
+fn overscan_levels_of(hdu: &HDU, image: &Image) -> Result<Vec<f64>, CcdError> {
+    let bias = section(hdu, &Keyword::BIASSEC)?
+        .ok_or(CcdError::MissingSection(Keyword::BIASSEC))?;
+    if !image.contains(&bias) {
+        return Err(CcdError::SectionOutOfBounds(Keyword::BIASSEC))
+    }
+    let columns = bias.x.lower()..(bias.x.upper() + 1);
+    let row_mean = |y: usize| columns.clone().map(|x| image.value(x, y)).sum::<f64>() / bias.width() as f64;
+    let rows = bias.y.lower()..(bias.y.upper() + 1);
+    let overall = rows.clone().map(row_mean).sum::<f64>() / bias.height() as f64;
+    Ok((1..(image.height + 1))
+        .map(|y| if rows.contains(&y) { row_mean(y) } else { overall })
+        .collect())
+}
+
+/// Subtract the bias level of every row, measured in `BIASSEC`, and trim the
+/// image to the section that holds data.
+pub fn subtract_overscan(hdu: &HDU) -> Result<Image, CcdError> {
+    let image = image(hdu)?;
+    let levels = overscan_levels_of(hdu, &image)?;
+    let (keyword, section) = trim_section(hdu, &image)?;
+    let mut trimmed = image.cut(&section).ok_or(CcdError::SectionOutOfBounds(keyword))?;
+    for j in 0..trimmed.height {
+        let level = levels[section.y.at(j) - 1];
+        for value in &mut trimmed.values[(j * trimmed.width)..((j + 1) * trimmed.width)] {
+            *value -= level;
+        }
+    }
+    Ok(trimmed)
+}
+
+fn section(hdu: &HDU, keyword: &Keyword) -> Result<Option<Section>, CcdError> {
+    header_section(&hdu.header, keyword)
+        .map_err(|error| CcdError::InvalidSection { keyword: keyword.clone(), error })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use super::super::section::{Interval, Section};
+    use super::super::types::{HDU, Header, KeywordRecord, Keyword, Value};
+    use super::{trim, overscan_levels, subtract_overscan, Image, CcdError};
+
+    const DATA: [u8; 8] = [10u8, 11u8, 12u8, 2u8, 20u8, 21u8, 22u8, 4u8];
+
+    fn hdu<'a>(sections: Vec<(Keyword, &'a str)>) -> HDU<'a> {
+        let mut records = vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(2i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(4i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(2u16), Value::Integer(2i64), Option::None),
+        );
+        for (keyword, section) in sections {
+            records.push(KeywordRecord::new(keyword, Value::CharacterString(Cow::Borrowed(section)), Option::None));
+        }
+        HDU::with_data(Header::new(records), &DATA)
+    }
+
+    #[test]
+    fn cut_should_flip_reversed_sections() {
+        let image = Image::new(2, 2, vec!(1f64, 2f64, 3f64, 4f64));
+
+        assert_eq!(image.cut(&Section::new(Interval::new(2, 1), Interval::new(1, 2))),
+                   Some(Image::new(2, 2, vec!(2f64, 1f64, 4f64, 3f64))));
+        assert_eq!(image.cut(&Section::new(Interval::new(1, 3), Interval::new(1, 2))), None);
+    }
+
+    #[test]
+    fn trim_should_prefer_trimsec_over_datasec() {
+        let both = hdu(vec!((Keyword::DATASEC, "[1:3,1:2]"), (Keyword::TRIMSEC, "[2:3,2:2]")));
+        let datasec = hdu(vec!((Keyword::DATASEC, "[1:3,1:2]")));
+
+        assert_eq!(trim(&both), Ok(Image::new(2, 1, vec!(21f64, 22f64))));
+        assert_eq!(trim(&datasec), Ok(Image::new(3, 2, vec!(10f64, 11f64, 12f64, 20f64, 21f64, 22f64))));
+        assert_eq!(trim(&hdu(vec!())).map(|image| image.values.len()), Ok(8));
+    }
+
+    #[test]
+    fn subtract_overscan_should_subtract_the_level_of_every_row() {
+        let hdu = hdu(vec!((Keyword::DATASEC, "[1:3,1:2]"), (Keyword::BIASSEC, "[4:4,1:2]")));
+
+        assert_eq!(overscan_levels(&hdu), Ok(vec!(2f64, 4f64)));
+        assert_eq!(subtract_overscan(&hdu), Ok(Image::new(3, 2, vec!(8f64, 9f64, 10f64, 16f64, 17f64, 18f64))));
+    }
+
+    #[test]
+    fn reductions_should_report_problems_with_sections() {
+        assert_eq!(overscan_levels(&hdu(vec!())), Err(CcdError::MissingSection(Keyword::BIASSEC)));
+        assert_eq!(trim(&hdu(vec!((Keyword::TRIMSEC, "[1:5,1:2]")))), Err(CcdError::SectionOutOfBounds(Keyword::TRIMSEC)));
+    }
+}
@@ -0,0 +1,331 @@
+//! The checksum module computes and verifies the `DATASUM` and `CHECKSUM`
+//! keywords.
+//!
+//! Both are based on the 32-bit 1's complement sum of the big-endian words of
+//! a unit. `DATASUM` holds the sum of the data unit as an unsigned decimal
+//! string. `CHECKSUM` holds a 16 character ASCII encoding, chosen such that
+//! the sum of the complete HDU, header and data unit, is zero.
+//!
+//! Verifying `CHECKSUM` needs the header exactly as it is stored, so it works
+//! on the raw bytes of an HDU instead of on a parsed `HDU`.
+//!
+//! With the `rayon` feature, `verify_all_checksums` sums the HDUs of a file on
+//! several threads.
+
+use std::borrow::Cow;
+use nom::IResult;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use super::error::Error;
+use super::parser::header;
+use super::types::{block_count, HDU, Header, Keyword, Value, ValueRetrievalError, BLOCK_SIZE};
+use super::writer::{write_header, WriteError};
+
+/// The value of `CHECKSUM` before the checksum is computed.
+const ZERO_CHECKSUM: &str = "0000000000000000";
+/// Characters that do not occur in an encoded checksum.
+const EXCLUDED: [u8; 13] = [
+    0x3a, 0x3b, 0x3c, 0x3d, 0x3e, 0x3f, 0x40,
+    0x5b, 0x5c, 0x5d, 0x5e, 0x5f, 0x60,
+];
+
+/// Add the big-endian 32-bit words of `bytes` to `sum`, in 1's complement
+/// arithmetic. The length of `bytes` should be a multiple of 4, which is the
+/// case for complete FITS units.
+pub fn add(sum: u32, bytes: &[u8]) -> u32 {
+    let mut result = u64::from(sum);
+    for word in bytes.chunks(4) {
+        let mut buffer = [0u8; 4];
+        buffer[..word.len()].copy_from_slice(word);
+        result += u64::from(u32::from_be_bytes(buffer));
+        result = (result & 0xffff_ffff) + (result >> 32);
+    }
+    result as u32
+}
+
+/// The 1's complement sum of the big-endian 32-bit words of `bytes`.
+pub fn checksum(bytes: &[u8]) -> u32 {
+    add(0, bytes)
+}
+
+/// Encode a sum as the 16 character ASCII string used for `CHECKSUM`. With
+/// `complement` the complement of the sum is encoded, as is done when writing.
+pub fn encode(sum: u32, complement: bool) -> String {
+    let value = if complement { !sum } else { sum };
+    let mut ascii = [0u8; 16];
+    for i in 0..4 {
+        let byte = (value >> (24 - 8 * i)) & 0xff;
+        let quotient = (byte / 4 + 0x30) as u8;
+        let remainder = (byte % 4) as u8;
+        let mut ch = [quotient; 4];
+        ch[0] += remainder;
+        let mut check = true;
+        while check {
+            check = false;
+            for excluded in EXCLUDED.iter() {
+                for j in [0, 2] {
+                    if ch[j] == *excluded || ch[j + 1] == *excluded {
+                        ch[j] += 1;
+                        ch[j + 1] -= 1;
+                        check = true;
+                    }
+                }
+            }
+        }
+        for j in 0..4 {
+            ascii[4 * j + i] = ch[j];
+        }
+    }
+    (0..16).map(|i| ascii[(i + 15) % 16] as char).collect()
+}
+
+/// Whether a keyword is present, and if so whether it holds the right value.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ChecksumStatus {
+    /// The keyword is not present.
+    Missing,
+    /// The keyword is present and its value is correct.
+    Valid,
+    /// The keyword is present but its value is not correct.
+    Invalid,
+}
+
+/// The outcome of verifying the checksums of an HDU.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ChecksumReport {
+    /// The status of `DATASUM`.
+    pub datasum: ChecksumStatus,
+    /// The status of `CHECKSUM`.
+    pub checksum: ChecksumStatus,
+}
+
+impl ChecksumReport {
+    /// Whether none of the present keywords is invalid.
+    pub fn is_valid(&self) -> bool {
+        self.datasum != ChecksumStatus::Invalid && self.checksum != ChecksumStatus::Invalid
+    }
+}
+
+/// Verify `DATASUM` and `CHECKSUM` of a single HDU, given its raw bytes: the
+/// header followed by the data unit, including padding.
+pub fn verify_checksum(hdu: &[u8]) -> Result<ChecksumReport, Error> {
+    let (data, h) = match header(hdu) {
+        IResult::Done(data, h) => (data, h),
+        _ => return Err(Error::InvalidHeader { offset: 0 }),
+    };
+    let data_size = h.data_unit_size().unwrap_or(usize::MAX).min(data.len());
+    Ok(report(&h, checksum(&data[0..data_size]), checksum(hdu)))
+}
+
+/// Verify `DATASUM` and `CHECKSUM` of all HDUs in the raw bytes of a FITS file.
+pub fn verify_all(data: &[u8]) -> Result<Vec<ChecksumReport>, Error> {
+    Ok(spans(data)?.iter().map(|span| span.report(data)).collect())
+}
+
+/// Verify `DATASUM` and `CHECKSUM` of all HDUs in the raw bytes of a FITS file
+/// like `verify_all` does, summing the HDUs on several threads. The headers
+/// are parsed first, which is cheap compared to summing the data units.
+#[cfg(feature = "rayon")]
+pub fn verify_all_checksums(data: &[u8]) -> Result<Vec<ChecksumReport>, Error> {
+    Ok(spans(data)?.par_iter().map(|span| span.report(data)).collect())
+}
+
+// Where an HDU is in the raw bytes of a file, with its parsed header.
+struct Span<'a> {
+    header: Header<'a>,
+    offset: usize,
+    header_size: usize,
+    data_size: usize,
+}
+
+impl<'a> Span<'a> {
+    fn report(&self, data: &[u8]) -> ChecksumReport {
+        let start = self.offset + self.header_size;
+        let end = start + self.data_size;
+        report(&self.header, checksum(&data[start..end]), checksum(&data[self.offset..end]))
+    }
+}
+
+fn spans(data: &[u8]) -> Result<Vec<Span<'_>>, Error> {
+    let mut spans = vec!();
+    let mut offset = 0;
+    while spans.is_empty() || offset < data.len() {
+        let input = &data[offset..];
+        let (rest, h) = match header(input) {
+            IResult::Done(rest, h) => (rest, h),
+            _ => return Err(Error::InvalidHeader { offset: offset as u64 }),
+        };
+        let header_size = input.len() - rest.len();
+        let data_size = match h.data_unit_size() {
+            Some(size) if size <= rest.len() => size,
+            _ => return Err(Error::UnexpectedEof { offset: (offset + header_size) as u64 }),
+        };
+        spans.push(Span { header: h, offset, header_size, data_size });
+        offset += header_size + data_size;
+    }
+    Ok(spans)
+}
+
+/// Verify `DATASUM` and `CHECKSUM` of a parsed header, given the sum of its
+/// data unit and the sum of the complete HDU.
+pub fn report(header: &Header, datasum: u32, hdusum: u32) -> ChecksumReport {
+    let datasum = datasum_status(header, datasum);
+    let checksum = match header.get_str(&Keyword::CHECKSUM) {
+        Ok(_) if hdusum == 0 || hdusum == 0xffff_ffff => ChecksumStatus::Valid,
+        Err(ValueRetrievalError::KeywordNotPresent) => ChecksumStatus::Missing,
+        _ => ChecksumStatus::Invalid,
+    };
+    ChecksumReport { datasum, checksum }
+}
+
+fn datasum_status(header: &Header, datasum: u32) -> ChecksumStatus {
+    match header.datasum() {
+        Ok(value) if value == datasum => ChecksumStatus::Valid,
+        Err(ValueRetrievalError::KeywordNotPresent) => ChecksumStatus::Missing,
+        _ => ChecksumStatus::Invalid,
+    }
+}
+
+/// Verify `DATASUM` of a parsed HDU against the sum of its data array. Unlike
+/// `CHECKSUM`, this does not need the raw bytes, because the padding is known
+/// from the kind of HDU.
+pub fn verify_datasum(hdu: &HDU) -> ChecksumStatus {
+    datasum_status(&hdu.header, data_unit_sum(hdu))
+}
+
+// The sum of the data unit of `hdu`, including its padding. Zeros do not add
+// to the sum, the blanks that pad ASCII tables do.
+fn data_unit_sum(hdu: &HDU) -> u32 {
+    let bytes = hdu.data().bytes();
+    let fill = hdu.header.kind().fill();
+    if fill == 0u8 || bytes.is_empty() {
+        return checksum(bytes)
+    }
+    let mut padded = bytes.to_vec();
+    padded.resize(block_count(bytes.len()) * BLOCK_SIZE, fill);
+    checksum(&padded)
+}
+
+/// Compute `DATASUM` and `CHECKSUM` of an HDU and store them in its header,
+/// replacing existing values. The checksum is computed for the header as it is
+/// written by the `writer` module.
+pub fn compute_checksum(hdu: &mut HDU) -> Result<(), WriteError> {
+    let datasum = data_unit_sum(hdu);
+    hdu.header.set(Keyword::DATASUM, string(datasum.to_string()), Option::Some("data unit checksum"));
+    hdu.header.set(Keyword::CHECKSUM, string(ZERO_CHECKSUM.to_string()), Option::Some("HDU checksum"));
+    let mut bytes: Vec<u8> = vec!();
+    write_header(&mut bytes, &hdu.header)?;
+    let sum = add(checksum(&bytes), &datasum.to_be_bytes());
+    hdu.header.set(Keyword::CHECKSUM, string(encode(sum, true)), Option::None);
+    Ok(())
+}
+
+fn string<'a>(s: String) -> Value<'a> {
+    Value::CharacterString(Cow::Owned(s))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::io::Cursor;
+    use super::super::parser::parse;
+    use super::super::reader::FitsReader;
+    use super::super::types::{Fits, HDU, Header, KeywordRecord, Keyword, Value};
+    use super::super::writer::write_fits;
+    use super::{add, checksum, encode, verify_all, verify_checksum, verify_datasum, compute_checksum, ChecksumStatus};
+
+    #[test]
+    fn add_should_wrap_carries_around() {
+        assert_eq!(add(0xffff_fffe, &[0, 0, 0, 3]), 2);
+        assert_eq!(checksum(&[0, 0, 0, 1, 0, 0, 0, 2]), 3);
+    }
+
+    #[test]
+    fn encode_should_produce_the_reference_encoding() {
+        // The example of the checksum convention: the complement of 868229149
+        // is encoded as "hcHjjc9ghcEghc9g".
+        assert_eq!(encode(868229149, true), "hcHjjc9ghcEghc9g");
+    }
+
+    #[test]
+    fn computed_checksums_should_verify_after_writing() {
+        let data = [1u8, 2u8, 3u8, 4u8, 5u8];
+        let header = Header::new(vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(1i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(5i64), Option::None),
+        ));
+        let mut hdu = HDU::with_data(header, &data);
+        compute_checksum(&mut hdu).unwrap();
+        let mut buffer: Vec<u8> = vec!();
+
+        write_fits(&mut buffer, &Fits::new(hdu, vec!())).unwrap();
+        let report = verify_checksum(&buffer).unwrap();
+
+        assert_eq!(report.datasum, ChecksumStatus::Valid);
+        assert_eq!(report.checksum, ChecksumStatus::Valid);
+        assert_eq!(parse(&buffer).unwrap().primary_hdu.header.get_str(&Keyword::DATASUM), Ok("100795140"));
+    }
+
+    #[test]
+    fn modified_data_should_fail_verification() {
+        let mut hdu = HDU::with_data(Header::new(vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(1i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(4i64), Option::None),
+            KeywordRecord::new(Keyword::OBJECT, Value::CharacterString(Cow::Borrowed("TRAPPIST-1")), Option::None),
+        )), &[1u8, 2u8, 3u8, 4u8]);
+        compute_checksum(&mut hdu).unwrap();
+        let mut buffer: Vec<u8> = vec!();
+        write_fits(&mut buffer, &Fits::new(hdu, vec!())).unwrap();
+
+        buffer[2880] = 9u8;
+        let report = verify_checksum(&buffer).unwrap();
+
+        assert_eq!(report.datasum, ChecksumStatus::Invalid);
+        assert_eq!(report.checksum, ChecksumStatus::Invalid);
+        assert!(!report.is_valid());
+    }
+
+    #[test]
+    fn datasum_of_parsed_hdus_should_be_verified() {
+        let data = include_bytes!("../../assets/images/k2-trappist1-unofficial-tpf-long-cadence.fits");
+        let fits = parse(data).unwrap();
+
+        for hdu in fits.iter_hdus() {
+            assert_eq!(verify_datasum(hdu), ChecksumStatus::Valid);
+        }
+        assert_eq!(verify_datasum(&HDU::new(Header::new(vec!()))), ChecksumStatus::Missing);
+    }
+
+    #[test]
+    fn checksums_of_a_k2_file_should_be_valid() {
+        let data = include_bytes!("../../assets/images/k2-trappist1-unofficial-tpf-long-cadence.fits");
+        let mut reader = FitsReader::new(Cursor::new(&data[..]));
+        let entries: Vec<_> = reader.by_ref().map(|entry| entry.unwrap()).collect();
+
+        let reports = verify_all(data).unwrap();
+
+        assert_eq!(reports.len(), 3);
+        for (entry, report) in entries.iter().zip(reports.iter()) {
+            assert_eq!(report.datasum, ChecksumStatus::Valid);
+            assert_eq!(report.checksum, ChecksumStatus::Valid);
+            assert_eq!(reader.verify_checksum(entry).unwrap(), *report);
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn checksums_verified_in_parallel_should_match_sequential_verification() {
+        let data = include_bytes!("../../assets/images/k2-trappist1-unofficial-tpf-long-cadence.fits");
+        let mut modified = data.to_vec();
+        modified[3 * 2880] ^= 1u8;
+
+        assert_eq!(super::verify_all_checksums(data).unwrap(), verify_all(data).unwrap());
+        let reports = super::verify_all_checksums(&modified).unwrap();
+        assert_eq!(reports, verify_all(&modified).unwrap());
+        assert!(reports.iter().any(|report| !report.is_valid()));
+    }
+}
@@ -0,0 +1,689 @@
+//! The compression module decompresses tile-compressed images, e.g. the
+//! extensions of `.fits.fz` files written by `fpack`.
+//!
+//! A tile-compressed image is stored in a binary table with `ZIMAGE = T`. The
+//! image is divided into tiles of `ZTILEn` pixels, and every row of the table
+//! holds one tile, compressed with the algorithm in `ZCMPTYPE`, in the heap
+//! array of the `COMPRESSED_DATA` column. `ZBITPIX`, `ZNAXIS` and `ZNAXISn`
+//! describe the image itself.
+//!
+//! The `RICE_1`, `GZIP_1`, `GZIP_2` and `NOCOMPRESS` algorithms are supported,
+//! as are floating point images quantized with `ZSCALE` and `ZZERO`, with or
+//! without subtractive dithering.
+//!
+//! A decompressed image is an `IMAGE` extension, unless `ZTENSION` says
+//! otherwise, with the keywords of the table that do not describe the
+//! compression.
+
+use std::convert::TryFrom;
+use std::error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::io::Read;
+use std::str::FromStr;
+use flate2::read::GzDecoder;
+use super::table::{BinaryTable, TableError};
+use super::types::{Bitpix, BitpixError, Fits, HDU, HduKind, Header, KeywordRecord, Keyword, Value, ValueRetrievalError};
+
+/// The number of values in the table of random numbers used for dithering.
+const N_RANDOM: usize = 10000;
+/// The quantized value that signals a pixel that is exactly zero, with
+/// `SUBTRACTIVE_DITHER_2`.
+const ZERO_VALUE: i64 = -2147483646;
+/// Keywords of a compressed image, apart from the indexed ones, that do not
+/// carry over to the decompressed image.
+const COMPRESSION_KEYWORDS: [&str; 21] = [
+    "ZIMAGE", "ZCMPTYPE", "ZBITPIX", "ZNAXIS", "ZTILE", "ZNAME", "ZVAL", "ZMASKCMP", "ZQUANTIZ",
+    "ZDITHER", "ZSIMPLE", "ZTENSION", "ZEXTEND", "ZBLOCKED", "ZPCOUNT", "ZGCOUNT", "ZHECKSUM",
+    "ZDATASUM", "ZBLANK", "ZSCALE", "ZZERO",
+];
+
+/// Problems that occur when decompressing an image.
+#[derive(Debug, PartialEq)]
+pub enum CompressionError {
+    /// The HDU does not hold a tile-compressed image.
+    NotCompressed,
+    /// The table holding the image could not be read.
+    Table(TableError),
+    /// A keyword that describes the compression has an invalid value.
+    InvalidValue {
+        /// The name of the keyword.
+        keyword: String,
+        /// What is wrong with the value.
+        error: ValueRetrievalError,
+    },
+    /// `ZBITPIX` is not a valid `BITPIX`.
+    InvalidBitpix(BitpixError),
+    /// The table does not have a `COMPRESSED_DATA` column.
+    MissingColumn(String),
+    /// The compression algorithm of `ZCMPTYPE` is not supported.
+    UnsupportedAlgorithm(String),
+    /// The quantization method of `ZQUANTIZ` is not supported.
+    UnsupportedQuantization(String),
+    /// A tile could not be decompressed, e.g. because it ends prematurely.
+    CorruptTile(usize),
+    /// The decompressed image, `ZNAXISn` values of `ZBITPIX`, takes more
+    /// bytes than can be allocated.
+    TooLarge,
+}
+
+impl Display for CompressionError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            CompressionError::NotCompressed => write!(f, "the HDU does not hold a compressed image"),
+            CompressionError::Table(ref error) => write!(f, "{}", error),
+            CompressionError::InvalidValue { ref keyword, ref error } => write!(f, "{}: {}", keyword, error),
+            CompressionError::InvalidBitpix(ref error) => write!(f, "Z{}", error),
+            CompressionError::MissingColumn(ref name) => write!(f, "the column {} is missing", name),
+            CompressionError::UnsupportedAlgorithm(ref name) => write!(f, "compression algorithm {} is not supported", name),
+            CompressionError::UnsupportedQuantization(ref name) => write!(f, "quantization method {} is not supported", name),
+            CompressionError::CorruptTile(row) => write!(f, "the tile in row {} could not be decompressed", row),
+            CompressionError::TooLarge => write!(f, "the decompressed image is too large"),
+        }
+    }
+}
+
+impl error::Error for CompressionError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            CompressionError::Table(ref error) => Some(error),
+            CompressionError::InvalidBitpix(ref error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<TableError> for CompressionError {
+    fn from(error: TableError) -> Self {
+        CompressionError::Table(error)
+    }
+}
+
+/// Whether `hdu` holds a tile-compressed image, i.e. is a binary table with `ZIMAGE = T`.
+pub fn is_compressed(hdu: &HDU) -> bool {
+    hdu.header.kind() == HduKind::BinTable &&
+        hdu.header.get_logical(&keyword("ZIMAGE")) == Ok(true)
+}
+
+/// Decompress all tile-compressed images of a FITS file, leaving the other
+/// HDUs as they are.
+pub fn decompress_fits(fits: Fits) -> Result<Fits<'static>, CompressionError> {
+    let decompress_owned = |hdu: HDU| if is_compressed(&hdu) { decompress(&hdu) } else { Ok(hdu.into_owned()) };
+    let primary_hdu = decompress_owned(fits.primary_hdu)?;
+    let extensions = fits.extensions.into_iter().map(decompress_owned).collect::<Result<Vec<_>, _>>()?;
+    Ok(Fits::new(primary_hdu, extensions))
+}
+
+/// Decompress the image held by `hdu`.
+pub fn decompress(hdu: &HDU) -> Result<HDU<'static>, CompressionError> {
+    if !is_compressed(hdu) {
+        return Err(CompressionError::NotCompressed)
+    }
+    let image = CompressedImage::from_header(&hdu.header)?;
+    let table = BinaryTable::from_hdu(hdu)?;
+    let data = image.decompress(&table)?;
+    Ok(HDU::with_owned_data(image.header(&hdu.header), data))
+}
+
+// The parameters of a compressed image.
+struct CompressedImage {
+    algorithm: String,
+    bitpix: Bitpix,
+    axes: Vec<usize>,
+    tiles: Vec<usize>,
+    block_size: usize,
+    bytepix: usize,
+    quantization: Option<String>,
+    dither_seed: usize,
+    scale: Option<f64>,
+    zero: Option<f64>,
+    blank: Option<i64>,
+    xtension: String,
+    random: Vec<f64>,
+}
+
+impl CompressedImage {
+    fn from_header(header: &Header) -> Result<CompressedImage, CompressionError> {
+        let algorithm = string(header, "ZCMPTYPE")?.unwrap_or_default().trim_end().to_string();
+        if !["RICE_1", "RICE_ONE", "GZIP_1", "GZIP_2", "NOCOMPRESS"].contains(&algorithm.as_str()) {
+            return Err(CompressionError::UnsupportedAlgorithm(algorithm))
+        }
+        let bitpix = Bitpix::try_from(required(integer(header, "ZBITPIX")?, "ZBITPIX")?)
+            .map_err(CompressionError::InvalidBitpix)?;
+        let naxis = size(header, "ZNAXIS", 0)?;
+        let mut axes = vec!();
+        let mut tiles = vec!();
+        for n in 1..(naxis + 1) {
+            let name = format!("ZNAXIS{}", n);
+            let axis = required(integer(header, &name)?, &name)?;
+            let axis = usize::try_from(axis).map_err(|_| invalid(&name, ValueRetrievalError::NotAnInteger))?;
+            axes.push(axis);
+            tiles.push(size(header, &format!("ZTILE{}", n), if n == 1 { axis } else { 1 })?.max(1));
+        }
+        let mut block_size = 32;
+        let mut bytepix = 4;
+        let mut n = 1;
+        while let Some(name) = string(header, &format!("ZNAME{}", n))? {
+            match name.trim_end() {
+                "BLOCKSIZE" => block_size = size(header, &format!("ZVAL{}", n), 32)?,
+                "BYTEPIX" => bytepix = size(header, &format!("ZVAL{}", n), 4)?,
+                _ => {}
+            }
+            n += 1;
+        }
+        let quantization = string(header, "ZQUANTIZ")?.map(|method| method.trim_end().to_string());
+        let random = match quantization {
+            Some(ref method) if method.starts_with("SUBTRACTIVE_DITHER") => random_values(),
+            _ => vec!(),
+        };
+        Ok(CompressedImage {
+            algorithm,
+            bitpix,
+            axes,
+            tiles,
+            block_size,
+            bytepix,
+            quantization,
+            dither_seed: size(header, "ZDITHER0", 1)?,
+            scale: real(header, "ZSCALE")?,
+            zero: real(header, "ZZERO")?,
+            blank: integer(header, "ZBLANK")?,
+            xtension: string(header, "ZTENSION")?.map(|xtension| xtension.trim_end().to_string()).unwrap_or_else(|| "IMAGE".to_string()),
+            random,
+        })
+    }
+
+    // The header of the decompressed image.
+    fn header(&self, header: &Header) -> Header<'static> {
+        let mut records = vec!(
+            KeywordRecord::new(Keyword::XTENSION, Value::CharacterString(self.xtension.clone().into()), Option::Some("extension type")),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(self.bitpix.value()), Option::Some("array data type")),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(self.axes.len() as i64), Option::Some("number of array dimensions")),
+        );
+        for (index, axis) in self.axes.iter().enumerate() {
+            records.push(KeywordRecord::new(Keyword::NAXISn((index + 1) as u16), Value::Integer(*axis as i64), Option::None));
+        }
+        records.push(KeywordRecord::new(Keyword::PCOUNT, Value::Integer(0i64), Option::Some("number of parameters")));
+        records.push(KeywordRecord::new(Keyword::GCOUNT, Value::Integer(1i64), Option::Some("number of groups")));
+        for record in &header.keyword_records {
+            if !describes_table(record.keyword()) {
+                records.push(record.clone().into_owned());
+            }
+        }
+        records.push(KeywordRecord::new(Keyword::END, Value::Undefined, Option::None));
+        Header::new(records)
+    }
+
+    fn is_quantized(&self, table: &BinaryTable) -> bool {
+        self.bitpix.is_floating_point() && (self.scale.is_some() || table.column_index("ZSCALE").is_some())
+    }
+
+    // The data array of the image, assembled from all tiles.
+    fn decompress(&self, table: &BinaryTable) -> Result<Vec<u8>, CompressionError> {
+        let width = self.bitpix.bytes();
+        let size = self.axes.iter().try_fold(width, |product, axis| product.checked_mul(*axis)).ok_or(CompressionError::TooLarge)?;
+        let mut data = vec!();
+        data.try_reserve_exact(size).map_err(|_| CompressionError::TooLarge)?;
+        data.resize(size, 0u8);
+        let counts: Vec<usize> = self.axes.iter().zip(self.tiles.iter()).map(|(axis, tile)| axis.div_ceil(*tile)).collect();
+        let rows: usize = if self.axes.is_empty() { 0 } else { counts.iter().product() };
+        for row in 0..rows.min(table.rows()) {
+            let mut index = row;
+            let mut start = vec!();
+            let mut shape = vec!();
+            for (i, count) in counts.iter().enumerate() {
+                start.push((index % count) * self.tiles[i]);
+                shape.push(self.tiles[i].min(self.axes[i] - start[i]));
+                index /= count;
+            }
+            let tile = self.tile(table, row, shape.iter().product())?;
+            self.place(&mut data, &tile, &start, &shape);
+        }
+        Ok(data)
+    }
+
+    // Copy a tile into the image, line by line along the first axis.
+    fn place(&self, data: &mut [u8], tile: &[u8], start: &[usize], shape: &[usize]) {
+        let width = self.bitpix.bytes();
+        let line = shape[0] * width;
+        for (l, bytes) in tile.chunks(line).enumerate() {
+            let mut rest = l;
+            let mut offset = start[0];
+            let mut stride = 1;
+            for i in 1..shape.len() {
+                stride *= self.axes[i - 1];
+                offset += (start[i] + rest % shape[i]) * stride;
+                rest /= shape[i];
+            }
+            data[(offset * width)..(offset * width + bytes.len())].copy_from_slice(bytes);
+        }
+    }
+
+    // The big-endian bytes of the pixels of the tile in `row`.
+    fn tile(&self, table: &BinaryTable, row: usize, pixels: usize) -> Result<Vec<u8>, CompressionError> {
+        let width = self.bitpix.bytes();
+        let compressed = match table.column_index("COMPRESSED_DATA") {
+            Some(column) => table.heap_field(row, column)?,
+            None => return Err(CompressionError::MissingColumn("COMPRESSED_DATA".to_string())),
+        };
+        let tile = if !compressed.is_empty() {
+            if self.is_quantized(table) {
+                let quantized = self.decompress_tile(compressed, pixels, 4).ok_or(CompressionError::CorruptTile(row))?;
+                return self.unquantize(table, row, &quantized)
+            }
+            self.decompress_tile(compressed, pixels, width)
+        } else if let Some(column) = table.column_index("GZIP_COMPRESSED_DATA").filter(|c| table.descriptor(row, *c).map(|d| d.0 > 0) == Ok(true)) {
+            gunzip(table.heap_field(row, column)?)
+        } else if let Some(column) = table.column_index("UNCOMPRESSED_DATA") {
+            Some(table.heap_field(row, column)?.to_vec())
+        } else {
+            None
+        };
+        tile.filter(|tile| tile.len() == pixels * width).ok_or(CompressionError::CorruptTile(row))
+    }
+
+    // Decompress a tile into `pixels` big-endian values of `width` bytes.
+    fn decompress_tile(&self, compressed: &[u8], pixels: usize, width: usize) -> Option<Vec<u8>> {
+        let bytes = match self.algorithm.as_str() {
+            "RICE_1" | "RICE_ONE" => {
+                let values = rice_decompress(compressed, pixels, self.block_size, self.bytepix)?;
+                values.iter().flat_map(|value| value.to_be_bytes()[(8 - width)..].to_vec()).collect()
+            }
+            "GZIP_1" => gunzip(compressed)?,
+            "GZIP_2" => unshuffle(&gunzip(compressed)?, width),
+            "NOCOMPRESS" => compressed.to_vec(),
+            _ => return None,
+        };
+        Some(bytes).filter(|bytes| bytes.len() == pixels * width)
+    }
+
+    // Turn quantized 32-bit integers into floating point values.
+    fn unquantize(&self, table: &BinaryTable, row: usize, quantized: &[u8]) -> Result<Vec<u8>, CompressionError> {
+        let scale = self.column_or(table, row, "ZSCALE", self.scale)?.unwrap_or(1f64);
+        let zero = self.column_or(table, row, "ZZERO", self.zero)?.unwrap_or(0f64);
+        let blank = self.column_or(table, row, "ZBLANK", self.blank.map(|blank| blank as f64))?.map(|blank| blank as i64);
+        let method = self.quantization.clone().unwrap_or_else(|| "NO_DITHER".to_string());
+        let mut dither = match method.as_str() {
+            "NO_DITHER" => None,
+            "SUBTRACTIVE_DITHER_1" | "SUBTRACTIVE_DITHER_2" => Some(Dither::new(&self.random, row, self.dither_seed)),
+            _ => return Err(CompressionError::UnsupportedQuantization(method)),
+        };
+        let mut values = vec!();
+        for bytes in quantized.chunks(4) {
+            let raw = i64::from(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+            let offset = dither.as_mut().map(Dither::next).unwrap_or(0.5);
+            let value = if Some(raw) == blank {
+                f64::NAN
+            } else if method == "SUBTRACTIVE_DITHER_2" && raw == ZERO_VALUE {
+                0f64
+            } else {
+                (raw as f64 - offset + 0.5) * scale + zero
+            };
+            match self.bitpix {
+                Bitpix::F32 => values.extend_from_slice(&(value as f32).to_be_bytes()),
+                _ => values.extend_from_slice(&value.to_be_bytes()),
+            }
+        }
+        Ok(values)
+    }
+
+    // The value of a column in `row`, or the keyword value when there is no such column.
+    fn column_or(&self, table: &BinaryTable, row: usize, name: &str, keyword_value: Option<f64>) -> Result<Option<f64>, CompressionError> {
+        match table.column_index(name) {
+            Some(column) => {
+                let field = table.field(row, column)?;
+                Ok(match field.len() {
+                    8 => Some(f64::from_be_bytes([field[0], field[1], field[2], field[3], field[4], field[5], field[6], field[7]])),
+                    4 if name == "ZBLANK" => Some(f64::from(i32::from_be_bytes([field[0], field[1], field[2], field[3]]))),
+                    4 => Some(f64::from(f32::from_be_bytes([field[0], field[1], field[2], field[3]]))),
+                    _ => keyword_value,
+                })
+            }
+            None => Ok(keyword_value),
+        }
+    }
+}
+
+// The sequence of random offsets of subtractive dithering, as defined by the
+// tiled image compression convention.
+struct Dither<'r> {
+    random: &'r [f64],
+    seed: usize,
+    next: usize,
+}
+
+impl<'r> Dither<'r> {
+    // The dither of the tile in `row`, counted from 0.
+    fn new(random: &'r [f64], row: usize, dither_seed: usize) -> Dither<'r> {
+        let seed = (row + dither_seed.max(1) - 1) % N_RANDOM;
+        let next = (random[seed] * 500f64) as usize;
+        Dither { random, seed, next }
+    }
+
+    fn next(&mut self) -> f64 {
+        let value = self.random[self.next];
+        self.next += 1;
+        if self.next == N_RANDOM {
+            self.seed = (self.seed + 1) % N_RANDOM;
+            self.next = (self.random[self.seed] * 500f64) as usize;
+        }
+        value
+    }
+}
+
+// The random numbers of the Park and Miller minimal standard generator.
+fn random_values() -> Vec<f64> {
+    let a = 16807f64;
+    let m = 2147483647f64;
+    let mut seed = 1f64;
+    (0..N_RANDOM).map(|_| {
+        let temp = a * seed;
+        seed = temp - m * (temp / m).trunc();
+        seed / m
+    }).collect()
+}
+
+// Reads a compressed stream bit by bit, most significant bit first.
+struct Bits<'b> {
+    bytes: &'b [u8],
+    position: usize,
+}
+
+impl<'b> Bits<'b> {
+    fn read(&mut self, count: usize) -> Option<u32> {
+        let mut value = 0u32;
+        for _ in 0..count {
+            let byte = self.bytes.get(self.position / 8)?;
+            let bit = (byte >> (7 - self.position % 8)) & 1;
+            value = (value << 1) | u32::from(bit);
+            self.position += 1;
+        }
+        Some(value)
+    }
+
+    fn leading_zeros(&mut self) -> Option<u32> {
+        let mut zeros = 0;
+        while self.read(1)? == 0 {
+            zeros += 1;
+        }
+        Some(zeros)
+    }
+}
+
+// Decompress `pixels` values compressed with the Rice algorithm, in blocks of
+// `block_size` values of `bytepix` bytes.
+fn rice_decompress(compressed: &[u8], pixels: usize, block_size: usize, bytepix: usize) -> Option<Vec<i64>> {
+    let (fs_bits, fs_max) = match bytepix {
+        1 => (3, 6),
+        2 => (4, 14),
+        4 => (5, 25),
+        _ => return None,
+    };
+    let bits = 8 * bytepix;
+    let mask = if bytepix == 4 { u32::MAX } else { (1u32 << bits) - 1 };
+    let mut stream = Bits { bytes: compressed, position: 0 };
+    let mut last = stream.read(bits)?;
+    let mut values = Vec::with_capacity(pixels);
+    while values.len() < pixels {
+        let fs = stream.read(fs_bits)? as i64 - 1;
+        let end = (values.len() + block_size.max(1)).min(pixels);
+        while values.len() < end {
+            let difference = if fs < 0 {
+                0
+            } else if fs == fs_max {
+                stream.read(bits)?
+            } else {
+                let zeros = stream.leading_zeros()?;
+                (zeros << fs) | stream.read(fs as usize)?
+            };
+            let difference = if difference & 1 == 0 { difference >> 1 } else { !(difference >> 1) };
+            last = last.wrapping_add(difference) & mask;
+            values.push(match bytepix {
+                1 => i64::from(last as u8),
+                2 => i64::from(last as u16 as i16),
+                _ => i64::from(last as i32),
+            });
+        }
+    }
+    Some(values)
+}
+
+fn gunzip(compressed: &[u8]) -> Option<Vec<u8>> {
+    let mut bytes = vec!();
+    GzDecoder::new(compressed).read_to_end(&mut bytes).ok()?;
+    Some(bytes)
+}
+
+// Undo the byte shuffling of `GZIP_2`, which stores the most significant bytes
+// of all values first.
+fn unshuffle(shuffled: &[u8], width: usize) -> Vec<u8> {
+    let count = shuffled.len() / width;
+    let mut bytes = vec!(0u8; shuffled.len());
+    for (index, byte) in shuffled.iter().enumerate() {
+        bytes[(index % count) * width + index / count] = *byte;
+    }
+    bytes
+}
+
+// Whether a keyword describes the table or the compression, rather than the image.
+fn describes_table(keyword: &Keyword) -> bool {
+    match *keyword {
+        Keyword::XTENSION | Keyword::BITPIX | Keyword::NAXIS | Keyword::NAXISn(_) | Keyword::PCOUNT |
+        Keyword::GCOUNT | Keyword::TFIELDS | Keyword::TTYPEn(_) | Keyword::TFORMn(_) | Keyword::TUNITn(_) |
+        Keyword::TSCALn(_) | Keyword::TZEROn(_) | Keyword::TNULLn(_) | Keyword::TDIMn(_) | Keyword::TDISPn(_) |
+        Keyword::THEAP | Keyword::CHECKSUM | Keyword::DATASUM | Keyword::END => true,
+        Keyword::Custom(ref name) => COMPRESSION_KEYWORDS.contains(&name.trim_end_matches(|c: char| c.is_ascii_digit())),
+        _ => false,
+    }
+}
+
+fn keyword(name: &str) -> Keyword {
+    Keyword::from_str(name).unwrap_or_else(|_| Keyword::Custom(name.to_string()))
+}
+
+fn invalid(name: &str, error: ValueRetrievalError) -> CompressionError {
+    CompressionError::InvalidValue { keyword: name.to_string(), error }
+}
+
+// The value of a keyword, or `None` when it is not present.
+fn optional<T>(name: &str, result: Result<T, ValueRetrievalError>) -> Result<Option<T>, CompressionError> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(ValueRetrievalError::KeywordNotPresent) => Ok(None),
+        Err(error) => Err(invalid(name, error)),
+    }
+}
+
+fn required<T>(value: Option<T>, name: &str) -> Result<T, CompressionError> {
+    value.ok_or_else(|| invalid(name, ValueRetrievalError::KeywordNotPresent))
+}
+
+fn string(header: &Header, name: &str) -> Result<Option<String>, CompressionError> {
+    optional(name, header.get_str(&keyword(name)).map(str::to_string))
+}
+
+fn integer(header: &Header, name: &str) -> Result<Option<i64>, CompressionError> {
+    optional(name, header.get_int(&keyword(name)))
+}
+
+fn real(header: &Header, name: &str) -> Result<Option<f64>, CompressionError> {
+    optional(name, header.get_real(&keyword(name)))
+}
+
+fn size(header: &Header, name: &str, default: usize) -> Result<usize, CompressionError> {
+    match integer(header, name)? {
+        Some(value) => usize::try_from(value).map_err(|_| invalid(name, ValueRetrievalError::NotAnInteger)),
+        None => Ok(default),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::io::Write;
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use super::super::types::{HDU, Header, KeywordRecord, Keyword, Value};
+    use super::{decompress, is_compressed, random_values, rice_decompress, unshuffle, CompressionError, N_RANDOM};
+
+    fn string(s: &'static str) -> Value<'static> {
+        Value::CharacterString(Cow::Borrowed(s))
+    }
+
+    // A compressed image of `width` by `height` pixels in tiles of one row,
+    // with one compressed tile per entry of `tiles`.
+    fn compressed_hdu(algorithm: &'static str, zbitpix: i64, width: usize, tiles: &[Vec<u8>], extra: Vec<(&str, Value<'static>)>) -> (Header<'static>, Vec<u8>) {
+        let mut data = vec!();
+        let mut heap = vec!();
+        for tile in tiles {
+            data.extend_from_slice(&(tile.len() as u32).to_be_bytes());
+            data.extend_from_slice(&(heap.len() as u32).to_be_bytes());
+            heap.extend_from_slice(tile);
+        }
+        let pcount = heap.len() as i64;
+        data.append(&mut heap);
+        let mut records = vec!(
+            KeywordRecord::new(Keyword::XTENSION, string("BINTABLE"), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(2i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(2u16), Value::Integer(tiles.len() as i64), Option::None),
+            KeywordRecord::new(Keyword::PCOUNT, Value::Integer(pcount), Option::None),
+            KeywordRecord::new(Keyword::GCOUNT, Value::Integer(1i64), Option::None),
+            KeywordRecord::new(Keyword::TFIELDS, Value::Integer(1i64), Option::None),
+            KeywordRecord::new(Keyword::TTYPEn(1u16), string("COMPRESSED_DATA"), Option::None),
+            KeywordRecord::new(Keyword::TFORMn(1u16), string("1PB(100)"), Option::None),
+            KeywordRecord::new(Keyword::Custom("ZIMAGE".to_string()), Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::Custom("ZCMPTYPE".to_string()), string(algorithm), Option::None),
+            KeywordRecord::new(Keyword::Custom("ZBITPIX".to_string()), Value::Integer(zbitpix), Option::None),
+            KeywordRecord::new(Keyword::Custom("ZNAXIS".to_string()), Value::Integer(2i64), Option::None),
+            KeywordRecord::new(Keyword::Custom("ZNAXIS1".to_string()), Value::Integer(width as i64), Option::None),
+            KeywordRecord::new(Keyword::Custom("ZNAXIS2".to_string()), Value::Integer(tiles.len() as i64), Option::None),
+            KeywordRecord::new(Keyword::OBJECT, string("TRAPPIST-1"), Option::None),
+        );
+        for (name, value) in extra {
+            records.push(KeywordRecord::new(Keyword::Custom(name.to_string()), value, Option::None));
+        }
+        records.push(KeywordRecord::new(Keyword::END, Value::Undefined, Option::None));
+        (Header::new(records), data)
+    }
+
+    fn gzip(bytes: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(vec!(), Compression::default());
+        encoder.write_all(bytes).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn rice_should_decode_low_entropy_and_normal_blocks() {
+        // The first value, 10, is stored as is. The block of differences
+        // 0, 1, -2, 1 is mapped onto 0, 2, 3, 2 and coded with fs = 1.
+        assert_eq!(rice_decompress(&[0, 0, 0, 10, 0x14, 0x9a], 4, 32, 4), Some(vec!(10, 11, 9, 10)));
+        // All differences are zero, so the block is coded with fs = -1 only.
+        assert_eq!(rice_decompress(&[0, 10, 0], 3, 32, 2), Some(vec!(10, 10, 10)));
+        assert_eq!(rice_decompress(&[0, 0, 0, 10, 0x14], 4, 32, 4), None);
+    }
+
+    #[test]
+    fn rice_should_decode_high_entropy_blocks() {
+        // With bytepix 1, fs_max is 6, so the fs code 0b111 signals raw
+        // differences of 8 bits. The difference -3 maps onto 5.
+        assert_eq!(rice_decompress(&[200, 0b1110_0000, 0b1010_0000], 1, 32, 1), Some(vec!(197)));
+    }
+
+    #[test]
+    fn random_values_should_match_the_convention() {
+        let random = random_values();
+
+        assert_eq!(random.len(), N_RANDOM);
+        assert_eq!((random[N_RANDOM - 1] * 2147483647f64).round(), 1043618065f64);
+    }
+
+    #[test]
+    fn unshuffle_should_interleave_bytes() {
+        assert_eq!(unshuffle(&[1, 3, 2, 4], 2), vec!(1, 2, 3, 4));
+    }
+
+    #[test]
+    fn gzip_compressed_images_should_decompress() {
+        let rows = vec!(gzip(&[0, 1, 0, 2, 0, 3]), gzip(&[1, 0, 255, 255, 0, 6]));
+        let (header, data) = compressed_hdu("GZIP_1", 16, 3, &rows, vec!());
+        let hdu = HDU::with_data(header, &data);
+
+        let image = decompress(&hdu).unwrap();
+
+        assert!(is_compressed(&hdu));
+        assert_eq!(image.header.get_str(&Keyword::XTENSION), Ok("IMAGE"));
+        assert_eq!(image.header.get_str(&Keyword::OBJECT), Ok("TRAPPIST-1"));
+        assert_eq!(image.header.get(&Keyword::Custom("ZIMAGE".to_string())), None);
+        assert_eq!(image.data_array().unwrap().axes(), &[3, 2]);
+        assert_eq!(image.data_array().unwrap().values::<i16>(), Ok(vec!(1, 2, 3, 256, -1, 6)));
+    }
+
+    #[test]
+    fn rice_compressed_images_should_decompress() {
+        let rows = vec!(vec!(0, 0, 0, 10, 0x14, 0x9a), vec!(0, 0, 0, 7, 0));
+        let (header, data) = compressed_hdu("RICE_1", 32, 4, &rows, vec!(
+            ("ZNAME1", string("BLOCKSIZE")), ("ZVAL1", Value::Integer(32i64)),
+            ("ZNAME2", string("BYTEPIX")), ("ZVAL2", Value::Integer(4i64)),
+        ));
+
+        let image = decompress(&HDU::with_data(header, &data)).unwrap();
+
+        assert_eq!(image.data_array().unwrap().values::<i32>(), Ok(vec!(10, 11, 9, 10, 7, 7, 7, 7)));
+    }
+
+    #[test]
+    fn quantized_images_should_be_scaled() {
+        let rows = vec!(gzip(&[0, 0, 0, 4, 0x80, 0, 0, 1]));
+        let (header, data) = compressed_hdu("GZIP_1", -32, 2, &rows, vec!(
+            ("ZQUANTIZ", string("NO_DITHER")), ("ZSCALE", Value::Real(0.5f64)), ("ZZERO", Value::Real(1f64)),
+            ("ZBLANK", Value::Integer(-2147483647i64)),
+        ));
+
+        let image = decompress(&HDU::with_data(header, &data)).unwrap();
+        let values = image.data_array().unwrap().values::<f32>().unwrap();
+
+        assert_eq!(values[0], 3f32);
+        assert!(values[1].is_nan());
+    }
+
+    #[test]
+    fn dithered_images_should_subtract_the_random_offsets() {
+        let rows = vec!(gzip(&[0, 0, 0, 4, 0, 0, 0, 6]));
+        let (header, data) = compressed_hdu("GZIP_1", -64, 2, &rows, vec!(
+            ("ZQUANTIZ", string("SUBTRACTIVE_DITHER_1")), ("ZDITHER0", Value::Integer(1i64)),
+            ("ZSCALE", Value::Real(0.5f64)), ("ZZERO", Value::Real(1f64)),
+        ));
+        let random = random_values();
+        let first = (random[0] * 500f64) as usize;
+
+        let image = decompress(&HDU::with_data(header, &data)).unwrap();
+
+        assert_eq!(image.data_array().unwrap().values::<f64>(), Ok(vec!(
+            (4f64 - random[first] + 0.5f64) * 0.5f64 + 1f64,
+            (6f64 - random[first + 1] + 0.5f64) * 0.5f64 + 1f64,
+        )));
+    }
+
+    #[test]
+    fn unsupported_images_should_be_refused() {
+        let rows = vec!(vec!(0u8));
+        let (header, data) = compressed_hdu("HCOMPRESS_1", 16, 1, &rows, vec!());
+        let (corrupt, corrupt_data) = compressed_hdu("RICE_1", 16, 1, &rows, vec!());
+
+        assert_eq!(decompress(&HDU::with_data(header, &data)), Err(CompressionError::UnsupportedAlgorithm("HCOMPRESS_1".to_string())));
+        assert_eq!(decompress(&HDU::with_data(corrupt, &corrupt_data)), Err(CompressionError::CorruptTile(0)));
+        assert_eq!(decompress(&HDU::new(Header::new(vec!()))), Err(CompressionError::NotCompressed));
+    }
+
+    #[test]
+    fn images_larger_than_memory_should_be_refused() {
+        let rows = vec!(vec!(0u8));
+        let (overflowing, data) = compressed_hdu("NOCOMPRESS", 64, 1 << 62, &rows, vec!());
+        let (huge, huge_data) = compressed_hdu("NOCOMPRESS", 64, 1 << 60, &rows, vec!());
+
+        assert_eq!(decompress(&HDU::with_data(overflowing, &data)), Err(CompressionError::TooLarge));
+        assert_eq!(decompress(&HDU::with_data(huge, &huge_data)), Err(CompressionError::TooLarge));
+    }
+}
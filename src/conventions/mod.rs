@@ -0,0 +1,119 @@
+//! The conventions module reports which optional conventions a FITS file uses.
+//!
+//! Besides the standard, many files rely on registered conventions that not
+//! every reader supports. `Conventions::of` tells which of them are in use, so
+//! that tooling can assess whether a file can be read downstream.
+
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use super::compression::is_compressed;
+use super::types::{Fits, HDU, HduKind, Keyword, Value};
+
+/// The length of the longest character string that fits in a single card.
+const MAXIMUM_STRING_LENGTH: usize = 68;
+
+/// The optional conventions that are used by a FITS file.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Conventions {
+    /// Character strings are continued over several cards with `CONTINUE`.
+    pub long_strings: bool,
+    /// Keywords longer than 8 characters are written with `HIERARCH`.
+    pub hierarch: bool,
+    /// Images are tile-compressed in binary tables.
+    pub tile_compression: bool,
+    /// HDUs carry `CHECKSUM` or `DATASUM`.
+    pub checksums: bool,
+    /// Binary tables store variable-length arrays in a heap.
+    pub heap: bool,
+}
+
+impl Conventions {
+    /// The conventions that are used by any HDU of `fits`.
+    pub fn of(fits: &Fits) -> Conventions {
+        fits.iter_hdus().fold(Conventions::default(), |conventions, hdu| {
+            let hdu = Conventions::of_hdu(hdu);
+            Conventions {
+                long_strings: conventions.long_strings || hdu.long_strings,
+                hierarch: conventions.hierarch || hdu.hierarch,
+                tile_compression: conventions.tile_compression || hdu.tile_compression,
+                checksums: conventions.checksums || hdu.checksums,
+                heap: conventions.heap || hdu.heap,
+            }
+        })
+    }
+
+    /// The conventions that are used by a single HDU.
+    pub fn of_hdu(hdu: &HDU) -> Conventions {
+        let records = &hdu.header.keyword_records;
+        Conventions {
+            long_strings: records.iter().any(|record| match *record.value() {
+                // Continued strings are joined when parsing, a quote takes two characters in a card.
+                Value::CharacterString(ref s) => s.len() + s.matches('\'').count() > MAXIMUM_STRING_LENGTH,
+                _ => *record.keyword() == Keyword::CONTINUE,
+            }),
+            hierarch: records.iter().any(|record| record.keyword().to_string().starts_with("HIERARCH")),
+            tile_compression: is_compressed(hdu),
+            checksums: records.iter().any(|record| *record.keyword() == Keyword::CHECKSUM || *record.keyword() == Keyword::DATASUM),
+            heap: hdu.header.kind() == HduKind::BinTable && hdu.header.get_int(&Keyword::PCOUNT).unwrap_or(0) > 0,
+        }
+    }
+
+    /// The names of the conventions that are used.
+    pub fn names(&self) -> Vec<&'static str> {
+        [
+            (self.long_strings, "long strings"),
+            (self.hierarch, "HIERARCH"),
+            (self.tile_compression, "tile compression"),
+            (self.checksums, "checksums"),
+            (self.heap, "heap"),
+        ].iter().filter(|&&(used, _)| used).map(|&(_, name)| name).collect()
+    }
+}
+
+impl Display for Conventions {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let names = self.names();
+        if names.is_empty() {
+            write!(f, "no optional conventions")
+        } else {
+            write!(f, "{}", names.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use super::super::checksum::compute_checksum;
+    use super::super::parser::parse;
+    use super::super::types::{Header, HDU, KeywordRecord, Keyword, Value};
+    use super::Conventions;
+
+    #[test]
+    fn conventions_should_be_reported_for_all_hdus_of_a_file() {
+        let data = include_bytes!("../../assets/images/k2-trappist1-unofficial-tpf-long-cadence.fits");
+        let mut fits = parse(data).unwrap();
+
+        assert_eq!(Conventions::of(&fits), Conventions { checksums: true, ..Conventions::default() });
+        fits.extensions[0].header.set(Keyword::PCOUNT, Value::Integer(1i64), Option::None);
+        assert_eq!(Conventions::of(&fits), Conventions { checksums: true, heap: true, ..Conventions::default() });
+    }
+
+    #[test]
+    fn conventions_should_report_long_strings_and_checksums() {
+        let long = "a".repeat(69);
+        let mut hdu = HDU::new(Header::new(vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(0i64), Option::None),
+            KeywordRecord::new(Keyword::OBJECT, Value::CharacterString(Cow::Borrowed(&long)), Option::None),
+        )));
+        compute_checksum(&mut hdu).unwrap();
+
+        let conventions = Conventions::of_hdu(&hdu);
+
+        assert_eq!(conventions, Conventions { long_strings: true, checksums: true, ..Conventions::default() });
+        assert_eq!(conventions.to_string(), "long strings, checksums");
+        assert_eq!(Conventions::default().to_string(), "no optional conventions");
+    }
+}
@@ -0,0 +1,405 @@
+//! The diff module compares two versions of a FITS file keyword by keyword.
+//!
+//! Records are paired by keyword. When a keyword occurs more than once in a
+//! header, the n-th occurrence in one version is paired with the n-th
+//! occurrence in the other version.
+//!
+//! For golden-file tests, `Fits::normalized` removes the differences that do
+//! not matter, e.g. the padding of strings or the date a file was written.
+//! `assert_fits_eq!` and `assert_header_eq!` compare normalized versions and
+//! report what differs.
+
+use std::borrow::Cow;
+use std::fmt::{Display, Formatter, Error};
+use super::types::{Fits, HDU, Header, KeywordRecord, Keyword, Value};
+
+/// A keyword-level change between two versions of a header.
+#[derive(Debug, PartialEq)]
+pub enum Change<'b, 'a: 'b> {
+    /// The record only occurs in the newer version.
+    Added(&'b KeywordRecord<'a>),
+    /// The record only occurs in the older version.
+    Removed(&'b KeywordRecord<'a>),
+    /// The record occurs in both versions, but its value or comment differs.
+    Modified {
+        /// The record in the older version.
+        before: &'b KeywordRecord<'a>,
+        /// The record in the newer version.
+        after: &'b KeywordRecord<'a>,
+    },
+}
+
+impl<'b, 'a: 'b> Change<'b, 'a> {
+    /// The keyword this change is about.
+    pub fn keyword(&self) -> &'b Keyword {
+        match *self {
+            Change::Added(record) | Change::Removed(record) => record.keyword(),
+            Change::Modified { after, .. } => after.keyword(),
+        }
+    }
+
+    /// Whether the value of the keyword changed.
+    pub fn value_changed(&self) -> bool {
+        match *self {
+            Change::Modified { before, after } => before.value() != after.value(),
+            _ => true,
+        }
+    }
+
+    /// Whether the comment of the keyword changed.
+    pub fn comment_changed(&self) -> bool {
+        match *self {
+            Change::Modified { before, after } => before.comment() != after.comment(),
+            _ => true,
+        }
+    }
+}
+
+impl<'b, 'a: 'b> Display for Change<'b, 'a> {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        match *self {
+            Change::Added(record) => {
+                write!(f, "{} added: {}", record.keyword(), record.value())?;
+                write_comment(f, record)
+            },
+            Change::Removed(record) => {
+                write!(f, "{} removed: {}", record.keyword(), record.value())?;
+                write_comment(f, record)
+            },
+            Change::Modified { before, after } => {
+                write!(f, "{}", after.keyword())?;
+                if self.value_changed() {
+                    write!(f, " value: {} -> {}", before.value(), after.value())?;
+                }
+                if self.comment_changed() {
+                    write!(f, " comment: \"{}\" -> \"{}\"",
+                           before.comment().unwrap_or(""), after.comment().unwrap_or(""))?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+fn write_comment(f: &mut Formatter, record: &KeywordRecord) -> Result<(), Error> {
+    match record.comment() {
+        Some(comment) => write!(f, " / {}", comment),
+        None => Ok(()),
+    }
+}
+
+/// The changes of a single HDU between two versions of a FITS file.
+#[derive(Debug, PartialEq)]
+pub struct HduDiff<'b, 'a: 'b> {
+    /// The index of the HDU, 0 being the primary HDU.
+    pub index: usize,
+    /// The keyword-level changes, in the order of the newer header.
+    pub changes: Vec<Change<'b, 'a>>,
+}
+
+impl<'b, 'a: 'b> Display for HduDiff<'b, 'a> {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        for change in &self.changes {
+            writeln!(f, "HDU {}: {}", self.index, change)?;
+        }
+        Ok(())
+    }
+}
+
+/// Determine the keyword-level changes between two versions of a header.
+pub fn diff_headers<'b, 'a: 'b>(before: &'b Header<'a>, after: &'b Header<'a>) -> Vec<Change<'b, 'a>> {
+    diff_records(&before.keyword_records, &after.keyword_records)
+}
+
+/// Determine the keyword-level changes per HDU between two versions of a FITS
+/// file. Only HDUs that changed are reported. An HDU that is present in only
+/// one of the versions is reported with all its records added or removed.
+pub fn diff_fits<'b, 'a: 'b>(before: &'b Fits<'a>, after: &'b Fits<'a>) -> Vec<HduDiff<'b, 'a>> {
+    let before_hdus = hdus(before);
+    let after_hdus = hdus(after);
+    let count = before_hdus.len().max(after_hdus.len());
+    (0..count)
+        .map(|index| {
+            let changes = diff_records(
+                records(before_hdus.get(index)),
+                records(after_hdus.get(index)));
+            HduDiff { index, changes }
+        })
+        .filter(|hdu_diff| !hdu_diff.changes.is_empty())
+        .collect()
+}
+
+/// Keywords whose values change whenever a file is written.
+pub const VOLATILE_KEYWORDS: [Keyword; 3] = [Keyword::CHECKSUM, Keyword::DATASUM, Keyword::DATE];
+
+impl<'a> Header<'a> {
+    /// A copy of this header that can be compared with other normalized
+    /// headers. Trailing spaces of strings are removed, as are `END` and the
+    /// `VOLATILE_KEYWORDS`. The mandatory keywords keep their order, the other
+    /// records are sorted by keyword.
+    pub fn normalized(&self) -> Header<'static> {
+        let (mut mandatory, mut others): (Vec<KeywordRecord<'static>>, Vec<KeywordRecord<'static>>) = self.keyword_records.iter()
+            .filter(|record| *record.keyword() != Keyword::END && !VOLATILE_KEYWORDS.contains(record.keyword()))
+            .map(|record| {
+                let mut record = record.clone().into_owned();
+                if let Value::CharacterString(ref value) = *record.value() {
+                    let trimmed = value.trim_end().to_string();
+                    record.set_value(Value::CharacterString(Cow::Owned(trimmed)));
+                }
+                record
+            })
+            .partition(|record| is_mandatory(record.keyword()));
+        others.sort_by_key(|record| record.keyword().to_string());
+        mandatory.append(&mut others);
+        Header::new(mandatory)
+    }
+}
+
+impl<'a> HDU<'a> {
+    /// A copy of this HDU with a normalized header, see `Header::normalized`.
+    pub fn normalized(&self) -> HDU<'static> {
+        let header = self.header.normalized();
+        HDU::with_owned_data(header, self.data().bytes().to_vec())
+    }
+}
+
+impl<'a> Fits<'a> {
+    /// A copy of this file with every HDU normalized, see `Header::normalized`.
+    pub fn normalized(&self) -> Fits<'static> {
+        Fits::new(self.primary_hdu.normalized(), self.extensions.iter().map(HDU::normalized).collect())
+    }
+}
+
+fn is_mandatory(keyword: &Keyword) -> bool {
+    matches!(*keyword,
+        Keyword::SIMPLE | Keyword::XTENSION | Keyword::BITPIX | Keyword::NAXIS | Keyword::NAXISn(_) |
+        Keyword::PCOUNT | Keyword::GCOUNT | Keyword::TFIELDS)
+}
+
+/// Describe how two files differ after normalization, `None` when they are
+/// equal. Used by `assert_fits_eq!`.
+pub fn normalized_difference(left: &Fits, right: &Fits) -> Option<String> {
+    let (left, right) = (left.normalized(), right.normalized());
+    if left == right {
+        return None
+    }
+    let mut description: String = diff_fits(&left, &right).iter().map(HduDiff::to_string).collect();
+    let (left_hdus, right_hdus) = (hdus(&left), hdus(&right));
+    for (index, (before, after)) in left_hdus.iter().zip(&right_hdus).enumerate() {
+        if before.data() != after.data() {
+            description.push_str(&format!("HDU {}: data differs\n", index));
+        }
+    }
+    if left_hdus.len() != right_hdus.len() {
+        description.push_str(&format!("{} HDUs -> {} HDUs\n", left_hdus.len(), right_hdus.len()));
+    }
+    Some(description)
+}
+
+/// Describe how two headers differ after normalization, `None` when they are
+/// equal. Used by `assert_header_eq!`.
+pub fn normalized_header_difference(left: &Header, right: &Header) -> Option<String> {
+    let (left, right) = (left.normalized(), right.normalized());
+    let changes = diff_headers(&left, &right);
+    if changes.is_empty() {
+        None
+    } else {
+        Some(changes.iter().map(|change| format!("{}\n", change)).collect())
+    }
+}
+
+/// Assert that two `Fits` are equal after normalization, see
+/// `Fits::normalized`. On failure the differences are reported.
+#[macro_export]
+macro_rules! assert_fits_eq {
+    ($left: expr, $right: expr) => {
+        if let Some(difference) = $crate::diff::normalized_difference(&$left, &$right) {
+            panic!("assertion failed: FITS files differ after normalization\n{}", difference)
+        }
+    };
+}
+
+/// Assert that two `Header`s are equal after normalization, see
+/// `Header::normalized`. On failure the differences are reported.
+#[macro_export]
+macro_rules! assert_header_eq {
+    ($left: expr, $right: expr) => {
+        if let Some(difference) = $crate::diff::normalized_header_difference(&$left, &$right) {
+            panic!("assertion failed: headers differ after normalization\n{}", difference)
+        }
+    };
+}
+
+fn hdus<'b, 'a: 'b>(fits: &'b Fits<'a>) -> Vec<&'b HDU<'a>> {
+    let mut hdus = vec!(&fits.primary_hdu);
+    hdus.extend(fits.extensions.iter());
+    hdus
+}
+
+fn records<'b, 'a: 'b>(hdu: Option<&&'b HDU<'a>>) -> &'b [KeywordRecord<'a>] {
+    match hdu {
+        Some(hdu) => &hdu.header.keyword_records,
+        None => &[],
+    }
+}
+
+fn diff_records<'b, 'a: 'b>(before: &'b [KeywordRecord<'a>], after: &'b [KeywordRecord<'a>]) -> Vec<Change<'b, 'a>> {
+    let mut paired = vec![false; before.len()];
+    let mut changes = vec!();
+    for (index, record) in after.iter().enumerate() {
+        let occurrence = occurrence(after, index);
+        match position_of_occurrence(before, record.keyword(), occurrence) {
+            Some(position) => {
+                paired[position] = true;
+                let previous = &before[position];
+                if previous.value() != record.value() || previous.comment() != record.comment() {
+                    changes.push(Change::Modified { before: previous, after: record });
+                }
+            },
+            None => changes.push(Change::Added(record)),
+        }
+    }
+    for (index, record) in before.iter().enumerate() {
+        if !paired[index] {
+            changes.push(Change::Removed(record));
+        }
+    }
+    changes
+}
+
+fn occurrence(records: &[KeywordRecord], index: usize) -> usize {
+    let keyword = records[index].keyword();
+    records[..index].iter().filter(|record| record.keyword() == keyword).count()
+}
+
+fn position_of_occurrence(records: &[KeywordRecord], keyword: &Keyword, occurrence: usize) -> Option<usize> {
+    records.iter()
+        .enumerate()
+        .filter(|&(_, record)| record.keyword() == keyword)
+        .map(|(position, _)| position)
+        .nth(occurrence)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use super::super::types::{Fits, HDU, Header, KeywordRecord, Keyword, Value};
+    use super::super::parser::parse;
+    use super::super::writer::write_fits;
+    use super::{diff_headers, diff_fits, normalized_difference, Change};
+
+    fn header<'a>(object: &'a str, comment: &'a str) -> Header<'a> {
+        Header::new(vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::OBJECT, Value::CharacterString(Cow::Borrowed(object)), Option::Some(comment)),
+        ))
+    }
+
+    #[test]
+    fn identical_headers_should_have_no_changes() {
+        let before = header("EPIC 200164267", "target");
+        let after = header("EPIC 200164267", "target");
+
+        assert_eq!(diff_headers(&before, &after), vec!());
+    }
+
+    #[test]
+    fn changed_value_should_be_reported() {
+        let before = header("EPIC 200164267", "target");
+        let after = header("TRAPPIST-1", "target");
+
+        let changes = diff_headers(&before, &after);
+
+        assert_eq!(changes.len(), 1);
+        assert!(changes[0].value_changed());
+        assert!(!changes[0].comment_changed());
+        assert_eq!(changes[0].to_string(), "OBJECT value: 'EPIC 200164267' -> 'TRAPPIST-1'");
+    }
+
+    #[test]
+    fn changed_comment_should_be_reported() {
+        let before = header("EPIC 200164267", "target");
+        let after = header("EPIC 200164267", "string version of target id");
+
+        let changes = diff_headers(&before, &after);
+
+        assert_eq!(changes.len(), 1);
+        assert!(!changes[0].value_changed());
+        assert_eq!(changes[0].to_string(), "OBJECT comment: \"target\" -> \"string version of target id\"");
+    }
+
+    #[test]
+    fn added_and_removed_records_should_be_reported() {
+        let before = Header::new(vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::ORIGIN, Value::CharacterString(Cow::Borrowed("NASA")), Option::None),
+        ));
+        let after = Header::new(vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::DATE, Value::CharacterString(Cow::Borrowed("2017-03-08")), Option::Some("file creation date.")),
+        ));
+
+        let changes = diff_headers(&before, &after);
+
+        assert_eq!(changes, vec!(
+            Change::Added(&after.keyword_records[1]),
+            Change::Removed(&before.keyword_records[1]),
+        ));
+        assert_eq!(changes[0].to_string(), "DATE added: '2017-03-08' / file creation date.");
+        assert_eq!(changes[1].to_string(), "ORIGIN removed: 'NASA    '");
+    }
+
+    #[test]
+    fn fits_diff_should_report_changes_per_hdu() {
+        let before = Fits::new(HDU::new(header("EPIC 200164267", "target")), vec!());
+        let after = Fits::new(
+            HDU::new(header("EPIC 200164267", "target")),
+            vec!(HDU::new(Header::new(vec!(
+                KeywordRecord::new(Keyword::XTENSION, Value::CharacterString(Cow::Borrowed("IMAGE")), Option::None),
+            )))));
+
+        let diffs = diff_fits(&before, &after);
+
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].index, 1);
+        assert_eq!(diffs[0].to_string(), "HDU 1: XTENSION added: 'IMAGE   '\n");
+    }
+
+    #[test]
+    fn normalized_files_should_ignore_padding_order_and_volatile_keywords() {
+        let data = [1u8, 2u8];
+        let records = |date: &'static str, object: &'static str| vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(1i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(2i64), Option::None),
+            KeywordRecord::new(Keyword::DATE, Value::CharacterString(Cow::Borrowed(date)), Option::None),
+            KeywordRecord::new(Keyword::OBJECT, Value::CharacterString(Cow::Borrowed(object)), Option::None),
+            KeywordRecord::new(Keyword::ORIGIN, Value::CharacterString(Cow::Borrowed("NASA")), Option::None),
+        );
+        let mut reordered = records("2017-03-09", "TRAPPIST-1  ");
+        reordered.swap(5, 6);
+        let original = Fits::new(HDU::with_data(Header::new(records("2017-03-08", "TRAPPIST-1")), &data), vec!());
+        let rewritten = Fits::new(HDU::with_data(Header::new(reordered), &data), vec!());
+
+        assert_fits_eq!(original, rewritten);
+        assert_header_eq!(original.primary_hdu.header, rewritten.primary_hdu.header);
+        assert_eq!(original.normalized().primary_hdu.header.keyword_records.len(), 6);
+
+        let changed = Fits::new(HDU::with_data(Header::new(records("2017-03-08", "EPIC 200164267")), &[1u8, 3u8]), vec!());
+
+        assert_eq!(normalized_difference(&original, &changed),
+            Some("HDU 0: OBJECT value: 'TRAPPIST-1' -> 'EPIC 200164267'\nHDU 0: data differs\n".to_string()));
+    }
+
+    #[test]
+    fn written_files_should_equal_the_original_after_normalization() {
+        let data = include_bytes!("../../assets/images/k2-trappist1-unofficial-tpf-long-cadence.fits");
+        let original = parse(data).unwrap();
+        let mut buffer: Vec<u8> = vec!();
+
+        write_fits(&mut buffer, &original).unwrap();
+
+        assert_fits_eq!(original, parse(&buffer).unwrap());
+    }
+}
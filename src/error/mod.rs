@@ -0,0 +1,193 @@
+//! The error module collects the problems of all modules in a single `Error`.
+//!
+//! Every module reports its own problems with a dedicated type, e.g.
+//! `AxisError` or `WriteError`. All of them convert into `Error`, so that an
+//! application can use `?` across modules.
+
+use std::error;
+use std::fmt::{Display, Formatter};
+use std::fmt;
+use std::io;
+use super::reader::ReadError;
+use super::types::{AxisError, BitpixError, DataArrayError, ExtensionError, ValueRetrievalError};
+use super::writer::WriteError;
+
+/// A problem with a FITS file, or with reading or writing it.
+#[derive(Debug)]
+pub enum Error {
+    /// The underlying reader or writer failed.
+    Io(io::Error),
+    /// The data ended before the structure it describes was complete.
+    UnexpectedEof {
+        /// Byte offset of the header or data unit that could not be completed.
+        offset: u64,
+    },
+    /// The header starting at this offset could not be parsed.
+    InvalidHeader {
+        /// Byte offset of the header.
+        offset: u64,
+    },
+    /// A card could not be parsed.
+    InvalidCard {
+        /// Byte offset of the card.
+        offset: u64,
+        /// The text of the card.
+        card: String,
+    },
+    /// The `BITPIX` keyword of the header starting at this offset is missing or invalid.
+    InvalidBitpix {
+        /// Byte offset of the header.
+        offset: u64,
+        /// What is wrong with `BITPIX`.
+        error: BitpixError,
+    },
+    /// The `NAXIS` and `NAXISn` keywords of the header starting at this offset
+    /// are inconsistent.
+    InvalidAxes {
+        /// Byte offset of the header.
+        offset: u64,
+        /// What is wrong with the axes.
+        error: AxisError,
+    },
+    /// The header starting at this offset has `SIMPLE = F`, i.e. the file does
+    /// not conform to the FITS standard.
+    NotConforming {
+        /// Byte offset of the header.
+        offset: u64,
+    },
+    /// The data unit of the HDU whose header starts at this offset is too
+    /// large to be addressed, or to be loaded in memory.
+    TooLarge {
+        /// Byte offset of the header.
+        offset: u64,
+    },
+    /// The HDU whose header starts at this offset does not contain an image.
+    NotAnImage {
+        /// Byte offset of the header.
+        offset: u64,
+    },
+    /// A value could not be retrieved from a header.
+    Value(ValueRetrievalError),
+    /// A data array could not be decoded.
+    DataArray(DataArrayError),
+    /// A FITS structure could not be written.
+    Write(WriteError),
+    /// The extensions of a FITS structure could not be changed.
+    Extension(ExtensionError),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref error) => write!(f, "{}", error),
+            Error::UnexpectedEof { offset } => write!(f, "unexpected end of file in the unit starting at byte {}", offset),
+            Error::InvalidHeader { offset } => write!(f, "invalid header at byte {}", offset),
+            Error::InvalidCard { offset, ref card } => write!(f, "invalid card at byte {}: \"{}\"", offset, card.trim_end()),
+            Error::InvalidBitpix { offset, ref error } => write!(f, "header at byte {}: {}", offset, error),
+            Error::InvalidAxes { offset, ref error } => write!(f, "header at byte {}: {}", offset, error),
+            Error::NotConforming { offset } => write!(f, "header at byte {}: SIMPLE = F, the file does not conform to the FITS standard", offset),
+            Error::TooLarge { offset } => write!(f, "the data unit of the header at byte {} is too large", offset),
+            Error::NotAnImage { offset } => write!(f, "the HDU with the header at byte {} does not contain an image", offset),
+            Error::Value(ref error) => write!(f, "{}", error),
+            Error::DataArray(ref error) => write!(f, "{}", error),
+            Error::Write(ref error) => write!(f, "{}", error),
+            Error::Extension(ref error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Io(ref error) => Some(error),
+            Error::InvalidBitpix { ref error, .. } => Some(error),
+            Error::InvalidAxes { ref error, .. } => Some(error),
+            Error::Value(ref error) => Some(error),
+            Error::DataArray(ref error) => Some(error),
+            Error::Write(ref error) => Some(error),
+            Error::Extension(ref error) => Some(error),
+            Error::UnexpectedEof { .. } | Error::InvalidHeader { .. } | Error::InvalidCard { .. } | Error::NotConforming { .. } | Error::TooLarge { .. } | Error::NotAnImage { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<ValueRetrievalError> for Error {
+    fn from(error: ValueRetrievalError) -> Self {
+        Error::Value(error)
+    }
+}
+
+impl From<DataArrayError> for Error {
+    fn from(error: DataArrayError) -> Self {
+        Error::DataArray(error)
+    }
+}
+
+impl From<ExtensionError> for Error {
+    fn from(error: ExtensionError) -> Self {
+        Error::Extension(error)
+    }
+}
+
+impl From<WriteError> for Error {
+    fn from(error: WriteError) -> Self {
+        match error {
+            WriteError::Io(error) => Error::Io(error),
+            error => Error::Write(error),
+        }
+    }
+}
+
+impl From<ReadError> for Error {
+    fn from(error: ReadError) -> Self {
+        match error {
+            ReadError::Io(error) => Error::Io(error),
+            ReadError::UnexpectedEof { offset } => Error::UnexpectedEof { offset },
+            ReadError::InvalidHeader { offset } => Error::InvalidHeader { offset },
+            ReadError::InvalidCard { offset, card } => Error::InvalidCard { offset, card },
+            ReadError::InvalidBitpix { offset, error } => Error::InvalidBitpix { offset, error },
+            ReadError::InvalidAxes { offset, error } => Error::InvalidAxes { offset, error },
+            ReadError::TooLarge { offset } => Error::TooLarge { offset },
+            ReadError::NotAnImage { offset } => Error::NotAnImage { offset },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use super::super::reader::ReadError;
+    use super::super::types::AxisError;
+    use super::Error;
+
+    #[test]
+    fn errors_should_describe_where_the_problem_is() {
+        let data = [
+            (Error::InvalidCard { offset: 160, card: format!("{:<80}", "HIERARCH ESO DET") },
+             "invalid card at byte 160: \"HIERARCH ESO DET\""),
+            (Error::InvalidAxes { offset: 5760, error: AxisError::MissingAxis(2u16) },
+             "header at byte 5760: NAXIS2 is missing"),
+            (Error::UnexpectedEof { offset: 2880 },
+             "unexpected end of file in the unit starting at byte 2880"),
+        ];
+
+        for (error, expected) in data.iter() {
+            assert_eq!(error.to_string(), *expected);
+        }
+    }
+
+    #[test]
+    fn read_errors_should_convert_into_errors() {
+        let io_error: Error = ReadError::Io(io::Error::other("disk on fire")).into();
+        let axes_error: Error = ReadError::InvalidAxes { offset: 0, error: AxisError::MissingNaxis }.into();
+
+        assert_eq!(io_error.to_string(), "disk on fire");
+        assert_eq!(axes_error.to_string(), "header at byte 0: NAXIS is missing");
+    }
+}
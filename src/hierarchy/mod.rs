@@ -0,0 +1,216 @@
+//! The hierarchy module exposes the structure of multi-extension files.
+//!
+//! Two conventions are supported. The `EXTLEVEL` keyword places extensions in
+//! a hierarchy, level 1 being the highest. The grouping convention collects
+//! HDUs in groups. A group is described by a grouping table, i.e. an extension
+//! with `EXTNAME = 'GROUPING'`, and its members refer to it with the `GRPIDn`
+//! and `GRPLCn` keywords.
+//!
+//! HDUs are identified by their index, 0 being the primary HDU.
+
+use super::types::{Fits, HDU, Header, Keyword, Value};
+
+/// An extension in the `EXTLEVEL` hierarchy, together with the extensions below it.
+#[derive(Debug, PartialEq)]
+pub struct Node {
+    /// The index of the HDU.
+    pub index: usize,
+    /// The extensions one level lower that follow this extension.
+    pub children: Vec<Node>,
+}
+
+/// Arrange the extensions of a FITS file according to their `EXTLEVEL`.
+///
+/// An extension without `EXTLEVEL` is at level 1, and so is an extension
+/// whose level is malformed, i.e. below 1 or `i64::MAX`, which leaves no
+/// level below it. An extension is placed below the closest preceding
+/// extension with a lower level.
+pub fn extension_hierarchy(fits: &Fits) -> Vec<Node> {
+    let levels: Vec<(usize, i64)> = fits.extensions.iter()
+        .enumerate()
+        .map(|(index, hdu)| {
+            let level = hdu.header.get_int(&Keyword::EXTLEVEL).ok()
+                .filter(|level| (1..i64::MAX).contains(level))
+                .unwrap_or(1i64);
+            (index + 1, level)
+        })
+        .collect();
+    let mut position = 0;
+    nodes(&levels, &mut position, 1i64)
+}
+
+fn nodes(levels: &[(usize, i64)], position: &mut usize, level: i64) -> Vec<Node> {
+    let mut result = vec!();
+    while *position < levels.len() && levels[*position].1 >= level {
+        let (index, own_level) = levels[*position];
+        *position += 1;
+        let children = nodes(levels, position, own_level + 1);
+        result.push(Node { index, children });
+    }
+    result
+}
+
+/// Membership of an HDU in a group, as described by `GRPIDn` and `GRPLCn`.
+#[derive(Debug, PartialEq)]
+pub struct GroupMembership<'b> {
+    /// The value of `GRPIDn`. A positive value is the `EXTVER` of a grouping
+    /// table in the same file, a negative value refers to a grouping table in
+    /// another file.
+    pub id: i64,
+    /// The value of `GRPLCn`, the location of a grouping table in another file.
+    pub location: Option<&'b str>,
+}
+
+/// The groups an HDU is a member of.
+pub fn memberships<'b>(header: &'b Header) -> Vec<GroupMembership<'b>> {
+    header.keyword_records.iter()
+        .filter_map(|record| {
+            match (record.keyword(), record.value()) {
+                (&Keyword::GRPIDn(n), &Value::Integer(id)) => {
+                    let location = header.get_str(&Keyword::GRPLCn(n)).ok();
+                    Some(GroupMembership { id, location })
+                },
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// A grouping table in a FITS file, together with the HDUs in the same file
+/// that are a member of it.
+#[derive(Debug, PartialEq)]
+pub struct Group<'b> {
+    /// The index of the grouping table.
+    pub index: usize,
+    /// The value of `GRPNAME`, the name of the group.
+    pub name: Option<&'b str>,
+    /// The value of `EXTVER` of the grouping table, by which members refer to it.
+    pub version: i64,
+    /// The indices of the HDUs that are a member of this group.
+    pub members: Vec<usize>,
+}
+
+/// All grouping tables in a FITS file.
+pub fn groups<'b>(fits: &'b Fits) -> Vec<Group<'b>> {
+    let hdus: Vec<&'b HDU> = Some(&fits.primary_hdu).into_iter().chain(fits.extensions.iter()).collect();
+    hdus.iter()
+        .enumerate()
+        .filter(|&(_, hdu)| is_grouping_table(&hdu.header))
+        .map(|(index, hdu)| {
+            let version = hdu.header.get_int(&Keyword::EXTVER).unwrap_or(1i64);
+            let members = hdus.iter()
+                .enumerate()
+                .filter(|&(_, member)| {
+                    memberships(&member.header).iter().any(|membership| membership.id == version)
+                })
+                .map(|(member_index, _)| member_index)
+                .collect();
+            Group {
+                index,
+                name: hdu.header.get_trimmed_str(&Keyword::GRPNAME).ok(),
+                version,
+                members,
+            }
+        })
+        .collect()
+}
+
+fn is_grouping_table(header: &Header) -> bool {
+    header.get_str(&Keyword::EXTNAME)
+        .map(|name| name.trim_end() == "GROUPING")
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use super::super::types::{Fits, HDU, Header, KeywordRecord, Keyword, Value};
+    use super::{extension_hierarchy, memberships, groups, Node, Group, GroupMembership};
+
+    fn primary<'a>() -> HDU<'a> {
+        HDU::new(Header::new(vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+        )))
+    }
+
+    fn extension<'a>(records: Vec<KeywordRecord<'a>>) -> HDU<'a> {
+        let mut all = vec!(KeywordRecord::new(Keyword::XTENSION, Value::CharacterString(Cow::Borrowed("IMAGE")), Option::None));
+        all.extend(records);
+        HDU::new(Header::new(all))
+    }
+
+    fn level<'a>(level: i64) -> HDU<'a> {
+        extension(vec!(KeywordRecord::new(Keyword::EXTLEVEL, Value::Integer(level), Option::None)))
+    }
+
+    #[test]
+    fn extensions_without_extlevel_should_be_at_the_top() {
+        let fits = Fits::new(primary(), vec!(extension(vec!()), extension(vec!())));
+
+        assert_eq!(extension_hierarchy(&fits), vec!(
+            Node { index: 1, children: vec!() },
+            Node { index: 2, children: vec!() },
+        ));
+    }
+
+    #[test]
+    fn extensions_should_be_placed_below_a_preceding_higher_level() {
+        let fits = Fits::new(primary(), vec!(level(1), level(2), level(3), level(2), level(1)));
+
+        assert_eq!(extension_hierarchy(&fits), vec!(
+            Node { index: 1, children: vec!(
+                Node { index: 2, children: vec!(
+                    Node { index: 3, children: vec!() },
+                )},
+                Node { index: 4, children: vec!() },
+            )},
+            Node { index: 5, children: vec!() },
+        ));
+    }
+
+    #[test]
+    fn malformed_levels_should_be_at_the_top() {
+        let fits = Fits::new(primary(), vec!(level(i64::MAX), level(2), level(0), level(i64::MAX - 1)));
+
+        assert_eq!(extension_hierarchy(&fits), vec!(
+            Node { index: 1, children: vec!(
+                Node { index: 2, children: vec!() },
+            )},
+            Node { index: 3, children: vec!(
+                Node { index: 4, children: vec!() },
+            )},
+        ));
+    }
+
+    #[test]
+    fn memberships_should_pair_grpid_with_grplc() {
+        let header = Header::new(vec!(
+            KeywordRecord::new(Keyword::GRPIDn(1u16), Value::Integer(1i64), Option::None),
+            KeywordRecord::new(Keyword::GRPIDn(2u16), Value::Integer(-1i64), Option::None),
+            KeywordRecord::new(Keyword::GRPLCn(2u16), Value::CharacterString(Cow::Borrowed("other.fits")), Option::None),
+        ));
+
+        assert_eq!(memberships(&header), vec!(
+            GroupMembership { id: 1i64, location: None },
+            GroupMembership { id: -1i64, location: Some("other.fits") },
+        ));
+    }
+
+    #[test]
+    fn groups_should_collect_members_of_grouping_tables() {
+        let fits = Fits::new(primary(), vec!(
+            extension(vec!(
+                KeywordRecord::new(Keyword::EXTNAME, Value::CharacterString(Cow::Borrowed("GROUPING")), Option::None),
+                KeywordRecord::new(Keyword::EXTVER, Value::Integer(2i64), Option::None),
+                KeywordRecord::new(Keyword::GRPNAME, Value::CharacterString(Cow::Borrowed("CCDS    ")), Option::None),
+            )),
+            extension(vec!(KeywordRecord::new(Keyword::GRPIDn(1u16), Value::Integer(2i64), Option::None))),
+            extension(vec!(KeywordRecord::new(Keyword::GRPIDn(1u16), Value::Integer(3i64), Option::None))),
+            extension(vec!(KeywordRecord::new(Keyword::GRPIDn(1u16), Value::Integer(2i64), Option::None))),
+        ));
+
+        assert_eq!(groups(&fits), vec!(
+            Group { index: 1, name: Some("CCDS"), version: 2i64, members: vec!(2, 4) },
+        ));
+    }
+}
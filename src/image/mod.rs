@@ -0,0 +1,335 @@
+//! The image module builds image HDUs from values in memory and reads the
+//! images of existing HDUs.
+//!
+//! `ImageHdu::from_data` writes the mandatory keywords itself, so that only
+//! the shape and the values of an image need to be known. The shape lists the
+//! axes like `NAXISn` does, i.e. the first axis varies fastest.
+//!
+//! `ImageHdu::section` copies a subregion of an image, e.g. a cutout around a
+//! source, without decoding the rest of the image.
+//!
+//! `ImageHdu::from_unsigned` stores unsigned integers with the offset in
+//! `BZERO`, as the standard prescribes; `DataArray::unsigned_values` reads them
+//! back without the wraparound arithmetic.
+//!
+//! `ImageHdu::read_image_into` decodes an image into an existing buffer, so
+//! that rereading a frame, e.g. in a monitoring loop, does not allocate.
+
+use std::error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
+use super::builder::HeaderBuilder;
+use super::types::{Bitpix, DataArray, DataArrayError, HDU, HduKind, Keyword, KeywordRecord, Pixel, Scaling, Unsigned, Value};
+use super::writer::WriteError;
+
+/// Problems that occur when building or reading an image.
+#[derive(Debug)]
+pub enum ImageError {
+    /// The HDU is neither a primary HDU with an array nor an `IMAGE` extension.
+    NotAnImage,
+    /// The HDU has no data array.
+    MissingData,
+    /// The type of the values does not correspond with the requested `BITPIX`.
+    BitpixMismatch {
+        /// The requested `BITPIX`.
+        expected: Bitpix,
+        /// The `BITPIX` of the type of the values.
+        actual: Bitpix,
+    },
+    /// The number of values does not correspond with the shape.
+    ShapeMismatch {
+        /// The number of values the shape describes.
+        expected: usize,
+        /// The number of values given.
+        actual: usize,
+    },
+    /// A record could not be added to the header.
+    Header(WriteError),
+    /// The number of ranges of a section differs from the number of axes.
+    DimensionMismatch {
+        /// The number of axes of the image.
+        expected: usize,
+        /// The number of ranges.
+        actual: usize,
+    },
+    /// The range for `NAXISn` of a section does not lie within the axis.
+    OutOfRange(usize),
+    /// The values of the image could not be decoded.
+    Data(DataArrayError),
+}
+
+impl Display for ImageError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            ImageError::NotAnImage => write!(f, "the HDU does not contain an image"),
+            ImageError::MissingData => write!(f, "the HDU has no data array"),
+            ImageError::BitpixMismatch { expected, actual } =>
+                write!(f, "values with BITPIX {} can not be stored with BITPIX {}", actual.value(), expected.value()),
+            ImageError::ShapeMismatch { expected, actual } =>
+                write!(f, "the shape describes {} values, but {} are given", expected, actual),
+            ImageError::Header(ref error) => write!(f, "{}", error),
+            ImageError::DimensionMismatch { expected, actual } =>
+                write!(f, "the image has {} axes, but the section has {} ranges", expected, actual),
+            ImageError::OutOfRange(axis) => write!(f, "the range of the section does not lie within NAXIS{}", axis),
+            ImageError::Data(ref error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl error::Error for ImageError {}
+
+impl From<WriteError> for ImageError {
+    fn from(error: WriteError) -> Self {
+        ImageError::Header(error)
+    }
+}
+
+impl From<DataArrayError> for ImageError {
+    fn from(error: DataArrayError) -> Self {
+        ImageError::Data(error)
+    }
+}
+
+/// The image of an HDU, borrowing its data.
+#[derive(Debug)]
+pub struct ImageHdu<'i> {
+    bitpix: Bitpix,
+    axes: Vec<usize>,
+    scaling: Scaling,
+    data: &'i [u8],
+}
+
+impl<'i> ImageHdu<'i> {
+    /// Build an `IMAGE` extension with axes `shape` that holds `data`. The
+    /// `records` follow the mandatory keywords. A file starts with a primary
+    /// HDU, e.g. the one of `Fits::minimal`, which the extension can follow.
+    ///
+    /// Fails when `bitpix` is not the `BITPIX` of `T`, when `data` does not
+    /// have as many values as `shape` describes, or when `records` contains a
+    /// mandatory keyword.
+    pub fn from_data<'a, T: Pixel + Copy>(shape: &[usize], bitpix: Bitpix, data: &[T], records: Vec<KeywordRecord<'a>>) -> Result<HDU<'a>, ImageError> {
+        if T::BITPIX != bitpix {
+            return Err(ImageError::BitpixMismatch { expected: bitpix, actual: T::BITPIX })
+        }
+        let expected = if shape.is_empty() { 0 } else { shape.iter().product() };
+        if expected != data.len() {
+            return Err(ImageError::ShapeMismatch { expected, actual: data.len() })
+        }
+        let header = records.into_iter()
+            .fold(HeaderBuilder::extension("IMAGE", bitpix, shape, 0, 1), HeaderBuilder::keyword_record)
+            .build()?;
+        let mut bytes = Vec::with_capacity(data.len() * bitpix.bytes());
+        for value in data {
+            value.extend_big_endian(&mut bytes);
+        }
+        Ok(HDU::with_owned_data(header, bytes))
+    }
+
+    /// Build an `IMAGE` extension that holds unsigned integers, e.g. `u16`
+    /// values as `BITPIX = 16` with `BZERO = 32768`. The `records` follow the
+    /// mandatory keywords, `BSCALE` and `BZERO`.
+    pub fn from_unsigned<'a, T: Unsigned + Copy>(shape: &[usize], data: &[T], records: Vec<KeywordRecord<'a>>) -> Result<HDU<'a>, ImageError> {
+        // The offset of `u64` does not fit in an integer value.
+        let bzero = if T::BZERO <= i64::MAX as f64 { Value::Integer(T::BZERO as i64) } else { Value::Real(T::BZERO) };
+        let mut scaling = vec!(
+            KeywordRecord::new(Keyword::BSCALE, Value::Integer(1i64), Option::Some("default scaling factor")),
+            KeywordRecord::new(Keyword::BZERO, bzero, Option::Some("offset data range to that of unsigned integers")),
+        );
+        scaling.extend(records);
+        let stored: Vec<T::Stored> = data.iter().map(|value| value.to_stored()).collect();
+        ImageHdu::from_data(shape, T::Stored::BITPIX, &stored, scaling)
+    }
+
+    /// Read the image of `hdu`, i.e. of a primary HDU that does not contain
+    /// random groups or of an `IMAGE` extension.
+    pub fn from_hdu<'a>(hdu: &'i HDU<'a>) -> Result<ImageHdu<'i>, ImageError> {
+        let header = &hdu.header;
+        if header.kind() != HduKind::Image {
+            return Err(ImageError::NotAnImage)
+        }
+        let data_array = hdu.data_array().ok_or(ImageError::MissingData)?;
+        Ok(ImageHdu {
+            bitpix: data_array.bitpix(),
+            axes: data_array.axes().to_vec(),
+            scaling: data_array.scaling(),
+            data: data_array.bytes(),
+        })
+    }
+
+    /// The type of the values of the image.
+    pub fn bitpix(&self) -> Bitpix {
+        self.bitpix
+    }
+
+    /// The axes of the image, i.e. `NAXIS1` up to `NAXISn`.
+    pub fn axes(&self) -> &[usize] {
+        &self.axes
+    }
+
+    /// The values of the image as a data array, e.g. to decode them.
+    pub fn data_array(&self) -> DataArray<'i> {
+        DataArray::new(self.bitpix, self.axes.clone(), self.data).with_scaling(self.scaling)
+    }
+
+    /// Decode the values of the image into `buffer`, replacing its contents.
+    /// The type `T` should correspond with `BITPIX`. Once `buffer` has grown
+    /// to the size of the image, rereading an image of that size reuses its
+    /// allocation.
+    pub fn read_image_into<T: Pixel>(&self, buffer: &mut Vec<T>) -> Result<(), ImageError> {
+        Ok(self.data_array().values_into(buffer)?)
+    }
+
+    /// Copy the subregion of the image given by a range for each axis, the
+    /// range of `NAXIS1` first. Only the bytes of the subregion are read.
+    pub fn section(&self, ranges: &[Range<usize>]) -> Result<DataArray<'static>, ImageError> {
+        if ranges.len() != self.axes.len() {
+            return Err(ImageError::DimensionMismatch { expected: self.axes.len(), actual: ranges.len() })
+        }
+        for (index, (range, axis)) in ranges.iter().zip(&self.axes).enumerate() {
+            if range.start > range.end || range.end > *axis {
+                return Err(ImageError::OutOfRange(index + 1))
+            }
+        }
+        let axes: Vec<usize> = ranges.iter().map(ExactSizeIterator::len).collect();
+        let size = self.bitpix.bytes();
+        let mut data = Vec::with_capacity(axes.iter().product::<usize>() * size);
+        if !axes.is_empty() && axes.iter().all(|axis| *axis > 0) {
+            let mut strides = vec!(size);
+            for axis in &self.axes[..self.axes.len() - 1] {
+                let stride = strides[strides.len() - 1] * axis;
+                strides.push(stride);
+            }
+            let run = axes[0] * size;
+            let mut index: Vec<usize> = ranges.iter().map(|range| range.start).collect();
+            loop {
+                let offset: usize = index.iter().zip(&strides).map(|(i, stride)| i * stride).sum();
+                data.extend_from_slice(&self.data[offset..offset + run]);
+                // Advance to the next run, like an odometer over the other axes.
+                let mut axis = 1;
+                while axis < index.len() {
+                    index[axis] += 1;
+                    if index[axis] < ranges[axis].end {
+                        break
+                    }
+                    index[axis] = ranges[axis].start;
+                    axis += 1;
+                }
+                if axis == index.len() {
+                    break
+                }
+            }
+        }
+        Ok(DataArray::from_vec(self.bitpix, axes, data).with_scaling(self.scaling))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use super::super::parser::parse;
+    use super::super::testgen;
+    use super::super::types::{Bitpix, DataArrayError, Fits, KeywordRecord, Keyword, Value};
+    use super::super::writer::{write_fits, WriteError};
+    use super::{ImageError, ImageHdu};
+
+    #[test]
+    fn images_should_be_built_from_values() {
+        let values = [1i16, -2i16, 3i16, -4i16, 5i16, -6i16];
+        let records = vec!(KeywordRecord::new(Keyword::OBJECT, Value::CharacterString(Cow::Borrowed("TRAPPIST-1")), Option::None));
+        let mut fits = Fits::minimal();
+        fits.push_extension(ImageHdu::from_data(&[3, 2], Bitpix::I16, &values, records).unwrap());
+        let mut buffer: Vec<u8> = vec!();
+
+        write_fits(&mut buffer, &fits).unwrap();
+
+        let parsed = parse(&buffer).unwrap();
+        let image = ImageHdu::from_hdu(&parsed.extensions[0]).unwrap();
+        assert_eq!(image.axes(), &[3usize, 2usize]);
+        assert_eq!(image.data_array().values::<i16>().unwrap(), values.to_vec());
+        assert_eq!(parsed.extensions[0].header.get_trimmed_str(&Keyword::OBJECT), Ok("TRAPPIST-1"));
+        assert!(matches!(ImageHdu::from_hdu(&parsed.primary_hdu), Err(ImageError::MissingData)));
+    }
+
+    #[test]
+    fn unsigned_images_should_be_stored_with_an_offset() {
+        let small = [0u16, 1u16, 32768u16, 65535u16];
+        let large = [0u64, 1u64, 1u64 << 63, u64::MAX];
+        let mut fits = Fits::minimal();
+        fits.push_extension(ImageHdu::from_unsigned(&[2, 2], &small, vec!()).unwrap());
+        fits.push_extension(ImageHdu::from_unsigned(&[4], &large, vec!()).unwrap());
+        let mut buffer: Vec<u8> = vec!();
+
+        write_fits(&mut buffer, &fits).unwrap();
+
+        let parsed = parse(&buffer).unwrap();
+        let image = ImageHdu::from_hdu(&parsed.extensions[0]).unwrap();
+        assert_eq!(image.bitpix(), Bitpix::I16);
+        assert_eq!(image.data_array().values::<i16>().unwrap(), vec!(-32768, -32767, 0, 32767));
+        assert_eq!(image.data_array().unsigned_values::<u16>().unwrap(), small.to_vec());
+        assert_eq!(image.data_array().physical_values().unwrap(), vec!(0f64, 1f64, 32768f64, 65535f64));
+        assert!(matches!(image.data_array().unsigned_values::<u32>(), Err(DataArrayError::NotUnsigned)));
+        let image = ImageHdu::from_hdu(&parsed.extensions[1]).unwrap();
+        assert_eq!(image.data_array().unsigned_values::<u64>().unwrap(), large.to_vec());
+    }
+
+    #[test]
+    fn building_an_image_should_fail_fast() {
+        let values = [1f32, 2f32, 3f32];
+        let naxis = vec!(KeywordRecord::new(Keyword::NAXIS, Value::Integer(1i64), Option::None));
+
+        assert!(matches!(ImageHdu::from_data(&[2, 2], Bitpix::F32, &values, vec!()),
+            Err(ImageError::ShapeMismatch { expected: 4, actual: 3 })));
+        assert!(matches!(ImageHdu::from_data(&[3], Bitpix::F64, &values, vec!()),
+            Err(ImageError::BitpixMismatch { expected: Bitpix::F64, actual: Bitpix::F32 })));
+        assert!(matches!(ImageHdu::from_data(&[3], Bitpix::F32, &values, naxis),
+            Err(ImageError::Header(WriteError::MisplacedKeyword(Keyword::NAXIS)))));
+    }
+
+    #[test]
+    fn images_should_be_read_into_a_reused_buffer() {
+        let hdu = testgen::image_extension(Bitpix::I32, &[11, 13], vec!()).unwrap();
+        let image = ImageHdu::from_hdu(&hdu).unwrap();
+        let mut buffer: Vec<i32> = vec!();
+
+        image.read_image_into(&mut buffer).unwrap();
+        let capacity = buffer.capacity();
+        let first = buffer.as_ptr();
+        image.read_image_into(&mut buffer).unwrap();
+
+        assert_eq!(buffer, image.data_array().values::<i32>().unwrap());
+        assert_eq!(buffer.capacity(), capacity);
+        assert_eq!(buffer.as_ptr(), first);
+        assert!(matches!(image.read_image_into(&mut Vec::<f32>::new()), Err(ImageError::Data(_))));
+    }
+
+    #[test]
+    fn sections_should_copy_a_subregion() {
+        let values: Vec<i32> = (0..24).collect();
+        let hdu = ImageHdu::from_data(&[4, 3, 2], Bitpix::I32, &values, vec!()).unwrap();
+        let image = ImageHdu::from_hdu(&hdu).unwrap();
+
+        let section = image.section(&[1..3, 0..2, 1..2]).unwrap();
+
+        assert_eq!(section.axes(), &[2usize, 2usize, 1usize]);
+        assert_eq!(section.values::<i32>().unwrap(), vec!(13, 14, 17, 18));
+        assert_eq!(image.section(&[0..4, 0..3, 0..2]).unwrap().values::<i32>().unwrap(), values);
+        assert_eq!(image.section(&[0..0, 0..3, 0..2]).unwrap().bytes().len(), 0);
+        assert!(matches!(image.section(&[0..4, 2..4, 0..1]), Err(ImageError::OutOfRange(2))));
+        assert!(matches!(image.section(&[0..4, 0..3]), Err(ImageError::DimensionMismatch { expected: 3, actual: 2 })));
+    }
+
+    #[test]
+    fn sections_should_cut_out_an_image() {
+        let hdu = testgen::image_extension(Bitpix::I32, &[11, 13], vec!()).unwrap();
+        let image = ImageHdu::from_hdu(&hdu).unwrap();
+        let values = image.data_array().values::<i32>().unwrap();
+        let width = image.axes()[0];
+
+        let cutout = image.section(&[2..5, 3..7]).unwrap().values::<i32>().unwrap();
+
+        assert_eq!(cutout.len(), 12);
+        assert_eq!(cutout[0], values[3 * width + 2]);
+        assert_eq!(cutout[11], values[6 * width + 4]);
+    }
+}
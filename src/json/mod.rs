@@ -0,0 +1,141 @@
+//! The json module encodes header contents as JSON, so that tools built on
+//! this crate can emit output that is easily scripted.
+//!
+//! Every keyword record becomes a single object, which makes the output
+//! suitable for JSON lines, i.e. one object per line.
+
+use super::schema::KeywordSchema;
+use super::types::{KeywordRecord, Value};
+use super::validator::Finding;
+
+/// Encode a `str` as a JSON string, including the quotes.
+pub fn string(s: &str) -> String {
+    let mut result = String::with_capacity(s.len() + 2);
+    result.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+/// Encode a value. Character strings lose their insignificant trailing spaces,
+/// complex numbers become a pair and an undefined value becomes `null`.
+pub fn value(value: &Value) -> String {
+    match *value {
+        Value::CharacterString(ref s) => string(s.trim_end()),
+        Value::Logical(b) => b.to_string(),
+        Value::Integer(n) => n.to_string(),
+        Value::Real(r) => real(r),
+        Value::Complex((re, im)) => format!("[{},{}]", real(re), real(im)),
+        Value::Undefined => "null".to_string(),
+        Value::CommentText(ref text) | Value::Unparsed(ref text) => string(text),
+    }
+}
+
+fn real(r: f64) -> String {
+    if r.is_finite() { r.to_string() } else { "null".to_string() }
+}
+
+/// Encode a keyword record of the HDU with index `hdu` as a single object,
+/// with the fields `hdu`, `keyword`, `value` and `comment`.
+pub fn record(hdu: usize, record: &KeywordRecord) -> String {
+    format!("{{\"hdu\":{},\"keyword\":{},\"value\":{},\"comment\":{}}}",
+            hdu,
+            string(&record.keyword().to_string()),
+            value(record.value()),
+            record.comment().map(string).unwrap_or_else(|| "null".to_string()))
+}
+
+/// Encode a finding of the validator about the HDU with index `hdu` as a
+/// single object, with the fields `hdu`, `severity`, `rule`, `card`, `offset`
+/// and `message`.
+pub fn finding(hdu: usize, finding: &Finding) -> String {
+    format!("{{\"hdu\":{},\"severity\":{},\"rule\":{},\"card\":{},\"offset\":{},\"message\":{}}}",
+            hdu,
+            string(&finding.severity.to_string()),
+            string(&finding.rule),
+            finding.card.map(|card| card.to_string()).unwrap_or_else(|| "null".to_string()),
+            finding.offset.map(|offset| offset.to_string()).unwrap_or_else(|| "null".to_string()),
+            string(&finding.message))
+}
+
+/// Encode what a schema expects of a keyword as a single object, with the
+/// fields `keyword`, `type`, `frequency` and `range`, the smallest and
+/// largest value as a pair. A mix of types is `null`, as is a missing range.
+pub fn keyword_schema(schema: &KeywordSchema) -> String {
+    format!("{{\"keyword\":{},\"type\":{},\"frequency\":{},\"range\":{}}}",
+            string(&schema.keyword.to_string()),
+            schema.value_type.map(|value_type| string(&value_type.to_string())).unwrap_or_else(|| "null".to_string()),
+            real(schema.frequency),
+            schema.range.map(|(min, max)| format!("[{},{}]", real(min), real(max))).unwrap_or_else(|| "null".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use super::super::schema::{KeywordSchema, ValueType};
+    use super::super::types::{KeywordRecord, Keyword, Value};
+    use super::super::validator::{Finding, Severity};
+    use super::{string, value, record, finding, keyword_schema};
+
+    #[test]
+    fn string_should_escape_special_characters() {
+        assert_eq!(string("a \"quoted\" \\ path\n"), "\"a \\\"quoted\\\" \\\\ path\\n\"");
+        assert_eq!(string("\u{1}"), "\"\\u0001\"");
+    }
+
+    #[test]
+    fn values_should_be_encoded_by_type() {
+        let data = [
+            (Value::CharacterString(Cow::Borrowed("K2      ")), "\"K2\""),
+            (Value::Logical(true), "true"),
+            (Value::Integer(-32i64), "-32"),
+            (Value::Real(1.5f64), "1.5"),
+            (Value::Complex((1f64, -2f64)), "[1,-2]"),
+            (Value::Undefined, "null"),
+        ];
+
+        for (input, expected) in data.iter() {
+            assert_eq!(value(input), *expected);
+        }
+    }
+
+    #[test]
+    fn record_should_be_a_single_object() {
+        let with_comment = KeywordRecord::new(Keyword::NAXIS, Value::Integer(0i64), Option::Some("number of array dimensions"));
+        let without_comment = KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None);
+
+        assert_eq!(record(0, &with_comment),
+                   "{\"hdu\":0,\"keyword\":\"NAXIS\",\"value\":0,\"comment\":\"number of array dimensions\"}");
+        assert_eq!(record(2, &without_comment),
+                   "{\"hdu\":2,\"keyword\":\"SIMPLE\",\"value\":true,\"comment\":null}");
+    }
+
+    #[test]
+    fn finding_should_be_a_single_object() {
+        let about_card = Finding::new(Severity::Warning, "duplicate-keyword", "OBJECT occurs more than once").at(4);
+
+        assert_eq!(finding(1, &about_card),
+                   "{\"hdu\":1,\"severity\":\"warning\",\"rule\":\"duplicate-keyword\",\"card\":4,\"offset\":null,\"message\":\"OBJECT occurs more than once\"}");
+        assert_eq!(finding(0, &about_card.at_offset(320)),
+                   "{\"hdu\":0,\"severity\":\"warning\",\"rule\":\"duplicate-keyword\",\"card\":4,\"offset\":320,\"message\":\"OBJECT occurs more than once\"}");
+    }
+
+    #[test]
+    fn keyword_schema_should_be_a_single_object() {
+        let exposure = KeywordSchema { keyword: Keyword::EXPOSURE, value_type: Some(ValueType::Real), range: Some((12.5f64, 60f64)), frequency: 0.5f64 };
+        let object = KeywordSchema { keyword: Keyword::OBJECT, value_type: None, range: None, frequency: 1f64 };
+
+        assert_eq!(keyword_schema(&exposure), "{\"keyword\":\"EXPOSURE\",\"type\":\"real\",\"frequency\":0.5,\"range\":[12.5,60]}");
+        assert_eq!(keyword_schema(&object), "{\"keyword\":\"OBJECT\",\"type\":null,\"frequency\":1,\"range\":null}");
+    }
+}
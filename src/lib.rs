@@ -4,12 +4,61 @@
 //! The *Flexible Image Transport System* ([FITS](https://en.wikipedia.org/wiki/FITS)) is
 //! > an open standard defining a digital file format useful for storage,
 //! > transmission and processing of scientific and other images.
+//!
+//! Files are parsed by `parser::parse` into a `types::Fits`, whose HDUs carry a
+//! `types::Header` and a data unit. `table::BinaryTable` reads the columns of
+//! a binary table and `writer::write_fits` writes a `Fits` back. Their
+//! documentation starts with an example.
 
 #[macro_use]
 extern crate nom;
+extern crate flate2;
+#[cfg(feature = "chrono")]
+extern crate chrono;
+#[cfg(feature = "ndarray")]
+extern crate ndarray;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "tokio")]
+extern crate tokio;
+#[cfg(test)]
+#[macro_use]
+extern crate quickcheck;
 
+#[cfg(feature = "ndarray")]
+pub mod array;
+pub mod builder;
+pub mod ccd;
+pub mod checksum;
+pub mod compression;
+pub mod conventions;
+pub mod diff;
+pub mod error;
+pub mod hierarchy;
+pub mod image;
+pub mod json;
+pub mod lightcurve;
+pub mod mosaic;
+pub mod ogip;
 pub mod parser;
+pub mod pretty;
+pub mod random_groups;
+pub mod reader;
+pub mod schema;
+pub mod section;
+#[cfg(feature = "serde")]
+pub mod serialization;
+pub mod table;
+#[cfg(any(test, feature = "testgen"))]
+pub mod testgen;
+pub mod time;
 pub mod types;
+pub mod units;
+pub mod validator;
+pub mod wcs;
+pub mod writer;
 
 #[cfg(test)]
 mod tests {
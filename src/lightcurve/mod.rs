@@ -0,0 +1,257 @@
+//! The lightcurve module prepares light curves, i.e. flux measured over time,
+//! for analysis.
+//!
+//! Light curves of K2 and similar missions are sampled at a fixed cadence,
+//! interrupted by gaps, and contain `NaN` for cadences without a measurement.
+//! The reductions below skip `NaN` flux.
+//!
+//! A Lomb-Scargle periodogram finds periodic signals, e.g. transits or
+//! rotation, in such unevenly sampled measurements.
+
+use std::f64::consts::PI;
+
+/// A series of flux measurements, ordered by time.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LightCurve {
+    /// The times of the measurements, e.g. the `TIME` column of a K2 target pixel file.
+    pub time: Vec<f64>,
+    /// The flux of the measurements, one for every time.
+    pub flux: Vec<f64>,
+}
+
+/// An interval without measurements.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Gap {
+    /// The time of the last measurement before the gap.
+    pub start: f64,
+    /// The time of the first measurement after the gap.
+    pub end: f64,
+}
+
+impl Gap {
+    /// The length of this gap.
+    pub fn duration(&self) -> f64 {
+        self.end - self.start
+    }
+}
+
+impl LightCurve {
+    /// Create a `LightCurve` from times and fluxes. Measurements are sorted by
+    /// time; surplus times or fluxes are dropped.
+    pub fn new(time: Vec<f64>, flux: Vec<f64>) -> LightCurve {
+        let mut measurements: Vec<(f64, f64)> = time.into_iter().zip(flux).collect();
+        measurements.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let (time, flux) = measurements.into_iter().unzip();
+        LightCurve { time, flux }
+    }
+
+    /// The number of measurements.
+    pub fn len(&self) -> usize {
+        self.time.len()
+    }
+
+    /// Whether this light curve has no measurements.
+    pub fn is_empty(&self) -> bool {
+        self.time.is_empty()
+    }
+
+    /// The same light curve without the measurements that have `NaN` as time or flux.
+    pub fn without_nan(&self) -> LightCurve {
+        let (time, flux) = self.time.iter().zip(self.flux.iter())
+            .filter(|&(t, f)| !t.is_nan() && !f.is_nan())
+            .map(|(t, f)| (*t, *f))
+            .unzip();
+        LightCurve { time, flux }
+    }
+
+    /// The median of the flux, or `NaN` when there is no flux.
+    pub fn median_flux(&self) -> f64 {
+        median(self.flux.clone())
+    }
+
+    /// Resample into bins of `width`, starting at the first measurement. Every
+    /// bin with flux becomes a single measurement at its center, holding the
+    /// median flux of the bin. Empty bins are left out.
+    ///
+    /// Returns `None` when `width` is not positive.
+    pub fn bin(&self, width: f64) -> Option<LightCurve> {
+        if width.is_nan() || width <= 0f64 {
+            return None
+        }
+        let curve = self.without_nan();
+        let mut binned = LightCurve { time: vec!(), flux: vec!() };
+        let start = match curve.time.first() {
+            Some(start) => *start,
+            None => return Some(binned),
+        };
+        let mut bin = 0f64;
+        let mut fluxes = vec!();
+        for (time, flux) in curve.time.iter().zip(curve.flux.iter()) {
+            let index = ((time - start) / width).floor();
+            if index != bin && !fluxes.is_empty() {
+                binned.push(start + (bin + 0.5) * width, median(fluxes));
+                fluxes = vec!();
+            }
+            bin = index;
+            fluxes.push(*flux);
+        }
+        if !fluxes.is_empty() {
+            binned.push(start + (bin + 0.5) * width, median(fluxes));
+        }
+        Some(binned)
+    }
+
+    /// The gaps between consecutive measurements that are longer than
+    /// `threshold`. Measurements with `NaN` flux count as missing.
+    pub fn gaps(&self, threshold: f64) -> Vec<Gap> {
+        let curve = self.without_nan();
+        curve.time.windows(2)
+            .map(|pair| Gap { start: pair[0], end: pair[1] })
+            .filter(|gap| gap.duration() > threshold)
+            .collect()
+    }
+
+    /// Divide the flux by its median, so that the flux is around 1. Returns
+    /// `None` when the median is zero or there is no flux.
+    pub fn normalize(&self) -> Option<LightCurve> {
+        let median = self.median_flux();
+        if median == 0f64 || median.is_nan() {
+            return None
+        }
+        Some(LightCurve {
+            time: self.time.clone(),
+            flux: self.flux.iter().map(|flux| flux / median).collect(),
+        })
+    }
+
+    /// The Lomb-Scargle periodogram of this light curve at `frequencies`, in
+    /// cycles per unit of time. The power is normalized to lie between 0 and 1,
+    /// where 1 means a sinusoid at that frequency fits the flux perfectly.
+    pub fn lomb_scargle(&self, frequencies: &[f64]) -> Periodogram {
+        let curve = self.without_nan();
+        let mean = curve.flux.iter().sum::<f64>() / curve.len() as f64;
+        let flux: Vec<f64> = curve.flux.iter().map(|flux| flux - mean).collect();
+        let total: f64 = flux.iter().map(|flux| flux * flux).sum();
+        let power = frequencies.iter().map(|frequency| {
+            let omega = 2f64 * PI * frequency;
+            let (sin, cos) = curve.time.iter()
+                .fold((0f64, 0f64), |(s, c), time| (s + (2f64 * omega * time).sin(), c + (2f64 * omega * time).cos()));
+            let tau = sin.atan2(cos) / (2f64 * omega);
+            let (mut yc, mut ys, mut cc, mut ss) = (0f64, 0f64, 0f64, 0f64);
+            for (time, flux) in curve.time.iter().zip(flux.iter()) {
+                let phase = omega * (time - tau);
+                yc += flux * phase.cos();
+                ys += flux * phase.sin();
+                cc += phase.cos().powi(2);
+                ss += phase.sin().powi(2);
+            }
+            let power = (yc * yc / cc + ys * ys / ss) / total;
+            if power.is_nan() { 0f64 } else { power }
+        }).collect();
+        Periodogram { frequency: frequencies.to_vec(), power }
+    }
+
+    fn push(&mut self, time: f64, flux: f64) {
+        self.time.push(time);
+        self.flux.push(flux);
+    }
+}
+
+/// The power of a light curve at a range of frequencies.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Periodogram {
+    /// The frequencies, in cycles per unit of time.
+    pub frequency: Vec<f64>,
+    /// The normalized power at every frequency.
+    pub power: Vec<f64>,
+}
+
+impl Periodogram {
+    /// The frequency with the highest power and that power, or `None` when
+    /// there are no frequencies.
+    pub fn peak(&self) -> Option<(f64, f64)> {
+        self.frequency.iter().cloned().zip(self.power.iter().cloned())
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+    }
+}
+
+/// `count` evenly spaced frequencies from `minimum` up to and including `maximum`.
+pub fn frequency_grid(minimum: f64, maximum: f64, count: usize) -> Vec<f64> {
+    match count {
+        0 => vec!(),
+        1 => vec!(minimum),
+        _ => (0..count).map(|i| minimum + (maximum - minimum) * i as f64 / (count - 1) as f64).collect(),
+    }
+}
+
+/// The median of `values`, ignoring `NaN`. Returns `NaN` when no value remains.
+pub fn median(mut values: Vec<f64>) -> f64 {
+    values.retain(|value| !value.is_nan());
+    if values.is_empty() {
+        return f64::NAN
+    }
+    values.sort_by(|a, b| a.total_cmp(b));
+    let middle = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[middle - 1] + values[middle]) / 2f64
+    } else {
+        values[middle]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+    use super::{frequency_grid, median, Gap, LightCurve};
+
+    #[test]
+    fn median_should_ignore_nan() {
+        assert_eq!(median(vec!(3f64, f64::NAN, 1f64, 2f64)), 2f64);
+        assert_eq!(median(vec!(4f64, 1f64, 2f64, 3f64)), 2.5f64);
+        assert!(median(vec!(f64::NAN)).is_nan());
+    }
+
+    #[test]
+    fn bin_should_take_the_median_of_every_bin() {
+        let curve = LightCurve::new(
+            vec!(0f64, 0.5f64, 0.9f64, 1.2f64, 3.1f64, 3.2f64),
+            vec!(1f64, 9f64, 2f64, 4f64, f64::NAN, 6f64),
+        );
+
+        assert_eq!(curve.bin(1f64), Some(LightCurve::new(vec!(0.5f64, 1.5f64, 3.5f64), vec!(2f64, 4f64, 6f64))));
+        assert_eq!(curve.bin(0f64), None);
+    }
+
+    #[test]
+    fn gaps_should_skip_missing_flux() {
+        let curve = LightCurve::new(
+            vec!(0f64, 1f64, 2f64, 3f64, 7f64),
+            vec!(1f64, 1f64, f64::NAN, 1f64, 1f64),
+        );
+
+        assert_eq!(curve.gaps(1.5f64), vec!(Gap { start: 1f64, end: 3f64 }, Gap { start: 3f64, end: 7f64 }));
+    }
+
+    #[test]
+    fn normalize_should_divide_by_the_median() {
+        let curve = LightCurve::new(vec!(2f64, 1f64, 3f64), vec!(4f64, 2f64, 8f64));
+
+        assert_eq!(curve.time, vec!(1f64, 2f64, 3f64));
+        assert_eq!(curve.normalize(), Some(LightCurve::new(vec!(1f64, 2f64, 3f64), vec!(0.5f64, 1f64, 2f64))));
+        assert_eq!(LightCurve::new(vec!(1f64), vec!(0f64)).normalize(), None);
+    }
+
+    #[test]
+    fn lomb_scargle_should_find_the_period_of_a_sinusoid() {
+        let time: Vec<f64> = (0..200).map(|i| i as f64 * 0.1 + (i % 7) as f64 * 0.013).collect();
+        let flux: Vec<f64> = time.iter().map(|t| 1f64 + 0.01 * (2f64 * PI * t / 2.5f64).sin()).collect();
+        let frequencies = frequency_grid(0.05f64, 2f64, 400);
+
+        let periodogram = LightCurve::new(time, flux).lomb_scargle(&frequencies);
+        let (frequency, power) = periodogram.peak().unwrap();
+
+        assert_eq!(periodogram.power.len(), 400);
+        assert!((frequency - 0.4f64).abs() < 0.01f64, "peak at {}", frequency);
+        assert!(power > 0.99f64 && power <= 1f64 + 1e-9);
+    }
+}
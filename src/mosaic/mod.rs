@@ -0,0 +1,224 @@
+//! The mosaic module assembles the extensions of a detector mosaic into a
+//! single focal-plane image.
+//!
+//! Every extension with a `DETSEC` keyword is a tile of the mosaic. `DETSEC`
+//! tells where the tile goes in detector coordinates and `DATASEC` which part
+//! of the extension holds data, i.e. without overscan. An extension without
+//! `DATASEC` is used as a whole.
+
+use super::section::{header_section, Interval, Section, ParseSectionError};
+use super::types::{Fits, Keyword, DataArrayError};
+
+/// An extension that is part of a mosaic.
+#[derive(Debug, PartialEq)]
+pub struct Tile {
+    /// The index of the HDU, 0 being the primary HDU.
+    pub index: usize,
+    /// The part of the extension that holds data, the value of `DATASEC`.
+    pub data_section: Section,
+    /// The place of the data in the focal plane, the value of `DETSEC`.
+    pub detector_section: Section,
+}
+
+/// A focal-plane image assembled from the tiles of a mosaic.
+#[derive(Debug, PartialEq)]
+pub struct Mosaic {
+    /// The part of the focal plane covered by this image.
+    pub geometry: Section,
+    /// The pixel values, `NAXIS1` varying fastest. Pixels not covered by any
+    /// tile are `NaN`.
+    pub values: Vec<f64>,
+}
+
+impl Mosaic {
+    /// The value at pixel `(x, y)`, counted from 0 at the corner of the geometry.
+    pub fn value(&self, x: usize, y: usize) -> f64 {
+        self.values[y * self.geometry.width() + x]
+    }
+}
+
+/// Problems that could occur when assembling a mosaic.
+#[derive(Debug, PartialEq)]
+pub enum MosaicError {
+    /// None of the extensions has a `DETSEC` keyword.
+    NoTiles,
+    /// A section keyword of an HDU could not be parsed.
+    InvalidSection {
+        /// The index of the HDU.
+        index: usize,
+        /// The keyword holding the section.
+        keyword: Keyword,
+        /// What is wrong with the section.
+        error: ParseSectionError,
+    },
+    /// The HDU does not hold a two dimensional data array.
+    NotAnImage(usize),
+    /// The `DATASEC` of the HDU lies outside the data array.
+    SectionOutOfBounds(usize),
+    /// `DATASEC` and `DETSEC` of the HDU differ in size, e.g. because of binning.
+    SizeMismatch(usize),
+    /// The data array of the HDU could not be decoded.
+    Data {
+        /// The index of the HDU.
+        index: usize,
+        /// What went wrong while decoding.
+        error: DataArrayError,
+    },
+}
+
+/// The tiles of a mosaic, in the order of the extensions.
+pub fn tiles(fits: &Fits) -> Result<Vec<Tile>, MosaicError> {
+    let mut tiles = vec!();
+    for (offset, hdu) in fits.extensions.iter().enumerate() {
+        let index = offset + 1;
+        let section = |keyword: Keyword| {
+            header_section(&hdu.header, &keyword)
+                .map_err(|error| MosaicError::InvalidSection { index, keyword, error })
+        };
+        let detector_section = match section(Keyword::DETSEC)? {
+            Some(detector_section) => detector_section,
+            None => continue,
+        };
+        let data_section = match section(Keyword::DATASEC)? {
+            Some(data_section) => data_section,
+            None => {
+                let axes = hdu.header.axes().unwrap_or_default();
+                if axes.len() != 2 || axes[0] == 0 || axes[1] == 0 {
+                    return Err(MosaicError::NotAnImage(index))
+                }
+                Section::new(Interval::new(1, axes[0]), Interval::new(1, axes[1]))
+            },
+        };
+        if data_section.width() != detector_section.width() || data_section.height() != detector_section.height() {
+            return Err(MosaicError::SizeMismatch(index))
+        }
+        tiles.push(Tile { index, data_section, detector_section });
+    }
+    if tiles.is_empty() {
+        return Err(MosaicError::NoTiles)
+    }
+    Ok(tiles)
+}
+
+/// The part of the focal plane covered by the tiles of a mosaic, i.e. the
+/// bounding box of all `DETSEC`s.
+pub fn geometry(fits: &Fits) -> Result<Section, MosaicError> {
+    tiles(fits).map(|tiles| bounding_box(&tiles))
+}
+
+fn bounding_box(tiles: &[Tile]) -> Section {
+    let sections: Vec<&Section> = tiles.iter().map(|tile| &tile.detector_section).collect();
+    Section::new(
+        Interval::new(
+            sections.iter().map(|section| section.x.lower()).min().unwrap_or(1),
+            sections.iter().map(|section| section.x.upper()).max().unwrap_or(1)),
+        Interval::new(
+            sections.iter().map(|section| section.y.lower()).min().unwrap_or(1),
+            sections.iter().map(|section| section.y.upper()).max().unwrap_or(1)))
+}
+
+/// Assemble the tiles of a mosaic into a single image. The overscan of every
+/// tile is trimmed and flipped sections are honored.
+pub fn assemble(fits: &Fits) -> Result<Mosaic, MosaicError> {
+    let tiles = tiles(fits)?;
+    let geometry = bounding_box(&tiles);
+    let mut values = vec![f64::NAN; geometry.width() * geometry.height()];
+    for tile in &tiles {
+        let hdu = &fits.extensions[tile.index - 1];
+        let data_array = match hdu.data_array() {
+            Some(data_array) if data_array.axes().len() == 2 => data_array,
+            _ => return Err(MosaicError::NotAnImage(tile.index)),
+        };
+        let width = data_array.axes()[0];
+        let height = data_array.axes()[1];
+        if tile.data_section.x.upper() > width || tile.data_section.y.upper() > height {
+            return Err(MosaicError::SectionOutOfBounds(tile.index))
+        }
+        let data = data_array.to_f64()
+            .map_err(|error| MosaicError::Data { index: tile.index, error })?;
+        for j in 0..tile.data_section.height() {
+            for i in 0..tile.data_section.width() {
+                let source = (tile.data_section.y.at(j) - 1) * width + tile.data_section.x.at(i) - 1;
+                let x = tile.detector_section.x.at(i) - geometry.x.first;
+                let y = tile.detector_section.y.at(j) - geometry.y.first;
+                values[y * geometry.width() + x] = data[source];
+            }
+        }
+    }
+    Ok(Mosaic { geometry, values })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use super::super::section::{Interval, Section};
+    use super::super::types::{Fits, HDU, Header, KeywordRecord, Keyword, Value};
+    use super::{assemble, geometry, MosaicError};
+
+    fn primary<'a>() -> HDU<'a> {
+        HDU::new(Header::new(vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(0i64), Option::None),
+        )))
+    }
+
+    fn amplifier<'a>(data: &'a [u8], datasec: &'a str, detsec: &'a str) -> HDU<'a> {
+        HDU::with_data(Header::new(vec!(
+            KeywordRecord::new(Keyword::XTENSION, Value::CharacterString(Cow::Borrowed("IMAGE")), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(2i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(3i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(2u16), Value::Integer(2i64), Option::None),
+            KeywordRecord::new(Keyword::PCOUNT, Value::Integer(0i64), Option::None),
+            KeywordRecord::new(Keyword::GCOUNT, Value::Integer(1i64), Option::None),
+            KeywordRecord::new(Keyword::DATASEC, Value::CharacterString(Cow::Borrowed(datasec)), Option::None),
+            KeywordRecord::new(Keyword::DETSEC, Value::CharacterString(Cow::Borrowed(detsec)), Option::None),
+        )), data)
+    }
+
+    #[test]
+    fn assemble_should_trim_overscan_and_place_tiles() {
+        let left = [1u8, 2u8, 99u8, 3u8, 4u8, 99u8];
+        let right = [99u8, 5u8, 6u8, 99u8, 7u8, 8u8];
+        let fits = Fits::new(primary(), vec!(
+            amplifier(&left, "[1:2,1:2]", "[1:2,1:2]"),
+            amplifier(&right, "[2:3,1:2]", "[3:4,1:2]"),
+        ));
+
+        let mosaic = assemble(&fits).unwrap();
+
+        assert_eq!(mosaic.geometry, Section::new(Interval::new(1, 4), Interval::new(1, 2)));
+        assert_eq!(mosaic.values, vec!(1f64, 2f64, 5f64, 6f64, 3f64, 4f64, 7f64, 8f64));
+    }
+
+    #[test]
+    fn assemble_should_flip_reversed_detector_sections() {
+        let data = [1u8, 2u8, 99u8, 3u8, 4u8, 99u8];
+        let fits = Fits::new(primary(), vec!(amplifier(&data, "[1:2,1:2]", "[2:1,1:2]")));
+
+        let mosaic = assemble(&fits).unwrap();
+
+        assert_eq!(mosaic.values, vec!(2f64, 1f64, 4f64, 3f64));
+    }
+
+    #[test]
+    fn geometry_should_cover_all_detector_sections() {
+        let data = [0u8; 6];
+        let fits = Fits::new(primary(), vec!(
+            amplifier(&data, "[1:2,1:2]", "[11:12,5:6]"),
+            amplifier(&data, "[1:2,1:2]", "[13:14,7:8]"),
+        ));
+
+        assert_eq!(geometry(&fits), Ok(Section::new(Interval::new(11, 14), Interval::new(5, 8))));
+    }
+
+    #[test]
+    fn assemble_should_refuse_binned_tiles_and_files_without_tiles() {
+        let data = [0u8; 6];
+        let binned = Fits::new(primary(), vec!(amplifier(&data, "[1:2,1:2]", "[1:4,1:4]")));
+
+        assert_eq!(assemble(&binned), Err(MosaicError::SizeMismatch(1)));
+        assert_eq!(assemble(&Fits::new(primary(), vec!())), Err(MosaicError::NoTiles));
+    }
+}
@@ -0,0 +1,385 @@
+//! The ogip module reads the spectral files of the OGIP conventions, as used
+//! by X-ray and gamma-ray missions.
+//!
+//! A PHA file holds a spectrum: counts, or rates, per detector channel. An ARF
+//! holds the effective area per energy bin, and an RMF the probability that a
+//! photon of an energy bin is detected in a channel. The `MATRIX` of an RMF is
+//! stored sparsely: every energy bin has `N_GRP` groups of `N_CHAN` channels
+//! starting at `F_CHAN`, often in variable-length arrays.
+//!
+//! Forward modeling folds a model through these: multiply the photon flux per
+//! energy bin by the effective area and the exposure, then fold the result
+//! through the response matrix to get the expected counts per channel.
+
+use std::convert::TryFrom;
+use std::error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use super::table::{BinaryTable, TableError};
+use super::types::{Header, HDU, Keyword, ValueRetrievalError};
+
+/// Problems that occur when reading OGIP spectral files.
+#[derive(Debug, PartialEq)]
+pub enum OgipError {
+    /// The table could not be read.
+    Table(TableError),
+    /// The table does not have a column that is needed.
+    MissingColumn(String),
+    /// A keyword is missing or has a value of the wrong type.
+    InvalidValue {
+        /// The keyword.
+        keyword: Keyword,
+        /// What is wrong with the value.
+        error: ValueRetrievalError,
+    },
+    /// The groups of a row of the response matrix do not match its `MATRIX`
+    /// or fall outside the channels.
+    InvalidGroups(usize),
+    /// A model has the wrong number of values.
+    LengthMismatch {
+        /// The number of values that is needed.
+        expected: usize,
+        /// The number of values of the model.
+        actual: usize,
+    },
+}
+
+impl Display for OgipError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            OgipError::Table(ref error) => write!(f, "{}", error),
+            OgipError::MissingColumn(ref name) => write!(f, "the column {} is missing", name),
+            OgipError::InvalidValue { ref keyword, ref error } => write!(f, "{}: {}", keyword, error),
+            OgipError::InvalidGroups(row) => write!(f, "the groups of row {} do not match the matrix", row),
+            OgipError::LengthMismatch { expected, actual } => write!(f, "expected {} values, got {}", expected, actual),
+        }
+    }
+}
+
+impl error::Error for OgipError {}
+
+impl From<TableError> for OgipError {
+    fn from(error: TableError) -> Self {
+        OgipError::Table(error)
+    }
+}
+
+/// A spectrum, i.e. the `SPECTRUM` extension of a PHA file.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Spectrum {
+    /// The detector channel of every row, i.e. `CHANNEL`.
+    pub channel: Vec<i64>,
+    /// The counts per channel, i.e. `COUNTS`, when the spectrum holds counts.
+    pub counts: Option<Vec<f64>>,
+    /// The counts per second per channel, i.e. `RATE`, when the spectrum holds rates.
+    pub rate: Option<Vec<f64>>,
+    /// The statistical error per channel, i.e. `STAT_ERR`, if present.
+    pub statistical_error: Option<Vec<f64>>,
+    /// The quality flag per channel, i.e. `QUALITY`, if present. 0 is good.
+    pub quality: Option<Vec<i64>>,
+    /// The grouping flag per channel, i.e. `GROUPING`, if present.
+    pub grouping: Option<Vec<i64>>,
+    /// The exposure time in seconds, i.e. `EXPOSURE`.
+    pub exposure: f64,
+    /// The scaling of the background region, i.e. `BACKSCAL`, 1 when absent.
+    pub background_scale: f64,
+    /// The scaling of the area, i.e. `AREASCAL`, 1 when absent.
+    pub area_scale: f64,
+}
+
+impl Spectrum {
+    /// Read a spectrum from the `SPECTRUM` extension of a PHA file.
+    pub fn from_hdu(hdu: &HDU) -> Result<Spectrum, OgipError> {
+        let table = BinaryTable::from_hdu(hdu)?;
+        let counts = optional_column(&table, "COUNTS")?;
+        let rate = optional_column(&table, "RATE")?;
+        if counts.is_none() && rate.is_none() {
+            return Err(OgipError::MissingColumn("COUNTS".to_string()))
+        }
+        Ok(Spectrum {
+            channel: integers(column(&table, "CHANNEL")?),
+            counts,
+            rate,
+            statistical_error: optional_column(&table, "STAT_ERR")?,
+            quality: optional_column(&table, "QUALITY")?.map(integers),
+            grouping: optional_column(&table, "GROUPING")?.map(integers),
+            exposure: real(&hdu.header, Keyword::EXPOSURE, None)?,
+            background_scale: real(&hdu.header, Keyword::BACKSCAL, Some(1f64))?,
+            area_scale: real(&hdu.header, Keyword::AREASCAL, Some(1f64))?,
+        })
+    }
+
+    /// The counts per second per channel, computed from the counts when the
+    /// spectrum holds counts.
+    pub fn rates(&self) -> Vec<f64> {
+        match (&self.rate, &self.counts) {
+            (Some(rate), _) => rate.clone(),
+            (None, Some(counts)) => counts.iter().map(|count| count / self.exposure).collect(),
+            (None, None) => vec!(),
+        }
+    }
+}
+
+/// An effective area, i.e. the `SPECRESP` extension of an ARF.
+#[derive(Debug, PartialEq, Clone)]
+pub struct EffectiveArea {
+    /// The lower edge of every energy bin, i.e. `ENERG_LO`.
+    pub energy_low: Vec<f64>,
+    /// The upper edge of every energy bin, i.e. `ENERG_HI`.
+    pub energy_high: Vec<f64>,
+    /// The effective area of every energy bin, i.e. `SPECRESP`.
+    pub response: Vec<f64>,
+}
+
+impl EffectiveArea {
+    /// Read an effective area from the `SPECRESP` extension of an ARF.
+    pub fn from_hdu(hdu: &HDU) -> Result<EffectiveArea, OgipError> {
+        let table = BinaryTable::from_hdu(hdu)?;
+        Ok(EffectiveArea {
+            energy_low: column(&table, "ENERG_LO")?,
+            energy_high: column(&table, "ENERG_HI")?,
+            response: column(&table, "SPECRESP")?,
+        })
+    }
+
+    /// Multiply a flux per energy bin by the effective area.
+    pub fn apply(&self, flux: &[f64]) -> Result<Vec<f64>, OgipError> {
+        check_length(self.response.len(), flux)?;
+        Ok(flux.iter().zip(&self.response).map(|(flux, area)| flux * area).collect())
+    }
+}
+
+/// A run of consecutive channels of a row of a response matrix.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ResponseGroup {
+    /// The first channel of the group, i.e. an element of `F_CHAN`.
+    pub first_channel: i64,
+    /// The response of each channel of the group.
+    pub values: Vec<f64>,
+}
+
+/// A response matrix, i.e. the `MATRIX` or `SPECRESP MATRIX` extension of an RMF.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ResponseMatrix {
+    /// The lower edge of every energy bin, i.e. `ENERG_LO`.
+    pub energy_low: Vec<f64>,
+    /// The upper edge of every energy bin, i.e. `ENERG_HI`.
+    pub energy_high: Vec<f64>,
+    /// The number of the first channel, i.e. `TLMIN` of `F_CHAN`, 1 when absent.
+    pub first_channel: i64,
+    /// The number of channels, i.e. `DETCHANS`.
+    pub channels: usize,
+    /// The groups of every energy bin.
+    pub groups: Vec<Vec<ResponseGroup>>,
+}
+
+impl ResponseMatrix {
+    /// Read a response matrix from the `MATRIX` extension of an RMF.
+    pub fn from_hdu(hdu: &HDU) -> Result<ResponseMatrix, OgipError> {
+        let header = &hdu.header;
+        let table = BinaryTable::from_hdu(hdu)?;
+        let index = |name: &str| table.column_index(name).ok_or_else(|| OgipError::MissingColumn(name.to_string()));
+        let (first, count, matrix) = (index("F_CHAN")?, index("N_CHAN")?, index("MATRIX")?);
+        let number_of_groups = integers(column(&table, "N_GRP")?);
+        let tlmin = Keyword::Custom(format!("TLMIN{}", first + 1));
+        let first_channel = real(header, tlmin, Some(1f64))? as i64;
+        let detchans = real(header, Keyword::DETCHANS, None)?;
+        let channels = usize::try_from(detchans as i64)
+            .map_err(|_| OgipError::InvalidValue { keyword: Keyword::DETCHANS, error: ValueRetrievalError::NotAnInteger })?;
+        let mut groups = vec!();
+        for (row, number_of_groups) in number_of_groups.into_iter().enumerate() {
+            let number_of_groups = usize::try_from(number_of_groups).map_err(|_| OgipError::InvalidGroups(row))?;
+            let firsts = table.numbers(row, first)?;
+            let counts = table.numbers(row, count)?;
+            let values = table.numbers(row, matrix)?;
+            if firsts.len() < number_of_groups || counts.len() < number_of_groups {
+                return Err(OgipError::InvalidGroups(row))
+            }
+            let mut start = 0;
+            let mut row_groups = vec!();
+            for (&first, &count) in firsts.iter().zip(&counts).take(number_of_groups) {
+                let (first, count) = (first as i64, count as usize);
+                let end = start + count;
+                let last = first - first_channel + count as i64;
+                if end > values.len() || first < first_channel || last > channels as i64 {
+                    return Err(OgipError::InvalidGroups(row))
+                }
+                row_groups.push(ResponseGroup { first_channel: first, values: values[start..end].to_vec() });
+                start = end;
+            }
+            groups.push(row_groups);
+        }
+        Ok(ResponseMatrix {
+            energy_low: column(&table, "ENERG_LO")?,
+            energy_high: column(&table, "ENERG_HI")?,
+            first_channel,
+            channels,
+            groups,
+        })
+    }
+
+    /// The response of an energy bin for every channel.
+    pub fn row(&self, energy: usize) -> Option<Vec<f64>> {
+        self.groups.get(energy).map(|groups| {
+            let mut row = vec![0f64; self.channels];
+            for group in groups {
+                let offset = (group.first_channel - self.first_channel) as usize;
+                row[offset..(offset + group.values.len())].copy_from_slice(&group.values);
+            }
+            row
+        })
+    }
+
+    /// Fold a model through the response matrix, i.e. turn the expected
+    /// photons per energy bin into the expected counts per channel.
+    pub fn fold(&self, photons: &[f64]) -> Result<Vec<f64>, OgipError> {
+        check_length(self.groups.len(), photons)?;
+        let mut counts = vec![0f64; self.channels];
+        for (groups, photons) in self.groups.iter().zip(photons) {
+            for group in groups {
+                let offset = (group.first_channel - self.first_channel) as usize;
+                for (count, response) in counts[offset..].iter_mut().zip(&group.values) {
+                    *count += photons * response;
+                }
+            }
+        }
+        Ok(counts)
+    }
+}
+
+fn column(table: &BinaryTable, name: &str) -> Result<Vec<f64>, OgipError> {
+    optional_column(table, name)?.ok_or_else(|| OgipError::MissingColumn(name.to_string()))
+}
+
+fn optional_column(table: &BinaryTable, name: &str) -> Result<Option<Vec<f64>>, OgipError> {
+    match table.column_index(name) {
+        Some(index) => Ok(Some(table.scalars(index)?)),
+        None => Ok(None),
+    }
+}
+
+fn integers(values: Vec<f64>) -> Vec<i64> {
+    values.into_iter().map(|value| value as i64).collect()
+}
+
+fn real(header: &Header, keyword: Keyword, default: Option<f64>) -> Result<f64, OgipError> {
+    match (header.get_real(&keyword), default) {
+        (Ok(value), _) => Ok(value),
+        (Err(ValueRetrievalError::KeywordNotPresent), Some(default)) => Ok(default),
+        (Err(error), _) => Err(OgipError::InvalidValue { keyword, error }),
+    }
+}
+
+fn check_length(expected: usize, values: &[f64]) -> Result<(), OgipError> {
+    if values.len() == expected {
+        Ok(())
+    } else {
+        Err(OgipError::LengthMismatch { expected, actual: values.len() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use super::super::types::{Header, HDU, KeywordRecord, Keyword, Value};
+    use super::{EffectiveArea, OgipError, ResponseGroup, ResponseMatrix, Spectrum};
+
+    fn table(columns: Vec<(&'static str, &'static str)>, width: i64, rows: i64, mut extra: Vec<KeywordRecord<'static>>, data: Vec<u8>, heap: usize) -> HDU<'static> {
+        let mut records = vec!(
+            KeywordRecord::new(Keyword::XTENSION, Value::CharacterString(Cow::Borrowed("BINTABLE")), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(2i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(width), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(2u16), Value::Integer(rows), Option::None),
+            KeywordRecord::new(Keyword::PCOUNT, Value::Integer(heap as i64), Option::None),
+            KeywordRecord::new(Keyword::GCOUNT, Value::Integer(1i64), Option::None),
+            KeywordRecord::new(Keyword::TFIELDS, Value::Integer(columns.len() as i64), Option::None),
+        );
+        for (n, (name, format)) in columns.into_iter().enumerate() {
+            let n = n as u16 + 1;
+            records.push(KeywordRecord::new(Keyword::TTYPEn(n), Value::CharacterString(Cow::Borrowed(name)), Option::None));
+            records.push(KeywordRecord::new(Keyword::TFORMn(n), Value::CharacterString(Cow::Borrowed(format)), Option::None));
+        }
+        records.append(&mut extra);
+        HDU::with_owned_data(Header::new(records), data)
+    }
+
+    #[test]
+    fn spectra_should_read_counts_per_channel() {
+        let mut data = vec!();
+        for (channel, counts) in [(1i16, 10i32), (2i16, 20i32), (3i16, 40i32)] {
+            data.extend_from_slice(&channel.to_be_bytes());
+            data.extend_from_slice(&counts.to_be_bytes());
+        }
+        let hdu = table(vec!(("CHANNEL", "1I"), ("COUNTS", "1J")), 6, 3, vec!(
+            KeywordRecord::new(Keyword::EXPOSURE, Value::Real(10f64), Option::None),
+            KeywordRecord::new(Keyword::BACKSCAL, Value::Real(0.5f64), Option::None),
+        ), data, 0);
+
+        let spectrum = Spectrum::from_hdu(&hdu).unwrap();
+
+        assert_eq!(spectrum.channel, vec!(1i64, 2i64, 3i64));
+        assert_eq!(spectrum.counts, Some(vec!(10f64, 20f64, 40f64)));
+        assert_eq!(spectrum.rate, None);
+        assert_eq!(spectrum.background_scale, 0.5f64);
+        assert_eq!(spectrum.area_scale, 1f64);
+        assert_eq!(spectrum.rates(), vec!(1f64, 2f64, 4f64));
+    }
+
+    #[test]
+    fn spectra_without_exposure_should_be_refused() {
+        let hdu = table(vec!(("CHANNEL", "1I"), ("RATE", "1E")), 6, 0, vec!(), vec!(), 0);
+
+        assert!(matches!(Spectrum::from_hdu(&hdu), Err(OgipError::InvalidValue { keyword: Keyword::EXPOSURE, .. })));
+    }
+
+    #[test]
+    fn effective_areas_should_scale_a_flux() {
+        let mut data = vec!();
+        for (low, high, area) in [(0.1f32, 0.2f32, 100f32), (0.2f32, 0.3f32, 200f32)] {
+            data.extend_from_slice(&low.to_be_bytes());
+            data.extend_from_slice(&high.to_be_bytes());
+            data.extend_from_slice(&area.to_be_bytes());
+        }
+        let hdu = table(vec!(("ENERG_LO", "1E"), ("ENERG_HI", "1E"), ("SPECRESP", "1E")), 12, 2, vec!(), data, 0);
+
+        let arf = EffectiveArea::from_hdu(&hdu).unwrap();
+
+        assert_eq!(arf.response, vec!(100f64, 200f64));
+        assert_eq!(arf.apply(&[1f64, 0.5f64]), Ok(vec!(100f64, 100f64)));
+        assert_eq!(arf.apply(&[1f64]), Err(OgipError::LengthMismatch { expected: 2, actual: 1 }));
+    }
+
+    #[test]
+    fn response_matrices_should_read_groups_from_the_heap() {
+        let mut data = vec!();
+        let rows: [(f32, f32, i16, i16, i16, u32, u32); 2] = [
+            (0.1, 0.2, 1, 0, 2, 2, 0),
+            (0.2, 0.3, 1, 1, 2, 2, 8),
+        ];
+        for (low, high, groups, first, count, elements, offset) in rows {
+            data.extend_from_slice(&low.to_be_bytes());
+            data.extend_from_slice(&high.to_be_bytes());
+            data.extend_from_slice(&groups.to_be_bytes());
+            data.extend_from_slice(&first.to_be_bytes());
+            data.extend_from_slice(&count.to_be_bytes());
+            data.extend_from_slice(&elements.to_be_bytes());
+            data.extend_from_slice(&offset.to_be_bytes());
+        }
+        for value in [0.75f32, 0.25f32, 0.5f32, 0.5f32] {
+            data.extend_from_slice(&value.to_be_bytes());
+        }
+        let columns = vec!(("ENERG_LO", "1E"), ("ENERG_HI", "1E"), ("N_GRP", "1I"), ("F_CHAN", "1I"), ("N_CHAN", "1I"), ("MATRIX", "1PE(2)"));
+        let hdu = table(columns, 22, 2, vec!(
+            KeywordRecord::new(Keyword::DETCHANS, Value::Integer(3i64), Option::None),
+            KeywordRecord::new(Keyword::Custom("TLMIN4".to_string()), Value::Integer(0i64), Option::None),
+        ), data, 16);
+
+        let rmf = ResponseMatrix::from_hdu(&hdu).unwrap();
+
+        assert_eq!(rmf.first_channel, 0);
+        assert_eq!(rmf.groups[1], vec!(ResponseGroup { first_channel: 1, values: vec!(0.5f64, 0.5f64) }));
+        assert_eq!(rmf.row(0), Some(vec!(0.75f64, 0.25f64, 0f64)));
+        assert_eq!(rmf.fold(&[4f64, 2f64]), Ok(vec!(3f64, 2f64, 1f64)));
+    }
+}
@@ -1,33 +1,402 @@
 //! The parser module is responsible for parsing FITS files.
-
+//!
+//! Headers consist of cards of 80 characters. The text of each card is
+//! validated once, after which its keyword, value and comment are parsed as `str`.
+//!
+//! `parse` reads as much of a value as it understands and ignores the rest of
+//! the card, and refuses padding after `END` that is not blank. `parse_lenient`
+//! keeps a card whose value and comment can not be parsed completely as
+//! `Value::Unparsed` instead, skips padding that is not blank, and reports both
+//! in the `warnings` of the header.
+//!
+//! A primary header with `SIMPLE = F` announces that the file does not conform
+//! to the standard. `parse` refuses such a file with `Error::NotConforming`,
+//! `parse_lenient` parses it as any other file with a warning, after which
+//! `Fits::conforms` tells it apart.
+//!
+//! Parsing does not panic on any input, which makes it fit for untrusted
+//! uploads: `Fits::parse_strict` and `Fits::parse_lenient` name that entry
+//! point. Sizes that overflow are reported as `Error::TooLarge`.
+//!
+//! With the `rayon` feature, `Fits::parse_parallel` parses the HDUs of a file
+//! on several threads.
+
+use std::borrow::Cow;
+use std::ops::Range;
 use std::str;
 use std::str::FromStr;
-use nom::{is_space, is_digit};
-use super::types::{Fits, HDU, Header, KeywordRecord, Keyword, Value, BlankRecord};
+use nom::{ErrorKind, IResult, Needed};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use super::error::Error;
+use super::types::{Fits, HDU, Header, HeaderWarning, KeywordRecord, Keyword, Value, BlankRecord, AxisError, BitpixError, BLOCK_SIZE};
+
+/// The size in bytes of a card.
+const CARD_SIZE: usize = 80;
 
-named!(#[doc = "Will parse data from a FITS file into a `Fits` structure"], pub fits<&[u8], Fits>,
+named!(#[doc = "Will parse data from a FITS file into a `Fits` structure"], pub fits<&[u8], Fits<'_>>,
        do_parse!(
            hdu: hdu >>
            extensions: extensions >>
                (Fits::new(hdu, extensions))
        ));
 
-named!(hdu<&[u8], HDU>,
+/// Parse data from a FITS file into a `Fits` structure.
+///
+/// In contrast with `fits`, all data should be consumed, and a failure is
+/// reported with the byte offset of the problem and, when a card is at fault,
+/// the text of that card.
+pub fn parse(data: &[u8]) -> Result<Fits<'_>, Error> {
+    parse_with(data, false)
+}
+
+/// Parse data from a FITS file like `parse` does, but keep the cards whose
+/// value can not be parsed, and skip padding after `END` that is not blank.
+/// The value of such a card is `Value::Unparsed`. Each problem is reported in
+/// `Header::warnings`, so that files of quirky writers can be read while
+/// noting what could not be interpreted.
+pub fn parse_lenient(data: &[u8]) -> Result<Fits<'_>, Error> {
+    parse_with(data, true)
+}
+
+/// Parse data from a FITS file like `parse` does, and tell where the header
+/// and data unit of each HDU are in `data`. The extents can be used to copy
+/// HDUs between files, or to hand a data unit to other code, without deriving
+/// them from `NAXISn`.
+pub fn parse_with_info(data: &[u8]) -> Result<(Fits<'_>, Vec<HduInfo>), Error> {
+    let mut offset = 0;
+    let mut hdus = vec!();
+    let mut infos = vec!();
+    while hdus.is_empty() || offset < data.len() {
+        let (hdu, info) = parse_hdu(data, offset, false)?;
+        offset = info.data_range.end;
+        hdus.push(hdu);
+        infos.push(info);
+    }
+    Ok((fits_of(hdus)?, infos))
+}
+
+/// Where the header and the data unit of an HDU are in the bytes of a file.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HduInfo {
+    /// The bytes of the header, including the `END` card and its padding.
+    pub header_range: Range<usize>,
+    /// The bytes of the data unit, including its padding. The values take up
+    /// the first `HDU::data().bytes().len()` bytes.
+    pub data_range: Range<usize>,
+}
+
+fn parse_with(data: &[u8], lenient: bool) -> Result<Fits<'_>, Error> {
+    let mut offset = 0;
+    let mut hdus = vec!();
+    while hdus.is_empty() || offset < data.len() {
+        let (hdu, info) = parse_hdu(data, offset, lenient)?;
+        hdus.push(hdu);
+        offset = info.data_range.end;
+    }
+    fits_of(hdus)
+}
+
+fn fits_of<'a>(hdus: Vec<HDU<'a>>) -> Result<Fits<'a>, Error> {
+    let mut hdus = hdus.into_iter();
+    let primary_hdu = hdus.next().ok_or(Error::UnexpectedEof { offset: 0 })?;
+    Ok(Fits::new(primary_hdu, hdus.collect()))
+}
+
+impl<'a> Fits<'a> {
+    /// Parse data from a FITS file like `parse` does. It returns an error
+    /// instead of panicking on any input, e.g. on absurd values of `NAXISn`,
+    /// so that untrusted files can be parsed.
+    pub fn parse_strict(data: &'a [u8]) -> Result<Fits<'a>, Error> {
+        parse(data)
+    }
+
+    /// Parse data from a FITS file like `parse_lenient` does. Like
+    /// `parse_strict`, it does not panic on any input.
+    pub fn parse_lenient(data: &'a [u8]) -> Result<Fits<'a>, Error> {
+        parse_lenient(data)
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<'a> Fits<'a> {
+    /// Parse data from a FITS file like `parse` does, parsing the headers and
+    /// data units on several threads. The offsets of the HDUs are found first,
+    /// by parsing only the cards that determine the size of a data unit, which
+    /// pays off for files with many HDUs. Of several problems, the one in the
+    /// first HDU is reported.
+    pub fn parse_parallel(data: &'a [u8]) -> Result<Fits<'a>, Error> {
+        let offsets = hdu_offsets(data)?;
+        let hdus: Vec<Result<HDU<'a>, Error>> = offsets.par_iter()
+            .map(|offset| parse_hdu(data, *offset, false).map(|(hdu, _)| hdu))
+            .collect();
+        fits_of(hdus.into_iter().collect::<Result<Vec<HDU<'a>>, Error>>()?)
+    }
+}
+
+// The offsets of the HDUs in `data`. An HDU that can not be scanned is parsed
+// completely, which reports its problem or finds its size after all.
+#[cfg(feature = "rayon")]
+fn hdu_offsets(data: &[u8]) -> Result<Vec<usize>, Error> {
+    let mut offset = 0;
+    let mut offsets = vec!();
+    while offsets.is_empty() || offset < data.len() {
+        let size = match scan_hdu(&data[offset..]) {
+            Some(size) => size,
+            None => parse_hdu(data, offset, false)?.1.data_range.end - offset,
+        };
+        offsets.push(offset);
+        offset += size;
+    }
+    Ok(offsets)
+}
+
+// The size of the HDU at the start of `input`, determined from the cards that
+// describe the data unit.
+#[cfg(feature = "rayon")]
+fn scan_hdu(input: &[u8]) -> Option<usize> {
+    const STRUCTURAL: [&[u8]; 6] = [b"SIMPLE ", b"BITPIX ", b"NAXIS", b"PCOUNT ", b"GCOUNT ", b"GROUPS "];
+    let mut records = vec!();
+    for (index, card) in input.chunks(CARD_SIZE).take_while(|card| card.len() == CARD_SIZE).enumerate() {
+        if end_record(card).is_done() {
+            let size = (index + 1) * CARD_SIZE;
+            let header_size = size + padding_cards(size, &input[size..]) * CARD_SIZE;
+            let data_size = Header::new(records).data_unit_size()?;
+            return if header_size + data_size <= input.len() { Some(header_size + data_size) } else { None }
+        }
+        if STRUCTURAL.iter().any(|keyword| card.starts_with(keyword)) {
+            match keyword_record(card) {
+                IResult::Done(_, record) => records.push(record),
+                _ => return None,
+            }
+        }
+    }
+    None
+}
+
+fn parse_hdu(data: &[u8], offset: usize, lenient: bool) -> Result<(HDU<'_>, HduInfo), Error> {
+    let input = &data[offset..];
+    let result = if lenient { lenient_header(input) } else { header(input) };
+    let (rest, h) = match result {
+        IResult::Done(rest, h) => (rest, h),
+        _ => {
+            return Err(match first_invalid_card(input, lenient) {
+                Some(index) => Error::InvalidCard {
+                    offset: (offset + index * CARD_SIZE) as u64,
+                    card: String::from_utf8_lossy(&input[(index * CARD_SIZE)..((index + 1) * CARD_SIZE)]).into_owned(),
+                },
+                None => Error::UnexpectedEof { offset: offset as u64 },
+            })
+        },
+    };
+    if let Err(error) = h.bitpix() {
+        return Err(Error::InvalidBitpix { offset: offset as u64, error })
+    }
+    if let Err(error) = h.axes() {
+        return Err(Error::InvalidAxes { offset: offset as u64, error })
+    }
+    if !lenient && h.get_logical(&Keyword::SIMPLE) == Ok(false) {
+        return Err(Error::NotConforming { offset: offset as u64 })
+    }
+    let header_size = input.len() - rest.len();
+    let data_size = match h.data_unit_size() {
+        Some(size) if size <= rest.len() => size,
+        Some(_) => return Err(Error::UnexpectedEof { offset: (offset + header_size) as u64 }),
+        None => return Err(Error::TooLarge { offset: offset as u64 }),
+    };
+    let data_offset = offset + header_size;
+    let info = HduInfo { header_range: offset..data_offset, data_range: data_offset..(data_offset + data_size) };
+    Ok((HDU::with_data(h, &rest[0..data_size]), info))
+}
+
+/// The index of the first card of a header that can not be parsed, if there
+/// is one before the `END` card, or of the first card after `END` that is not
+/// blank. Cards that are not complete are not considered.
+pub fn invalid_card(header: &[u8]) -> Option<usize> {
+    first_invalid_card(header, false)
+}
+
+fn first_invalid_card(header: &[u8], lenient: bool) -> Option<usize> {
+    let cards: Vec<&[u8]> = header.chunks(CARD_SIZE).take_while(|card| card.len() == CARD_SIZE).collect();
+    for (index, card) in cards.iter().enumerate() {
+        if end_record(card).is_done() {
+            if lenient {
+                return None
+            }
+            let padding = padding_cards((index + 1) * CARD_SIZE, &header[((index + 1) * CARD_SIZE)..]);
+            return cards.iter().enumerate().skip(index + 1).take(padding)
+                .find(|&(_, card)| !blank_record(card).is_done())
+                .map(|(index, _)| index)
+        }
+        let record = if lenient { lenient_keyword_record(card) } else { keyword_record(card) };
+        if !record.is_done() {
+            return Some(index)
+        }
+    }
+    None
+}
+
+named!(hdu<&[u8], HDU<'_>>,
+       do_parse!(
+           h: map_res!(map_res!(header, with_valid_bitpix), with_valid_axes) >>
+           data: take!(h.data_unit_size().unwrap_or(usize::MAX)) >>
+               (HDU::with_data(h, data))
+       ));
+
+fn with_valid_bitpix(header: Header) -> Result<Header, BitpixError> {
+    header.bitpix().map(|_| header)
+}
+
+fn with_valid_axes(header: Header) -> Result<Header, AxisError> {
+    header.axes().map(|_| header)
+}
+
+/// Will parse a header, including the `END` record and the blank records that
+/// pad it to a multiple of `BLOCK_SIZE` bytes, or to the end of `input` when it
+/// ends before. Padding that is not blank is refused.
+pub fn header(input: &[u8]) -> IResult<&[u8], Header<'_>> {
+    let (rest, records) = try_parse!(input, terminated!(many0!(keyword_record), end_record));
+    let (rest, _) = try_parse!(rest, count!(blank_record, padding_cards(input.len() - rest.len(), rest)));
+    IResult::Done(rest, Header::new(join_continued_strings(records)))
+}
+
+// Like `header`, but skipping padding that is not blank, with a warning.
+fn lenient_header(input: &[u8]) -> IResult<&[u8], Header<'_>> {
+    let (rest, records) = try_parse!(input, terminated!(many0!(lenient_keyword_record), end_record));
+    let (rest, padding) = try_parse!(rest, take!(padding_cards(input.len() - rest.len(), rest) * CARD_SIZE));
+    let mut warnings: Vec<HeaderWarning> = records.iter().enumerate()
+        .filter_map(|(card, record)| match (record.keyword(), record.value()) {
+            (_, &Value::Unparsed(_)) => Some(HeaderWarning::new(card, format!("the value of {} could not be parsed", record.keyword()))),
+            (Keyword::Custom(name), _) if !record.keyword().round_trips() => Some(HeaderWarning::new(card, format!("{} is not a valid keyword", name))),
+            (&Keyword::SIMPLE, &Value::Logical(false)) => Some(HeaderWarning::new(card, "SIMPLE = F, the file does not conform to the FITS standard")),
+            _ => None,
+        })
+        .collect();
+    if let Some(card) = padding.chunks(CARD_SIZE).position(|card| !blank_record(card).is_done()) {
+        warnings.push(HeaderWarning::new(records.len() + 1 + card, "the padding after END is not blank"));
+    }
+    IResult::Done(rest, Header::new(join_continued_strings(records)).with_warnings(warnings))
+}
+
+// The number of cards that pad a header of `size` bytes to a multiple of `BLOCK_SIZE`, as far
+// as they are present in `rest`.
+fn padding_cards(size: usize, rest: &[u8]) -> usize {
+    ((BLOCK_SIZE - size % BLOCK_SIZE) % BLOCK_SIZE / CARD_SIZE).min(rest.len() / CARD_SIZE)
+}
+
+fn join_continued_strings(records: Vec<KeywordRecord>) -> Vec<KeywordRecord> {
+    let mut joined: Vec<KeywordRecord> = vec!();
+    for record in records {
+        if *record.keyword() == Keyword::CONTINUE {
+            if let Some(previous) = joined.last_mut() {
+                if previous.is_continued() {
+                    previous.append_continuation(&record);
+                    continue
+                }
+            }
+        }
+        joined.push(record);
+    }
+    joined
+}
+
+// The text of a card is validated once. Everything the parsers below match is ASCII, so they
+// can test and slice the text by bytes without decoding or validating its parts again.
+named!(keyword_record<&[u8], KeywordRecord<'_>>,
+       flat_map!(
+           map_res!(take!(CARD_SIZE), str::from_utf8),
+           alt!(commentary_record | continue_record | value_record)
+       ));
+
+// A value record is only accepted when its value and comment explain the whole card.
+named!(lenient_keyword_record<&[u8], KeywordRecord<'_>>,
+       flat_map!(
+           map_res!(take!(CARD_SIZE), str::from_utf8),
+           alt!(commentary_record | continue_record | complete_value_record | custom_value_record | unparsed_record)
+       ));
+
+// Like `take!`, but counting bytes, which are the characters of the text of a card.
+fn take_ascii(input: &str, count: usize) -> IResult<&str, &str> {
+    if input.len() >= count && input.is_char_boundary(count) {
+        IResult::Done(&input[count..], &input[..count])
+    } else {
+        IResult::Incomplete(Needed::Size(count))
+    }
+}
+
+// Like `take_while!`, but testing bytes instead of decoding characters.
+fn take_bytes_while(input: &str, predicate: fn(u8) -> bool) -> IResult<&str, &str> {
+    let end = input.bytes().position(|chr| !predicate(chr)).unwrap_or(input.len());
+    IResult::Done(&input[end..], &input[..end])
+}
+
+// Like `take_while1!`, but testing bytes instead of decoding characters.
+fn take_bytes_while1(input: &str, predicate: fn(u8) -> bool) -> IResult<&str, &str> {
+    match take_bytes_while(input, predicate) {
+        IResult::Done(_, "") => IResult::Error(error_position!(ErrorKind::TakeWhile1, input)),
+        result => result,
+    }
+}
+
+fn spaces(input: &str) -> IResult<&str, &str> {
+    take_bytes_while(input, |chr| chr == b' ' || chr == b'\t')
+}
+
+fn digits(input: &str) -> IResult<&str, &str> {
+    match take_bytes_while1(input, |chr| chr.is_ascii_digit()) {
+        IResult::Error(_) => IResult::Error(error_position!(ErrorKind::Digit, input)),
+        result => result,
+    }
+}
+
+named!(continue_record<&str, KeywordRecord<'_>>,
        do_parse!(
-           h: header >>
-               take!(h.data_array_size()/8) >>
-               (HDU::new(h))
+           tag!("CONTINUE  ") >>
+           vc: valuecomment >>
+               (KeywordRecord::new(Keyword::CONTINUE, vc.0, vc.1.map(|c| c.trim() )))
        ));
 
-named!(header<&[u8], Header>,
+named!(commentary_record<&str, KeywordRecord<'_>>,
        do_parse!(
-           records: many0!(keyword_record) >>
-               end_record >>
-               many0!(blank_record) >>
-               (Header::new(records))
+           key: commentary_keyword >>
+           text: call!(take_ascii, 72) >>
+               (KeywordRecord::new(key, Value::CommentText(Cow::Borrowed(text.trim_end())), Option::None))
+       ));
+
+named!(commentary_keyword<&str, Keyword>,
+       alt!(
+           value!(Keyword::COMMENT, tag!("COMMENT ")) |
+           value!(Keyword::HISTORY, tag!("HISTORY ")) |
+           value!(Keyword::BlankField, tag!("        "))
        ));
 
-named!(keyword_record<&[u8], KeywordRecord>,
+fn value_record(card: &str) -> IResult<&str, KeywordRecord<'_>> {
+    with_comment_column(card, value_record_fields(card))
+}
+
+fn complete_value_record(card: &str) -> IResult<&str, KeywordRecord<'_>> {
+    with_comment_column(card, complete_value_record_fields(card))
+}
+
+// Remember the column of the `/` before the comment of a record parsed from `card`.
+fn with_comment_column<'a>(card: &'a str, result: IResult<&'a str, KeywordRecord<'a>>) -> IResult<&'a str, KeywordRecord<'a>> {
+    match result {
+        IResult::Done(rest, record) => {
+            // The comment is a slice of the card, so its offset is where it starts.
+            let column = record.comment()
+                .map(|comment| (comment.as_ptr() as usize).wrapping_sub(card.as_ptr() as usize))
+                .filter(|start| *start <= card.len())
+                .and_then(|start| card[..start].rfind('/'));
+            match column {
+                Some(column) => IResult::Done(rest, record.with_comment_column(column)),
+                None => IResult::Done(rest, record),
+            }
+        },
+        result => result,
+    }
+}
+
+named!(value_record_fields<&str, KeywordRecord<'_>>,
        do_parse!(
            key: keyword  >>
                tag!("= ") >>
@@ -35,48 +404,81 @@ named!(keyword_record<&[u8], KeywordRecord>,
                (KeywordRecord::new(key, vc.0, vc.1.map(|c| c.trim() )))
        ));
 
-named!(keyword<&[u8], Keyword>,
+named!(complete_value_record_fields<&str, KeywordRecord<'_>>,
+       do_parse!(
+           key: keyword  >>
+               tag!("= ") >>
+           vc: flat_map!(call!(take_ascii, 70), terminated!(valuecomment_of_field, eof!())) >>
+               (KeywordRecord::new(key, vc.0, vc.1.map(|c| c.trim() )))
+       ));
+
+// A record with a keyword that does not parse, e.g. `NAXIS1000`, kept as a custom keyword.
+named!(custom_value_record<&str, KeywordRecord<'_>>,
+       do_parse!(
+           key: map!(call!(take_ascii, 8), |name: &str| Keyword::Custom(name.trim_end().to_string())) >>
+               tag!("= ") >>
+           vc: flat_map!(call!(take_ascii, 70), terminated!(valuecomment_of_field, eof!())) >>
+               (KeywordRecord::new(key, vc.0, vc.1.map(|c| c.trim() )))
+       ));
+
+named!(unparsed_record<&str, KeywordRecord<'_>>,
+       do_parse!(
+           key: keyword  >>
+               tag!("= ") >>
+           text: call!(take_ascii, 70) >>
+               (KeywordRecord::new(key, Value::Unparsed(Cow::Borrowed(text.trim())), Option::None))
+       ));
+
+named!(keyword<&str, Keyword>,
        map_res!(
-           map_res!(
-               take!(8),
-               str::from_utf8),
+           call!(take_ascii, 8),
            Keyword::from_str
        ));
 
-named!(valuecomment<&[u8], (Value, Option<&str>)>,
+named!(valuecomment<&str, (Value<'_>, Option<&str>)>,
        flat_map!(
-           take!(70),
+           call!(take_ascii, 70),
+           valuecomment_of_field
+       ));
+
+named!(valuecomment_of_field<&str, (Value<'_>, Option<&str>)>,
+       terminated!(
            pair!(
                value,
                opt!(complete!(comment))
-           )));
+           ),
+           call!(spaces)
+       ));
 
-named!(value<&[u8], Value>,
-       alt_complete!(character_string | logical_constant | real | integer | undefined));
+named!(value<&str, Value<'_>>,
+       alt_complete!(character_string | logical_constant | complex | real | integer | undefined));
 
-named!(character_string<&[u8], Value>,
+named!(character_string<&str, Value<'_>>,
        map!(
-           map_res!(
-               ws!(delimited!(
-                   tag!("'"),
-                   take_while!(is_allowed_in_character_string),
-                   tag!("'")
-               )),
-               str::from_utf8
+           // Only the spaces around the quotes are skipped, those inside belong to the string.
+           delimited!(
+               pair!(call!(spaces), tag!("'")),
+               recognize!(many0!(alt!(call!(take_bytes_while1, is_allowed_in_character_string) | complete!(tag!("''"))))),
+               pair!(tag!("'"), call!(spaces))
            ),
-           Value::CharacterString
+           character_string_from_str
        ));
 
+fn character_string_from_str(s: &str) -> Value<'_> {
+    if s.contains("''") {
+        Value::CharacterString(Cow::Owned(s.replace("''", "'")))
+    } else {
+        Value::CharacterString(Cow::Borrowed(s))
+    }
+}
+
 fn is_allowed_in_character_string(chr: u8) -> bool {
-    is_restricted_ascii(chr) && chr != 39
+    is_restricted_ascii(chr) && chr != b'\''
 }
 
-named!(logical_constant<&[u8], Value>,
+named!(logical_constant<&str, Value<'_>>,
        map_res!(
-           map_res!(
-               ws!(alt!(tag!("T") | tag!("F"))),
-               str::from_utf8
-           ),
+           sep!(spaces, alt!(tag!("T") | tag!("F"))),
            logical_constant_from_str
        ));
 
@@ -86,7 +488,7 @@ pub enum ParseLogicalConstantError {
     UnknownConstant
 }
 
-fn logical_constant_from_str(constant: &str) -> Result<Value, ParseLogicalConstantError> {
+fn logical_constant_from_str(constant: &str) -> Result<Value<'static>, ParseLogicalConstantError> {
     match constant {
         "T" => Ok(Value::Logical(true)),
         "F" => Ok(Value::Logical(false)),
@@ -94,77 +496,97 @@ fn logical_constant_from_str(constant: &str) -> Result<Value, ParseLogicalConsta
     }
 }
 
-named!(integer<&[u8], Value>,
-       map!(
-           map_res!(
-               map_res!(
-                   ws!(take_while!(is_digit)), // TODO negative numbers, prefix zeroes
-                   str::from_utf8
-               ),
-               i64::from_str
-           ),
-           Value::Integer
+named!(integer<&str, Value<'_>>,
+       map_res!(
+           sep!(spaces, integer_literal),
+           integer_from_str
        ));
 
-named!(real<&[u8], Value>,
+// An integer that does not fit in an `i64` is kept as a real number, e.g.
+// `BZERO = 9223372036854775808` of unsigned 64-bit integers.
+fn integer_from_str(number: &str) -> Result<Value<'static>, RealParseError> {
+    match i64::from_str(number) {
+        Ok(n) => Ok(Value::Integer(n)),
+        Err(_) => real_from_str(number).map(Value::Real),
+    }
+}
+
+named!(integer_literal<&str, &str>,
+       recognize!(pair!(opt!(complete!(sign)), digits)));
+
+named!(real<&str, Value<'_>>,
        map!(
            map_res!(
-               ws!(tuple!(take_while!(is_digit), tag!("."), take_while!(is_digit))),
-               tuple_to_f64
+               sep!(spaces, real_literal),
+               real_from_str
            ),
            Value::Real
        ));
 
-/// Reasons for converting to a f64 from a parse triple (left, _, right) to fail.
+named!(real_literal<&str, &str>,
+       recognize!(tuple!(
+           opt!(complete!(sign)),
+           alt_complete!(
+               recognize!(tuple!(digits, tag!("."), opt!(complete!(digits)), opt!(complete!(exponent)))) |
+               recognize!(tuple!(tag!("."), digits, opt!(complete!(exponent)))) |
+               recognize!(pair!(digits, exponent))
+           )
+       )));
+
+named!(exponent<&str, &str>,
+       recognize!(tuple!(one_of!("EeDd"), opt!(complete!(sign)), digits)));
+
+named!(sign<&str, &str>,
+       alt!(tag!("+") | tag!("-")));
+
+/// Problems that could occur when converting the text of a real number to a `f64`.
+#[derive(Debug)]
 pub enum RealParseError {
-    /// When left is not parse-able as `str`.
+    /// When the integer part is not parse-able as `str`. The text is checked
+    /// before it is converted, so this is no longer reported.
     IntegerPartUnparseable,
-    /// When right is not parse-able as `str`.
+    /// When the fractional part is not parse-able as `str`. The text is
+    /// checked before it is converted, so this is no longer reported.
     FractionalPartUnparseable,
-    /// When the combination is not a `f64`.
+    /// When the number is not a `f64`.
     NotARealNumber,
 }
 
-fn tuple_to_f64((left, _, right): (&[u8], &[u8], &[u8])) -> Result<f64, RealParseError> {
-    match str::from_utf8(left) {
-        Ok(integer_part) => {
-            match str::from_utf8(right) {
-                Ok(fractional_part) => {
-                    let mut number = String::from("");
-                    number.push_str(integer_part);
-                    number.push_str(".");
-                    number.push_str(fractional_part);
-
-                    match f64::from_str(&number) {
-                        Ok(result) => Ok(result),
-                        Err(_) => Err(RealParseError::NotARealNumber)
-                    }
-                }
-                Err(_) => Err(RealParseError::FractionalPartUnparseable)
-            }
-        }
-        Err(_) => Err(RealParseError::IntegerPartUnparseable)
-    }
+fn real_from_str(number: &str) -> Result<f64, RealParseError> {
+    f64::from_str(&number.replace(['D', 'd'], "E")).map_err(|_| RealParseError::NotARealNumber)
 }
 
-named!(undefined<&[u8], Value>,
+named!(complex<&str, Value<'_>>,
        map!(
-           take_while!(is_space),
-           |_| { Value::Undefined}
+           sep!(spaces, delimited!(
+               tag!("("),
+               separated_pair!(complex_part, tag!(","), complex_part),
+               tag!(")")
+           )),
+           Value::Complex
        ));
 
-named!(comment<&[u8], &str>,
+named!(complex_part<&str, f64>,
        map_res!(
-           do_parse!(
-               tag!("/") >>
-                   comment: take_while!(is_restricted_ascii) >>
-                   (comment)
-           ),
-           str::from_utf8
+           sep!(spaces, alt_complete!(real_literal | integer_literal)),
+           real_from_str
+       ));
+
+named!(undefined<&str, Value<'_>>,
+       map!(
+           call!(spaces),
+           |_| { Value::Undefined}
+       ));
+
+named!(comment<&str, &str>,
+       do_parse!(
+           tag!("/") >>
+               comment: call!(take_bytes_while, is_restricted_ascii) >>
+               (comment)
        ));
 
 fn is_restricted_ascii(chr: u8) -> bool {
-    32u8 <= chr && chr <= 126u8
+    (32u8..=126u8).contains(&chr)
 }
 
 named!(end_record<&[u8], Keyword>,
@@ -182,14 +604,21 @@ named!(blank_record<&[u8], BlankRecord>,
            |_| { BlankRecord }
        ));
 
-named!(extensions<&[u8], Vec<HDU> >,
+named!(extensions<&[u8], Vec<HDU<'_>> >,
        many0!(hdu));
 
 #[cfg(test)]
+#[allow(non_fmt_panics, clippy::useless_vec)]
 mod tests {
+    use std::borrow::Cow;
+    use std::thread;
     use nom::{IResult};
-    use super::super::types::{HDU, Header, KeywordRecord, Keyword, Value, BlankRecord};
-    use super::{fits, header, keyword_record, keyword, valuecomment, character_string, logical_constant, real, integer, undefined, end_record, blank_record};
+    use super::super::error::Error;
+    use quickcheck::TestResult;
+    use super::super::types::{Fits, HDU, Header, HeaderWarning, KeywordRecord, Keyword, Value, BlankRecord};
+    use super::super::testgen::target_pixel_file;
+    use super::super::writer::{write_fits, WriteError};
+    use super::{HduInfo, parse, parse_lenient, parse_with_info, fits, hdu, header, keyword_record, keyword, valuecomment, character_string, logical_constant, complex, real, integer, undefined, end_record, blank_record};
 
     #[test]
     fn it_should_parse_a_fits_file(){
@@ -201,6 +630,7 @@ mod tests {
             IResult::Done(tail, f) => {
                 assert_eq!(f.primary_hdu, HDU::new(long_cadence_header()));
                 assert_eq!(f.extensions.len(), 2);
+                assert_eq!(f.extensions[0].data().bytes().len(), 2932usize * 3599usize);
                 assert_eq!(tail.len(), 0);
             },
             IResult::Error(_) => panic!("Did not expect an error"),
@@ -208,6 +638,287 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_strict_should_refuse_absurd_sizes() {
+        let cards = [
+            "SIMPLE  =                    T", "BITPIX  =                   64", "NAXIS   =                    3",
+            "NAXIS1  =  9223372036854775807", "NAXIS2  =  9223372036854775807", "NAXIS3  =  9223372036854775807", "END",
+        ];
+        let mut data: Vec<u8> = cards.iter().flat_map(|card| format!("{:<80}", card).into_bytes()).collect();
+        data.resize(2880, b' ');
+
+        assert!(matches!(Fits::parse_strict(&data), Err(Error::TooLarge { offset: 0 })));
+        assert!(matches!(Fits::parse_lenient(&data), Err(Error::TooLarge { offset: 0 })));
+        assert!(matches!(Fits::parse_strict(&[]), Err(Error::UnexpectedEof { offset: 0 })));
+    }
+
+    // The structural keywords a header of arbitrary values is built from.
+    const STRUCTURAL: [&str; 8] = ["BITPIX", "NAXIS", "NAXIS1", "NAXIS2", "PCOUNT", "GCOUNT", "GROUPS", "XTENSION"];
+
+    quickcheck! {
+        fn parse_should_not_panic_on_arbitrary_bytes(data: Vec<u8>) -> bool {
+            let mut padded = data.clone();
+            padded.resize(data.len().div_ceil(2880) * 2880, b' ');
+            for input in [&data, &padded] {
+                let _ = Fits::parse_strict(input);
+                let _ = Fits::parse_lenient(input);
+            }
+            true
+        }
+
+        fn parse_should_not_panic_on_arbitrary_structural_values(values: Vec<(u8, i64)>, data: Vec<u8>) -> bool {
+            let mut bytes: Vec<u8> = format!("{:<80}", "SIMPLE  =                    T").into_bytes();
+            for (keyword, value) in values.into_iter().take(30) {
+                bytes.extend(format!("{:<8}= {:>20}", STRUCTURAL[keyword as usize % STRUCTURAL.len()], value).bytes());
+                bytes.resize(bytes.len().div_ceil(80) * 80, b' ');
+            }
+            bytes.extend(format!("{:<80}", "END").bytes());
+            bytes.resize(2880 * bytes.len().div_ceil(2880), b' ');
+            bytes.extend(data);
+            if let Ok(fits) = Fits::parse_lenient(&bytes) {
+                for hdu in fits.iter_hdus() {
+                    let _ = hdu.data_array().map(|array| array.to_f64());
+                }
+            }
+            let _ = Fits::parse_strict(&bytes);
+            true
+        }
+
+        fn written_values_should_parse_to_the_same_values(n: i64, b: bool, r: f64, s: String) -> TestResult {
+            let s: String = s.chars().filter(|c| (' '..='~').contains(c)).collect();
+            let expected = Value::CharacterString(Cow::Borrowed(&s));
+            let header = Header::new(vec!(
+                KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+                KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None),
+                KeywordRecord::new(Keyword::NAXIS, Value::Integer(0i64), Option::None),
+                KeywordRecord::new(Keyword::Custom("COUNT".to_string()), Value::Integer(n), Option::None),
+                KeywordRecord::new(Keyword::EXTEND, Value::Logical(b), Option::None),
+                KeywordRecord::new(Keyword::EQUINOX, Value::Real(r), Option::None),
+                KeywordRecord::new(Keyword::OBJECT, expected.clone(), Option::Some("arbitrary text")),
+            ));
+            let mut buffer: Vec<u8> = vec!();
+            let written = write_fits(&mut buffer, &Fits::new(HDU::new(header), vec!()));
+            if !r.is_finite() {
+                return TestResult::from_bool(matches!(written, Err(WriteError::NonFiniteValue(Keyword::EQUINOX))))
+            }
+
+            let parsed = Fits::parse_strict(&buffer).unwrap().primary_hdu.header;
+            TestResult::from_bool(
+                parsed.get_int(&Keyword::Custom("COUNT".to_string())) == Ok(n) &&
+                parsed.get_logical(&Keyword::EXTEND) == Ok(b) &&
+                parsed.get_real(&Keyword::EQUINOX) == Ok(r) &&
+                parsed.get_trimmed_str(&Keyword::OBJECT).ok() == expected.as_trimmed_str())
+        }
+    }
+
+    fn owned_fits() -> Fits<'static> {
+        let data = include_bytes!("../../assets/images/k2-trappist1-unofficial-tpf-long-cadence.fits").to_vec();
+
+        match fits(&data) {
+            IResult::Done(_, f) => f.into_owned(),
+            _ => panic!("Did not expect the test file to fail"),
+        }
+    }
+
+    #[test]
+    fn owned_fits_should_outlive_the_parsed_data(){
+        let f = owned_fits();
+
+        let handle = thread::spawn(move || {
+            assert_eq!(f.primary_hdu, HDU::new(long_cadence_header()));
+            f.extensions.len()
+        });
+
+        assert_eq!(handle.join().unwrap(), 2);
+    }
+
+    #[test]
+    fn a_view_should_borrow_from_an_owned_fits(){
+        let data = include_bytes!("../../assets/images/k2-trappist1-unofficial-tpf-long-cadence.fits");
+        let owned = owned_fits();
+
+        let view = owned.view();
+
+        assert_eq!(view, parse(data).unwrap());
+        assert!(view.iter_hdus().flat_map(|hdu| hdu.header.keyword_records.iter())
+            .all(|record| !matches!(*record.value(), Value::CharacterString(Cow::Owned(_)))));
+        assert_eq!(view.extensions[1].data().bytes().as_ptr(), owned.extensions[1].data().bytes().as_ptr());
+    }
+
+    #[test]
+    fn hdu_should_refuse_a_header_with_a_missing_axis(){
+        let data = target_pixel_file();
+        let mut corrupted = data[2880..].to_vec();
+        corrupted[(4*80)..(4*80 + 8)].copy_from_slice(b"NAXIS3  ");
+
+        match hdu(&corrupted) {
+            IResult::Error(_) => (),
+            _ => panic!("Expected an error"),
+        }
+    }
+
+    #[test]
+    fn parse_should_parse_the_same_structure_as_fits(){
+        let data = target_pixel_file();
+        let expected = match fits(&data) {
+            IResult::Done(_, f) => f,
+            _ => panic!("Did not expect the test file to fail"),
+        };
+
+        assert_eq!(parse(&data).unwrap(), expected);
+    }
+
+    #[test]
+    fn parse_should_report_the_offset_and_text_of_an_invalid_card(){
+        let data = target_pixel_file();
+        let mut corrupted = data.to_vec();
+        corrupted[(2880 + 3*80)..(2880 + 4*80)].copy_from_slice(format!("{:<80}", "HIERARCH ESO DET CHIP").as_bytes());
+
+        match parse(&corrupted) {
+            Err(Error::InvalidCard { offset, card }) => {
+                assert_eq!(offset, (2880 + 3*80) as u64);
+                assert_eq!(card.trim_end(), "HIERARCH ESO DET CHIP");
+            },
+            _ => panic!("Expected an invalid card"),
+        }
+    }
+
+    #[test]
+    fn parse_with_info_should_tell_the_extents_of_every_hdu(){
+        let data = target_pixel_file();
+
+        let (fits, infos) = parse_with_info(&data).unwrap();
+
+        assert_eq!(fits, parse(&data).unwrap());
+        assert_eq!(infos.len(), 3);
+        assert_eq!(infos[0], HduInfo { header_range: 0..2880, data_range: 2880..2880 });
+        assert_eq!(infos[1].header_range, 2880..5760);
+        assert_eq!(infos[2].data_range.end, data.len());
+        let table = &data[infos[1].data_range.clone()];
+        assert_eq!(&table[0..fits.extensions[0].data().bytes().len()], fits.extensions[0].data().bytes());
+    }
+
+    #[test]
+    fn parse_lenient_should_keep_cards_with_an_unparsed_value(){
+        let data = target_pixel_file();
+        let mut quirky = data.to_vec();
+        quirky[(4*80)..(5*80)].copy_from_slice(format!("{:<80}", "CHANNEL = sixty-eight / CCD channel").as_bytes());
+
+        let strict = parse(&quirky).unwrap();
+        let lenient = parse_lenient(&quirky).unwrap();
+
+        assert_eq!(strict.primary_hdu.header.get(&Keyword::CHANNEL), Some(&Value::Undefined));
+        assert!(strict.primary_hdu.header.warnings().is_empty());
+        assert_eq!(lenient.primary_hdu.header.get(&Keyword::CHANNEL), Some(&Value::Unparsed(Cow::Borrowed("sixty-eight / CCD channel"))));
+        assert_eq!(lenient.primary_hdu.header.warnings(), &[HeaderWarning::new(4, "the value of CHANNEL could not be parsed")]);
+        assert_eq!(lenient.primary_hdu.header.get_int(&Keyword::MODULE), Ok(19i64));
+        assert!(lenient.extensions.iter().all(|hdu| hdu.header.warnings().is_empty()));
+
+        quirky[(4*80)..(5*80)].copy_from_slice(format!("{:<80}", "HIERARCH ESO DET CHIP").as_bytes());
+        match parse_lenient(&quirky) {
+            Err(Error::InvalidCard { offset, .. }) => assert_eq!(offset, (4*80) as u64),
+            _ => panic!("Expected an invalid card"),
+        }
+    }
+
+    #[test]
+    fn parse_lenient_should_keep_indexed_keywords_with_index_0(){
+        let data = target_pixel_file();
+        let mut quirky = data.to_vec();
+        quirky[(4*80)..(5*80)].copy_from_slice(format!("{:<80}", "NAXIS0  =                   68 / CCD channel").as_bytes());
+
+        let lenient = parse_lenient(&quirky).unwrap();
+
+        assert!(matches!(parse(&quirky), Err(Error::InvalidCard { offset: 320, .. })));
+        assert_eq!(lenient.primary_hdu.header.get_int(&Keyword::Custom("NAXIS0".to_string())), Ok(68i64));
+        assert_eq!(lenient.primary_hdu.header.warnings(), &[HeaderWarning::new(4, "NAXIS0 is not a valid keyword")]);
+    }
+
+    #[test]
+    fn padding_after_the_end_record_should_be_blank_unless_lenient(){
+        let data = target_pixel_file();
+        let mut padded = data.to_vec();
+        padded[(20*80)..(21*80)].copy_from_slice(format!("{:<80}", "written by a quirky writer").as_bytes());
+
+        match parse(&padded) {
+            Err(Error::InvalidCard { offset, card }) => {
+                assert_eq!(offset, (20*80) as u64);
+                assert_eq!(card.trim_end(), "written by a quirky writer");
+            },
+            _ => panic!("Expected an invalid card"),
+        }
+        let lenient = parse_lenient(&padded).unwrap();
+        assert_eq!(lenient.primary_hdu.header.warnings(), &[HeaderWarning::new(20, "the padding after END is not blank")]);
+        assert_eq!(lenient.extensions.len(), 2);
+    }
+
+    #[test]
+    fn parse_should_report_truncated_data(){
+        let data = target_pixel_file();
+
+        match parse(&data[0..(4*2880)]) {
+            Err(Error::UnexpectedEof { offset }) => assert_eq!(offset, 5760u64),
+            _ => panic!("Expected an unexpected end of file"),
+        }
+        match parse(&data[0..400]) {
+            Err(Error::UnexpectedEof { offset }) => assert_eq!(offset, 0u64),
+            _ => panic!("Expected an unexpected end of file"),
+        }
+    }
+
+    #[test]
+    fn simple_false_should_be_refused_unless_lenient(){
+        let data = target_pixel_file();
+        let mut nonconforming = data.to_vec();
+        nonconforming[29] = b'F';
+
+        match parse(&nonconforming) {
+            Err(Error::NotConforming { offset }) => assert_eq!(offset, 0u64),
+            _ => panic!("Expected a file that does not conform"),
+        }
+        let fits = parse_lenient(&nonconforming).unwrap();
+        assert!(!fits.conforms());
+        assert_eq!(fits.primary_hdu.header.warnings(), &[HeaderWarning::new(0, "SIMPLE = F, the file does not conform to the FITS standard")]);
+        assert!(parse(&data).unwrap().conforms());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parse_parallel_should_parse_the_same_structure_as_parse(){
+        let data = target_pixel_file();
+
+        assert_eq!(Fits::parse_parallel(&data).unwrap(), parse(&data).unwrap());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parse_parallel_should_report_the_same_problems_as_parse(){
+        let data = target_pixel_file();
+        let mut corrupted = data.to_vec();
+        corrupted[(2880 + 3*80)..(2880 + 4*80)].copy_from_slice(format!("{:<80}", "HIERARCH ESO DET CHIP").as_bytes());
+
+        match Fits::parse_parallel(&corrupted) {
+            Err(Error::InvalidCard { offset, .. }) => assert_eq!(offset, (2880 + 3*80) as u64),
+            _ => panic!("Expected an invalid card"),
+        }
+        match Fits::parse_parallel(&data[0..(4*2880)]) {
+            Err(Error::UnexpectedEof { offset }) => assert_eq!(offset, 5760u64),
+            _ => panic!("Expected an unexpected end of file"),
+        }
+    }
+
+    #[test]
+    fn hdu_should_refuse_a_header_with_an_unknown_bitpix(){
+        let data = target_pixel_file();
+        let mut corrupted = data[2880..].to_vec();
+        corrupted[80..110].copy_from_slice(b"BITPIX  =                   12");
+
+        match hdu(&corrupted) {
+            IResult::Error(_) => (),
+            _ => panic!("Expected an error"),
+        }
+    }
+
     #[test]
     fn header_should_parse_a_primary_header(){
         let data = include_bytes!("../../assets/images/k2-trappist1-unofficial-tpf-long-cadence.fits");
@@ -252,37 +963,37 @@ mod tests {
                                Value::Integer(2i64),
                                Option::Some("number of standard extensions")),
             KeywordRecord::new(Keyword::EXTNAME,
-                               Value::CharacterString("PRIMARY "),
+                               Value::CharacterString(Cow::Borrowed("PRIMARY ")),
                                Option::Some("name of extension")),
             KeywordRecord::new(Keyword::EXTVER,
                                Value::Integer(1i64),
                                Option::Some("extension version number (not format version)")),
             KeywordRecord::new(Keyword::ORIGIN,
-                               Value::CharacterString("Unofficial data product"),
+                               Value::CharacterString(Cow::Borrowed("Unofficial data product")),
                                Option::Some("institution responsible for creating this")),
             KeywordRecord::new(Keyword::DATE,
-                               Value::CharacterString("2017-03-08"),
+                               Value::CharacterString(Cow::Borrowed("2017-03-08")),
                                Option::Some("file creation date.")),
             KeywordRecord::new(Keyword::CREATOR,
-                               Value::CharacterString("kadenza "),
+                               Value::CharacterString(Cow::Borrowed("kadenza ")),
                                Option::Some("pipeline job and program u")),
             KeywordRecord::new(Keyword::PROCVER,
-                               Value::CharacterString("2.1.dev "),
+                               Value::CharacterString(Cow::Borrowed("2.1.dev ")),
                                Option::Some("SW version")),
             KeywordRecord::new(Keyword::FILEVER,
-                               Value::CharacterString("0.0     "),
+                               Value::CharacterString(Cow::Borrowed("0.0     ")),
                                Option::Some("file format version")),
             KeywordRecord::new(Keyword::TIMVERSN,
-                               Value::CharacterString(""),
+                               Value::CharacterString(Cow::Borrowed("")),
                                Option::Some("OGIP memo number for file format")),
             KeywordRecord::new(Keyword::TELESCOP,
-                               Value::CharacterString("Kepler  "),
+                               Value::CharacterString(Cow::Borrowed("Kepler  ")),
                                Option::Some("telescope")),
             KeywordRecord::new(Keyword::INSTRUME,
-                               Value::CharacterString("Kepler Photometer"),
+                               Value::CharacterString(Cow::Borrowed("Kepler Photometer")),
                                Option::Some("detector type")),
             KeywordRecord::new(Keyword::OBJECT,
-                               Value::CharacterString("EPIC 200164267"),
+                               Value::CharacterString(Cow::Borrowed("EPIC 200164267")),
                                Option::Some("string version of target id")),
             KeywordRecord::new(Keyword::KEPLERID,
                                Value::Integer(200164267i64),
@@ -297,28 +1008,28 @@ mod tests {
                                Value::Integer(4i64),
                                Option::Some("CCD output")),
             KeywordRecord::new(Keyword::CAMPAIGN,
-                               Value::CharacterString(""),
+                               Value::CharacterString(Cow::Borrowed("")),
                                Option::Some("Observing campaign number")),
             KeywordRecord::new(Keyword::DATA_REL,
-                               Value::CharacterString(""),
+                               Value::CharacterString(Cow::Borrowed("")),
                                Option::Some("data release version number")),
             KeywordRecord::new(Keyword::OBSMODE,
-                               Value::CharacterString("long cadence"),
+                               Value::CharacterString(Cow::Borrowed("long cadence")),
                                Option::Some("observing mode")),
             KeywordRecord::new(Keyword::MISSION,
-                               Value::CharacterString("K2      "),
+                               Value::CharacterString(Cow::Borrowed("K2      ")),
                                Option::Some("Mission name")),
             KeywordRecord::new(Keyword::TTABLEID,
-                               Value::CharacterString(""),
+                               Value::CharacterString(Cow::Borrowed("")),
                                Option::Some("target table id")),
             KeywordRecord::new(Keyword::RADESYS,
-                               Value::CharacterString("ICRS    "),
+                               Value::CharacterString(Cow::Borrowed("ICRS    ")),
                                Option::Some("reference frame of celestial coordinates")),
             KeywordRecord::new(Keyword::RA_OBJ,
-                               Value::CharacterString(""),
+                               Value::CharacterString(Cow::Borrowed("")),
                                Option::Some("[deg] right ascension")),
             KeywordRecord::new(Keyword::DEC_OBJ,
-                               Value::CharacterString(""),
+                               Value::CharacterString(Cow::Borrowed("")),
                                Option::Some("[deg] declination")),
             KeywordRecord::new(Keyword::EQUINOX,
                                Value::Real(2000.0f64),
@@ -396,10 +1107,10 @@ mod tests {
                                Value::Undefined,
                                Option::Some("unique 2MASS catalog ID")),
             KeywordRecord::new(Keyword::CHECKSUM,
-                               Value::CharacterString("7k7A7h637h697h69"),
+                               Value::CharacterString(Cow::Borrowed("7k7A7h637h697h69")),
                                Option::Some("HDU checksum updated 2017-03-08T02:47:56")),
             KeywordRecord::new(Keyword::DATASUM,
-                               Value::CharacterString("0       "),
+                               Value::CharacterString(Cow::Borrowed("0       ")),
                                Option::Some("data unit checksum updated 2017-03-08T02:47:56")),
         ))
     }
@@ -415,7 +1126,7 @@ mod tests {
             IResult::Done(_,k) => {
                 assert_eq!(k, KeywordRecord::new(
                     Keyword::OBJECT,
-                    Value::CharacterString("EPIC 200164267"),
+                    Value::CharacterString(Cow::Borrowed("EPIC 200164267")),
                     Option::Some("string version of target id")
                 ))
             },
@@ -424,6 +1135,121 @@ mod tests {
         }
     }
 
+    #[test]
+    fn keyword_record_should_parse_complex_values(){
+        let data = [
+            ("CVALUE  = (123, 45.7)          / complex value                                   ", (123f64, 45.7f64)),
+            ("CVALUE  =            (123, 45)                                                  ", (123f64, 45f64)),
+        ];
+
+        for (card, pair) in data.iter() {
+            match keyword_record(card.as_bytes()) {
+                IResult::Done(_, k) => assert_eq!(*k.value(), Value::Complex(*pair)),
+                IResult::Error(_) => panic!("Did not expect an error"),
+                IResult::Incomplete(_) => panic!("Did not expect to be incomplete")
+            }
+        }
+    }
+
+    #[test]
+    fn keyword_record_should_keep_the_column_of_its_comment_while_the_value_is_unchanged(){
+        let card = format!("{:<80}", "NAXIS   =                    2               / number of axes");
+
+        let mut record = match keyword_record(card.as_bytes()) {
+            IResult::Done(_, record) => record,
+            _ => panic!("Expected a keyword record"),
+        };
+
+        assert_eq!(record.comment_column(), Some(45));
+        assert_eq!(record.to_string(), card);
+        record.set_value(Value::Integer(2i64));
+        assert_eq!(record.to_string(), card);
+        record.set_value(Value::Integer(3i64));
+        assert_eq!(record.to_string().trim_end(), "NAXIS   =                    3 / number of axes");
+    }
+
+    #[test]
+    fn keyword_record_should_validate_the_text_of_a_card_once(){
+        let mut card = "OBJECT  = 'TRAPPIST-1'         / name of the target                              ".as_bytes().to_vec();
+
+        card[51] = 0x7f;
+        match keyword_record(&card) {
+            IResult::Done(_, k) => assert_eq!(k.comment(), Some("name of the target")),
+            _ => panic!("Did not expect to fail on a printable prefix of the comment")
+        }
+
+        card[51] = 0xc3;
+        assert!(keyword_record(&card).is_err());
+    }
+
+    #[test]
+    fn keyword_record_should_parse_commentary_records(){
+        let data = [
+            ("COMMENT   FITS (Flexible Image Transport System) format is defined in 'Astronomy",
+             Keyword::COMMENT, "  FITS (Flexible Image Transport System) format is defined in 'Astronomy"),
+            ("HISTORY = not a value                                                           ",
+             Keyword::HISTORY, "= not a value"),
+            ("                                                                                ",
+             Keyword::BlankField, ""),
+        ];
+
+        for (input, keyword, text) in data.iter() {
+            match keyword_record(input.as_bytes()) {
+                IResult::Done(_, k) => {
+                    assert_eq!(k, KeywordRecord::new(
+                        keyword.clone(),
+                        Value::CommentText(Cow::Borrowed(text)),
+                        Option::None,
+                    ))
+                },
+                IResult::Error(_) => panic!("Did not expect an error"),
+                IResult::Incomplete(_) => panic!("Did not expect to be incomplete")
+            }
+        }
+    }
+
+    #[test]
+    fn header_should_parse_commentary_before_the_end_record(){
+        let mut data = String::new();
+        data.push_str("SIMPLE  =                    T                                                  ");
+        data.push_str("COMMENT a comment                                                               ");
+        data.push_str("                                                                                ");
+        data.push_str("HISTORY some history                                                            ");
+        data.push_str("END                                                                             ");
+
+        match header(data.as_bytes()) {
+            IResult::Done(_, h) => {
+                assert_eq!(h.keyword_records.len(), 4);
+                assert_eq!(h.comments(), vec!("a comment"));
+                assert_eq!(h.history(), vec!("some history"));
+            },
+            IResult::Error(_) => panic!("Did not expect an error"),
+            IResult::Incomplete(_) => panic!("Did not expect to be incomplete")
+        }
+    }
+
+    #[test]
+    fn header_should_join_continued_strings(){
+        let mut data = String::new();
+        data.push_str("SIMPLE  =                    T                                                  ");
+        data.push_str("OBJECT  = 'a string that is &'                                                  ");
+        data.push_str("CONTINUE  'continued over &'                                                    ");
+        data.push_str("CONTINUE  'several cards'      / the comment                                    ");
+        data.push_str("CONTINUE  'not continued'                                                       ");
+        data.push_str("END                                                                             ");
+
+        match header(data.as_bytes()) {
+            IResult::Done(_, h) => {
+                assert_eq!(h.keyword_records.len(), 3);
+                assert_eq!(h.get_str(&Keyword::OBJECT), Ok("a string that is continued over several cards"));
+                assert_eq!(h.keyword_records[1].comment(), Some("the comment"));
+                assert_eq!(*h.keyword_records[2].keyword(), Keyword::CONTINUE);
+            },
+            IResult::Error(_) => panic!("Did not expect an error"),
+            IResult::Incomplete(_) => panic!("Did not expect to be incomplete")
+        }
+    }
+
     #[test]
     fn keyword_record_should_parse_a_keyword_record_without_a_comment(){
         let data = "KEPLERID=            200164267                                                  "
@@ -446,14 +1272,13 @@ mod tests {
 
     #[test]
     fn valuecomment_should_parse_a_valuecomment(){
-        let data = "'EPIC 200164267'     / string version of target id                    "
-            .as_bytes();
+        let data = "'EPIC 200164267'     / string version of target id                    ";
 
         let result = valuecomment(data);
 
         match result {
             IResult::Done(_, (value, comment)) => {
-                assert_eq!(value, Value::CharacterString("EPIC 200164267"));
+                assert_eq!(value, Value::CharacterString(Cow::Borrowed("EPIC 200164267")));
                 assert_eq!(comment, Option::Some(" string version of target id                    "));
             },
             IResult::Error(_) => panic!("Did not expect an error"),
@@ -463,8 +1288,7 @@ mod tests {
 
     #[test]
     fn valuecomment_should_parse_a_valuecomment_without_a_comment(){
-        let data = "200164267                                                                                         "
-            .as_bytes();
+        let data = "200164267                                                                                         ";
 
         let result = valuecomment(data);
 
@@ -480,28 +1304,59 @@ mod tests {
 
     #[test]
     fn character_string_should_parse_an_quote_delimited_string(){
-        let data = "   'EPIC 200164267'   "
-            .as_bytes();
+        let data = "   'EPIC 200164267'   ";
+
+        let result = character_string(data);
+
+        match result {
+            IResult::Done(_, value) => {
+                assert_eq!(value, Value::CharacterString(Cow::Borrowed("EPIC 200164267")));
+            },
+            IResult::Error(_) => panic!("Did not expect an error"),
+            IResult::Incomplete(_) => panic!("Did not expect to be incomplete")
+        }
+    }
+
+    #[test]
+    fn character_string_should_unescape_doubled_quotes(){
+        let data = "   'O''HARA '   / name";
 
         let result = character_string(data);
 
         match result {
             IResult::Done(_, value) => {
-                assert_eq!(value, Value::CharacterString("EPIC 200164267"));
+                assert_eq!(value, Value::CharacterString(Cow::Borrowed("O'HARA ")));
             },
             IResult::Error(_) => panic!("Did not expect an error"),
             IResult::Incomplete(_) => panic!("Did not expect to be incomplete")
         }
     }
 
+    #[test]
+    fn keyword_record_should_keep_the_padding_of_a_character_string(){
+        let data = [
+            ("EXTNAME = 'PRIMARY '                                                            ", "PRIMARY ", "PRIMARY"),
+            ("OBSERVER= 'O''HARA'          / doubled quotes                                   ", "O'HARA", "O'HARA"),
+            ("OBJECT  = '        '                                                            ", "        ", " "),
+            ("OBJECT  = '  TRAPPIST-1 '                                                       ", "  TRAPPIST-1 ", "  TRAPPIST-1"),
+        ];
+
+        for (card, raw, trimmed) in data.iter() {
+            match keyword_record(card.as_bytes()) {
+                IResult::Done(_, k) => {
+                    assert_eq!(k.value().as_str(), Some(*raw));
+                    assert_eq!(k.value().as_trimmed_str(), Some(*trimmed));
+                },
+                _ => panic!("Did not expect to fail on {}", card)
+            }
+        }
+    }
 
     #[allow(non_snake_case)]
     #[test]
     fn logical_constant_should_parse_an_uppercase_T_or_F(){
         for (constant, boolean) in vec!(("T", true), ("F", false), ("   T ", true)) {
-            let data = constant.as_bytes();
-
-            let result = logical_constant(data);
+            let result = logical_constant(constant);
 
             match result {
                 IResult::Done(_, value) => assert_eq!(value, Value::Logical(boolean)),
@@ -514,9 +1369,7 @@ mod tests {
     #[test]
     fn real_should_parse_an_floating_point_number() {
         for (input, f) in vec!(("1.0", 1f64), ("37.0", 37f64), ("51.0", 51f64)) {
-            let data = input.as_bytes();
-
-            let result = real(data);
+            let result = real(input);
 
             match result {
                 IResult::Done(_, value) => assert_eq!(value, Value::Real(f)),
@@ -527,11 +1380,63 @@ mod tests {
     }
 
     #[test]
-    fn integer_should_parse_an_integer() {
-        for (input, n) in vec!(("1", 1i64), ("37", 37i64), ("51", 51i64)) {
-            let data = input.as_bytes();
+    fn real_should_parse_signs_exponents_and_missing_digits() {
+        let data = [
+            ("-1.5", -1.5f64),
+            ("+2.", 2f64),
+            (".5", 0.5f64),
+            ("1.0E-05", 1e-5f64),
+            ("6.02E23", 6.02e23f64),
+            ("-3.0D2", -300f64),
+            ("1E3", 1000f64),
+        ];
+
+        for (input, f) in data.iter() {
+            match real(input) {
+                IResult::Done(_, value) => assert_eq!(value, Value::Real(*f)),
+                IResult::Error(_) => panic!("Did not expect an error"),
+                IResult::Incomplete(_) => panic!("Did not expect to be incomplete")
+            }
+        }
+    }
+
+    #[test]
+    fn real_should_refuse_integers() {
+        assert!(!real("32 ").is_done());
+    }
+
+    #[test]
+    fn complex_should_parse_integer_and_real_pairs() {
+        for (input, pair) in [("(1, -2)", (1f64, -2f64)), (" ( 1.5E1 ,.5 ) ", (15f64, 0.5f64))] {
+            match complex(input) {
+                IResult::Done(_, value) => assert_eq!(value, Value::Complex(pair)),
+                IResult::Error(_) => panic!("Did not expect an error"),
+                IResult::Incomplete(_) => panic!("Did not expect to be incomplete")
+            }
+        }
+    }
+
+    #[test]
+    fn value_should_parse_negative_numbers_before_a_comment() {
+        let data = [
+            ("                 -32 / array data type", Value::Integer(-32i64)),
+            ("             +007", Value::Integer(7i64)),
+            ("           -1.0E-05 / small", Value::Real(-1e-5f64)),
+        ];
+
+        for (input, expected) in data.iter() {
+            match valuecomment(&format!("{:<70}", input)) {
+                IResult::Done(_, (value, _)) => assert_eq!(value, *expected),
+                IResult::Error(_) => panic!("Did not expect an error"),
+                IResult::Incomplete(_) => panic!("Did not expect to be incomplete")
+            }
+        }
+    }
 
-            let result = integer(data);
+    #[test]
+    fn integer_should_parse_an_integer() {
+        for (input, n) in vec!(("1", 1i64), ("37", 37i64), ("51", 51i64), ("-32", -32i64), ("+5", 5i64)) {
+            let result = integer(input);
 
             match result {
                 IResult::Done(_, value) => assert_eq!(value, Value::Integer(n)),
@@ -541,12 +1446,18 @@ mod tests {
         }
     }
 
+    #[test]
+    fn integer_should_parse_an_integer_that_overflows_as_a_real() {
+        match integer("9223372036854775808") {
+            IResult::Done(_, value) => assert_eq!(value, Value::Real(9223372036854775808f64)),
+            _ => panic!("Expected a value"),
+        }
+    }
+
     #[test]
     fn undefined_should_parse_any_amount_of_whitespace() {
         for input in vec!(" ", "\t", "    \t   ") {
-            let data = input.as_bytes();
-
-            let result = undefined(data);
+            let result = undefined(input);
 
             match result {
                 IResult::Done(_, value) => assert_eq!(value, Value::Undefined),
@@ -558,8 +1469,7 @@ mod tests {
 
     #[test]
     fn keyword_should_parse_a_keyword(){
-        let data = "OBJECT  "
-            .as_bytes();
+        let data = "OBJECT  ";
 
         let result = keyword(data);
 
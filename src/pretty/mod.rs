@@ -0,0 +1,136 @@
+//! The pretty module renders headers for humans.
+//!
+//! Keywords, values and comments are aligned in columns. Optionally the
+//! columns are colored with ANSI escape codes, for display in a terminal.
+
+use super::types::{Header, KeywordRecord, Keyword, Value};
+
+const RESET: &str = "\x1b[0m";
+const KEYWORD: &str = "\x1b[1;34m";
+const STRING: &str = "\x1b[32m";
+const NUMBER: &str = "\x1b[36m";
+const LOGICAL: &str = "\x1b[33m";
+const COMMENT: &str = "\x1b[2m";
+
+/// Options that determine how a header is rendered.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct PrettyOptions {
+    /// Color the columns with ANSI escape codes.
+    pub color: bool,
+    /// Leave out the comments of keyword records.
+    pub hide_comments: bool,
+}
+
+impl PrettyOptions {
+    /// Options for rendering without color.
+    pub fn plain() -> PrettyOptions {
+        PrettyOptions::default()
+    }
+
+    /// Options for rendering with color.
+    pub fn colored() -> PrettyOptions {
+        PrettyOptions { color: true, ..PrettyOptions::default() }
+    }
+}
+
+/// Render a header in aligned columns, one line per keyword record. The `END`
+/// record is left out.
+pub fn render(header: &Header, options: &PrettyOptions) -> String {
+    let records: Vec<&KeywordRecord> = header.keyword_records.iter()
+        .filter(|record| *record.keyword() != Keyword::END)
+        .collect();
+    let keyword_width = records.iter()
+        .map(|record| record.keyword().to_string().len())
+        .max()
+        .unwrap_or(0)
+        .max(8);
+    let value_width = records.iter()
+        .filter(|record| !is_commentary(record))
+        .map(|record| record.value().to_string().len())
+        .max()
+        .unwrap_or(0);
+    let mut result = String::new();
+    for record in records {
+        let keyword = format!("{:<width$}", record.keyword(), width = keyword_width);
+        let mut line = paint(&keyword, KEYWORD, options);
+        if is_commentary(record) {
+            line.push(' ');
+            line.push_str(&paint(&record.value().to_string(), COMMENT, options));
+        } else {
+            let value = format!("{:<width$}", record.value(), width = value_width);
+            line.push_str(" = ");
+            line.push_str(&paint(&value, color_of(record.value()), options));
+            match record.comment() {
+                Some(comment) if !options.hide_comments => {
+                    line.push_str(" / ");
+                    line.push_str(&paint(comment, COMMENT, options));
+                },
+                _ => (),
+            }
+        }
+        result.push_str(line.trim_end());
+        result.push('\n');
+    }
+    result
+}
+
+fn is_commentary(record: &KeywordRecord) -> bool {
+    matches!(*record.value(), Value::CommentText(_))
+}
+
+fn color_of(value: &Value) -> &'static str {
+    match *value {
+        Value::CharacterString(_) => STRING,
+        Value::Logical(_) => LOGICAL,
+        Value::Integer(_) | Value::Real(_) | Value::Complex(_) => NUMBER,
+        Value::Undefined | Value::CommentText(_) | Value::Unparsed(_) => "",
+    }
+}
+
+fn paint(text: &str, color: &str, options: &PrettyOptions) -> String {
+    if options.color && !color.is_empty() {
+        format!("{}{}{}", color, text, RESET)
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use super::super::types::{Header, KeywordRecord, Keyword, Value};
+    use super::{render, PrettyOptions};
+
+    fn header<'a>() -> Header<'a> {
+        Header::new(vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::Some("conforms to FITS standards")),
+            KeywordRecord::new(Keyword::OBJECT, Value::CharacterString(Cow::Borrowed("EPIC 200164267")), Option::Some("target")),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(0i64), Option::None),
+            KeywordRecord::new(Keyword::HISTORY, Value::CommentText(Cow::Borrowed("created")), Option::None),
+            KeywordRecord::new(Keyword::END, Value::Undefined, Option::None),
+        ))
+    }
+
+    #[test]
+    fn render_should_align_columns() {
+        assert_eq!(render(&header(), &PrettyOptions::plain()),
+                   "SIMPLE   = T                / conforms to FITS standards\n\
+                    OBJECT   = 'EPIC 200164267' / target\n\
+                    NAXIS    = 0\n\
+                    HISTORY  created\n");
+    }
+
+    #[test]
+    fn render_should_hide_comments_on_request() {
+        let options = PrettyOptions { hide_comments: true, ..PrettyOptions::plain() };
+
+        assert!(!render(&header(), &options).contains("target"));
+    }
+
+    #[test]
+    fn render_should_color_columns_on_request() {
+        let rendered = render(&header(), &PrettyOptions::colored());
+
+        assert!(rendered.starts_with("\x1b[1;34mSIMPLE  \x1b[0m = \x1b[33mT               \x1b[0m / \x1b[2mconforms"));
+    }
+}
@@ -0,0 +1,264 @@
+//! The random groups module reads primary HDUs that use the random groups
+//! convention, as written by radio interferometry software like AIPS.
+//!
+//! A random groups HDU has `GROUPS = T` and `NAXIS1 = 0`. Its data consists of
+//! `GCOUNT` groups. Each group starts with `PCOUNT` parameters, e.g. the `UU`,
+//! `VV` and `DATE` of a visibility, followed by an array with the axes
+//! `NAXIS2` up to `NAXISm`. Parameters are named by `PTYPEn` and scaled by
+//! `PSCALn` and `PZEROn`. Parameters that share a name are meant to be added,
+//! which keeps the precision of e.g. a Julian date in single precision values.
+
+use std::convert::TryFrom;
+use std::error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use super::types::{Bitpix, BitpixError, DataArray, DataArrayError, Header, HDU, Keyword, Scaling, ValueRetrievalError};
+
+/// A random parameter of the groups.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Parameter {
+    /// The name of the parameter, i.e. `PTYPEn`.
+    pub name: Option<String>,
+    /// The factor of the raw value, i.e. `PSCALn`.
+    pub scale: f64,
+    /// The offset of the raw value, i.e. `PZEROn`.
+    pub zero: f64,
+}
+
+impl Parameter {
+    /// The physical value of a raw parameter value.
+    pub fn apply(&self, raw: f64) -> f64 {
+        self.zero + self.scale * raw
+    }
+}
+
+/// Problems that occur when reading random groups.
+#[derive(Debug, PartialEq)]
+pub enum GroupsError {
+    /// The HDU does not use the random groups convention.
+    NotRandomGroups,
+    /// A keyword has a value of the wrong type.
+    InvalidValue {
+        /// The keyword.
+        keyword: Keyword,
+        /// What is wrong with the value.
+        error: ValueRetrievalError,
+    },
+    /// `BITPIX` is invalid.
+    InvalidBitpix(BitpixError),
+    /// The data unit is smaller than the groups described by the header.
+    MissingData,
+    /// There is no group with this index.
+    OutOfRange(usize),
+    /// No parameter has this name.
+    UnknownParameter(String),
+    /// The values of a group could not be decoded.
+    DataArray(DataArrayError),
+}
+
+impl Display for GroupsError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            GroupsError::NotRandomGroups => write!(f, "the HDU does not contain random groups"),
+            GroupsError::InvalidValue { ref keyword, ref error } => write!(f, "{}: {}", keyword, error),
+            GroupsError::InvalidBitpix(ref error) => write!(f, "{}", error),
+            GroupsError::MissingData => write!(f, "the data unit is smaller than GCOUNT groups"),
+            GroupsError::OutOfRange(group) => write!(f, "there is no group {}", group),
+            GroupsError::UnknownParameter(ref name) => write!(f, "there is no parameter {}", name),
+            GroupsError::DataArray(ref error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl error::Error for GroupsError {}
+
+impl From<DataArrayError> for GroupsError {
+    fn from(error: DataArrayError) -> Self {
+        GroupsError::DataArray(error)
+    }
+}
+
+/// The random groups of a primary HDU, borrowing its data.
+#[derive(Debug)]
+pub struct RandomGroups<'g> {
+    parameters: Vec<Parameter>,
+    axes: Vec<usize>,
+    count: usize,
+    width: usize,
+    bitpix: Bitpix,
+    scaling: Scaling,
+    data: &'g [u8],
+}
+
+impl<'g> RandomGroups<'g> {
+    /// Read the layout of the random groups in `hdu`.
+    pub fn from_hdu<'a>(hdu: &'g HDU<'a>) -> Result<RandomGroups<'g>, GroupsError> {
+        let header = &hdu.header;
+        if !header.is_random_groups() {
+            return Err(GroupsError::NotRandomGroups)
+        }
+        let bitpix = header.bitpix().map_err(GroupsError::InvalidBitpix)?;
+        let scaling = header.scaling().map_err(|error| GroupsError::InvalidValue { keyword: Keyword::BSCALE, error })?;
+        let axes = header.axes()
+            .map_err(|_| GroupsError::InvalidValue { keyword: Keyword::NAXIS, error: ValueRetrievalError::NotAnInteger })?
+            .split_off(1);
+        let count = non_negative(header, Keyword::GCOUNT, 1)?;
+        let parameter_count = non_negative(header, Keyword::PCOUNT, 0)?;
+        let mut parameters = vec!();
+        for n in 1..(parameter_count + 1) {
+            let n = u16::try_from(n)
+                .map_err(|_| GroupsError::InvalidValue { keyword: Keyword::PCOUNT, error: ValueRetrievalError::NotAnInteger })?;
+            let name = optional(Keyword::PTYPEn(n), header.get_str(&Keyword::PTYPEn(n)))?.map(|name| name.trim_end().to_string());
+            let scale = optional(Keyword::PSCALn(n), header.get_real(&Keyword::PSCALn(n)))?.unwrap_or(1f64);
+            let zero = optional(Keyword::PZEROn(n), header.get_real(&Keyword::PZEROn(n)))?.unwrap_or(0f64);
+            parameters.push(Parameter { name, scale, zero });
+        }
+        let data = hdu.data().bytes();
+        let width = axes.iter().try_fold(1usize, |product, axis| product.checked_mul(*axis))
+            .and_then(|values| values.checked_add(parameter_count))
+            .and_then(|values| values.checked_mul(bitpix.bytes()))
+            .ok_or(GroupsError::MissingData)?;
+        let size = width.checked_mul(count).filter(|size| *size <= data.len()).ok_or(GroupsError::MissingData)?;
+        Ok(RandomGroups { parameters, axes, count, width, bitpix, scaling, data: &data[0..size] })
+    }
+
+    /// The random parameters of each group.
+    pub fn parameters(&self) -> &[Parameter] {
+        &self.parameters
+    }
+
+    /// The axes of the array of each group, i.e. `NAXIS2` up to `NAXISm`.
+    pub fn axes(&self) -> &[usize] {
+        &self.axes
+    }
+
+    /// The number of groups, i.e. `GCOUNT`.
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    /// Whether there are no groups.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// The physical values of the parameters of a group.
+    pub fn parameter_values(&self, group: usize) -> Result<Vec<f64>, GroupsError> {
+        let bytes = &self.group(group)?[0..self.parameters.len() * self.bitpix.bytes()];
+        let raw = DataArray::new(self.bitpix, vec!(self.parameters.len()), bytes).to_f64()?;
+        Ok(raw.into_iter().zip(&self.parameters).map(|(raw, parameter)| parameter.apply(raw)).collect())
+    }
+
+    /// The value of the parameter with a name, case-insensitive. The values of
+    /// parameters that share the name are added.
+    pub fn parameter(&self, group: usize, name: &str) -> Result<f64, GroupsError> {
+        let values = self.parameter_values(group)?;
+        let matching: Vec<f64> = self.parameters.iter().zip(values)
+            .filter(|(parameter, _)| parameter.name.as_ref().is_some_and(|candidate| candidate.eq_ignore_ascii_case(name)))
+            .map(|(_, value)| value)
+            .collect();
+        if matching.is_empty() {
+            Err(GroupsError::UnknownParameter(name.to_string()))
+        } else {
+            Ok(matching.into_iter().sum())
+        }
+    }
+
+    /// The physical values of the array of a group, i.e. with `BSCALE`,
+    /// `BZERO` and `BLANK` applied. The first axis varies fastest.
+    pub fn array(&self, group: usize) -> Result<Vec<f64>, GroupsError> {
+        let bytes = &self.group(group)?[self.parameters.len() * self.bitpix.bytes()..];
+        let size = bytes.len() / self.bitpix.bytes();
+        Ok(DataArray::new(self.bitpix, vec!(size), bytes).with_scaling(self.scaling).physical_values()?)
+    }
+
+    fn group(&self, group: usize) -> Result<&'g [u8], GroupsError> {
+        if group >= self.count {
+            return Err(GroupsError::OutOfRange(group))
+        }
+        Ok(&self.data[group * self.width..(group + 1) * self.width])
+    }
+}
+
+fn non_negative(header: &Header, keyword: Keyword, default: i64) -> Result<usize, GroupsError> {
+    let value = optional(keyword.clone(), header.get_int(&keyword))?.unwrap_or(default);
+    usize::try_from(value).map_err(|_| GroupsError::InvalidValue { keyword, error: ValueRetrievalError::NotAnInteger })
+}
+
+fn optional<T>(keyword: Keyword, result: Result<T, ValueRetrievalError>) -> Result<Option<T>, GroupsError> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(ValueRetrievalError::KeywordNotPresent) => Ok(None),
+        Err(error) => Err(GroupsError::InvalidValue { keyword, error }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use super::super::parser::parse;
+    use super::super::types::{Fits, Header, HDU, KeywordRecord, Keyword, Value};
+    use super::super::writer::write_fits;
+    use super::{GroupsError, RandomGroups};
+
+    fn uv_data() -> HDU<'static> {
+        let header = Header::new(vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(16i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(3i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(0i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(2u16), Value::Integer(3i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(3u16), Value::Integer(1i64), Option::None),
+            KeywordRecord::new(Keyword::GROUPS, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::PCOUNT, Value::Integer(3i64), Option::None),
+            KeywordRecord::new(Keyword::GCOUNT, Value::Integer(2i64), Option::None),
+            KeywordRecord::new(Keyword::BSCALE, Value::Real(0.5f64), Option::None),
+            KeywordRecord::new(Keyword::PTYPEn(1u16), Value::CharacterString(Cow::Borrowed("UU")), Option::None),
+            KeywordRecord::new(Keyword::PSCALn(1u16), Value::Real(2f64), Option::None),
+            KeywordRecord::new(Keyword::PTYPEn(2u16), Value::CharacterString(Cow::Borrowed("DATE")), Option::None),
+            KeywordRecord::new(Keyword::PZEROn(2u16), Value::Real(2450000f64), Option::None),
+            KeywordRecord::new(Keyword::PTYPEn(3u16), Value::CharacterString(Cow::Borrowed("DATE")), Option::None),
+            KeywordRecord::new(Keyword::END, Value::Undefined, Option::None),
+        ));
+        let values: Vec<i16> = vec!(1, 10, 1, 2, 4, 6, -1, 20, 2, 8, 10, 12);
+        HDU::with_owned_data(header, values.into_iter().flat_map(i16::to_be_bytes).collect())
+    }
+
+    #[test]
+    fn random_groups_should_apply_parameter_and_array_scaling() {
+        let hdu = uv_data();
+        let groups = RandomGroups::from_hdu(&hdu).unwrap();
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups.axes(), &[3usize, 1usize]);
+        assert_eq!(groups.parameter_values(1).unwrap(), vec!(-2f64, 2450020f64, 2f64));
+        assert_eq!(groups.parameter(0, "uu"), Ok(2f64));
+        assert_eq!(groups.parameter(1, "DATE"), Ok(2450022f64));
+        assert_eq!(groups.parameter(0, "VV"), Err(GroupsError::UnknownParameter("VV".to_string())));
+        assert_eq!(groups.array(0).unwrap(), vec!(1f64, 2f64, 3f64));
+        assert_eq!(groups.array(2), Err(GroupsError::OutOfRange(2)));
+    }
+
+    #[test]
+    fn random_groups_should_survive_writing_and_parsing() {
+        let mut bytes = vec!();
+        write_fits(&mut bytes, &Fits::new(uv_data(), vec!())).unwrap();
+
+        let fits = parse(&bytes).unwrap();
+        let groups = RandomGroups::from_hdu(&fits.primary_hdu).unwrap();
+
+        assert_eq!(bytes.len(), 2 * 2880);
+        assert_eq!(groups.array(1).unwrap(), vec!(4f64, 5f64, 6f64));
+    }
+
+    #[test]
+    fn an_image_should_not_be_random_groups() {
+        let hdu = HDU::new(Header::new(vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(16i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(0i64), Option::None),
+        )));
+
+        assert_eq!(RandomGroups::from_hdu(&hdu).unwrap_err(), GroupsError::NotRandomGroups);
+    }
+}
@@ -0,0 +1,890 @@
+//! The reader module parses FITS files from a stream, one HDU at a time.
+//!
+//! In contrast with `parser::fits`, the file does not have to be in memory.
+//! Only headers are read eagerly, data units are skipped and can be read on
+//! request. `FitsReader::read_image_decimated` reads a preview of an image,
+//! skipping the rows that are left out.
+//!
+//! With the `tokio` feature, `FitsAsyncReader` does the same for asynchronous
+//! readers, e.g. of files behind HTTP range requests. Only the headers are
+//! fetched while listing the HDUs of a file.
+
+use std::convert::TryFrom;
+use std::error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::io::{Read, Seek, SeekFrom};
+#[cfg(feature = "tokio")]
+use std::future::Future;
+#[cfg(feature = "tokio")]
+use std::pin::Pin;
+#[cfg(feature = "tokio")]
+use std::task::{Context, Poll};
+use nom::IResult;
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncRead, AsyncSeek, ReadBuf};
+use super::checksum;
+use super::checksum::ChecksumReport;
+use super::parser::{header, invalid_card};
+use super::types::{DataArray, HDU, HduKind, Header, AxisError, BitpixError};
+
+/// The size in bytes of a FITS block.
+const BLOCK_SIZE: usize = 2880;
+/// The size in bytes of a card.
+const CARD_SIZE: usize = 80;
+
+/// Problems that could occur when reading a FITS file from a stream.
+#[derive(Debug)]
+pub enum ReadError {
+    /// The underlying reader failed.
+    Io(io::Error),
+    /// The stream ended in the middle of a header, or of a data unit that is
+    /// consumed from a stream.
+    UnexpectedEof {
+        /// Byte offset of the header of the HDU that could not be completed.
+        offset: u64,
+    },
+    /// The header starting at this offset could not be parsed.
+    InvalidHeader {
+        /// Byte offset of the header that could not be parsed.
+        offset: u64,
+    },
+    /// A card of a header could not be parsed.
+    InvalidCard {
+        /// Byte offset of the card.
+        offset: u64,
+        /// The text of the card.
+        card: String,
+    },
+    /// The `BITPIX` keyword of the header starting at this offset is missing
+    /// or invalid.
+    InvalidBitpix {
+        /// Byte offset of the header with an invalid `BITPIX`.
+        offset: u64,
+        /// What is wrong with `BITPIX`.
+        error: BitpixError,
+    },
+    /// The `NAXIS` and `NAXISn` keywords of the header starting at this offset
+    /// are inconsistent.
+    InvalidAxes {
+        /// Byte offset of the header with inconsistent axes.
+        offset: u64,
+        /// What is wrong with the axes.
+        error: AxisError,
+    },
+    /// The data unit of the HDU whose header starts at this offset is too
+    /// large to be addressed, or to be loaded in memory.
+    TooLarge {
+        /// Byte offset of the header of the HDU.
+        offset: u64,
+    },
+    /// The HDU whose header starts at this offset does not contain an image.
+    NotAnImage {
+        /// Byte offset of the header of the HDU.
+        offset: u64,
+    },
+}
+
+impl Display for ReadError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            ReadError::Io(ref error) => write!(f, "{}", error),
+            ReadError::UnexpectedEof { offset } => write!(f, "unexpected end of file in the header starting at byte {}", offset),
+            ReadError::InvalidHeader { offset } => write!(f, "invalid header at byte {}", offset),
+            ReadError::InvalidCard { offset, ref card } => write!(f, "invalid card at byte {}: \"{}\"", offset, card.trim_end()),
+            ReadError::InvalidBitpix { offset, ref error } => write!(f, "header at byte {}: {}", offset, error),
+            ReadError::InvalidAxes { offset, ref error } => write!(f, "header at byte {}: {}", offset, error),
+            ReadError::TooLarge { offset } => write!(f, "the data unit of the header at byte {} is too large", offset),
+            ReadError::NotAnImage { offset } => write!(f, "the HDU with the header at byte {} does not contain an image", offset),
+        }
+    }
+}
+
+impl error::Error for ReadError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            ReadError::Io(ref error) => Some(error),
+            ReadError::InvalidBitpix { ref error, .. } => Some(error),
+            ReadError::InvalidAxes { ref error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ReadError {
+    fn from(error: io::Error) -> Self {
+        ReadError::Io(error)
+    }
+}
+
+/// A header read from a stream, together with the location of its data unit.
+#[derive(Debug, PartialEq, Clone)]
+pub struct HduEntry {
+    /// The header of this HDU.
+    pub header: Header<'static>,
+    /// Byte offset of the header within the stream.
+    pub header_offset: u64,
+    /// Byte offset of the data unit within the stream.
+    pub data_offset: u64,
+    /// Length in bytes of the data unit, including padding.
+    pub data_length: u64,
+}
+
+/// Reads a FITS file HDU by HDU, without loading data units in memory.
+///
+/// Iterating a `FitsReader` yields an `HduEntry` per HDU. Use `read_hdu` to
+/// load the data unit of an entry.
+pub struct FitsReader<R> {
+    inner: R,
+    position: u64,
+    header_offset: u64,
+    done: bool,
+}
+
+impl<R: Read + Seek> FitsReader<R> {
+    /// Create a `FitsReader` that reads from the start of `inner`. All offsets
+    /// are relative to the start of the stream.
+    pub fn new(inner: R) -> FitsReader<R> {
+        FitsReader { inner, position: 0, header_offset: 0, done: false }
+    }
+
+    /// Read the header of the next HDU and skip its data unit. Returns `None`
+    /// when the stream is exhausted.
+    pub fn next_entry(&mut self) -> Option<Result<HduEntry, ReadError>> {
+        if self.done {
+            return None
+        }
+        let result = self.read_entry();
+        match result {
+            Ok(Some(entry)) => Some(Ok(entry)),
+            Ok(None) => {
+                self.done = true;
+                None
+            },
+            Err(error) => {
+                self.done = true;
+                Some(Err(error))
+            },
+        }
+    }
+
+    /// Load the HDU of an entry, including its data array.
+    pub fn read_hdu(&mut self, entry: &HduEntry) -> Result<HDU<'static>, ReadError> {
+        let mut data = buffer_for(entry)?;
+        self.inner.seek(SeekFrom::Start(entry.data_offset))?;
+        (&mut self.inner).take(entry.data_length).read_to_end(&mut data)?;
+        if (data.len() as u64) < entry.data_length {
+            return Err(ReadError::UnexpectedEof { offset: entry.header_offset })
+        }
+        Ok(HDU::with_data(entry.header.clone(), &data).into_owned())
+    }
+
+    /// Verify `DATASUM` and `CHECKSUM` of the HDU of an entry. The HDU is read
+    /// block by block, so the data unit is not loaded in memory.
+    pub fn verify_checksum(&mut self, entry: &HduEntry) -> Result<ChecksumReport, ReadError> {
+        self.inner.seek(SeekFrom::Start(entry.header_offset))?;
+        let mut headersum = 0u32;
+        let mut datasum = 0u32;
+        let mut position = entry.header_offset;
+        while position < entry.data_offset + entry.data_length {
+            let mut block = [0u8; BLOCK_SIZE];
+            if read_block(&mut self.inner, &mut block)? < BLOCK_SIZE {
+                return Err(ReadError::UnexpectedEof { offset: entry.header_offset })
+            }
+            if position < entry.data_offset {
+                headersum = checksum::add(headersum, &block);
+            } else {
+                datasum = checksum::add(datasum, &block);
+            }
+            position += BLOCK_SIZE as u64;
+        }
+        let hdusum = checksum::add(headersum, &datasum.to_be_bytes());
+        Ok(checksum::report(&entry.header, datasum, hdusum))
+    }
+
+    /// Read every `factor`-th value along each axis of the image of an entry,
+    /// starting with the first, e.g. for a quick look at a huge image. Only
+    /// the rows that hold such values are read, the others are skipped by
+    /// seeking. A `factor` of 1 reads the complete image.
+    pub fn read_image_decimated(&mut self, entry: &HduEntry, factor: usize) -> Result<DataArray<'static>, ReadError> {
+        let offset = entry.header_offset;
+        if factor == 0 {
+            return Err(ReadError::Io(io::Error::new(io::ErrorKind::InvalidInput, "the decimation factor should be at least 1")))
+        }
+        if entry.header.kind() != HduKind::Image {
+            return Err(ReadError::NotAnImage { offset })
+        }
+        let bitpix = entry.header.bitpix().map_err(|error| ReadError::InvalidBitpix { offset, error })?;
+        let axes = entry.header.axes().map_err(|error| ReadError::InvalidAxes { offset, error })?;
+        let decimated: Vec<usize> = axes.iter().map(|axis| axis.div_ceil(factor)).collect();
+        let scaling = entry.header.scaling().unwrap_or_default();
+        let size = bitpix.bytes();
+        let mut data = vec!();
+        decimated.iter().try_fold(size, |product, axis| product.checked_mul(*axis))
+            .and_then(|length| data.try_reserve_exact(length).ok())
+            .ok_or(ReadError::TooLarge { offset })?;
+        if decimated.is_empty() || decimated.contains(&0) {
+            return Ok(DataArray::from_vec(bitpix, decimated, data).with_scaling(scaling))
+        }
+        // Every offset in the data unit fits once its size does.
+        axes.iter().try_fold(size as u64, |product, axis| product.checked_mul(*axis as u64))
+            .and_then(|length| length.checked_add(entry.data_offset))
+            .ok_or(ReadError::TooLarge { offset })?;
+        let row_length = axes[0].checked_mul(size).ok_or(ReadError::TooLarge { offset })?;
+        let mut row = vec!();
+        row.try_reserve_exact(row_length).map_err(|_| ReadError::TooLarge { offset })?;
+        row.resize(row_length, 0u8);
+        // The index of the current row along each axis but the first.
+        let mut index = vec![0usize; axes.len() - 1];
+        loop {
+            let (row_number, _) = index.iter().zip(&axes[1..])
+                .fold((0u64, 1u64), |(number, stride), (i, axis)| (number + *i as u64 * stride, stride * *axis as u64));
+            self.inner.seek(SeekFrom::Start(entry.data_offset + row_number * row_length as u64))?;
+            self.inner.read_exact(&mut row).map_err(|error| match error.kind() {
+                io::ErrorKind::UnexpectedEof => ReadError::UnexpectedEof { offset },
+                _ => ReadError::Io(error),
+            })?;
+            for value in row.chunks(size).step_by(factor) {
+                data.extend_from_slice(value);
+            }
+            // Advance to the next selected row, like an odometer over the other axes.
+            let mut axis = 0;
+            while axis < index.len() {
+                index[axis] += factor;
+                if index[axis] < axes[axis + 1] {
+                    break
+                }
+                index[axis] = 0;
+                axis += 1;
+            }
+            if axis == index.len() {
+                break
+            }
+        }
+        Ok(DataArray::from_vec(bitpix, decimated, data).with_scaling(scaling))
+    }
+
+    /// Unwrap this `FitsReader`, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn read_entry(&mut self) -> Result<Option<HduEntry>, ReadError> {
+        self.inner.seek(SeekFrom::Start(self.position))?;
+        let entry = read_entry(&mut self.inner, self.position)?;
+        match entry {
+            Some(ref entry) => {
+                self.position = entry.data_offset + entry.data_length;
+                self.header_offset = entry.header_offset;
+            },
+            // Seeking past the end succeeds, so the stream could end before
+            // the data unit of the previous entry does.
+            None => if self.inner.seek(SeekFrom::End(0))? < self.position {
+                return Err(ReadError::UnexpectedEof { offset: self.header_offset })
+            },
+        }
+        Ok(entry)
+    }
+}
+
+impl<R: Read + Seek> Iterator for FitsReader<R> {
+    type Item = Result<HduEntry, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_entry()
+    }
+}
+
+/// Reads a FITS file HDU by HDU from a stream that can not seek, e.g. standard
+/// input.
+///
+/// Data units that are not read with `read_hdu` are consumed when the next
+/// entry is read. The reader keeps track of the number of bytes consumed, so
+/// offsets in entries and errors are relative to the start of the stream.
+pub struct FitsStreamReader<R> {
+    inner: R,
+    position: u64,
+    header_offset: u64,
+    skip: u64,
+    done: bool,
+}
+
+impl<R: Read> FitsStreamReader<R> {
+    /// Create a `FitsStreamReader` that reads from the current position of
+    /// `inner`, which is taken as offset 0.
+    pub fn new(inner: R) -> FitsStreamReader<R> {
+        FitsStreamReader { inner, position: 0, header_offset: 0, skip: 0, done: false }
+    }
+
+    /// The number of bytes consumed from the stream.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Read the header of the next HDU. The data unit of the previous entry is
+    /// consumed first, if it was not read. Returns `None` when the stream is
+    /// exhausted.
+    pub fn next_entry(&mut self) -> Option<Result<HduEntry, ReadError>> {
+        if self.done {
+            return None
+        }
+        let result = self.read_entry();
+        match result {
+            Ok(Some(entry)) => Some(Ok(entry)),
+            Ok(None) => {
+                self.done = true;
+                None
+            },
+            Err(error) => {
+                self.done = true;
+                Some(Err(error))
+            },
+        }
+    }
+
+    /// Load the HDU of the last entry, including its data array. Only the data
+    /// unit that follows the stream position can be read.
+    pub fn read_hdu(&mut self, entry: &HduEntry) -> Result<HDU<'static>, ReadError> {
+        if entry.data_offset != self.position || entry.data_length != self.skip {
+            let message = format!("the data unit at byte {} was already consumed from the stream", entry.data_offset);
+            return Err(ReadError::Io(io::Error::new(io::ErrorKind::InvalidInput, message)))
+        }
+        let mut data = buffer_for(entry)?;
+        (&mut self.inner).take(entry.data_length).read_to_end(&mut data)?;
+        self.position += data.len() as u64;
+        self.skip = 0;
+        if (data.len() as u64) < entry.data_length {
+            self.done = true;
+            return Err(ReadError::UnexpectedEof { offset: entry.header_offset })
+        }
+        Ok(HDU::with_data(entry.header.clone(), &data).into_owned())
+    }
+
+    /// Unwrap this `FitsStreamReader`, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn read_entry(&mut self) -> Result<Option<HduEntry>, ReadError> {
+        if self.skip > 0 {
+            let skipped = io::copy(&mut (&mut self.inner).take(self.skip), &mut io::sink())?;
+            self.position += skipped;
+            if skipped < self.skip {
+                return Err(ReadError::UnexpectedEof { offset: self.header_offset })
+            }
+            self.skip = 0;
+        }
+        let entry = read_entry(&mut self.inner, self.position)?;
+        if let Some(ref entry) = entry {
+            self.position = entry.data_offset;
+            self.header_offset = entry.header_offset;
+            self.skip = entry.data_length;
+        }
+        Ok(entry)
+    }
+}
+
+impl<R: Read> Iterator for FitsStreamReader<R> {
+    type Item = Result<HduEntry, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_entry()
+    }
+}
+
+/// Reads a FITS file HDU by HDU from an asynchronous reader, without loading
+/// data units in memory. Data units are skipped by seeking, so listing the
+/// HDUs of a remote file only fetches their headers.
+///
+/// `next_entry` and `read_hdu` return futures, which behave like the methods
+/// of `FitsReader` with the same name.
+#[cfg(feature = "tokio")]
+pub struct FitsAsyncReader<R> {
+    inner: R,
+    position: u64,
+    done: bool,
+}
+
+#[cfg(feature = "tokio")]
+impl<R: AsyncRead + AsyncSeek + Unpin> FitsAsyncReader<R> {
+    /// Create a `FitsAsyncReader` that reads from the start of `inner`. All
+    /// offsets are relative to the start of the stream.
+    pub fn new(inner: R) -> FitsAsyncReader<R> {
+        FitsAsyncReader { inner, position: 0, done: false }
+    }
+
+    /// Read the header of the next HDU and skip its data unit. Resolves to
+    /// `None` when the stream is exhausted.
+    pub fn next_entry(&mut self) -> NextEntry<'_, R> {
+        let fill = Fill::new(self.position, vec!());
+        NextEntry { reader: self, fill }
+    }
+
+    /// Load the HDU of an entry, including its data array.
+    pub fn read_hdu(&mut self, entry: &HduEntry) -> ReadHdu<'_, R> {
+        let data = buffer_for(entry).map(|mut data| {
+            data.resize(entry.data_length as usize, 0u8);
+            data
+        });
+        let (fill, error) = match data {
+            Ok(data) => (Fill::new(entry.data_offset, data), Option::None),
+            Err(error) => (Fill::new(entry.data_offset, vec!()), Option::Some(error)),
+        };
+        ReadHdu { reader: self, header: entry.header.clone(), header_offset: entry.header_offset, fill, error }
+    }
+
+    /// Unwrap this `FitsAsyncReader`, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+/// The future of `FitsAsyncReader::next_entry`.
+#[cfg(feature = "tokio")]
+pub struct NextEntry<'r, R> {
+    reader: &'r mut FitsAsyncReader<R>,
+    fill: Fill,
+}
+
+#[cfg(feature = "tokio")]
+impl<'r, R: AsyncRead + AsyncSeek + Unpin> Future for NextEntry<'r, R> {
+    type Output = Option<Result<HduEntry, ReadError>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if this.reader.done {
+            return Poll::Ready(None)
+        }
+        let offset = this.fill.offset;
+        let complete = |data: &[u8]| !data.is_empty() && data.len().is_multiple_of(BLOCK_SIZE) && contains_end(&data[(data.len() - BLOCK_SIZE)..]);
+        let result = match this.fill.poll(&mut this.reader.inner, cx, complete) {
+            Poll::Pending => return Poll::Pending,
+            Poll::Ready(Err(error)) => Err(ReadError::Io(error)),
+            Poll::Ready(Ok(false)) if this.fill.filled == 0 => {
+                this.reader.done = true;
+                return Poll::Ready(None)
+            },
+            Poll::Ready(Ok(false)) => Err(ReadError::UnexpectedEof { offset }),
+            Poll::Ready(Ok(true)) => parse_entry(&this.fill.data[..this.fill.filled], offset),
+        };
+        match result {
+            Ok(ref entry) => this.reader.position = entry.data_offset + entry.data_length,
+            Err(_) => this.reader.done = true,
+        }
+        Poll::Ready(Some(result))
+    }
+}
+
+/// The future of `FitsAsyncReader::read_hdu`.
+#[cfg(feature = "tokio")]
+pub struct ReadHdu<'r, R> {
+    reader: &'r mut FitsAsyncReader<R>,
+    header: Header<'static>,
+    header_offset: u64,
+    fill: Fill,
+    error: Option<ReadError>,
+}
+
+#[cfg(feature = "tokio")]
+impl<'r, R: AsyncRead + AsyncSeek + Unpin> Future for ReadHdu<'r, R> {
+    type Output = Result<HDU<'static>, ReadError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if let Some(error) = this.error.take() {
+            return Poll::Ready(Err(error))
+        }
+        let length = this.fill.data.len();
+        match this.fill.poll(&mut this.reader.inner, cx, |data| data.len() == length) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Err(error)) => Poll::Ready(Err(ReadError::Io(error))),
+            Poll::Ready(Ok(false)) => Poll::Ready(Err(ReadError::UnexpectedEof { offset: this.header_offset })),
+            Poll::Ready(Ok(true)) => Poll::Ready(Ok(HDU::with_data(this.header.clone(), &this.fill.data).into_owned())),
+        }
+    }
+}
+
+// Seek to `offset` and read into `data`, until its first `filled` bytes are
+// complete. `data` grows by a block when it is full but not complete yet.
+#[cfg(feature = "tokio")]
+struct Fill {
+    offset: u64,
+    seeking: bool,
+    sought: bool,
+    data: Vec<u8>,
+    filled: usize,
+}
+
+#[cfg(feature = "tokio")]
+impl Fill {
+    fn new(offset: u64, data: Vec<u8>) -> Fill {
+        Fill { offset, seeking: false, sought: false, data, filled: 0 }
+    }
+
+    // Resolves to whether the data is complete, or `false` at the end of the stream.
+    fn poll<R, F>(&mut self, inner: &mut R, cx: &mut Context<'_>, complete: F) -> Poll<io::Result<bool>>
+        where R: AsyncRead + AsyncSeek + Unpin, F: Fn(&[u8]) -> bool {
+        if !self.sought {
+            if !self.seeking {
+                Pin::new(&mut *inner).start_seek(SeekFrom::Start(self.offset))?;
+                self.seeking = true;
+            }
+            match Pin::new(&mut *inner).poll_complete(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(result) => result?,
+            };
+            self.sought = true;
+        }
+        loop {
+            if complete(&self.data[..self.filled]) {
+                return Poll::Ready(Ok(true))
+            }
+            if self.filled == self.data.len() {
+                self.data.resize(self.filled + BLOCK_SIZE, 0u8);
+            }
+            let mut buffer = ReadBuf::new(&mut self.data[self.filled..]);
+            match Pin::new(&mut *inner).poll_read(cx, &mut buffer) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(result) => result?,
+            };
+            let size = buffer.filled().len();
+            if size == 0 {
+                return Poll::Ready(Ok(false))
+            }
+            self.filled += size;
+        }
+    }
+}
+
+// Read the HDU entry whose header starts at `header_offset`, where `inner` is positioned.
+fn read_entry<R: Read>(inner: &mut R, header_offset: u64) -> Result<Option<HduEntry>, ReadError> {
+    let mut buffer = vec!();
+    loop {
+        let mut block = [0u8; BLOCK_SIZE];
+        let size = read_block(inner, &mut block)?;
+        if size == 0 && buffer.is_empty() {
+            return Ok(None)
+        }
+        if size < BLOCK_SIZE {
+            return Err(ReadError::UnexpectedEof { offset: header_offset })
+        }
+        buffer.extend_from_slice(&block);
+        if contains_end(&block) {
+            break
+        }
+    }
+    parse_entry(&buffer, header_offset).map(Some)
+}
+
+// Parse the blocks of a header, the last of which contains `END`.
+fn parse_entry(buffer: &[u8], header_offset: u64) -> Result<HduEntry, ReadError> {
+    let header = match header(buffer) {
+        IResult::Done(_, h) => h.into_owned(),
+        _ => return Err(match invalid_card(buffer) {
+            Some(index) => ReadError::InvalidCard {
+                offset: header_offset + (index * CARD_SIZE) as u64,
+                card: String::from_utf8_lossy(&buffer[(index * CARD_SIZE)..((index + 1) * CARD_SIZE)]).into_owned(),
+            },
+            None => ReadError::InvalidHeader { offset: header_offset },
+        }),
+    };
+    if let Err(error) = header.bitpix() {
+        return Err(ReadError::InvalidBitpix { offset: header_offset, error })
+    }
+    if let Err(error) = header.axes() {
+        return Err(ReadError::InvalidAxes { offset: header_offset, error })
+    }
+    let data_offset = header_offset + buffer.len() as u64;
+    let data_length = header.data_unit_length().ok_or(ReadError::TooLarge { offset: header_offset })?;
+    data_offset.checked_add(data_length).ok_or(ReadError::TooLarge { offset: header_offset })?;
+    Ok(HduEntry { header, header_offset, data_offset, data_length })
+}
+
+// An empty buffer that can hold the data unit of `entry`, which could be larger
+// than the address space, e.g. on 32-bit targets.
+fn buffer_for(entry: &HduEntry) -> Result<Vec<u8>, ReadError> {
+    let mut data = vec!();
+    usize::try_from(entry.data_length).ok()
+        .and_then(|length| data.try_reserve_exact(length).ok())
+        .ok_or(ReadError::TooLarge { offset: entry.header_offset })?;
+    Ok(data)
+}
+
+fn read_block<R: Read>(reader: &mut R, block: &mut [u8]) -> io::Result<usize> {
+    let mut size = 0;
+    while size < block.len() {
+        match reader.read(&mut block[size..]) {
+            Ok(0) => break,
+            Ok(n) => size += n,
+            Err(ref error) if error.kind() == io::ErrorKind::Interrupted => (),
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(size)
+}
+
+fn contains_end(block: &[u8]) -> bool {
+    block.chunks(CARD_SIZE).any(|card| {
+        card.starts_with(b"END") && card[3..].iter().all(|b| *b == b' ')
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use nom::IResult;
+    use super::super::parser::{fits, parse};
+    use super::super::types::{AxisError, BitpixError, Header, KeywordRecord, Keyword, Value};
+    use super::super::testgen::target_pixel_file;
+    use super::super::writer::write_header;
+    use super::{FitsReader, FitsStreamReader, HduEntry, ReadError};
+
+    #[test]
+    fn reader_should_find_all_hdus() {
+        let data = target_pixel_file();
+        let reader = FitsReader::new(Cursor::new(&data[..]));
+
+        let entries: Vec<_> = reader.map(|entry| entry.unwrap()).collect();
+
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].header_offset, 0u64);
+        assert_eq!(entries[0].data_length, 0u64);
+        assert_eq!(entries[1].header_offset, 2880u64);
+        assert_eq!(entries[1].data_offset, 5760u64);
+    }
+
+    #[test]
+    fn reader_should_read_the_same_hdus_as_the_parser() {
+        let data = target_pixel_file();
+        let parsed = match fits(&data) {
+            IResult::Done(_, f) => f,
+            _ => panic!("Did not expect the test file to fail"),
+        };
+        let mut reader = FitsReader::new(Cursor::new(&data[..]));
+
+        let entries: Vec<_> = reader.by_ref().map(|entry| entry.unwrap()).collect();
+        let primary = reader.read_hdu(&entries[0]).unwrap();
+        let aperture = reader.read_hdu(&entries[2]).unwrap();
+
+        assert_eq!(primary, parsed.primary_hdu);
+        assert_eq!(aperture, parsed.extensions[1]);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn async_reader_should_read_the_same_hdus_as_the_reader() {
+        let data = target_pixel_file();
+        let expected: Vec<_> = FitsReader::new(Cursor::new(&data[..])).map(|entry| entry.unwrap()).collect();
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+
+        let mut reader = super::FitsAsyncReader::new(Cursor::new(&data[..]));
+        let mut entries = vec!();
+        while let Some(entry) = runtime.block_on(reader.next_entry()) {
+            entries.push(entry.unwrap());
+        }
+        let aperture = runtime.block_on(reader.read_hdu(&entries[2])).unwrap();
+
+        assert_eq!(entries, expected);
+        assert_eq!(aperture, FitsReader::new(Cursor::new(&data[..])).read_hdu(&expected[2]).unwrap());
+        assert!(runtime.block_on(reader.next_entry()).is_none());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn async_reader_should_report_a_truncated_data_unit() {
+        let data = target_pixel_file();
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let mut reader = super::FitsAsyncReader::new(Cursor::new(&data[0..(4 * 2880)]));
+
+        let primary = runtime.block_on(reader.next_entry()).unwrap().unwrap();
+        let target = runtime.block_on(reader.next_entry()).unwrap().unwrap();
+
+        assert_eq!(primary.data_offset, 2880u64);
+        assert!(matches!(runtime.block_on(reader.read_hdu(&target)), Err(ReadError::UnexpectedEof { offset: 2880 })));
+        assert!(runtime.block_on(reader.next_entry()).is_none());
+    }
+
+    #[test]
+    fn reader_should_read_a_decimated_image() {
+        let data = target_pixel_file();
+        let parsed = parse(&data).unwrap();
+        let mut reader = FitsReader::new(Cursor::new(&data[..]));
+        let entries: Vec<_> = reader.by_ref().map(|entry| entry.unwrap()).collect();
+        let image = parsed.extensions[1].data_array().unwrap();
+        let width = image.axes()[0];
+        let values = image.values::<i32>().unwrap();
+
+        let decimated = reader.read_image_decimated(&entries[2], 3).unwrap();
+
+        assert_eq!(decimated.axes(), &[width.div_ceil(3), image.axes()[1].div_ceil(3)]);
+        assert_eq!(decimated.scaling(), image.scaling());
+        let decimated_values = decimated.values::<i32>().unwrap();
+        assert_eq!(decimated_values[0], values[0]);
+        assert_eq!(decimated_values[1], values[3]);
+        assert_eq!(decimated_values[decimated.axes()[0]], values[3 * width]);
+        assert_eq!(reader.read_image_decimated(&entries[2], 1).unwrap(), image.view().into_owned());
+        assert!(matches!(reader.read_image_decimated(&entries[1], 2), Err(ReadError::NotAnImage { offset: 2880 })));
+        assert!(matches!(reader.read_image_decimated(&entries[2], 0), Err(ReadError::Io(_))));
+    }
+
+    #[test]
+    fn decimated_images_with_hostile_axes_should_be_too_large() {
+        let header = Header::new(vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(-64i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(2i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(1i64 << 62), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(2u16), Value::Integer(1i64 << 62), Option::None),
+        ));
+        let entry = HduEntry { header, header_offset: 0u64, data_offset: 2880u64, data_length: 0u64 };
+        let mut reader = FitsReader::new(Cursor::new(vec![0u8; 2880]));
+
+        assert!(matches!(reader.read_image_decimated(&entry, 1usize << 62), Err(ReadError::TooLarge { offset: 0 })));
+    }
+
+    #[test]
+    fn reader_should_report_a_truncated_header() {
+        let data = target_pixel_file();
+        let mut reader = FitsReader::new(Cursor::new(&data[0..1000]));
+
+        match reader.next() {
+            Some(Err(ReadError::UnexpectedEof { offset })) => assert_eq!(offset, 0u64),
+            _ => panic!("Expected an unexpected end of file"),
+        }
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn reader_should_report_the_offset_and_text_of_an_invalid_card() {
+        let data = target_pixel_file();
+        let mut corrupted = data.to_vec();
+        corrupted[(3*80)..(4*80)].copy_from_slice(format!("{:<80}", "HIERARCH ESO DET CHIP").as_bytes());
+        let mut reader = FitsReader::new(Cursor::new(corrupted));
+
+        match reader.next() {
+            Some(Err(ReadError::InvalidCard { offset, card })) => {
+                assert_eq!(offset, 3u64*80);
+                assert_eq!(card.trim_end(), "HIERARCH ESO DET CHIP");
+            },
+            _ => panic!("Expected an invalid card"),
+        }
+    }
+
+    #[test]
+    fn reader_should_report_an_unknown_bitpix() {
+        let data = target_pixel_file();
+        let mut corrupted = data.to_vec();
+        corrupted[80..110].copy_from_slice(b"BITPIX  =                   12");
+        let mut reader = FitsReader::new(Cursor::new(corrupted));
+
+        match reader.next() {
+            Some(Err(ReadError::InvalidBitpix { offset, error })) => {
+                assert_eq!(offset, 0u64);
+                assert_eq!(error, BitpixError::UnknownBitpix(12i64));
+            },
+            _ => panic!("Expected an invalid BITPIX"),
+        }
+    }
+
+    #[test]
+    fn reader_should_report_inconsistent_axes() {
+        let data = target_pixel_file();
+        let mut corrupted = data.to_vec();
+        corrupted[(2880 + 4*80)..(2880 + 4*80 + 8)].copy_from_slice(b"NAXIS3  ");
+        let mut reader = FitsReader::new(Cursor::new(corrupted));
+
+        assert!(reader.next().unwrap().is_ok());
+        match reader.next() {
+            Some(Err(ReadError::InvalidAxes { offset, error })) => {
+                assert_eq!(offset, 2880u64);
+                assert_eq!(error, AxisError::UnexpectedAxis(3u16));
+            },
+            _ => panic!("Expected inconsistent axes"),
+        }
+    }
+
+    #[test]
+    fn stream_reader_should_report_the_same_offsets_as_the_reader() {
+        let data = target_pixel_file();
+        let expected: Vec<_> = FitsReader::new(Cursor::new(&data[..])).map(|entry| entry.unwrap()).collect();
+        let mut reader = FitsStreamReader::new(&data[..]);
+
+        let entries: Vec<_> = reader.by_ref().map(|entry| entry.unwrap()).collect();
+
+        assert_eq!(entries, expected);
+        assert_eq!(reader.position(), data.len() as u64);
+    }
+
+    #[test]
+    fn stream_reader_should_read_only_the_data_unit_that_follows() {
+        let data = target_pixel_file();
+        let parsed = match fits(&data) {
+            IResult::Done(_, f) => f,
+            _ => panic!("Did not expect the test file to fail"),
+        };
+        let mut reader = FitsStreamReader::new(&data[..]);
+
+        let primary = reader.next().unwrap().unwrap();
+        let table = reader.next().unwrap().unwrap();
+        let aperture = reader.next().unwrap().unwrap();
+
+        assert!(reader.read_hdu(&primary).is_err());
+        assert!(reader.read_hdu(&table).is_err());
+        assert_eq!(reader.read_hdu(&aperture).unwrap(), parsed.extensions[1]);
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn stream_reader_should_report_a_truncated_data_unit_at_its_header() {
+        let data = target_pixel_file();
+        let mut reader = FitsStreamReader::new(&data[..(4*2880)]);
+
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().unwrap().is_ok());
+        match reader.next() {
+            Some(Err(ReadError::UnexpectedEof { offset })) => assert_eq!(offset, 2880u64),
+            _ => panic!("Expected an unexpected end of file"),
+        }
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn reader_should_report_a_truncated_data_unit() {
+        let data = target_pixel_file();
+        let mut reader = FitsReader::new(Cursor::new(&data[..(4*2880)]));
+
+        let primary = reader.next().unwrap().unwrap();
+        let target = reader.next().unwrap().unwrap();
+
+        assert!(reader.read_hdu(&primary).is_ok());
+        match reader.read_hdu(&target) {
+            Err(ReadError::UnexpectedEof { offset }) => assert_eq!(offset, 2880u64),
+            _ => panic!("Expected an unexpected end of file"),
+        }
+        match reader.next() {
+            Some(Err(ReadError::UnexpectedEof { offset })) => assert_eq!(offset, 2880u64),
+            _ => panic!("Expected an unexpected end of file"),
+        }
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn reader_should_report_a_data_unit_that_is_too_large() {
+        let header = Header::new(vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(-64i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(2i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(i64::MAX), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(2u16), Value::Integer(i64::MAX), Option::None),
+        ));
+        let mut data = vec!();
+        write_header(&mut data, &header).unwrap();
+        let mut reader = FitsReader::new(Cursor::new(data));
+
+        match reader.next() {
+            Some(Err(ReadError::TooLarge { offset })) => assert_eq!(offset, 0u64),
+            _ => panic!("Expected a data unit that is too large"),
+        }
+    }
+}
@@ -0,0 +1,259 @@
+//! The schema module describes the keywords that the headers of an archive
+//! are expected to hold.
+//!
+//! `HeaderSchema::infer` drafts a schema from the headers of many files: the
+//! keywords that occur in at least a given fraction of them, with the type and
+//! range of their values. The draft is a starting point for the validation
+//! rules of archives that never formalized their headers, e.g. by adding
+//! `HeaderSchema::check` to a `Validator` with `header_rule`.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use super::types::{Header, Keyword, Value};
+use super::validator::{Finding, Severity};
+
+/// The type of the values of a keyword.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ValueType {
+    /// Character strings.
+    CharacterString,
+    /// Logical constants.
+    Logical,
+    /// Integers.
+    Integer,
+    /// Real numbers, or a mix of integers and real numbers.
+    Real,
+    /// Complex numbers.
+    Complex,
+    /// No value.
+    Undefined,
+}
+
+impl ValueType {
+    /// The type of `value`, or `None` for commentary and unparsed values.
+    pub fn of(value: &Value) -> Option<ValueType> {
+        match *value {
+            Value::CharacterString(_) => Some(ValueType::CharacterString),
+            Value::Logical(_) => Some(ValueType::Logical),
+            Value::Integer(_) => Some(ValueType::Integer),
+            Value::Real(_) => Some(ValueType::Real),
+            Value::Complex(_) => Some(ValueType::Complex),
+            Value::Undefined => Some(ValueType::Undefined),
+            Value::CommentText(_) | Value::Unparsed(_) => None,
+        }
+    }
+
+    // Whether a value of type `actual` is acceptable, integers being acceptable as reals.
+    fn accepts(self, actual: ValueType) -> bool {
+        self == actual || (self == ValueType::Real && actual == ValueType::Integer)
+    }
+
+    // The type that covers both types, if there is one.
+    fn join(self, other: ValueType) -> Option<ValueType> {
+        if self.accepts(other) {
+            Some(self)
+        } else if other.accepts(self) {
+            Some(other)
+        } else {
+            None
+        }
+    }
+}
+
+impl Display for ValueType {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            ValueType::CharacterString => f.pad("string"),
+            ValueType::Logical => f.pad("logical"),
+            ValueType::Integer => f.pad("integer"),
+            ValueType::Real => f.pad("real"),
+            ValueType::Complex => f.pad("complex"),
+            ValueType::Undefined => f.pad("undefined"),
+        }
+    }
+}
+
+/// What is expected of a single keyword.
+#[derive(Debug, PartialEq, Clone)]
+pub struct KeywordSchema {
+    /// The keyword.
+    pub keyword: Keyword,
+    /// The type of its values, `None` when they differ in type.
+    pub value_type: Option<ValueType>,
+    /// The smallest and largest value, for numbers.
+    pub range: Option<(f64, f64)>,
+    /// The fraction of the headers that hold the keyword.
+    pub frequency: f64,
+}
+
+/// The keywords that headers are expected to hold, in the order they were
+/// first seen.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct HeaderSchema {
+    /// The expected keywords.
+    pub keywords: Vec<KeywordSchema>,
+}
+
+impl HeaderSchema {
+    /// Draft a schema of the keywords that occur in at least a fraction
+    /// `threshold` of `headers`, e.g. 0.9 for 90%. Commentary keywords are
+    /// left out.
+    pub fn infer<'h, 'a: 'h, I>(headers: I, threshold: f64) -> HeaderSchema where I: IntoIterator<Item = &'h Header<'a>> {
+        let mut keywords: Vec<(KeywordSchema, usize, bool)> = vec!();
+        // The position of every keyword in `keywords`.
+        let mut positions: HashMap<Keyword, usize> = HashMap::new();
+        let mut count = 0;
+        for header in headers {
+            count += 1;
+            for record in &header.keyword_records {
+                let value_type = match ValueType::of(record.value()) {
+                    Some(value_type) if *record.keyword() != Keyword::END => value_type,
+                    _ => continue,
+                };
+                let number = match *record.value() {
+                    Value::Integer(n) => Some(n as f64),
+                    Value::Real(r) => Some(r),
+                    _ => None,
+                };
+                let position = *positions.entry(record.keyword().clone()).or_insert_with(|| {
+                    let schema = KeywordSchema { keyword: record.keyword().clone(), value_type: Some(value_type), range: None, frequency: 0f64 };
+                    keywords.push((schema, 0, false));
+                    keywords.len() - 1
+                });
+                let (schema, occurrences, seen) = &mut keywords[position];
+                schema.value_type = schema.value_type.and_then(|known| known.join(value_type));
+                schema.range = match (schema.range, number) {
+                    (Some((min, max)), Some(n)) => Some((min.min(n), max.max(n))),
+                    (None, Some(n)) => Some((n, n)),
+                    (range, None) => range,
+                };
+                // A keyword that occurs twice in a header is counted once.
+                if !*seen {
+                    *occurrences += 1;
+                    *seen = true;
+                }
+            }
+            for (_, _, seen) in keywords.iter_mut() {
+                *seen = false;
+            }
+        }
+        HeaderSchema {
+            keywords: keywords.into_iter()
+                .map(|(schema, occurrences, _)| KeywordSchema { frequency: occurrences as f64 / count as f64, ..schema })
+                .filter(|schema| count > 0 && schema.frequency >= threshold)
+                .map(|schema| match schema.value_type {
+                    Some(ValueType::Integer) | Some(ValueType::Real) => schema,
+                    _ => KeywordSchema { range: None, ..schema },
+                })
+                .collect(),
+        }
+    }
+
+    /// Check a header against this schema. A missing keyword is a warning, a
+    /// value of another type is an error and a number outside the range is a
+    /// warning.
+    pub fn check(&self, header: &Header) -> Vec<Finding> {
+        let mut findings = vec!();
+        for schema in &self.keywords {
+            let index = match header.position_of(&schema.keyword) {
+                Some(index) => index,
+                None => {
+                    findings.push(Finding::new(Severity::Warning, "schema", format!("{} is missing", schema.keyword)));
+                    continue
+                },
+            };
+            let record = &header.keyword_records[index];
+            let actual = ValueType::of(record.value());
+            match (schema.value_type, actual) {
+                (Some(expected), Some(actual)) if !expected.accepts(actual) => {
+                    findings.push(Finding::new(Severity::Error, "schema", format!("{} should be {}, not {}", schema.keyword, expected, actual)).at(index));
+                    continue
+                },
+                _ => (),
+            }
+            let number = match *record.value() {
+                Value::Integer(n) => Some(n as f64),
+                Value::Real(r) => Some(r),
+                _ => None,
+            };
+            if let (Some((min, max)), Some(n)) = (schema.range, number) {
+                if n < min || n > max {
+                    findings.push(Finding::new(Severity::Warning, "schema", format!("{} = {} lies outside {}..{}", schema.keyword, n, min, max)).at(index));
+                }
+            }
+        }
+        findings
+    }
+}
+
+impl Display for HeaderSchema {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for schema in &self.keywords {
+            let value_type = schema.value_type.map(|value_type| value_type.to_string()).unwrap_or_else(|| "mixed".to_string());
+            write!(f, "{:<8} {:<9} {:>3.0}%", schema.keyword, value_type, schema.frequency * 100f64)?;
+            if let Some((min, max)) = schema.range {
+                write!(f, " {}..{}", min, max)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use super::super::types::{Header, KeywordRecord, Keyword, Value};
+    use super::super::validator::{Severity, Validator};
+    use super::super::parser::parse;
+    use super::{HeaderSchema, ValueType};
+
+    fn header(exposure: Value<'static>, object: Option<&'static str>) -> Header<'static> {
+        let mut records = vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::EXPOSURE, exposure, Option::None),
+            KeywordRecord::new(Keyword::COMMENT, Value::CommentText(Cow::Borrowed("a comment")), Option::None),
+        );
+        if let Some(object) = object {
+            records.push(KeywordRecord::new(Keyword::OBJECT, Value::CharacterString(Cow::Borrowed(object)), Option::None));
+        }
+        Header::new(records)
+    }
+
+    #[test]
+    fn schemas_should_be_inferred_from_frequent_keywords() {
+        let headers = vec!(
+            header(Value::Integer(30i64), Option::Some("TRAPPIST-1")),
+            header(Value::Real(12.5f64), Option::None),
+            header(Value::Integer(60i64), Option::None),
+        );
+
+        let schema = HeaderSchema::infer(&headers, 0.5);
+
+        assert_eq!(schema.keywords.len(), 2);
+        assert_eq!(schema.keywords[1].keyword, Keyword::EXPOSURE);
+        assert_eq!(schema.keywords[1].value_type, Option::Some(ValueType::Real));
+        assert_eq!(schema.keywords[1].range, Option::Some((12.5f64, 60f64)));
+        assert_eq!(HeaderSchema::infer(&headers, 0.3).keywords[2].frequency, 1f64 / 3f64);
+        assert_eq!(schema.to_string(), "SIMPLE   logical   100%\nEXPOSURE real      100% 12.5..60\n");
+    }
+
+    #[test]
+    fn headers_should_be_checked_against_a_schema() {
+        let headers = vec!(header(Value::Integer(30i64), Option::Some("TRAPPIST-1")), header(Value::Integer(60i64), Option::Some("K2-18")));
+        let schema = HeaderSchema::infer(&headers, 1.0);
+
+        let findings = schema.check(&header(Value::CharacterString(Cow::Borrowed("long")), Option::None));
+
+        assert_eq!(findings.len(), 2);
+        assert_eq!((findings[0].severity, findings[0].card), (Severity::Error, Option::Some(1)));
+        assert_eq!(findings[1].message, "OBJECT is missing");
+        assert_eq!(schema.check(&header(Value::Integer(90i64), Option::Some("WASP-12")))[0].message, "EXPOSURE = 90 lies outside 30..60");
+
+        let data = include_bytes!("../../assets/images/k2-trappist1-unofficial-tpf-long-cadence.fits");
+        let fits = parse(data).unwrap();
+        let validator = Validator::empty().header_rule("schema", move |header| schema.check(header));
+        assert_eq!(validator.verify(&fits).count(Severity::Warning), 4);
+    }
+}
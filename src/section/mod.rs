@@ -0,0 +1,189 @@
+//! The section module parses image sections in IRAF notation, e.g. `[1:1024,1:512]`.
+//!
+//! Keywords like `DATASEC`, `DETSEC` and `CCDSEC` describe a rectangular part of
+//! an image with a section. Pixel coordinates are 1-based and inclusive. An
+//! interval that runs backwards, e.g. `[1024:1,1:512]`, signals a flipped axis.
+
+use std::fmt::{Display, Formatter, Error};
+use std::str::FromStr;
+use super::types::{Header, Keyword, ValueRetrievalError};
+
+/// The pixels of a single axis that are part of a section.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Interval {
+    /// The first pixel, starting at 1.
+    pub first: usize,
+    /// The last pixel, inclusive. Smaller than `first` for a flipped axis.
+    pub last: usize,
+}
+
+impl Interval {
+    /// Create an `Interval` from its first to its last pixel.
+    pub fn new(first: usize, last: usize) -> Interval {
+        Interval { first, last }
+    }
+
+    /// The number of pixels in this interval.
+    pub fn size(&self) -> usize {
+        self.upper() - self.lower() + 1
+    }
+
+    /// Whether this interval runs backwards.
+    pub fn is_reversed(&self) -> bool {
+        self.last < self.first
+    }
+
+    /// The smallest pixel in this interval.
+    pub fn lower(&self) -> usize {
+        self.first.min(self.last)
+    }
+
+    /// The largest pixel in this interval.
+    pub fn upper(&self) -> usize {
+        self.first.max(self.last)
+    }
+
+    /// The pixel at `step` steps from `first`, following the direction of the interval.
+    pub fn at(&self, step: usize) -> usize {
+        if self.is_reversed() { self.first - step } else { self.first + step }
+    }
+}
+
+/// A rectangular part of a two dimensional image.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Section {
+    /// The interval along the first axis, i.e. `NAXIS1`.
+    pub x: Interval,
+    /// The interval along the second axis, i.e. `NAXIS2`.
+    pub y: Interval,
+}
+
+impl Section {
+    /// Create a `Section` from its intervals.
+    pub fn new(x: Interval, y: Interval) -> Section {
+        Section { x, y }
+    }
+
+    /// The number of pixels along the first axis.
+    pub fn width(&self) -> usize {
+        self.x.size()
+    }
+
+    /// The number of pixels along the second axis.
+    pub fn height(&self) -> usize {
+        self.y.size()
+    }
+}
+
+impl Display for Section {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        f.pad(&format!("[{}:{},{}:{}]", self.x.first, self.x.last, self.y.first, self.y.last))
+    }
+}
+
+/// Problems that could occur when parsing a section.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ParseSectionError {
+    /// The section is not enclosed in square brackets.
+    MissingBrackets,
+    /// The section does not have exactly two intervals.
+    NotTwoDimensional,
+    /// An interval is not of the form `first:last` with positive pixels.
+    InvalidInterval(String),
+    /// The value of the keyword is not a character string.
+    NotACharacterString,
+}
+
+impl FromStr for Section {
+    type Err = ParseSectionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let trimmed = s.trim();
+        if !trimmed.starts_with('[') || !trimmed.ends_with(']') {
+            return Err(ParseSectionError::MissingBrackets)
+        }
+        let intervals = trimmed[1..trimmed.len() - 1]
+            .split(',')
+            .map(interval)
+            .collect::<Result<Vec<Interval>, ParseSectionError>>()?;
+        if intervals.len() != 2 {
+            return Err(ParseSectionError::NotTwoDimensional)
+        }
+        Ok(Section::new(intervals[0], intervals[1]))
+    }
+}
+
+fn interval(input: &str) -> Result<Interval, ParseSectionError> {
+    let pixels = input.split(':')
+        .map(|pixel| pixel.trim().parse::<usize>().ok().filter(|pixel| *pixel > 0))
+        .collect::<Option<Vec<usize>>>();
+    match pixels {
+        Some(ref pixels) if pixels.len() == 2 => Ok(Interval::new(pixels[0], pixels[1])),
+        _ => Err(ParseSectionError::InvalidInterval(input.trim().to_string())),
+    }
+}
+
+/// The section stored in a header under `keyword`, or `None` when the keyword
+/// is not present.
+pub fn header_section(header: &Header, keyword: &Keyword) -> Result<Option<Section>, ParseSectionError> {
+    match header.get_str(keyword) {
+        Ok(value) => value.parse().map(Some),
+        Err(ValueRetrievalError::KeywordNotPresent) => Ok(None),
+        Err(_) => Err(ParseSectionError::NotACharacterString),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use super::super::types::{Header, KeywordRecord, Keyword, Value};
+    use super::{header_section, Interval, Section, ParseSectionError};
+
+    #[test]
+    fn section_should_be_parsed_from_iraf_notation() {
+        let section: Section = "[1:1024, 1:512]".parse().unwrap();
+
+        assert_eq!(section, Section::new(Interval::new(1, 1024), Interval::new(1, 512)));
+        assert_eq!(section.width(), 1024);
+        assert_eq!(section.height(), 512);
+        assert_eq!(section.to_string(), "[1:1024,1:512]");
+    }
+
+    #[test]
+    fn reversed_intervals_should_step_backwards() {
+        let section: Section = "[1024:1,1:512]".parse().unwrap();
+
+        assert!(section.x.is_reversed());
+        assert_eq!(section.width(), 1024);
+        assert_eq!(section.x.at(1), 1023);
+        assert_eq!(section.y.at(1), 2);
+    }
+
+    #[test]
+    fn malformed_sections_should_be_refused() {
+        let data = [
+            ("1:1024,1:512", ParseSectionError::MissingBrackets),
+            ("[1:1024]", ParseSectionError::NotTwoDimensional),
+            ("[1:1024,1:512,1:3]", ParseSectionError::NotTwoDimensional),
+            ("[0:1024,1:512]", ParseSectionError::InvalidInterval("0:1024".to_string())),
+            ("[1:1024,*]", ParseSectionError::InvalidInterval("*".to_string())),
+        ];
+
+        for (input, expected) in data.iter() {
+            assert_eq!(input.parse::<Section>(), Err(expected.clone()));
+        }
+    }
+
+    #[test]
+    fn header_section_should_distinguish_absent_keywords() {
+        let header = Header::new(vec!(
+            KeywordRecord::new(Keyword::DATASEC, Value::CharacterString(Cow::Borrowed("[33:2080,1:4096]")), Option::None),
+            KeywordRecord::new(Keyword::DETSEC, Value::Integer(1i64), Option::None),
+        ));
+
+        assert_eq!(header_section(&header, &Keyword::DATASEC),
+                   Ok(Some(Section::new(Interval::new(33, 2080), Interval::new(1, 4096)))));
+        assert_eq!(header_section(&header, &Keyword::CCDSEC), Ok(None));
+        assert_eq!(header_section(&header, &Keyword::DETSEC), Err(ParseSectionError::NotACharacterString));
+    }
+}
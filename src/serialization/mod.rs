@@ -0,0 +1,358 @@
+//! The serialization module connects headers and tables with `serde`. It is
+//! only available with the `serde` feature.
+//!
+//! Headers, keyword records and values implement `Serialize`, so that a header
+//! can be written as JSON or YAML by any `serde` format. A header becomes a
+//! sequence of records, every record a map with `keyword`, `value` and
+//! `comment`.
+//!
+//! The rows of a binary table deserialize into user structs, matching the
+//! fields of the struct with the names of the columns, i.e. `TTYPEn`, case
+//! insensitively. Columns without a matching field are skipped. Character
+//! columns become strings, logical columns booleans and numeric columns
+//! numbers, or sequences of numbers when a field holds more than one element.
+
+use std::error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use serde::de;
+use serde::de::{DeserializeOwned, DeserializeSeed, IntoDeserializer, MapAccess, Visitor};
+use serde::ser::{Serialize, Serializer, SerializeSeq, SerializeStruct};
+use super::table::{BinaryTable, DataType, TableError};
+use super::types::{Header, Keyword, KeywordRecord, Value};
+
+impl<'a> Serialize for Header<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.keyword_records.len()))?;
+        for record in &self.keyword_records {
+            seq.serialize_element(record)?;
+        }
+        seq.end()
+    }
+}
+
+impl<'a> Serialize for KeywordRecord<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut record = serializer.serialize_struct("KeywordRecord", 3)?;
+        record.serialize_field("keyword", self.keyword())?;
+        record.serialize_field("value", self.value())?;
+        record.serialize_field("comment", &self.comment())?;
+        record.end()
+    }
+}
+
+impl Serialize for Keyword {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Character strings lose their insignificant trailing spaces, complex
+/// numbers become a pair and an undefined value becomes nothing, e.g. `null`.
+impl<'a> Serialize for Value<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            Value::CharacterString(ref s) => serializer.serialize_str(s.trim_end()),
+            Value::Logical(b) => serializer.serialize_bool(b),
+            Value::Integer(n) => serializer.serialize_i64(n),
+            Value::Real(r) => serializer.serialize_f64(r),
+            Value::Complex(pair) => pair.serialize(serializer),
+            Value::Undefined => serializer.serialize_none(),
+            Value::CommentText(ref text) | Value::Unparsed(ref text) => serializer.serialize_str(text),
+        }
+    }
+}
+
+/// Problems that occur when deserializing a row of a table.
+#[derive(Debug, PartialEq)]
+pub enum RowError {
+    /// The table could not be read.
+    Table(TableError),
+    /// The column with this index has a type that can not be deserialized,
+    /// e.g. bits or complex numbers.
+    Unsupported(usize),
+    /// The row does not fit the type it is deserialized into.
+    Message(String),
+}
+
+impl Display for RowError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            RowError::Table(ref error) => write!(f, "{}", error),
+            RowError::Unsupported(column) => write!(f, "column {} can not be deserialized", column),
+            RowError::Message(ref message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl error::Error for RowError {}
+
+impl de::Error for RowError {
+    fn custom<T: Display>(message: T) -> Self {
+        RowError::Message(message.to_string())
+    }
+}
+
+impl From<TableError> for RowError {
+    fn from(error: TableError) -> Self {
+        RowError::Table(error)
+    }
+}
+
+impl<'t> BinaryTable<'t> {
+    /// Deserialize the row with index `row` into a `T`, whose fields are
+    /// named after the columns.
+    pub fn row_as<T: DeserializeOwned>(&self, row: usize) -> Result<T, RowError> {
+        if row >= self.rows() {
+            return Err(RowError::Table(TableError::OutOfRange { row, column: 0 }))
+        }
+        T::deserialize(RowDeserializer { table: self, row })
+    }
+
+    /// Deserialize every row into a `T`.
+    pub fn rows_as<T: DeserializeOwned>(&self) -> Result<Vec<T>, RowError> {
+        (0..self.rows()).map(|row| self.row_as(row)).collect()
+    }
+}
+
+struct RowDeserializer<'r, 't: 'r> {
+    table: &'r BinaryTable<'t>,
+    row: usize,
+}
+
+impl<'de, 'r, 't> de::Deserializer<'de> for RowDeserializer<'r, 't> {
+    type Error = RowError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RowError> {
+        visitor.visit_map(RowAccess { table: self.table, row: self.row, column: 0, fields: &[], skip: false })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(self, _: &'static str, fields: &'static [&'static str], visitor: V) -> Result<V::Value, RowError> {
+        visitor.visit_map(RowAccess { table: self.table, row: self.row, column: 0, fields, skip: false })
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+// Visits the named columns of a row. Column names that match a field of the
+// struct case insensitively are replaced by the name of that field.
+struct RowAccess<'r, 't: 'r> {
+    table: &'r BinaryTable<'t>,
+    row: usize,
+    column: usize,
+    fields: &'static [&'static str],
+    skip: bool,
+}
+
+impl<'de, 'r, 't> MapAccess<'de> for RowAccess<'r, 't> {
+    type Error = RowError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, RowError> {
+        let columns = self.table.columns();
+        while self.column < columns.len() && columns[self.column].name.is_none() {
+            self.column += 1;
+        }
+        match columns.get(self.column).and_then(|column| column.name.as_ref()) {
+            Some(name) => {
+                let field = self.fields.iter().find(|field| field.eq_ignore_ascii_case(name)).cloned();
+                self.skip = !self.fields.is_empty() && field.is_none();
+                seed.deserialize(field.unwrap_or(name.as_str()).into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, RowError> {
+        // A column without a field is ignored, there is no need to decode it.
+        let field = if self.skip { Field::Unsupported(self.column) } else { field(self.table, self.row, self.column)? };
+        self.column += 1;
+        seed.deserialize(field)
+    }
+}
+
+// The value of a field, decoded according to the type of its column.
+enum Field {
+    Text(String),
+    Bool(bool),
+    U8(u8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+    Array(Vec<Field>),
+    Unsupported(usize),
+}
+
+fn field(table: &BinaryTable, row: usize, column: usize) -> Result<Field, RowError> {
+    let format = table.columns()[column].format;
+    if format.data_type == DataType::Character {
+        let bytes = table.field(row, column)?;
+        let text = String::from_utf8_lossy(bytes);
+        return Ok(Field::Text(text.trim_end_matches([' ', '\0']).to_string()))
+    }
+    let bytes = match format.descriptor {
+        Some(_) => table.heap_field(row, column)?,
+        None => table.field(row, column)?,
+    };
+    let size = format.data_type.bytes(1).expect("a single element fits");
+    let mut elements = match bytes.chunks(size).map(|element| decode(format.data_type, element)).collect::<Option<Vec<Field>>>() {
+        Some(elements) => elements,
+        None => return Ok(Field::Unsupported(column)),
+    };
+    if format.descriptor.is_none() && format.repeat == 1 && !elements.is_empty() {
+        Ok(elements.remove(0))
+    } else {
+        Ok(Field::Array(elements))
+    }
+}
+
+fn decode(data_type: DataType, bytes: &[u8]) -> Option<Field> {
+    let mut buffer = [0u8; 8];
+    buffer[..bytes.len()].copy_from_slice(bytes);
+    match data_type {
+        DataType::Logical => Some(Field::Bool(bytes[0] == b'T')),
+        DataType::Byte => Some(Field::U8(bytes[0])),
+        DataType::Short => Some(Field::I16(i16::from_be_bytes([buffer[0], buffer[1]]))),
+        DataType::Int => Some(Field::I32(i32::from_be_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]))),
+        DataType::Long => Some(Field::I64(i64::from_be_bytes(buffer))),
+        DataType::Float => Some(Field::F32(f32::from_be_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]))),
+        DataType::Double => Some(Field::F64(f64::from_be_bytes(buffer))),
+        _ => None,
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Field {
+    type Error = RowError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RowError> {
+        match self {
+            Field::Text(text) => visitor.visit_string(text),
+            Field::Bool(b) => visitor.visit_bool(b),
+            Field::U8(n) => visitor.visit_u8(n),
+            Field::I16(n) => visitor.visit_i16(n),
+            Field::I32(n) => visitor.visit_i32(n),
+            Field::I64(n) => visitor.visit_i64(n),
+            Field::F32(x) => visitor.visit_f32(x),
+            Field::F64(x) => visitor.visit_f64(x),
+            Field::Array(elements) => visitor.visit_seq(de::value::SeqDeserializer::new(elements.into_iter())),
+            Field::Unsupported(column) => Err(RowError::Unsupported(column)),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RowError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RowError> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier
+    }
+}
+
+impl<'de> IntoDeserializer<'de, RowError> for Field {
+    type Deserializer = Field;
+
+    fn into_deserializer(self) -> Field {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate serde_json;
+
+    use std::borrow::Cow;
+    use serde::Deserialize;
+    use super::super::parser::parse;
+    use super::super::table::{BinaryTable, TableError};
+    use super::super::types::{Header, HDU, KeywordRecord, Keyword, Value};
+    use super::RowError;
+
+    #[test]
+    fn headers_should_serialize_as_a_sequence_of_records() {
+        let header = Header::new(vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Some("conforms")),
+            KeywordRecord::new(Keyword::OBJECT, Value::CharacterString(Cow::Borrowed("TRAPPIST-1  ")), None),
+            KeywordRecord::new(Keyword::Custom("PHASE".to_string()), Value::Complex((1f64, -1f64)), None),
+            KeywordRecord::new(Keyword::END, Value::Undefined, None),
+        ));
+
+        assert_eq!(serde_json::to_string(&header).unwrap(), concat!(
+            "[{\"keyword\":\"SIMPLE\",\"value\":true,\"comment\":\"conforms\"},",
+            "{\"keyword\":\"OBJECT\",\"value\":\"TRAPPIST-1\",\"comment\":null},",
+            "{\"keyword\":\"PHASE\",\"value\":[1.0,-1.0],\"comment\":null},",
+            "{\"keyword\":\"END\",\"value\":null,\"comment\":null}]"));
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Star {
+        name: String,
+        magnitude: f64,
+        flags: Vec<bool>,
+        neighbours: Option<Vec<i16>>,
+    }
+
+    #[test]
+    fn rows_should_deserialize_into_structs_by_column_name() {
+        let mut data = vec!();
+        data.extend_from_slice(b"ABC ");
+        data.extend_from_slice(&12.5f32.to_be_bytes());
+        data.extend_from_slice(b"TF");
+        data.extend_from_slice(&[0, 0, 0, 2, 0, 0, 0, 0]);
+        data.extend_from_slice(&[0, 7, 0, 9]);
+        let header = Header::new(vec!(
+            KeywordRecord::new(Keyword::XTENSION, Value::CharacterString(Cow::Borrowed("BINTABLE")), None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(2i64), None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(18i64), None),
+            KeywordRecord::new(Keyword::NAXISn(2u16), Value::Integer(1i64), None),
+            KeywordRecord::new(Keyword::PCOUNT, Value::Integer(4i64), None),
+            KeywordRecord::new(Keyword::GCOUNT, Value::Integer(1i64), None),
+            KeywordRecord::new(Keyword::TFIELDS, Value::Integer(4i64), None),
+            KeywordRecord::new(Keyword::TTYPEn(1u16), Value::CharacterString(Cow::Borrowed("NAME")), None),
+            KeywordRecord::new(Keyword::TFORMn(1u16), Value::CharacterString(Cow::Borrowed("4A")), None),
+            KeywordRecord::new(Keyword::TTYPEn(2u16), Value::CharacterString(Cow::Borrowed("magnitude")), None),
+            KeywordRecord::new(Keyword::TFORMn(2u16), Value::CharacterString(Cow::Borrowed("1E")), None),
+            KeywordRecord::new(Keyword::TTYPEn(3u16), Value::CharacterString(Cow::Borrowed("flags")), None),
+            KeywordRecord::new(Keyword::TFORMn(3u16), Value::CharacterString(Cow::Borrowed("2L")), None),
+            KeywordRecord::new(Keyword::TTYPEn(4u16), Value::CharacterString(Cow::Borrowed("neighbours")), None),
+            KeywordRecord::new(Keyword::TFORMn(4u16), Value::CharacterString(Cow::Borrowed("1PI")), None),
+        ));
+        let hdu = HDU::with_data(header, &data);
+        let table = BinaryTable::from_hdu(&hdu).unwrap();
+
+        let star: Star = table.row_as(0).unwrap();
+
+        assert_eq!(star, Star { name: "ABC".to_string(), magnitude: 12.5f64, flags: vec!(true, false), neighbours: Some(vec!(7, 9)) });
+        assert_eq!(table.row_as::<Star>(1), Err(RowError::Table(TableError::OutOfRange { row: 1, column: 0 })));
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Cadence {
+        time: f64,
+        cadenceno: i32,
+    }
+
+    #[test]
+    fn k2_rows_should_deserialize() {
+        let data = include_bytes!("../../assets/images/k2-trappist1-unofficial-tpf-long-cadence.fits");
+        let fits = parse(data).unwrap();
+        let table = BinaryTable::from_hdu(fits.hdu_by_name("TARGETTABLES").unwrap()).unwrap();
+
+        let cadences: Vec<Cadence> = table.rows_as().unwrap();
+
+        assert_eq!(cadences.len(), table.rows());
+        assert_eq!(cadences[1].cadenceno, cadences[0].cadenceno + 1);
+        assert!(cadences.iter().any(|cadence| cadence.time > 2000f64));
+    }
+}
@@ -0,0 +1,998 @@
+//! The table module reads binary tables, i.e. extensions with
+//! `XTENSION = 'BINTABLE'`.
+//!
+//! Every row of a binary table has the same width, `NAXIS1`, and consists of
+//! one field per column, laid out according to `TFORMn`. Columns with a `P` or
+//! `Q` descriptor hold the count and offset of an array in the heap, which
+//! starts `THEAP` bytes after the start of the table.
+//!
+//! Columns are found by their name, `TTYPEn`. `BinaryTable::column` and the
+//! rows of `BinaryTable::iter_rows` return physical values, i.e. scaled by
+//! `TSCALn` and `TZEROn`, with `NaN` for `TNULLn`. `BinaryTable::column_stats`
+//! summarizes a column in a single pass, without collecting its values.
+
+use std::convert::TryFrom;
+use std::borrow::Cow;
+use std::error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use super::types::{Bitpix, HDU, HduKind, Header, KeywordRecord, Keyword, MAXIMUM_INDEX, Pixel, Scaling, Value, ValueRetrievalError};
+use super::units;
+use super::units::{Real, UnitError};
+
+/// The type of the elements of a column, the letter of `TFORMn`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DataType {
+    /// `L`, a logical, `T` or `F`.
+    Logical,
+    /// `X`, a bit.
+    Bit,
+    /// `B`, an unsigned byte.
+    Byte,
+    /// `I`, a 16-bit integer.
+    Short,
+    /// `J`, a 32-bit integer.
+    Int,
+    /// `K`, a 64-bit integer.
+    Long,
+    /// `A`, a character.
+    Character,
+    /// `E`, a single precision floating point number.
+    Float,
+    /// `D`, a double precision floating point number.
+    Double,
+    /// `C`, a pair of single precision floating point numbers.
+    ComplexFloat,
+    /// `M`, a pair of double precision floating point numbers.
+    ComplexDouble,
+}
+
+impl DataType {
+    /// The data type signalled by a letter of `TFORMn`.
+    pub fn from_code(code: char) -> Option<DataType> {
+        match code {
+            'L' => Some(DataType::Logical),
+            'X' => Some(DataType::Bit),
+            'B' => Some(DataType::Byte),
+            'I' => Some(DataType::Short),
+            'J' => Some(DataType::Int),
+            'K' => Some(DataType::Long),
+            'A' => Some(DataType::Character),
+            'E' => Some(DataType::Float),
+            'D' => Some(DataType::Double),
+            'C' => Some(DataType::ComplexFloat),
+            'M' => Some(DataType::ComplexDouble),
+            _ => None,
+        }
+    }
+
+    /// The `BITPIX` of data arrays with values of this type, if there is one.
+    pub fn bitpix(self) -> Option<Bitpix> {
+        match self {
+            DataType::Byte => Some(Bitpix::U8),
+            DataType::Short => Some(Bitpix::I16),
+            DataType::Int => Some(Bitpix::I32),
+            DataType::Long => Some(Bitpix::I64),
+            DataType::Float => Some(Bitpix::F32),
+            DataType::Double => Some(Bitpix::F64),
+            _ => None,
+        }
+    }
+
+    /// The number of bytes taken by `count` elements of this type, or `None`
+    /// when that does not fit in a `usize`.
+    pub fn bytes(self, count: usize) -> Option<usize> {
+        match self {
+            DataType::Bit => Some(count.div_ceil(8)),
+            DataType::Logical | DataType::Byte | DataType::Character => Some(count),
+            DataType::Short => count.checked_mul(2),
+            DataType::Int | DataType::Float => count.checked_mul(4),
+            DataType::Long | DataType::Double | DataType::ComplexFloat => count.checked_mul(8),
+            DataType::ComplexDouble => count.checked_mul(16),
+        }
+    }
+}
+
+/// The kind of array descriptor of a variable-length array column.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Descriptor {
+    /// `P`, a pair of 32-bit integers.
+    P,
+    /// `Q`, a pair of 64-bit integers.
+    Q,
+}
+
+/// The format of a column, i.e. the value of `TFORMn`, e.g. `8A`, `1E` or
+/// `1PB(1024)`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ColumnFormat {
+    /// The number of elements in a field, or of descriptors for variable-length arrays.
+    /// A repeat count of 0 is allowed: the fields of such a column take no
+    /// bytes and hold no elements.
+    pub repeat: usize,
+    /// The type of the elements.
+    pub data_type: DataType,
+    /// The descriptor, when the elements are stored in the heap.
+    pub descriptor: Option<Descriptor>,
+    /// The maximum number of elements of a variable-length array, if given,
+    /// e.g. 1024 for `1PB(1024)`.
+    pub max: Option<usize>,
+}
+
+impl ColumnFormat {
+    /// The number of bytes a field of this format takes in a row, or `None`
+    /// when that does not fit in a `usize`.
+    pub fn width(&self) -> Option<usize> {
+        match self.descriptor {
+            Some(Descriptor::P) => self.repeat.checked_mul(8),
+            Some(Descriptor::Q) => self.repeat.checked_mul(16),
+            None => self.data_type.bytes(self.repeat),
+        }
+    }
+}
+
+/// Problems with the value of `TFORMn`.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ParseColumnFormatError {
+    /// The repeat count is not a number, or a field with this many elements
+    /// takes more bytes than fit in a `usize`.
+    InvalidRepeat,
+    /// The letter of the type is missing or unknown.
+    UnknownType,
+    /// The maximum number of elements of a variable-length array is not a number.
+    InvalidMax,
+}
+
+impl FromStr for ColumnFormat {
+    type Err = ParseColumnFormatError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let input = input.trim();
+        let digits = input.chars().take_while(|c| c.is_ascii_digit()).count();
+        let repeat = if digits == 0 { 1 } else {
+            input[0..digits].parse::<usize>().map_err(|_| ParseColumnFormatError::InvalidRepeat)?
+        };
+        let mut codes = input[digits..].chars();
+        let code = codes.next().ok_or(ParseColumnFormatError::UnknownType)?;
+        let descriptor = match code {
+            'P' => Some(Descriptor::P),
+            'Q' => Some(Descriptor::Q),
+            _ => None,
+        };
+        let data_type = match descriptor {
+            Some(_) => codes.next().and_then(DataType::from_code),
+            None => DataType::from_code(code),
+        }.ok_or(ParseColumnFormatError::UnknownType)?;
+        let rest = codes.as_str().trim();
+        let max = match descriptor {
+            Some(_) if rest.starts_with('(') && rest.ends_with(')') => Some(
+                rest[1..(rest.len() - 1)].trim().parse::<usize>().map_err(|_| ParseColumnFormatError::InvalidMax)?
+            ),
+            Some(_) if !rest.is_empty() => return Err(ParseColumnFormatError::InvalidMax),
+            _ => None,
+        };
+        let format = ColumnFormat { repeat, data_type, descriptor, max };
+        format.width().ok_or(ParseColumnFormatError::InvalidRepeat)?;
+        Ok(format)
+    }
+}
+
+/// A column of a binary table.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Column {
+    /// The name of the column, i.e. `TTYPEn` without trailing spaces.
+    pub name: Option<String>,
+    /// The format of the column, i.e. `TFORMn`.
+    pub format: ColumnFormat,
+    /// The offset of the field of this column from the start of a row.
+    pub offset: usize,
+    /// The unit of the column, i.e. `TUNITn` without trailing spaces.
+    pub unit: Option<String>,
+    /// The dimensions of a field, i.e. `TDIMn`, the first varying fastest.
+    pub dimensions: Option<Vec<usize>>,
+    /// How stored values map to physical values, i.e. `TSCALn`, `TZEROn`
+    /// and `TNULLn`.
+    pub scaling: Scaling,
+}
+
+/// Statistics of the physical values of a column, see `BinaryTable::column_stats`.
+/// Without values that are not null, `min`, `max`, `mean` and `std` are `NaN`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ColumnStats {
+    /// The number of values that are not null.
+    pub count: usize,
+    /// The number of null values, i.e. `TNULLn` or `NaN`.
+    pub nulls: usize,
+    /// The smallest value.
+    pub min: f64,
+    /// The largest value.
+    pub max: f64,
+    /// The mean of the values.
+    pub mean: f64,
+    /// The population standard deviation of the values.
+    pub std: f64,
+}
+
+/// Problems that occur when reading a binary table.
+#[derive(Debug, PartialEq)]
+pub enum TableError {
+    /// The HDU is not a binary table, or has no data.
+    NotABinaryTable,
+    /// A keyword that describes the table has an invalid value.
+    InvalidValue {
+        /// The keyword.
+        keyword: Keyword,
+        /// What is wrong with the value.
+        error: ValueRetrievalError,
+    },
+    /// The value of `TFORMn` could not be parsed.
+    InvalidFormat {
+        /// The number of the column, counted from 1.
+        column: usize,
+        /// What is wrong with the format.
+        error: ParseColumnFormatError,
+    },
+    /// The value of `TDIMn` could not be parsed, or does not match the
+    /// number of elements of a field. The number of the column is counted from 1.
+    InvalidDimensions(usize),
+    /// The fields of the columns do not fit in a row of `NAXIS1` bytes.
+    RowTooNarrow,
+    /// There is no column with this name.
+    MissingColumn(String),
+    /// There already is a column with this name.
+    DuplicateColumn(String),
+    /// The row or column does not exist.
+    OutOfRange {
+        /// The row, counted from 0.
+        row: usize,
+        /// The column, counted from 0.
+        column: usize,
+    },
+    /// The column is not a variable-length array column.
+    NotADescriptor(usize),
+    /// The column does not hold numbers stored in the row.
+    NotNumeric(usize),
+    /// The type of the values of a column does not correspond with the requested type.
+    TypeMismatch {
+        /// The column, counted from 0.
+        column: usize,
+        /// The type of the values of the column.
+        actual: DataType,
+    },
+    /// The array of a descriptor lies outside the heap.
+    HeapOutOfBounds {
+        /// The row, counted from 0.
+        row: usize,
+        /// The column, counted from 0.
+        column: usize,
+    },
+}
+
+impl Display for TableError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            TableError::NotABinaryTable => write!(f, "the HDU is not a binary table"),
+            TableError::InvalidValue { ref keyword, ref error } => write!(f, "{}: {}", keyword, error),
+            TableError::InvalidFormat { column, ref error } => write!(f, "TFORM{}: {:?}", column, error),
+            TableError::InvalidDimensions(column) => write!(f, "TDIM{} does not match TFORM{}", column, column),
+            TableError::RowTooNarrow => write!(f, "the columns do not fit in NAXIS1"),
+            TableError::MissingColumn(ref name) => write!(f, "the column {} is missing", name),
+            TableError::DuplicateColumn(ref name) => write!(f, "there already is a column {}", name),
+            TableError::OutOfRange { row, column } => write!(f, "there is no field at row {}, column {}", row, column),
+            TableError::NotADescriptor(column) => write!(f, "column {} does not hold variable-length arrays", column),
+            TableError::NotNumeric(column) => write!(f, "column {} does not hold numbers", column),
+            TableError::TypeMismatch { column, actual } => write!(f, "column {} holds values of type {:?}", column, actual),
+            TableError::HeapOutOfBounds { row, column } => write!(f, "the array at row {}, column {} lies outside the heap", row, column),
+        }
+    }
+}
+
+impl error::Error for TableError {}
+
+/// A binary table, borrowing the data of an HDU.
+///
+/// ```
+/// use fits_rs::parser::parse;
+/// use fits_rs::table::BinaryTable;
+///
+/// let data = std::fs::read("assets/images/k2-trappist1-unofficial-tpf-long-cadence.fits")?;
+/// let fits = parse(&data)?;
+/// let table = BinaryTable::from_hdu(&fits.extensions[0])?;
+///
+/// assert_eq!(table.rows(), 3599);
+/// assert_eq!(table.column_index("time"), Some(0));
+/// let times = table.column::<f64>("TIME")?;
+/// assert_eq!(times.len(), table.rows());
+/// let first = table.iter_rows().next().expect("the table has rows");
+/// assert_eq!(first.get::<f64>("TIME")?.to_bits(), times[0].to_bits());
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug)]
+pub struct BinaryTable<'t> {
+    columns: Vec<Column>,
+    row_width: usize,
+    rows: usize,
+    main: &'t [u8],
+    heap: &'t [u8],
+}
+
+impl<'t> BinaryTable<'t> {
+    /// Read the layout of the binary table in `hdu`.
+    pub fn from_hdu<'a>(hdu: &'t HDU<'a>) -> Result<BinaryTable<'t>, TableError> {
+        let header = &hdu.header;
+        if header.kind() != HduKind::BinTable {
+            return Err(TableError::NotABinaryTable)
+        }
+        let integer = |keyword: Keyword, default: Option<i64>| {
+            let value = match (header.get_int(&keyword), default) {
+                (Err(ValueRetrievalError::KeywordNotPresent), Some(default)) => default,
+                (Ok(value), _) => value,
+                (Err(error), _) => return Err(TableError::InvalidValue { keyword, error }),
+            };
+            usize::try_from(value).map_err(|_| TableError::InvalidValue { keyword, error: ValueRetrievalError::NotAnInteger })
+        };
+        let row_width = integer(Keyword::NAXISn(1), None)?;
+        let rows = integer(Keyword::NAXISn(2), None)?;
+        let fields = integer(Keyword::TFIELDS, Some(0))?;
+        let mut columns = vec!();
+        let mut offset = 0;
+        for n in 1..(fields + 1) {
+            let tform = header.get_str(&Keyword::TFORMn(n as u16))
+                .map_err(|error| TableError::InvalidValue { keyword: Keyword::TFORMn(n as u16), error })?;
+            let format = ColumnFormat::from_str(tform)
+                .map_err(|error| TableError::InvalidFormat { column: n, error })?;
+            let name = header.get_trimmed_str(&Keyword::TTYPEn(n as u16)).ok().map(str::to_string);
+            let unit = header.get_trimmed_str(&Keyword::TUNITn(n as u16)).ok().map(str::to_string);
+            let dimensions = match header.get_str(&Keyword::TDIMn(n as u16)) {
+                Ok(tdim) => Some(parse_dimensions(tdim)
+                    .filter(|dimensions| format.descriptor.is_some() || elements(dimensions) == Some(format.repeat))
+                    .ok_or(TableError::InvalidDimensions(n))?),
+                Err(_) => None,
+            };
+            let real = |keyword: Keyword, default: f64| match header.get_real(&keyword) {
+                Err(ValueRetrievalError::KeywordNotPresent) => Ok(default),
+                result => result.map_err(|error| TableError::InvalidValue { keyword, error }),
+            };
+            let scaling = Scaling {
+                bscale: real(Keyword::TSCALn(n as u16), 1f64)?,
+                bzero: real(Keyword::TZEROn(n as u16), 0f64)?,
+                blank: match header.get_int(&Keyword::TNULLn(n as u16)) {
+                    Err(ValueRetrievalError::KeywordNotPresent) => None,
+                    result => Some(result.map_err(|error| TableError::InvalidValue { keyword: Keyword::TNULLn(n as u16), error })?),
+                },
+            };
+            columns.push(Column { name, format, offset, unit, dimensions, scaling });
+            offset = format.width().and_then(|width| offset.checked_add(width)).ok_or(TableError::RowTooNarrow)?;
+        }
+        if offset > row_width {
+            return Err(TableError::RowTooNarrow)
+        }
+        let data = hdu.data().bytes();
+        let size = row_width.checked_mul(rows).filter(|size| *size <= data.len()).ok_or(TableError::NotABinaryTable)?;
+        let theap = integer(Keyword::THEAP, Some(size as i64))?.min(data.len());
+        Ok(BinaryTable { columns, row_width, rows, main: &data[0..size], heap: &data[theap..] })
+    }
+
+    /// The columns of this table.
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    /// The number of rows, i.e. `NAXIS2`.
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// The number of bytes of a row, i.e. `NAXIS1`.
+    pub fn row_width(&self) -> usize {
+        self.row_width
+    }
+
+    /// The raw big-endian bytes of the rows, without the heap.
+    pub fn bytes(&self) -> &'t [u8] {
+        self.main
+    }
+
+    /// The index of the column called `name`, compared case insensitively.
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|column| {
+            column.name.as_ref().is_some_and(|own| own.eq_ignore_ascii_case(name.trim_end()))
+        })
+    }
+
+    /// The raw big-endian bytes of the field at `row` and `column`, both counted from 0.
+    pub fn field(&self, row: usize, column: usize) -> Result<&'t [u8], TableError> {
+        match self.columns.get(column) {
+            Some(c) if row < self.rows => {
+                // The columns fit in a row, so their widths do too.
+                let start = row * self.row_width + c.offset;
+                let width = c.format.width().ok_or(TableError::RowTooNarrow)?;
+                Ok(&self.main[start..(start + width)])
+            }
+            _ => Err(TableError::OutOfRange { row, column }),
+        }
+    }
+
+    /// The first element of the field of every row of a numeric column, as `f64`.
+    pub fn scalars(&self, column: usize) -> Result<Vec<f64>, TableError> {
+        let format = self.columns.get(column).ok_or(TableError::OutOfRange { row: 0, column })?.format;
+        if format.descriptor.is_some() || format.repeat == 0 {
+            return Err(TableError::NotNumeric(column))
+        }
+        (0..self.rows).map(|row| {
+            let field = self.field(row, column)?;
+            number(format.data_type, field).ok_or(TableError::NotNumeric(column))
+        }).collect()
+    }
+
+    /// The physical values of the column called `name`, i.e. with `TSCALn`
+    /// and `TZEROn` applied and `TNULLn` replaced by `NaN`. Every element of
+    /// the field of a row is included, row by row.
+    ///
+    /// Fails when there is no such column, or when it does not hold numbers
+    /// stored in the row, e.g. characters or variable-length arrays.
+    pub fn column<T: Real>(&self, name: &str) -> Result<Vec<T>, TableError> {
+        let column = self.column_index(name).ok_or_else(|| TableError::MissingColumn(name.to_string()))?;
+        let mut values = vec!();
+        for row in 0..self.rows {
+            values.extend(self.physical_values(row, column)?);
+        }
+        Ok(values)
+    }
+
+    /// The rows of the table, in order.
+    pub fn iter_rows<'r>(&'r self) -> impl Iterator<Item = Row<'r, 't>> {
+        (0..self.rows).map(move |index| Row { table: self, index })
+    }
+
+    /// Statistics of the physical values of the column called `name`, see
+    /// `column`. The rows are read once and no values are collected, so that
+    /// this works for tables of any length. Null values, i.e. `TNULLn` and
+    /// `NaN`, are counted but left out of the other statistics.
+    pub fn column_stats(&self, name: &str) -> Result<ColumnStats, TableError> {
+        let column = self.column_index(name).ok_or_else(|| TableError::MissingColumn(name.to_string()))?;
+        let size = self.element_size(column)?;
+        let Column { format, scaling, .. } = self.columns[column];
+        let mut stats = ColumnStats { count: 0, nulls: 0, min: f64::NAN, max: f64::NAN, mean: f64::NAN, std: f64::NAN };
+        // The sum of squared differences from the mean, updated as in Welford's algorithm.
+        let mut squares = 0f64;
+        for row in 0..self.rows {
+            for element in self.field(row, column)?.chunks(size) {
+                let value = physical(format.data_type, &scaling, element);
+                if value.is_nan() {
+                    stats.nulls += 1;
+                    continue
+                }
+                stats.count += 1;
+                if stats.count == 1 {
+                    stats.min = value;
+                    stats.max = value;
+                    stats.mean = value;
+                } else {
+                    stats.min = stats.min.min(value);
+                    stats.max = stats.max.max(value);
+                    let delta = value - stats.mean;
+                    stats.mean += delta / stats.count as f64;
+                    squares += delta * (value - stats.mean);
+                }
+            }
+        }
+        if stats.count > 0 {
+            stats.std = (squares / stats.count as f64).sqrt();
+        }
+        Ok(stats)
+    }
+
+    fn physical_values<T: Real>(&self, row: usize, column: usize) -> Result<Vec<T>, TableError> {
+        let size = self.element_size(column)?;
+        let Column { format, scaling, .. } = self.columns[column];
+        let field = self.field(row, column)?;
+        Ok(field.chunks(size).map(|element| T::from_f64(physical(format.data_type, &scaling, element))).collect())
+    }
+
+    // The size of an element of a column that holds numbers stored in the row.
+    fn element_size(&self, column: usize) -> Result<usize, TableError> {
+        let format = self.columns.get(column).ok_or(TableError::OutOfRange { row: 0, column })?.format;
+        match format.data_type.bitpix() {
+            Some(bitpix) if format.descriptor.is_none() => Ok(bitpix.bytes()),
+            _ => Err(TableError::TypeMismatch { column, actual: format.data_type }),
+        }
+    }
+
+    /// The first element of the field of every row of the column called
+    /// `name`, converted from its `TUNITn` to `unit`, e.g. `mJy`.
+    pub fn column_in<T: Real>(&self, name: &str, unit: &str) -> Result<Vec<T>, UnitError> {
+        let index = self.column_index(name).ok_or_else(|| UnitError::MissingColumn(name.to_string()))?;
+        let own = self.columns[index].unit.as_ref().ok_or_else(|| UnitError::MissingUnit(name.to_string()))?;
+        let factor = units::factor(own, unit)?;
+        Ok(self.scalars(index)?.into_iter().map(|value| T::from_f64(value * factor)).collect())
+    }
+
+    /// Every element of the field at `row` and `column` of a numeric column,
+    /// as `f64`. For variable-length arrays these are the elements in the heap.
+    pub fn numbers(&self, row: usize, column: usize) -> Result<Vec<f64>, TableError> {
+        let format = self.columns.get(column).ok_or(TableError::OutOfRange { row, column })?.format;
+        let size = format.data_type.bitpix().ok_or(TableError::NotNumeric(column))?.bytes();
+        let bytes = match format.descriptor {
+            Some(_) => self.heap_field(row, column)?,
+            None => self.field(row, column)?,
+        };
+        bytes.chunks(size).map(|element| number(format.data_type, element).ok_or(TableError::NotNumeric(column))).collect()
+    }
+
+    /// The number of elements and the offset in the heap of the
+    /// variable-length array at `row` and `column`.
+    pub fn descriptor(&self, row: usize, column: usize) -> Result<(usize, usize), TableError> {
+        let field = self.field(row, column)?;
+        match self.columns[column].format.descriptor {
+            Some(Descriptor::P) if field.len() >= 8 => Ok((
+                u32::from_be_bytes([field[0], field[1], field[2], field[3]]) as usize,
+                u32::from_be_bytes([field[4], field[5], field[6], field[7]]) as usize,
+            )),
+            Some(Descriptor::Q) if field.len() >= 16 => {
+                let mut count = [0u8; 8];
+                let mut offset = [0u8; 8];
+                count.copy_from_slice(&field[0..8]);
+                offset.copy_from_slice(&field[8..16]);
+                let heap_out_of_bounds = || TableError::HeapOutOfBounds { row, column };
+                Ok((
+                    usize::try_from(u64::from_be_bytes(count)).map_err(|_| heap_out_of_bounds())?,
+                    usize::try_from(u64::from_be_bytes(offset)).map_err(|_| heap_out_of_bounds())?,
+                ))
+            }
+            _ => Err(TableError::NotADescriptor(column)),
+        }
+    }
+
+    /// The raw big-endian bytes in the heap of the variable-length array at
+    /// `row` and `column`. A descriptor whose array would end past the heap,
+    /// or past the largest `usize`, is out of bounds.
+    pub fn heap_field(&self, row: usize, column: usize) -> Result<&'t [u8], TableError> {
+        let (count, offset) = self.descriptor(row, column)?;
+        self.columns[column].format.data_type.bytes(count)
+            .and_then(|size| offset.checked_add(size))
+            .filter(|end| *end <= self.heap.len())
+            .map(|end| &self.heap[offset..end])
+            .ok_or(TableError::HeapOutOfBounds { row, column })
+    }
+
+    /// Decode the variable-length array at `row` and `column`. The type `T`
+    /// should correspond with the type of the column, e.g. `i32` for `1PJ`
+    /// and `f64` for `1QD`.
+    pub fn variable_length_array<T: Pixel>(&self, row: usize, column: usize) -> Result<Vec<T>, TableError> {
+        let bytes = self.heap_field(row, column)?;
+        let data_type = self.columns[column].format.data_type;
+        if data_type.bitpix() != Some(T::BITPIX) {
+            return Err(TableError::TypeMismatch { column, actual: data_type })
+        }
+        Ok(bytes.chunks(T::BITPIX.bytes()).map(T::from_big_endian).collect())
+    }
+
+    /// Decode the variable-length arrays of all rows of `column`.
+    pub fn variable_length_arrays<T: Pixel>(&self, column: usize) -> Result<Vec<Vec<T>>, TableError> {
+        (0..self.rows).map(|row| self.variable_length_array(row, column)).collect()
+    }
+}
+
+/// A row of a binary table.
+#[derive(Debug, Clone, Copy)]
+pub struct Row<'r, 't: 'r> {
+    table: &'r BinaryTable<'t>,
+    index: usize,
+}
+
+impl<'r, 't> Row<'r, 't> {
+    /// The index of the row, counted from 0.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The physical value of the column called `name` in this row, like
+    /// `BinaryTable::column` determines it. Only the first element of the
+    /// field is returned.
+    pub fn get<T: Real>(&self, name: &str) -> Result<T, TableError> {
+        let column = self.column_index(name)?;
+        self.table.physical_values(self.index, column)?
+            .into_iter().next().ok_or(TableError::NotNumeric(column))
+    }
+
+    /// The physical values of every element of the field of the column called
+    /// `name` in this row.
+    pub fn values<T: Real>(&self, name: &str) -> Result<Vec<T>, TableError> {
+        let column = self.column_index(name)?;
+        self.table.physical_values(self.index, column)
+    }
+
+    /// The characters of the column called `name` in this row, without
+    /// trailing spaces and anything after a NUL character.
+    pub fn text(&self, name: &str) -> Result<String, TableError> {
+        let column = self.column_index(name)?;
+        let data_type = self.table.columns[column].format.data_type;
+        if data_type != DataType::Character {
+            return Err(TableError::TypeMismatch { column, actual: data_type })
+        }
+        let field = self.table.field(self.index, column)?;
+        let end = field.iter().position(|byte| *byte == 0).unwrap_or(field.len());
+        Ok(String::from_utf8_lossy(&field[..end]).trim_end().to_string())
+    }
+
+    fn column_index(&self, name: &str) -> Result<usize, TableError> {
+        self.table.column_index(name).ok_or_else(|| TableError::MissingColumn(name.to_string()))
+    }
+}
+
+/// Edits of the columns of a table. A `BinaryTable` that was read before an
+/// edit still knows the columns by their old names; read it again with
+/// `from_hdu`.
+impl<'a> Header<'a> {
+    /// Rename the column called `from`, compared case insensitively, to `to`
+    /// by setting its `TTYPEn`.
+    pub fn rename_column(&mut self, from: &str, to: &str) -> Result<(), TableError> {
+        let n = self.column_number(from)?;
+        if self.column_number(to).is_ok_and(|other| other != n) {
+            return Err(TableError::DuplicateColumn(to.trim_end().to_string()))
+        }
+        self.set(Keyword::TTYPEn(n), Value::CharacterString(Cow::Owned(to.to_string())), None);
+        Ok(())
+    }
+
+    /// Set the unit of the column called `name`, compared case insensitively,
+    /// by setting its `TUNITn`. A new `TUNITn` follows the `TFORMn` of the column.
+    pub fn set_column_unit(&mut self, name: &str, unit: &str) -> Result<(), TableError> {
+        let n = self.column_number(name)?;
+        let value = Value::CharacterString(Cow::Owned(unit.to_string()));
+        if self.record_of(&Keyword::TUNITn(n)).is_some() {
+            self.set(Keyword::TUNITn(n), value, None);
+            return Ok(())
+        }
+        let record = KeywordRecord::new(Keyword::TUNITn(n), value, None);
+        let after = if self.record_of(&Keyword::TFORMn(n)).is_some() { Keyword::TFORMn(n) } else { Keyword::TTYPEn(n) };
+        self.insert_after(&after, record).map_err(|error| TableError::InvalidValue { keyword: after, error })
+    }
+
+    // The number of the column called `name`, counted from 1.
+    fn column_number(&self, name: &str) -> Result<u16, TableError> {
+        let fields = match self.get_int(&Keyword::TFIELDS) {
+            Err(ValueRetrievalError::KeywordNotPresent) => 0,
+            result => result.map_err(|error| TableError::InvalidValue { keyword: Keyword::TFIELDS, error })?,
+        };
+        (1..=fields.clamp(0, i64::from(MAXIMUM_INDEX)) as u16)
+            .find(|n| self.get_trimmed_str(&Keyword::TTYPEn(*n)).is_ok_and(|own| own.eq_ignore_ascii_case(name.trim_end())))
+            .ok_or_else(|| TableError::MissingColumn(name.to_string()))
+    }
+}
+
+// Parse the value of `TDIMn`, e.g. `(5,11)`.
+fn parse_dimensions(tdim: &str) -> Option<Vec<usize>> {
+    let inner = tdim.trim().strip_prefix('(')?.strip_suffix(')')?;
+    inner.split(',').map(|dimension| dimension.trim().parse::<usize>().ok()).collect()
+}
+
+// The number of elements of a field with these dimensions, if it fits in a `usize`.
+fn elements(dimensions: &[usize]) -> Option<usize> {
+    dimensions.iter().try_fold(1usize, |product, dimension| product.checked_mul(*dimension))
+}
+
+// The physical value of a numeric element, `NaN` when it equals `TNULLn`.
+fn physical(data_type: DataType, scaling: &Scaling, element: &[u8]) -> f64 {
+    let raw = number(data_type, element).unwrap_or(f64::NAN);
+    if data_type.bitpix().is_some_and(Bitpix::is_floating_point) {
+        scaling.apply(raw)
+    } else {
+        scaling.apply_to_integer(raw as i64)
+    }
+}
+
+fn number(data_type: DataType, field: &[u8]) -> Option<f64> {
+    match data_type {
+        DataType::Byte => Some(f64::from(field[0])),
+        DataType::Short => Some(f64::from(i16::from_be_bytes([field[0], field[1]]))),
+        DataType::Int => Some(f64::from(i32::from_be_bytes([field[0], field[1], field[2], field[3]]))),
+        DataType::Long => Some(i64::from_be_bytes([field[0], field[1], field[2], field[3], field[4], field[5], field[6], field[7]]) as f64),
+        DataType::Float => Some(f64::from(f32::from_be_bytes([field[0], field[1], field[2], field[3]]))),
+        DataType::Double => Some(f64::from_be_bytes([field[0], field[1], field[2], field[3], field[4], field[5], field[6], field[7]])),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::str::FromStr;
+    use super::super::parser::parse;
+    use super::super::types::{HDU, Header, KeywordRecord, Keyword, Value};
+    use super::super::testgen;
+    use super::super::units::UnitError;
+    use super::{BinaryTable, ColumnFormat, DataType, Descriptor, ParseColumnFormatError, TableError};
+
+    #[test]
+    fn column_formats_should_parse() {
+        assert_eq!(ColumnFormat::from_str("8A"), Ok(ColumnFormat { repeat: 8, data_type: DataType::Character, descriptor: None, max: None }));
+        assert_eq!(ColumnFormat::from_str("E").map(|format| format.width()), Ok(Some(4)));
+        assert_eq!(ColumnFormat::from_str("12X").map(|format| format.width()), Ok(Some(2)));
+        assert_eq!(ColumnFormat::from_str("1PB(2880)"), Ok(ColumnFormat { repeat: 1, data_type: DataType::Byte, descriptor: Some(Descriptor::P), max: Some(2880) }));
+        assert_eq!(ColumnFormat::from_str("1QD").map(|format| format.width()), Ok(Some(16)));
+        assert_eq!(ColumnFormat::from_str("4611686018427387904D"), Err(ParseColumnFormatError::InvalidRepeat));
+        assert_eq!(ColumnFormat::from_str("3Z"), Err(ParseColumnFormatError::UnknownType));
+        assert_eq!(ColumnFormat::from_str("1PE(ten)"), Err(ParseColumnFormatError::InvalidMax));
+    }
+
+    #[test]
+    fn heap_fields_should_follow_descriptors() {
+        let data = [
+            0u8, 7u8, 0u8, 0u8, 0u8, 2u8, 0u8, 0u8, 0u8, 1u8,
+            0u8, 9u8, 0u8, 0u8, 0u8, 1u8, 0u8, 0u8, 0u8, 5u8,
+            0u8, 1u8, 0u8, 2u8, 0u8, 3u8,
+        ];
+        let header = Header::new(vec!(
+            KeywordRecord::new(Keyword::XTENSION, Value::CharacterString(Cow::Borrowed("BINTABLE")), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(2i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(10i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(2u16), Value::Integer(2i64), Option::None),
+            KeywordRecord::new(Keyword::PCOUNT, Value::Integer(6i64), Option::None),
+            KeywordRecord::new(Keyword::GCOUNT, Value::Integer(1i64), Option::None),
+            KeywordRecord::new(Keyword::TFIELDS, Value::Integer(2i64), Option::None),
+            KeywordRecord::new(Keyword::TTYPEn(1u16), Value::CharacterString(Cow::Borrowed("ID")), Option::None),
+            KeywordRecord::new(Keyword::TFORMn(1u16), Value::CharacterString(Cow::Borrowed("1I")), Option::None),
+            KeywordRecord::new(Keyword::TTYPEn(2u16), Value::CharacterString(Cow::Borrowed("VALUES")), Option::None),
+            KeywordRecord::new(Keyword::TFORMn(2u16), Value::CharacterString(Cow::Borrowed("1PI(2)")), Option::None),
+        ));
+        let hdu = HDU::with_data(header, &data);
+
+        let table = BinaryTable::from_hdu(&hdu).unwrap();
+
+        assert_eq!(table.column_index("values"), Some(1));
+        assert_eq!(table.field(1, 0), Ok(&[0u8, 9u8][..]));
+        assert_eq!(table.descriptor(0, 1), Ok((2, 1)));
+        assert_eq!(table.heap_field(0, 1), Ok(&[1u8, 0u8, 2u8, 0u8][..]));
+        assert_eq!(table.heap_field(1, 1), Err(TableError::HeapOutOfBounds { row: 1, column: 1 }));
+        assert_eq!(table.numbers(0, 1), Ok(vec!(256f64, 512f64)));
+        assert_eq!(table.numbers(1, 0), Ok(vec!(9f64)));
+        assert_eq!(table.descriptor(0, 0), Err(TableError::NotADescriptor(0)));
+        assert_eq!(table.field(2, 0), Err(TableError::OutOfRange { row: 2, column: 0 }));
+    }
+
+    #[test]
+    fn variable_length_arrays_should_be_read_from_the_heap() {
+        let mut data = vec!();
+        data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 4]);
+        data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0]);
+        data.extend_from_slice(&[0, 0, 0, 0]);
+        data.extend_from_slice(&[0x40, 0x49, 0x0f, 0xdb, 0xbf, 0x80, 0, 0, 0x3f, 0x80, 0, 0]);
+        let header = Header::new(vec!(
+            KeywordRecord::new(Keyword::XTENSION, Value::CharacterString(Cow::Borrowed("BINTABLE")), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(2i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(16i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(2u16), Value::Integer(2i64), Option::None),
+            KeywordRecord::new(Keyword::PCOUNT, Value::Integer(16i64), Option::None),
+            KeywordRecord::new(Keyword::GCOUNT, Value::Integer(1i64), Option::None),
+            KeywordRecord::new(Keyword::TFIELDS, Value::Integer(1i64), Option::None),
+            KeywordRecord::new(Keyword::TTYPEn(1u16), Value::CharacterString(Cow::Borrowed("SPECTRUM")), Option::None),
+            KeywordRecord::new(Keyword::TFORMn(1u16), Value::CharacterString(Cow::Borrowed("1QE(2)")), Option::None),
+            KeywordRecord::new(Keyword::THEAP, Value::Integer(36i64), Option::None),
+        ));
+        let hdu = HDU::with_data(header, &data);
+
+        let table = BinaryTable::from_hdu(&hdu).unwrap();
+
+        assert_eq!(table.variable_length_arrays::<f32>(0), Ok(vec!(vec!(-1f32, 1f32), vec!(std::f32::consts::PI))));
+        assert_eq!(table.variable_length_array::<f64>(0, 0), Err(TableError::TypeMismatch { column: 0, actual: DataType::Float }));
+    }
+
+    #[test]
+    fn descriptors_beyond_the_address_space_should_be_out_of_bounds() {
+        let mut data = vec!();
+        data.extend_from_slice(&[0x40, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        data.extend_from_slice(&[0; 8]);
+        let header = Header::new(vec!(
+            KeywordRecord::new(Keyword::XTENSION, Value::CharacterString(Cow::Borrowed("BINTABLE")), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(2i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(16i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(2u16), Value::Integer(2i64), Option::None),
+            KeywordRecord::new(Keyword::PCOUNT, Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::GCOUNT, Value::Integer(1i64), Option::None),
+            KeywordRecord::new(Keyword::TFIELDS, Value::Integer(1i64), Option::None),
+            KeywordRecord::new(Keyword::TTYPEn(1u16), Value::CharacterString(Cow::Borrowed("SPECTRUM")), Option::None),
+            KeywordRecord::new(Keyword::TFORMn(1u16), Value::CharacterString(Cow::Borrowed("1QD")), Option::None),
+        ));
+        let hdu = HDU::with_data(header, &data);
+
+        let table = BinaryTable::from_hdu(&hdu).unwrap();
+
+        assert_eq!(table.descriptor(0, 0), Ok((1 << 62, 0)));
+        assert_eq!(table.heap_field(0, 0), Err(TableError::HeapOutOfBounds { row: 0, column: 0 }));
+        assert_eq!(table.heap_field(1, 0), Err(TableError::HeapOutOfBounds { row: 1, column: 0 }));
+        assert_eq!(table.variable_length_array::<f64>(1, 0), Err(TableError::HeapOutOfBounds { row: 1, column: 0 }));
+    }
+
+    #[test]
+    fn columns_should_be_converted_to_a_unit() {
+        let data = [0x3f, 0x80, 0, 0, 0x40, 0, 0, 0];
+        let header = Header::new(vec!(
+            KeywordRecord::new(Keyword::XTENSION, Value::CharacterString(Cow::Borrowed("BINTABLE")), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(2i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(4i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(2u16), Value::Integer(2i64), Option::None),
+            KeywordRecord::new(Keyword::TFIELDS, Value::Integer(1i64), Option::None),
+            KeywordRecord::new(Keyword::TTYPEn(1u16), Value::CharacterString(Cow::Borrowed("FLUX")), Option::None),
+            KeywordRecord::new(Keyword::TFORMn(1u16), Value::CharacterString(Cow::Borrowed("1E")), Option::None),
+            KeywordRecord::new(Keyword::TUNITn(1u16), Value::CharacterString(Cow::Borrowed("Jy      ")), Option::None),
+        ));
+        let hdu = HDU::with_data(header, &data);
+
+        let table = BinaryTable::from_hdu(&hdu).unwrap();
+
+        assert_eq!(table.columns()[0].unit, Some("Jy".to_string()));
+        assert_eq!(table.column_in::<f64>("FLUX", "mJy"), Ok(vec!(1000f64, 2000f64)));
+        assert_eq!(table.column_in::<f32>("flux", "kJy"), Ok(vec!(0.001f32, 0.002f32)));
+        assert_eq!(table.column_in::<f64>("FLUX", "mag"), Err(UnitError::Incompatible { from: "Jy".to_string(), to: "mag".to_string() }));
+        assert_eq!(table.column_in::<f64>("FLUX", "mJy/beam"), Err(UnitError::UnknownUnit("mJy/beam".to_string())));
+        assert_eq!(table.column_in::<f64>("TIME", "s"), Err(UnitError::MissingColumn("TIME".to_string())));
+    }
+
+    #[test]
+    fn columns_with_a_repeat_count_of_zero_should_have_empty_fields() {
+        let data = [0u8, 7u8, 0u8, 9u8];
+        let header = Header::new(vec!(
+            KeywordRecord::new(Keyword::XTENSION, Value::CharacterString(Cow::Borrowed("BINTABLE")), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(2i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(2i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(2u16), Value::Integer(2i64), Option::None),
+            KeywordRecord::new(Keyword::TFIELDS, Value::Integer(4i64), Option::None),
+            KeywordRecord::new(Keyword::TTYPEn(1u16), Value::CharacterString(Cow::Borrowed("EMPTY")), Option::None),
+            KeywordRecord::new(Keyword::TFORMn(1u16), Value::CharacterString(Cow::Borrowed("0E")), Option::None),
+            KeywordRecord::new(Keyword::TTYPEn(2u16), Value::CharacterString(Cow::Borrowed("ID")), Option::None),
+            KeywordRecord::new(Keyword::TFORMn(2u16), Value::CharacterString(Cow::Borrowed("1I")), Option::None),
+            KeywordRecord::new(Keyword::TTYPEn(3u16), Value::CharacterString(Cow::Borrowed("NAME")), Option::None),
+            KeywordRecord::new(Keyword::TFORMn(3u16), Value::CharacterString(Cow::Borrowed("0A")), Option::None),
+            KeywordRecord::new(Keyword::TTYPEn(4u16), Value::CharacterString(Cow::Borrowed("FLAGS")), Option::None),
+            KeywordRecord::new(Keyword::TFORMn(4u16), Value::CharacterString(Cow::Borrowed("0X")), Option::None),
+        ));
+        let hdu = HDU::with_data(header, &data);
+
+        let table = BinaryTable::from_hdu(&hdu).unwrap();
+
+        assert_eq!(table.columns().iter().map(|column| column.offset).collect::<Vec<_>>(), vec!(0, 0, 2, 2));
+        assert_eq!(table.field(1, 0), Ok(&[][..]));
+        assert_eq!(table.field(1, 3), Ok(&[][..]));
+        assert_eq!(table.column::<f64>("EMPTY"), Ok(vec!()));
+        assert_eq!(table.column::<f64>("ID"), Ok(vec!(7f64, 9f64)));
+        assert_eq!(table.numbers(0, 0), Ok(vec!()));
+        assert_eq!(table.column_stats("EMPTY").map(|stats| (stats.count, stats.nulls)), Ok((0, 0)));
+        assert_eq!(table.scalars(0), Err(TableError::NotNumeric(0)));
+        let row = table.iter_rows().next().unwrap();
+        assert_eq!(row.values::<f32>("EMPTY"), Ok(vec!()));
+        assert_eq!(row.get::<f32>("EMPTY"), Err(TableError::NotNumeric(0)));
+        assert_eq!(row.text("NAME"), Ok(String::new()));
+    }
+
+    #[test]
+    fn columns_wider_than_memory_should_not_fit_in_a_row() {
+        let records = |tform: &'static str| vec!(
+            KeywordRecord::new(Keyword::XTENSION, Value::CharacterString(Cow::Borrowed("BINTABLE")), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(2i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(2u16), Value::Integer(0i64), Option::None),
+            KeywordRecord::new(Keyword::TFIELDS, Value::Integer(2i64), Option::None),
+            KeywordRecord::new(Keyword::TFORMn(1u16), Value::CharacterString(Cow::Borrowed(tform)), Option::None),
+            KeywordRecord::new(Keyword::TFORMn(2u16), Value::CharacterString(Cow::Borrowed(tform)), Option::None),
+        );
+
+        assert_eq!(BinaryTable::from_hdu(&HDU::new(Header::new(records("4611686018427387904D")))).map(|_| ()),
+                   Err(TableError::InvalidFormat { column: 1, error: ParseColumnFormatError::InvalidRepeat }));
+        assert_eq!(BinaryTable::from_hdu(&HDU::new(Header::new(records("1152921504606846976D")))).map(|_| ()), Err(TableError::RowTooNarrow));
+    }
+
+    #[test]
+    fn dimensions_should_match_the_repeat_count() {
+        let records = |tdim: &'static str| vec!(
+            KeywordRecord::new(Keyword::XTENSION, Value::CharacterString(Cow::Borrowed("BINTABLE")), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(2i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(6i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(2u16), Value::Integer(0i64), Option::None),
+            KeywordRecord::new(Keyword::TFIELDS, Value::Integer(1i64), Option::None),
+            KeywordRecord::new(Keyword::TFORMn(1u16), Value::CharacterString(Cow::Borrowed("6B")), Option::None),
+            KeywordRecord::new(Keyword::TDIMn(1u16), Value::CharacterString(Cow::Borrowed(tdim)), Option::None),
+        );
+        let hdu = HDU::new(Header::new(records("(3, 2)")));
+        let table = BinaryTable::from_hdu(&hdu).unwrap();
+
+        assert_eq!(table.columns()[0].dimensions, Some(vec!(3, 2)));
+        assert_eq!(BinaryTable::from_hdu(&HDU::new(Header::new(records("(4,2)")))).map(|_| ()), Err(TableError::InvalidDimensions(1)));
+        assert_eq!(BinaryTable::from_hdu(&HDU::new(Header::new(records("3x2")))).map(|_| ()), Err(TableError::InvalidDimensions(1)));
+        assert_eq!(BinaryTable::from_hdu(&HDU::new(Header::new(records("(4294967296,4294967296,4294967296)")))).map(|_| ()), Err(TableError::InvalidDimensions(1)));
+    }
+
+    #[test]
+    fn columns_should_be_read_by_name_with_scaling() {
+        let data = [
+            b'A', b'B', 0, 0, 1, 0x3f, 0x80, 0, 0, 0,
+            b'C', 0, 0, 0xff, 0xff, 0x40, 0, 0, 0, 0,
+        ];
+        let header = Header::new(vec!(
+            KeywordRecord::new(Keyword::XTENSION, Value::CharacterString(Cow::Borrowed("BINTABLE")), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(2i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(10i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(2u16), Value::Integer(2i64), Option::None),
+            KeywordRecord::new(Keyword::TFIELDS, Value::Integer(3i64), Option::None),
+            KeywordRecord::new(Keyword::TTYPEn(1u16), Value::CharacterString(Cow::Borrowed("NAME")), Option::None),
+            KeywordRecord::new(Keyword::TFORMn(1u16), Value::CharacterString(Cow::Borrowed("3A")), Option::None),
+            KeywordRecord::new(Keyword::TTYPEn(2u16), Value::CharacterString(Cow::Borrowed("COUNTS")), Option::None),
+            KeywordRecord::new(Keyword::TFORMn(2u16), Value::CharacterString(Cow::Borrowed("1I")), Option::None),
+            KeywordRecord::new(Keyword::TSCALn(2u16), Value::Real(0.5f64), Option::None),
+            KeywordRecord::new(Keyword::TZEROn(2u16), Value::Integer(10i64), Option::None),
+            KeywordRecord::new(Keyword::TNULLn(2u16), Value::Integer(-1i64), Option::None),
+            KeywordRecord::new(Keyword::TTYPEn(3u16), Value::CharacterString(Cow::Borrowed("FLUX")), Option::None),
+            KeywordRecord::new(Keyword::TFORMn(3u16), Value::CharacterString(Cow::Borrowed("1E")), Option::None),
+        ));
+        let hdu = HDU::with_data(header, &data);
+
+        let table = BinaryTable::from_hdu(&hdu).unwrap();
+        let counts = table.column::<f64>("counts").unwrap();
+
+        assert_eq!(counts[0], 10.5f64);
+        assert!(counts[1].is_nan());
+        assert_eq!(table.column::<f32>("FLUX"), Ok(vec!(1f32, 2f32)));
+        assert_eq!(table.column::<f64>("NAME"), Err(TableError::TypeMismatch { column: 0, actual: DataType::Character }));
+        assert_eq!(table.column::<f64>("TIME"), Err(TableError::MissingColumn("TIME".to_string())));
+        let stats = table.column_stats("counts").unwrap();
+        assert_eq!((stats.count, stats.nulls, stats.min, stats.max, stats.std), (1, 1, 10.5f64, 10.5f64, 0f64));
+        assert!(table.column_stats("NAME").is_err());
+
+        let rows: Vec<(String, f64)> = table.iter_rows()
+            .map(|row| (row.text("NAME").unwrap(), row.get::<f64>("FLUX").unwrap()))
+            .collect();
+
+        assert_eq!(rows, vec!(("AB".to_string(), 1f64), ("C".to_string(), 2f64)));
+        assert_eq!(table.iter_rows().next().map(|row| row.text("FLUX")), Some(Err(TableError::TypeMismatch { column: 2, actual: DataType::Float })));
+    }
+
+    #[test]
+    fn column_stats_should_summarize_a_column_in_one_pass() {
+        let data = include_bytes!("../../assets/images/k2-trappist1-unofficial-tpf-long-cadence.fits");
+        let fits = parse(data).unwrap();
+        let table = BinaryTable::from_hdu(&fits.extensions[0]).unwrap();
+        let values: Vec<f64> = table.column::<f64>("FLUX").unwrap().into_iter().filter(|value| !value.is_nan()).collect();
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        let variance = values.iter().map(|value| (value - mean) * (value - mean)).sum::<f64>() / values.len() as f64;
+
+        let stats = table.column_stats("FLUX").unwrap();
+
+        assert_eq!(stats.count, values.len());
+        assert_eq!(stats.count + stats.nulls, table.column::<f64>("FLUX").unwrap().len());
+        assert_eq!(stats.min, values.iter().cloned().fold(f64::INFINITY, f64::min));
+        assert_eq!(stats.max, values.iter().cloned().fold(f64::NEG_INFINITY, f64::max));
+        assert!((stats.mean - mean).abs() <= 1e-9 * mean.abs());
+        assert!((stats.std - variance.sqrt()).abs() <= 1e-6 * variance.sqrt());
+        assert_eq!(table.column_stats("TIME").map(|stats| stats.count), Ok(table.rows()));
+    }
+
+    #[test]
+    fn columns_should_be_renamed_and_given_units() {
+        let mut hdu = testgen::binary_table(&[("SAP_FLUX", "1E"), ("TIME", "1D")], 3, vec!()).unwrap();
+
+        hdu.header.rename_column("sap_flux", "FLUX").unwrap();
+        hdu.header.set_column_unit("FLUX", "e-/s").unwrap();
+
+        assert_eq!(hdu.header.rename_column("FLUX", "time"), Err(TableError::DuplicateColumn("time".to_string())));
+        assert_eq!(hdu.header.set_column_unit("SAP_FLUX", "e-/s"), Err(TableError::MissingColumn("SAP_FLUX".to_string())));
+        assert_eq!(hdu.header.position_of(&Keyword::TUNITn(1)), hdu.header.position_of(&Keyword::TFORMn(1)).map(|position| position + 1));
+        hdu.header.set_column_unit("flux", "electron/s").unwrap();
+        assert_eq!(hdu.header.remove(&Keyword::TUNITn(1)).len(), 1);
+        hdu.header.set_column_unit("flux", "electron/s").unwrap();
+        let table = BinaryTable::from_hdu(&hdu).unwrap();
+        assert_eq!(table.column_index("FLUX"), Some(0));
+        assert_eq!(table.columns()[0].unit, Some("electron/s".to_string()));
+        assert_eq!(table.column::<f64>("FLUX").unwrap(), vec!(0f64, 1f64, 2f64));
+    }
+}
@@ -0,0 +1,227 @@
+//! The testgen module synthesizes FITS files, e.g. for tests and stress tests.
+//! It is only available with the `testgen` feature.
+//!
+//! Images and binary tables of any size, type and keywords are built in
+//! memory, so that tests do not depend on files that happen to be at hand.
+//! Their values follow a pattern, so that tests can tell what to expect:
+//! element `i` of an image, counted in the order of the file, holds
+//! `value(i)`, and so does element `i` of a numeric column, counted over
+//! all fields of the column.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::error;
+use std::str::FromStr;
+use super::builder::HeaderBuilder;
+use super::table::{ColumnFormat, DataType, ParseColumnFormatError};
+use super::types::{Bitpix, Fits, HDU, KeywordRecord, Keyword, Pixel, Value};
+use super::writer::{write_fits, WriteError};
+
+/// Problems that occur when synthesizing an HDU.
+#[derive(Debug)]
+pub enum GenerateError {
+    /// The format of a column could not be parsed.
+    InvalidFormat {
+        /// The number of the column, counted from 1.
+        column: usize,
+        /// What is wrong with the format.
+        error: ParseColumnFormatError,
+    },
+    /// The header could not be built, e.g. because a record repeats a
+    /// mandatory keyword.
+    Write(WriteError),
+}
+
+impl Display for GenerateError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            GenerateError::InvalidFormat { column, ref error } => write!(f, "the format of column {} is invalid: {:?}", column, error),
+            GenerateError::Write(ref error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl error::Error for GenerateError {}
+
+impl From<WriteError> for GenerateError {
+    fn from(error: WriteError) -> Self {
+        GenerateError::Write(error)
+    }
+}
+
+/// The value of element `index`, i.e. `index % 100`, which every type can hold.
+pub fn value(index: usize) -> i64 {
+    (index % 100) as i64
+}
+
+/// A primary HDU with an image of `bitpix` with axes `axes`, `NAXIS1` first.
+/// The `records` follow the mandatory keywords.
+pub fn image<'a>(bitpix: Bitpix, axes: &[usize], records: Vec<KeywordRecord<'a>>) -> Result<HDU<'a>, GenerateError> {
+    let header = records.into_iter()
+        .fold(HeaderBuilder::primary(bitpix, axes), HeaderBuilder::keyword_record)
+        .build()?;
+    Ok(HDU::with_owned_data(header, image_data(bitpix, axes)))
+}
+
+/// An `IMAGE` extension like the primary HDU of `image`.
+pub fn image_extension<'a>(bitpix: Bitpix, axes: &[usize], records: Vec<KeywordRecord<'a>>) -> Result<HDU<'a>, GenerateError> {
+    let header = records.into_iter()
+        .fold(HeaderBuilder::extension("IMAGE", bitpix, axes, 0, 1), HeaderBuilder::keyword_record)
+        .build()?;
+    Ok(HDU::with_owned_data(header, image_data(bitpix, axes)))
+}
+
+/// A `BINTABLE` extension with `rows` rows and a column per name and format,
+/// e.g. `("FLUX", "1E")`. The `records` follow the keywords that describe
+/// the columns.
+///
+/// Numeric columns hold the pattern of `value`. A character column holds the
+/// number of the row, a logical column alternates `T` and `F`, starting with
+/// `T`. Bits and the descriptors of variable-length arrays are zero.
+pub fn binary_table<'a>(columns: &[(&'a str, &'a str)], rows: usize, records: Vec<KeywordRecord<'a>>) -> Result<HDU<'a>, GenerateError> {
+    let mut formats = vec!();
+    for (index, &(_, tform)) in columns.iter().enumerate() {
+        let format = ColumnFormat::from_str(tform).map_err(|error| GenerateError::InvalidFormat { column: index + 1, error })?;
+        formats.push(format);
+    }
+    let row_width: usize = formats.iter().map(width).sum();
+    let mut builder = HeaderBuilder::extension("BINTABLE", Bitpix::U8, &[row_width, rows], 0, 1)
+        .record(Keyword::TFIELDS, Value::Integer(columns.len() as i64), Option::Some("number of columns"));
+    for (index, &(name, tform)) in columns.iter().enumerate() {
+        let n = (index + 1) as u16;
+        builder = builder
+            .record(Keyword::TTYPEn(n), Value::CharacterString(Cow::Borrowed(name)), Option::None)
+            .record(Keyword::TFORMn(n), Value::CharacterString(Cow::Borrowed(tform)), Option::None);
+    }
+    let header = records.into_iter().fold(builder, HeaderBuilder::keyword_record).build()?;
+
+    let mut bytes = Vec::with_capacity(row_width * rows);
+    for row in 0..rows {
+        for format in &formats {
+            field(format, row, &mut bytes);
+        }
+    }
+    Ok(HDU::with_owned_data(header, bytes))
+}
+
+/// The bytes of a file with `primary` and `extensions`, with `EXTEND` set
+/// when there are extensions.
+pub fn file(primary: HDU, extensions: Vec<HDU>) -> Result<Vec<u8>, WriteError> {
+    let mut fits = Fits::new(primary, vec!());
+    for extension in extensions {
+        fits.push_extension(extension);
+    }
+    let mut bytes = vec!();
+    write_fits(&mut bytes, &fits)?;
+    Ok(bytes)
+}
+
+/// A small file shaped like a target pixel file: a primary HDU without data
+/// that tells its `CHANNEL` and `MODULE`, a `BINTABLE` of 100 rows with a
+/// `TIME` and a 25 element `FLUX` column, and an 11 × 11 `IMAGE` extension.
+pub fn target_pixel_file() -> Vec<u8> {
+    let channel = KeywordRecord::new(Keyword::CHANNEL, Value::Integer(68i64), Option::Some("CCD channel"));
+    let module = KeywordRecord::new(Keyword::MODULE, Value::Integer(19i64), Option::Some("CCD module"));
+    let primary = image(Bitpix::U8, &[], vec!(channel, module)).expect("the primary HDU is valid");
+    let table = binary_table(&[("TIME", "1D"), ("FLUX", "25E")], 100, vec!()).expect("the table is valid");
+    let aperture = image_extension(Bitpix::I32, &[11, 11], vec!()).expect("the aperture is valid");
+    file(primary, vec!(table, aperture)).expect("the file can be written")
+}
+
+fn image_data(bitpix: Bitpix, axes: &[usize]) -> Vec<u8> {
+    let count = if axes.is_empty() { 0 } else { axes.iter().product() };
+    let mut bytes = Vec::with_capacity(count * bitpix.bytes());
+    for index in 0..count {
+        push(bitpix, value(index), &mut bytes);
+    }
+    bytes
+}
+
+// The width of a field of a parsed format, which fits.
+fn width(format: &ColumnFormat) -> usize {
+    format.width().expect("the width of a parsed format fits")
+}
+
+// Append the field of `row` of a column with `format`.
+fn field(format: &ColumnFormat, row: usize, bytes: &mut Vec<u8>) {
+    let width = width(format);
+    if format.descriptor.is_some() {
+        bytes.resize(bytes.len() + width, 0);
+        return
+    }
+    match format.data_type {
+        DataType::Character => {
+            let text = format!("{:<width$.width$}", row, width = format.repeat);
+            bytes.extend_from_slice(text.as_bytes());
+        },
+        DataType::Logical => bytes.resize(bytes.len() + width, if row.is_multiple_of(2) { b'T' } else { b'F' }),
+        DataType::Bit => bytes.resize(bytes.len() + width, 0),
+        DataType::ComplexFloat | DataType::ComplexDouble => {
+            let bitpix = if format.data_type == DataType::ComplexFloat { Bitpix::F32 } else { Bitpix::F64 };
+            for element in 0..format.repeat {
+                push(bitpix, value(row * format.repeat + element), bytes);
+                push(bitpix, 0, bytes);
+            }
+        },
+        data_type => {
+            let bitpix = data_type.bitpix().expect("the other types are numbers");
+            for element in 0..format.repeat {
+                push(bitpix, value(row * format.repeat + element), bytes);
+            }
+        },
+    }
+}
+
+// Append `value` as a value of `bitpix`.
+fn push(bitpix: Bitpix, value: i64, bytes: &mut Vec<u8>) {
+    match bitpix {
+        Bitpix::U8 => (value as u8).extend_big_endian(bytes),
+        Bitpix::I16 => (value as i16).extend_big_endian(bytes),
+        Bitpix::I32 => (value as i32).extend_big_endian(bytes),
+        Bitpix::I64 => value.extend_big_endian(bytes),
+        Bitpix::F32 => (value as f32).extend_big_endian(bytes),
+        Bitpix::F64 => (value as f64).extend_big_endian(bytes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use super::super::parser::parse;
+    use super::super::table::BinaryTable;
+    use super::super::types::{Bitpix, KeywordRecord, Keyword, Value};
+    use super::super::validator::{verify_strict, Severity};
+    use super::{binary_table, file, image, image_extension, value};
+
+    #[test]
+    fn synthesized_files_should_parse_and_pass_verification() {
+        let object = KeywordRecord::new(Keyword::OBJECT, Value::CharacterString(Cow::Borrowed("SYNTHETIC")), Option::None);
+        let bytes = file(image(Bitpix::I16, &[300, 20], vec!(object)).unwrap(), vec!(
+            image_extension(Bitpix::F32, &[7, 3, 2], vec!()).unwrap(),
+            binary_table(&[("TIME", "1D"), ("FLUX", "3E"), ("NAME", "4A"), ("GOOD", "1L"), ("SPECTRUM", "1PJ")], 1000, vec!()).unwrap(),
+        )).unwrap();
+
+        let fits = parse(&bytes).unwrap();
+
+        assert!(verify_strict(&bytes).unwrap().passes(Severity::Warning));
+        assert_eq!(fits.primary_hdu.header.get_str(&Keyword::OBJECT), Ok("SYNTHETIC"));
+        assert_eq!(fits.primary_hdu.data_array().unwrap().values::<i16>().unwrap()[4321], value(4321) as i16);
+        assert_eq!(fits.extensions[0].data_array().unwrap().values::<f32>().unwrap()[41], 41f32);
+        let table = BinaryTable::from_hdu(&fits.extensions[1]).unwrap();
+        assert_eq!(table.rows(), 1000);
+        assert_eq!(table.column::<f64>("FLUX").unwrap()[299], value(299) as f64);
+        let row = table.iter_rows().nth(12).unwrap();
+        assert_eq!(row.get::<f64>("TIME"), Ok(12f64));
+        assert_eq!(row.text("NAME"), Ok("12".to_string()));
+        assert_eq!(table.descriptor(12, 4), Ok((0, 0)));
+    }
+
+    #[test]
+    fn mandatory_keywords_should_not_be_repeated() {
+        let bitpix = KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None);
+
+        assert!(image(Bitpix::U8, &[2], vec!(bitpix)).is_err());
+        assert!(binary_table(&[("TIME", "1Z")], 1, vec!()).is_err());
+    }
+}
@@ -0,0 +1,385 @@
+//! The time module interprets the keywords that describe the time stamps of
+//! an HDU, and converts the times of a table into Julian dates.
+//!
+//! Times in a table are relative to a zero point: `BJDREFI + BJDREFF`, as
+//! written by Kepler, K2 and TESS, or `MJDREF`, `MJDREFI + MJDREFF` of the
+//! FITS standard. `TIMEUNIT` gives their unit, seconds when it is absent.
+//!
+//! `TIMEREF` tells where times are measured. `SOLARSYSTEM` means that they are
+//! barycentric, i.e. corrected for the light travel time to the barycenter of
+//! the solar system. Kepler and K2 tables store the correction in the
+//! `TIMECORR` column; subtracting it from `TIME` gives the time at the
+//! spacecraft again.
+//!
+//! Dates like those of `DATE` and `DATE-OBS` are read into a `DateTime` by
+//! `Header::get_datetime`. With the `chrono` feature they convert into a
+//! `chrono::NaiveDateTime`.
+
+use std::error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use super::table::{BinaryTable, TableError};
+use super::types::{Header, Keyword, ValueRetrievalError};
+
+/// The difference between a Julian date and a modified Julian date.
+pub const MJD_OFFSET: f64 = 2400000.5;
+
+/// How the times of an HDU are defined.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TimeReference {
+    /// The time scale, i.e. `TIMESYS`, e.g. `TDB` or `UTC`.
+    pub system: Option<String>,
+    /// Where times are measured, i.e. `TIMEREF`, e.g. `SOLARSYSTEM` or `LOCAL`.
+    pub position: Option<String>,
+    /// Where times are assigned, i.e. `TASSIGN`, e.g. `SPACECRAFT`.
+    pub assigned: Option<String>,
+    /// The Julian date of time zero, if any of the reference keywords is present.
+    pub zero_point: Option<f64>,
+    /// The unit of times, i.e. `TIMEUNIT`.
+    pub unit: String,
+}
+
+/// Problems that occur when interpreting times.
+#[derive(Debug, PartialEq)]
+pub enum TimeError {
+    /// A time keyword has a value of the wrong type.
+    InvalidValue {
+        /// The keyword.
+        keyword: Keyword,
+        /// What is wrong with the value.
+        error: ValueRetrievalError,
+    },
+    /// The unit of `TIMEUNIT` is not known.
+    UnknownUnit(String),
+    /// There is no zero point to turn relative times into Julian dates.
+    MissingZeroPoint,
+    /// The table does not have a column that is needed.
+    MissingColumn(String),
+    /// The table could not be read.
+    Table(TableError),
+    /// The date is not of the form `YYYY-MM-DD[Thh:mm:ss[.sss]]`, or is not
+    /// a valid date.
+    InvalidDateTime(String),
+}
+
+impl Display for TimeError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            TimeError::InvalidValue { ref keyword, ref error } => write!(f, "{}: {}", keyword, error),
+            TimeError::UnknownUnit(ref unit) => write!(f, "unknown time unit \"{}\"", unit),
+            TimeError::MissingZeroPoint => write!(f, "there is no time reference, e.g. BJDREFI or MJDREF"),
+            TimeError::MissingColumn(ref name) => write!(f, "the column {} is missing", name),
+            TimeError::Table(ref error) => write!(f, "{}", error),
+            TimeError::InvalidDateTime(ref date) => write!(f, "\"{}\" is not a valid date", date),
+        }
+    }
+}
+
+impl error::Error for TimeError {}
+
+impl From<TableError> for TimeError {
+    fn from(error: TableError) -> Self {
+        TimeError::Table(error)
+    }
+}
+
+impl TimeReference {
+    /// Read the time keywords of a header.
+    pub fn from_header(header: &Header) -> Result<TimeReference, TimeError> {
+        let string = |keyword: Keyword| optional(keyword.clone(), header.get_str(&keyword).map(|s| s.trim_end().to_string()));
+        let real = |keyword: Keyword| optional(keyword.clone(), header.get_real(&keyword));
+        let zero_point = match (real(Keyword::BJDREFI)?, real(Keyword::BJDREFF)?) {
+            (None, None) => match (real(Keyword::MJDREF)?, real(Keyword::MJDREFI)?, real(Keyword::MJDREFF)?) {
+                (Some(mjd), _, _) => Some(mjd + MJD_OFFSET),
+                (None, None, None) => None,
+                (None, integer, fraction) => Some(integer.unwrap_or(0f64) + fraction.unwrap_or(0f64) + MJD_OFFSET),
+            },
+            (integer, fraction) => Some(integer.unwrap_or(0f64) + fraction.unwrap_or(0f64)),
+        };
+        Ok(TimeReference {
+            system: string(Keyword::TIMESYS)?,
+            position: string(Keyword::TIMEREF)?,
+            assigned: string(Keyword::TASSIGN)?,
+            zero_point,
+            unit: string(Keyword::TIMEUNIT)?.unwrap_or_else(|| "s".to_string()),
+        })
+    }
+
+    /// Whether times are barycentric, i.e. `TIMEREF = 'SOLARSYSTEM'`.
+    pub fn is_barycentric(&self) -> bool {
+        self.position.as_ref().is_some_and(|position| position.eq_ignore_ascii_case("SOLARSYSTEM"))
+    }
+
+    /// The length of the unit of times, in days.
+    pub fn unit_in_days(&self) -> Result<f64, TimeError> {
+        match self.unit.as_str() {
+            "s" => Ok(1f64 / 86400f64),
+            "min" => Ok(1f64 / 1440f64),
+            "h" => Ok(1f64 / 24f64),
+            "d" => Ok(1f64),
+            "a" | "yr" => Ok(365.25f64),
+            unit => Err(TimeError::UnknownUnit(unit.to_string())),
+        }
+    }
+
+    /// The Julian date of a time relative to the zero point. For barycentric
+    /// times this is a barycentric Julian date.
+    pub fn to_julian_date(&self, time: f64) -> Result<f64, TimeError> {
+        let zero_point = self.zero_point.ok_or(TimeError::MissingZeroPoint)?;
+        Ok(zero_point + time * self.unit_in_days()?)
+    }
+
+    /// The modified Julian date of a time relative to the zero point.
+    pub fn to_modified_julian_date(&self, time: f64) -> Result<f64, TimeError> {
+        Ok(self.to_julian_date(time)? - MJD_OFFSET)
+    }
+
+    /// The `TIME` column of a table as Julian dates.
+    pub fn table_times(&self, table: &BinaryTable) -> Result<Vec<f64>, TimeError> {
+        column(table, "TIME")?.into_iter().map(|time| self.to_julian_date(time)).collect()
+    }
+
+    /// The `TIME` column of a table with the barycentric correction of the
+    /// `TIMECORR` column removed, as Julian dates. Times that are not
+    /// barycentric are returned as they are.
+    pub fn uncorrected_table_times(&self, table: &BinaryTable) -> Result<Vec<f64>, TimeError> {
+        if !self.is_barycentric() {
+            return self.table_times(table)
+        }
+        let corrections = column(table, "TIMECORR")?;
+        let unit = self.unit_in_days()?;
+        column(table, "TIME")?.into_iter().zip(corrections)
+            .map(|(time, correction)| self.to_julian_date(remove_correction(time * unit, correction) / unit))
+            .collect()
+    }
+}
+
+/// A date and time of the form `YYYY-MM-DDThh:mm:ss[.sss]`, as used by
+/// `DATE` and `DATE-OBS`. The time can be absent, i.e. `YYYY-MM-DD`, which
+/// means midnight. The old form `DD/MM/YY`, for 1900 up to 1999, is read too.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct DateTime {
+    /// The year, e.g. 2017.
+    pub year: i32,
+    /// The month, from 1 up to 12.
+    pub month: u32,
+    /// The day of the month, from 1.
+    pub day: u32,
+    /// The hour, from 0 up to 23.
+    pub hour: u32,
+    /// The minute, from 0 up to 59.
+    pub minute: u32,
+    /// The seconds, including the fraction. A leap second makes it 60 or more.
+    pub second: f64,
+}
+
+impl DateTime {
+    /// The modified Julian date of this date and time, in its own time scale.
+    pub fn to_modified_julian_date(&self) -> f64 {
+        // The Julian day number at noon, as given by Fliegel and Van Flandern.
+        let (year, month, day) = (i64::from(self.year), i64::from(self.month), i64::from(self.day));
+        let a = (month - 14) / 12;
+        let day_number = (1461 * (year + 4800 + a)) / 4 + (367 * (month - 2 - 12 * a)) / 12
+            - (3 * ((year + 4900 + a) / 100)) / 4 + day - 32075;
+        let fraction = (f64::from(self.hour) + f64::from(self.minute) / 60f64 + self.second / 3600f64) / 24f64;
+        (day_number - 2400001) as f64 + fraction
+    }
+
+    /// The Julian date of this date and time, in its own time scale.
+    pub fn to_julian_date(&self) -> f64 {
+        self.to_modified_julian_date() + MJD_OFFSET
+    }
+
+    /// Convert into a `chrono::NaiveDateTime`. Fails for dates that chrono
+    /// can not represent.
+    #[cfg(feature = "chrono")]
+    pub fn to_chrono(&self) -> Option<::chrono::NaiveDateTime> {
+        let whole = self.second.floor();
+        let mut nanoseconds = ((self.second - whole) * 1e9f64).round() as u32;
+        let mut second = whole as u32;
+        if second >= 60 {
+            nanoseconds += 1_000_000_000;
+            second = 59;
+        }
+        ::chrono::NaiveDate::from_ymd_opt(self.year, self.month, self.day)?
+            .and_hms_nano_opt(self.hour, self.minute, second, nanoseconds)
+    }
+}
+
+impl FromStr for DateTime {
+    type Err = TimeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let input = s.trim();
+        let invalid = || TimeError::InvalidDateTime(input.to_string());
+        let number = |digits: &str, length: usize| if digits.len() == length && digits.bytes().all(|b| b.is_ascii_digit()) {
+            digits.parse::<u32>().ok()
+        } else {
+            None
+        };
+        let (date, time) = match input.find('T') {
+            Some(index) => (&input[..index], Some(&input[index + 1..])),
+            None => (input, None),
+        };
+        let parts: Vec<&str> = date.split(['-', '/']).collect();
+        let (year, month, day) = match parts.as_slice() {
+            [year, month, day] if date.contains('-') =>
+                (number(year, 4).ok_or_else(invalid)? as i32, number(month, 2), number(day, 2)),
+            [day, month, year] if date.contains('/') && time.is_none() =>
+                (1900 + number(year, 2).ok_or_else(invalid)? as i32, number(month, 2), number(day, 2)),
+            _ => return Err(invalid()),
+        };
+        let (month, day) = (month.ok_or_else(invalid)?, day.ok_or_else(invalid)?);
+        let (hour, minute, second) = match time {
+            Some(time) => match time.split(':').collect::<Vec<&str>>().as_slice() {
+                [hour, minute, second] => {
+                    let whole = second.split('.').next().unwrap_or("");
+                    if number(whole, 2).is_none() || second[2..].contains(|c: char| c != '.' && !c.is_ascii_digit()) {
+                        return Err(invalid())
+                    }
+                    (number(hour, 2).ok_or_else(invalid)?, number(minute, 2).ok_or_else(invalid)?, second.parse::<f64>().map_err(|_| invalid())?)
+                },
+                _ => return Err(invalid()),
+            },
+            None => (0, 0, 0f64),
+        };
+        if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) || hour > 23 || minute > 59 || second >= 61f64 {
+            return Err(invalid())
+        }
+        Ok(DateTime { year, month, day, hour, minute, second })
+    }
+}
+
+impl Display for DateTime {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}T{:02}:{:02}:", self.year, self.month, self.day, self.hour, self.minute)?;
+        if self.second < 10f64 {
+            write!(f, "0")?;
+        }
+        write!(f, "{}", self.second)
+    }
+}
+
+impl<'a> Header<'a> {
+    /// The value of a date keyword, e.g. `DATE` or `DATE-OBS`, as a `DateTime`.
+    pub fn get_datetime(&self, keyword: &Keyword) -> Result<DateTime, TimeError> {
+        let value = self.get_str(keyword).map_err(|error| TimeError::InvalidValue { keyword: keyword.clone(), error })?;
+        DateTime::from_str(value)
+    }
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Remove a barycentric correction from a time, both in days.
+pub fn remove_correction(time: f64, correction: f64) -> f64 {
+    time - correction
+}
+
+/// Apply a barycentric correction to a time, both in days.
+pub fn apply_correction(time: f64, correction: f64) -> f64 {
+    time + correction
+}
+
+fn column(table: &BinaryTable, name: &str) -> Result<Vec<f64>, TimeError> {
+    let index = table.column_index(name).ok_or_else(|| TimeError::MissingColumn(name.to_string()))?;
+    Ok(table.scalars(index)?)
+}
+
+fn optional<T>(keyword: Keyword, result: Result<T, ValueRetrievalError>) -> Result<Option<T>, TimeError> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(ValueRetrievalError::KeywordNotPresent) => Ok(None),
+        Err(error) => Err(TimeError::InvalidValue { keyword, error }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use super::super::parser::parse;
+    use super::super::table::BinaryTable;
+    use super::super::types::{Header, KeywordRecord, Keyword, Value};
+    use std::str::FromStr;
+    use super::{DateTime, TimeError, TimeReference, MJD_OFFSET};
+
+    #[test]
+    fn mjdref_should_be_the_zero_point_without_bjdref() {
+        let header = Header::new(vec!(
+            KeywordRecord::new(Keyword::MJDREFI, Value::Integer(57000i64), Option::None),
+            KeywordRecord::new(Keyword::MJDREFF, Value::Real(0.25f64), Option::None),
+            KeywordRecord::new(Keyword::TIMEREF, Value::CharacterString(Cow::Borrowed("LOCAL")), Option::None),
+        ));
+
+        let reference = TimeReference::from_header(&header).unwrap();
+
+        assert!(!reference.is_barycentric());
+        assert_eq!(reference.zero_point, Some(57000.25f64 + MJD_OFFSET));
+        assert_eq!(reference.to_modified_julian_date(43200f64), Ok(57000.75f64));
+        assert_eq!(TimeReference::from_header(&Header::new(vec!())).unwrap().to_julian_date(1f64), Err(TimeError::MissingZeroPoint));
+    }
+
+    #[test]
+    fn k2_times_should_be_barycentric_julian_dates() {
+        let data = include_bytes!("../../assets/images/k2-trappist1-unofficial-tpf-long-cadence.fits");
+        let fits = parse(data).unwrap();
+        let hdu = fits.hdu_by_name("TARGETTABLES").unwrap();
+        let table = BinaryTable::from_hdu(hdu).unwrap();
+
+        let reference = TimeReference::from_header(&hdu.header).unwrap();
+        let times = reference.table_times(&table).unwrap();
+        let uncorrected = reference.uncorrected_table_times(&table).unwrap();
+
+        assert!(reference.is_barycentric());
+        assert_eq!(reference.system, Some("TDB".to_string()));
+        assert_eq!(reference.zero_point, Some(2454833f64));
+        let start = hdu.header.get_real(&Keyword::Custom("TSTART".to_string())).unwrap() + 2454833f64;
+        let first = times.iter().cloned().find(|time| !time.is_nan()).unwrap();
+        assert!(first >= start && first - start < 0.1f64, "{} is not close to {}", first, start);
+        let corrections = table.scalars(table.column_index("TIMECORR").unwrap()).unwrap();
+        let index = times.iter().position(|time| !time.is_nan()).unwrap();
+        assert!((times[index] - uncorrected[index] - corrections[index]).abs() < 1e-6);
+    }
+
+    #[test]
+    fn dates_should_be_parsed_from_header_values() {
+        let header = Header::new(vec!(
+            KeywordRecord::new(Keyword::DATE, Value::CharacterString(Cow::Borrowed("2017-03-12T14:05:09.25")), Option::None),
+            KeywordRecord::new(Keyword::Custom("DATE-OBS".to_string()), Value::CharacterString(Cow::Borrowed("1858-11-17")), Option::None),
+            KeywordRecord::new(Keyword::Custom("DATE-END".to_string()), Value::CharacterString(Cow::Borrowed("2017-02-29")), Option::None),
+        ));
+
+        let date = header.get_datetime(&Keyword::DATE).unwrap();
+
+        assert_eq!(date, DateTime { year: 2017, month: 3, day: 12, hour: 14, minute: 5, second: 9.25f64 });
+        assert_eq!(date.to_string(), "2017-03-12T14:05:09.25");
+        assert_eq!(header.get_datetime(&Keyword::Custom("DATE-OBS".to_string())).map(|date| date.to_modified_julian_date()), Ok(0f64));
+        assert_eq!(header.get_datetime(&Keyword::Custom("DATE-END".to_string())), Err(TimeError::InvalidDateTime("2017-02-29".to_string())));
+        assert_eq!(DateTime::from_str("2000-01-01T12:00:00").map(|date| date.to_julian_date()), Ok(2451545f64));
+        assert_eq!(DateTime::from_str("12/03/94").map(|date| (date.year, date.month, date.day)), Ok((1994, 3, 12)));
+        assert_eq!(DateTime::from_str("2016-12-31T23:59:60.5").map(|date| date.second), Ok(60.5f64));
+        assert!(DateTime::from_str("2017-3-12").is_err());
+        assert!(DateTime::from_str("2017-03-12T14:05").is_err());
+        assert!(DateTime::from_str("2017-03-12T14:05:+9").is_err());
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn dates_should_convert_into_chrono() {
+        use chrono::{NaiveDate, Timelike};
+
+        let date = DateTime::from_str("2017-03-12T14:05:09.25").unwrap().to_chrono().unwrap();
+        let leap = DateTime::from_str("2016-12-31T23:59:60").unwrap().to_chrono().unwrap();
+
+        assert_eq!(date, NaiveDate::from_ymd_opt(2017, 3, 12).unwrap().and_hms_milli_opt(14, 5, 9, 250).unwrap());
+        assert_eq!(leap.second(), 59);
+        assert_eq!(leap.nanosecond(), 1_000_000_000);
+    }
+}
@@ -1,9 +1,41 @@
 //! The types modules describes all the structures to express FITS files.
+//!
+//! The structures borrow from the bytes they are parsed from, e.g. a memory
+//! map or a network buffer, so parsing does not copy strings or data units.
+//! `FitsView` names such a borrowed `Fits`, `OwnedFits` one that owns all its
+//! data. `into_owned` turns a view into an owned structure, and `view`
+//! borrows a view from any structure, without copying.
 
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::error;
 use std::str::FromStr;
 use std::fmt::{Display, Formatter, Error};
+use super::pretty;
+use super::pretty::PrettyOptions;
+
+/// A `Fits` that borrows its strings and data units from a buffer of lifetime
+/// `'a`, as returned by the parser.
+pub type FitsView<'a> = Fits<'a>;
+
+/// A `Fits` that owns all its data, e.g. to keep it after its buffer is gone.
+pub type OwnedFits = Fits<'static>;
 
 /// Representation of a FITS file.
+///
+/// ```
+/// use fits_rs::parser::parse;
+/// use fits_rs::types::Keyword;
+///
+/// let data = std::fs::read("assets/images/k2-trappist1-unofficial-tpf-long-cadence.fits")?;
+/// let fits = parse(&data)?;
+///
+/// assert_eq!(fits.iter_hdus().count(), 3);
+/// assert_eq!(fits.primary_hdu.header.get_trimmed_str(&Keyword::TELESCOP), Ok("Kepler"));
+/// let table = fits.hdu_by_name("TARGETTABLES").expect("the file has a table");
+/// assert_eq!(table.header.get_int(&Keyword::NAXISn(2)), Ok(3599));
+/// # Ok::<(), fits_rs::error::Error>(())
+/// ```
 #[derive(Debug, PartialEq)]
 pub struct Fits<'a> {
     /// The primary HDU
@@ -12,150 +44,1554 @@ pub struct Fits<'a> {
     pub extensions: Vec<HDU<'a>>,
 }
 
-impl<'a> Fits<'a> {
-    /// Create a Fits structure with a given primary header
-    pub fn new(primary_hdu: HDU<'a>, extensions: Vec<HDU<'a>>) -> Fits<'a> {
-        Fits {
-            primary_hdu: primary_hdu,
-            extensions: extensions,
+impl<'a> Fits<'a> {
+    /// Create a Fits structure with a given primary header
+    pub fn new(primary_hdu: HDU<'a>, extensions: Vec<HDU<'a>>) -> Fits<'a> {
+        Fits {
+            primary_hdu,
+            extensions,
+        }
+    }
+
+    /// Whether the file conforms to the FITS standard, i.e. `SIMPLE` is not
+    /// `F`. Only `parse_lenient` parses files that do not conform.
+    pub fn conforms(&self) -> bool {
+        self.primary_hdu.header.get_logical(&Keyword::SIMPLE) != Ok(false)
+    }
+
+    /// All HDUs, the primary HDU first, followed by the extensions.
+    pub fn iter_hdus(&self) -> impl Iterator<Item = &HDU<'a>> {
+        Some(&self.primary_hdu)
+            .into_iter()
+            .chain(self.extensions.iter())
+    }
+
+    /// The HDU with this index, 0 being the primary HDU.
+    pub fn hdu(&self, index: usize) -> Option<&HDU<'a>> {
+        if index == 0 {
+            Some(&self.primary_hdu)
+        } else {
+            self.extensions.get(index - 1)
+        }
+    }
+
+    /// The first HDU with this `EXTNAME`, whatever its `EXTVER`. Names are
+    /// compared case sensitively, as the standard prescribes, see
+    /// `hdu_by_name_matching` to relax that.
+    pub fn hdu_by_name(&self, name: &str) -> Option<&HDU<'a>> {
+        self.hdu_by_name_matching(name, NameMatching::CaseSensitive)
+    }
+
+    /// The first HDU with this `EXTNAME`, compared as `matching` tells.
+    /// Archives disagree about the case of names, e.g. `EVENTS` and `events`.
+    pub fn hdu_by_name_matching(&self, name: &str, matching: NameMatching) -> Option<&HDU<'a>> {
+        self.iter_hdus().find(|hdu| hdu.has_name(name, matching))
+    }
+
+    /// The HDU with this `EXTNAME` and `EXTVER`. An HDU without `EXTVER` has
+    /// version 1.
+    pub fn hdu_by_name_ver(&self, name: &str, version: i64) -> Option<&HDU<'a>> {
+        self.iter_hdus().find(|hdu| hdu.has_name(name, NameMatching::CaseSensitive) && hdu.version() == version)
+    }
+
+    /// The first HDU that has a data unit. Many files have an empty primary
+    /// HDU, with the actual data in the first extension.
+    pub fn first_data_hdu(&self) -> Option<&HDU<'a>> {
+        self.iter_hdus().find(|hdu| !hdu.data().bytes().is_empty())
+    }
+
+    /// Add an extension after the others, keeping `EXTEND` and `NEXTEND`
+    /// consistent as `update_extend` does. `push_extension_raw` leaves the
+    /// primary header as it is.
+    pub fn push_extension(&mut self, hdu: HDU<'a>) {
+        self.push_extension_raw(hdu);
+        self.update_extend();
+    }
+
+    /// Add an extension after the others, without touching the primary header.
+    pub fn push_extension_raw(&mut self, hdu: HDU<'a>) {
+        self.extensions.push(hdu);
+    }
+
+    /// Insert an extension at `index`, 0 being the first extension. An index
+    /// past the last extension is refused. Like `push_extension`, `EXTEND`
+    /// and `NEXTEND` are kept consistent.
+    pub fn insert_extension(&mut self, index: usize, hdu: HDU<'a>) -> Result<(), ExtensionError> {
+        self.insert_extension_raw(index, hdu)?;
+        self.update_extend();
+        Ok(())
+    }
+
+    /// Insert an extension at `index` like `insert_extension`, without
+    /// touching the primary header.
+    pub fn insert_extension_raw(&mut self, index: usize, hdu: HDU<'a>) -> Result<(), ExtensionError> {
+        self.check_insertion(index)?;
+        self.extensions.insert(index, hdu);
+        Ok(())
+    }
+
+    /// Insert an extension at `index` like `insert_extension`, resolving a
+    /// collision of its `EXTNAME` and `EXTVER` with those of another
+    /// extension as `policy` tells. A missing `EXTVER` is added after
+    /// `EXTNAME`. The `CHECKSUM` of a header whose `EXTVER` changes no longer
+    /// holds and is removed, see `checksum::compute_checksum`.
+    pub fn insert_extension_versioned(&mut self, index: usize, mut hdu: HDU<'a>, policy: VersionPolicy) -> Result<(), ExtensionError> {
+        self.check_insertion(index)?;
+        if let Some(name) = hdu.name().map(str::to_string) {
+            let version = hdu.version();
+            let collides = self.extensions.iter().any(|other| other.has_name(&name, NameMatching::CaseSensitive) && other.version() == version);
+            match policy {
+                VersionPolicy::Next if collides => {
+                    let highest = self.extensions.iter()
+                        .filter(|other| other.has_name(&name, NameMatching::CaseSensitive))
+                        .map(HDU::version)
+                        .max()
+                        .unwrap_or(version);
+                    hdu.set_version(highest + 1);
+                },
+                VersionPolicy::Shift if collides => {
+                    for other in self.extensions.iter_mut() {
+                        let own = other.version();
+                        if other.has_name(&name, NameMatching::CaseSensitive) && own >= version {
+                            other.set_version(own + 1);
+                        }
+                    }
+                },
+                _ => (),
+            }
+        }
+        self.insert_extension(index, hdu)
+    }
+
+    // Whether an extension can be inserted at `index`.
+    fn check_insertion(&self, index: usize) -> Result<(), ExtensionError> {
+        if index > self.extensions.len() {
+            return Err(ExtensionError::OutOfRange { index, count: self.extensions.len() })
+        }
+        Ok(())
+    }
+
+    /// Remove the extension at `index`, if present. Like `push_extension`,
+    /// `EXTEND` and `NEXTEND` are kept consistent.
+    pub fn remove_extension(&mut self, index: usize) -> Option<HDU<'a>> {
+        let hdu = self.remove_extension_raw(index)?;
+        self.update_extend();
+        Some(hdu)
+    }
+
+    /// Remove the extension at `index` like `remove_extension`, without
+    /// touching the primary header.
+    pub fn remove_extension_raw(&mut self, index: usize) -> Option<HDU<'a>> {
+        if index >= self.extensions.len() {
+            return None
+        }
+        Some(self.extensions.remove(index))
+    }
+
+    /// Remove the first extension with this `EXTNAME`, compared like
+    /// `hdu_by_name` does, if present. Like `push_extension`, `EXTEND` and
+    /// `NEXTEND` are kept consistent.
+    pub fn remove_extension_by_name(&mut self, name: &str) -> Option<HDU<'a>> {
+        let index = self.extensions.iter().position(|hdu| hdu.has_name(name, NameMatching::CaseSensitive))?;
+        self.remove_extension(index)
+    }
+
+    /// Make the primary header agree with the extensions. `EXTEND = T` is
+    /// added after the `NAXISn` keywords when there are extensions, and an
+    /// existing `NEXTEND` is set to the number of extensions. When that
+    /// changes the header, its `CHECKSUM` no longer holds and is removed.
+    pub fn update_extend(&mut self) {
+        let count = self.extensions.len();
+        let header = &mut self.primary_hdu.header;
+        let original = header.clone();
+        if count > 0 && header.get_logical(&Keyword::EXTEND) != Ok(true) {
+            if header.get(&Keyword::EXTEND).is_some() {
+                header.set(Keyword::EXTEND, Value::Logical(true), Option::None);
+            } else {
+                let after = match header.get_int(&Keyword::NAXIS) {
+                    Ok(naxis) if naxis > 0 => Keyword::NAXISn(naxis as u16),
+                    _ => Keyword::NAXIS,
+                };
+                let record = KeywordRecord::new(Keyword::EXTEND, Value::Logical(true), Option::Some("extensions may be present"));
+                if header.insert_after(&after, record).is_err() {
+                    header.set(Keyword::EXTEND, Value::Logical(true), Option::Some("extensions may be present"));
+                }
+            }
+        }
+        if header.get(&Keyword::NEXTEND).is_some() {
+            header.set(Keyword::NEXTEND, Value::Integer(count as i64), Option::None);
+        }
+        if *header != original {
+            header.remove(&Keyword::CHECKSUM);
+        }
+    }
+
+    /// The header of the HDU with this index as it should be queried. An
+    /// extension with `INHERIT = T` inherits the records of the primary header
+    /// whose keyword it does not have itself, except the mandatory keywords,
+    /// `EXTEND`, `NEXTEND`, checksums and commentary. Other headers are
+    /// returned as they are.
+    pub fn effective_header(&self, index: usize) -> Option<Header<'a>> {
+        let hdu = self.hdu(index)?;
+        let mut header = hdu.header.clone();
+        if index > 0 && hdu.header.get_logical(&Keyword::INHERIT) == Ok(true) {
+            let inherited = self.primary_hdu.header.keyword_records.iter()
+                .filter(|record| is_inherited(record.keyword()) && hdu.header.get(record.keyword()).is_none())
+                .cloned();
+            header.keyword_records.extend(inherited);
+        }
+        Some(header)
+    }
+
+    /// Convert into a `Fits` that owns all its data, so that it no longer
+    /// borrows from the bytes it was parsed from.
+    pub fn into_owned(self) -> OwnedFits {
+        Fits::new(
+            self.primary_hdu.into_owned(),
+            self.extensions.into_iter().map(HDU::into_owned).collect())
+    }
+
+    /// A view of this `Fits` that borrows all its data from it.
+    pub fn view(&self) -> FitsView<'_> {
+        Fits::new(
+            self.primary_hdu.view(),
+            self.extensions.iter().map(HDU::view).collect())
+    }
+}
+
+// Whether an extension with `INHERIT = T` inherits this keyword from the primary header.
+fn is_inherited(keyword: &Keyword) -> bool {
+    !matches!(*keyword,
+        Keyword::SIMPLE | Keyword::BITPIX | Keyword::NAXIS | Keyword::NAXISn(_) | Keyword::EXTEND | Keyword::NEXTEND |
+        Keyword::GROUPS | Keyword::PCOUNT | Keyword::GCOUNT | Keyword::CHECKSUM | Keyword::DATASUM |
+        Keyword::COMMENT | Keyword::HISTORY | Keyword::BlankField | Keyword::INHERIT | Keyword::END)
+}
+
+/// Header Data Unit, combination of a header and a data unit.
+#[derive(Debug, PartialEq)]
+pub struct HDU<'a> {
+    /// The header of this HDU.
+    pub header: Header<'a>,
+    /// The data unit of this HDU.
+    data: HduData<'a>,
+}
+
+impl<'a> HDU<'a> {
+    /// Create an HDU with a header and an empty data unit.
+    pub fn new(header: Header<'a>) -> HDU<'a> {
+        HDU::with_data(header, &[])
+    }
+
+    /// Create an HDU with a header and the data unit that follows it.
+    ///
+    /// Only the bytes described by `BITPIX` and `NAXISn` are used, padding at
+    /// the end of the data unit is ignored. When `data` is shorter than the
+    /// header describes, the data unit is empty, as it is for `new`.
+    pub fn with_data(header: Header<'a>, data: &'a [u8]) -> HDU<'a> {
+        HDU::with_cow_data(header, Cow::Borrowed(data))
+    }
+
+    /// Create an HDU with a header and a data unit that it owns, e.g. one that
+    /// is computed instead of read. Like `with_data`, padding is ignored and
+    /// data that is too short leaves the data unit empty.
+    pub fn with_owned_data(header: Header<'a>, data: Vec<u8>) -> HDU<'a> {
+        HDU::with_cow_data(header, Cow::Owned(data))
+    }
+
+    fn with_cow_data(header: Header<'a>, data: Cow<'a, [u8]>) -> HDU<'a> {
+        let size = header.unpadded_data_unit_size();
+        let data = match data {
+            _ if size > data.len() => Cow::Borrowed(&[][..]),
+            Cow::Borrowed(data) => Cow::Borrowed(&data[0..size]),
+            Cow::Owned(mut data) => { data.truncate(size); Cow::Owned(data) }
+        };
+        let data = HduData::new(&header, data);
+        HDU { header, data }
+    }
+
+    /// The value of `EXTNAME` without its insignificant trailing spaces, if present.
+    pub fn name(&self) -> Option<&str> {
+        self.header.get_trimmed_str(&Keyword::EXTNAME).ok()
+    }
+
+    /// The value of `EXTVER`, 1 when it is not present.
+    pub fn version(&self) -> i64 {
+        self.header.get_int(&Keyword::EXTVER).unwrap_or(1i64)
+    }
+
+    // Set `EXTVER`, adding it after `EXTNAME` when it is not present, and
+    // remove the `CHECKSUM` that no longer holds.
+    fn set_version(&mut self, version: i64) {
+        self.header.remove(&Keyword::CHECKSUM);
+        if self.header.get(&Keyword::EXTVER).is_none() {
+            let record = KeywordRecord::new(Keyword::EXTVER, Value::Integer(version), Option::None);
+            if self.header.insert_after(&Keyword::EXTNAME, record).is_ok() {
+                return
+            }
+        }
+        self.header.set(Keyword::EXTVER, Value::Integer(version), Option::None);
+    }
+
+    // Trailing spaces of names are not significant.
+    fn has_name(&self, name: &str, matching: NameMatching) -> bool {
+        self.name().is_some_and(|own| match matching {
+            NameMatching::CaseSensitive => own == name.trim_end(),
+            NameMatching::CaseInsensitive => own.eq_ignore_ascii_case(name.trim_end()),
+        })
+    }
+
+    /// The data unit of this HDU, typed by `SIMPLE` or `XTENSION`.
+    pub fn data(&self) -> &HduData<'a> {
+        &self.data
+    }
+
+    /// The data array of this HDU, if it is an image that is not empty.
+    pub fn data_array(&self) -> Option<&DataArray<'a>> {
+        match self.data {
+            HduData::Image(ref data_array) if !data_array.bytes().is_empty() => Some(data_array),
+            _ => None,
+        }
+    }
+
+    /// Convert into an `HDU` that owns its header and data unit.
+    pub fn into_owned(self) -> HDU<'static> {
+        HDU {
+            header: self.header.into_owned(),
+            data: self.data.into_owned(),
+        }
+    }
+
+    /// A view of this `HDU` that borrows its header and data unit from it.
+    pub fn view(&self) -> HDU<'_> {
+        HDU {
+            header: self.header.view(),
+            data: self.data.view(),
+        }
+    }
+}
+
+/// How the `EXTNAME` of an HDU is compared with a name.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum NameMatching {
+    /// Case sensitively, as the standard prescribes.
+    CaseSensitive,
+    /// Case insensitively, like CFITSIO does.
+    CaseInsensitive,
+}
+
+/// What to do when an inserted extension has the `EXTNAME` and `EXTVER` of
+/// an extension that is already present.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum VersionPolicy {
+    /// Insert the extension as it is, leaving both with the same version.
+    Keep,
+    /// Give the inserted extension the highest version of its name plus one.
+    Next,
+    /// Keep the version of the inserted extension, and add one to the
+    /// versions of the extensions of that name with this version or higher.
+    Shift,
+}
+
+/// The kind of an HDU, as announced by `SIMPLE` or `XTENSION`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum HduKind {
+    /// A primary HDU without random groups, or an `IMAGE` extension.
+    Image,
+    /// A `BINTABLE` extension.
+    BinTable,
+    /// A `TABLE` extension.
+    AsciiTable,
+    /// A primary HDU with random groups, or any other extension.
+    Unknown,
+}
+
+impl HduKind {
+    /// The byte that pads a data unit of this kind to a multiple of
+    /// `BLOCK_SIZE`: a blank for ASCII tables, zero for everything else.
+    pub fn fill(self) -> u8 {
+        match self {
+            HduKind::AsciiTable => b' ',
+            _ => 0u8,
+        }
+    }
+}
+
+/// The data unit of an HDU, i.e. its bytes without padding, interpreted
+/// according to the kind of HDU.
+#[derive(Debug, PartialEq)]
+pub enum HduData<'a> {
+    /// The image of the primary HDU or of an `IMAGE` extension.
+    Image(DataArray<'a>),
+    /// The rows and heap of a `BINTABLE` extension.
+    BinTable(Cow<'a, [u8]>),
+    /// The rows of a `TABLE` extension.
+    AsciiTable(Cow<'a, [u8]>),
+    /// The data of random groups, of any other extension, or of an image with
+    /// an invalid `BITPIX`.
+    Unknown(Cow<'a, [u8]>),
+}
+
+impl<'a> HduData<'a> {
+    fn new(header: &Header<'a>, data: Cow<'a, [u8]>) -> HduData<'a> {
+        match header.kind() {
+            HduKind::Image => match header.bitpix() {
+                Ok(bitpix) => HduData::Image(DataArray {
+                    bitpix,
+                    axes: header.axes().unwrap_or_default(),
+                    data,
+                    scaling: header.scaling().unwrap_or_default(),
+                }),
+                Err(_) => HduData::Unknown(data),
+            },
+            HduKind::BinTable => HduData::BinTable(data),
+            HduKind::AsciiTable => HduData::AsciiTable(data),
+            HduKind::Unknown => HduData::Unknown(data),
+        }
+    }
+
+    /// The raw bytes of the data unit, without padding.
+    pub fn bytes(&self) -> &[u8] {
+        match *self {
+            HduData::Image(ref data_array) => data_array.bytes(),
+            HduData::BinTable(ref data) | HduData::AsciiTable(ref data) | HduData::Unknown(ref data) => data,
+        }
+    }
+
+    /// Convert into an `HduData` that owns its bytes.
+    pub fn into_owned(self) -> HduData<'static> {
+        match self {
+            HduData::Image(data_array) => HduData::Image(data_array.into_owned()),
+            HduData::BinTable(data) => HduData::BinTable(Cow::Owned(data.into_owned())),
+            HduData::AsciiTable(data) => HduData::AsciiTable(Cow::Owned(data.into_owned())),
+            HduData::Unknown(data) => HduData::Unknown(Cow::Owned(data.into_owned())),
+        }
+    }
+
+    /// A view of this `HduData` that borrows its bytes from it.
+    pub fn view(&self) -> HduData<'_> {
+        match *self {
+            HduData::Image(ref data_array) => HduData::Image(data_array.view()),
+            HduData::BinTable(ref data) => HduData::BinTable(Cow::Borrowed(data)),
+            HduData::AsciiTable(ref data) => HduData::AsciiTable(Cow::Borrowed(data)),
+            HduData::Unknown(ref data) => HduData::Unknown(Cow::Borrowed(data)),
+        }
+    }
+}
+
+/// The primary header of a FITS file.
+///
+/// ```
+/// use std::borrow::Cow;
+/// use fits_rs::types::{Header, KeywordRecord, Keyword, Value, ValueRetrievalError};
+///
+/// let mut header = Header::new(vec!(
+///     KeywordRecord::new(Keyword::OBJECT, Value::CharacterString(Cow::Borrowed("TRAPPIST-1  ")), Option::None),
+///     KeywordRecord::new(Keyword::EXPOSURE, Value::Integer(30), Option::Some("seconds")),
+/// ));
+///
+/// header.set(Keyword::EXPOSURE, Value::Real(12.5), Option::None);
+/// header.add_history("exposure corrected");
+///
+/// assert_eq!(header.get_trimmed_str(&Keyword::OBJECT), Ok("TRAPPIST-1"));
+/// assert_eq!(header.get_real(&Keyword::EXPOSURE), Ok(12.5));
+/// assert_eq!(header.get_int(&Keyword::EXPOSURE), Err(ValueRetrievalError::NotAnInteger));
+/// assert_eq!(header.history(), vec!("exposure corrected"));
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+pub struct Header<'a> {
+    /// The keyword records of the primary header.
+    pub keyword_records: Vec<KeywordRecord<'a>>,
+    warnings: Vec<HeaderWarning>,
+}
+
+impl<'a> Header<'a> {
+    /// Create a Header with a given set of keyword_records
+    pub fn new(keyword_records: Vec<KeywordRecord<'a>>) -> Header<'a> {
+        Header { keyword_records, warnings: vec!() }
+    }
+
+    /// Attach the problems that were worked around while parsing this header.
+    pub fn with_warnings(self, warnings: Vec<HeaderWarning>) -> Header<'a> {
+        Header { warnings, ..self }
+    }
+
+    /// The problems that were worked around while parsing this header, e.g.
+    /// by `parser::parse_lenient`. A header that is built has none.
+    pub fn warnings(&self) -> &[HeaderWarning] {
+        &self.warnings
+    }
+
+    /// Convert into a `Header` that owns all its keyword records.
+    pub fn into_owned(self) -> Header<'static> {
+        Header::new(self.keyword_records.into_iter().map(KeywordRecord::into_owned).collect())
+            .with_warnings(self.warnings)
+    }
+
+    /// A view of this `Header` that borrows its strings from it.
+    pub fn view(&self) -> Header<'_> {
+        Header::new(self.keyword_records.iter().map(KeywordRecord::view).collect())
+            .with_warnings(self.warnings.clone())
+    }
+
+    /// Determines the size in bits of the data array following this header,
+    /// including padding. A size that does not fit in a `usize` saturates at
+    /// `usize::MAX`, use `data_unit_length` to detect it.
+    pub fn data_array_size(&self) -> usize {
+        self.checked_data_array_size()
+            .and_then(|size| size.div_ceil(BLOCK_SIZE as u64 * 8).checked_mul(BLOCK_SIZE as u64 * 8))
+            .and_then(|size| usize::try_from(size).ok())
+            .unwrap_or(usize::MAX)
+    }
+
+    /// The number of blocks of the data unit following this header, or `None`
+    /// when its size does not fit in a `u64`.
+    pub fn data_block_count(&self) -> Option<u64> {
+        self.checked_data_array_size()
+            .map(|size| size.div_ceil(8).div_ceil(BLOCK_SIZE as u64))
+    }
+
+    /// The length in bytes of the data unit following this header, including
+    /// padding, or `None` when it does not fit in a `u64`. Use it for offsets
+    /// in files, which can exceed the address space of 32-bit targets.
+    pub fn data_unit_length(&self) -> Option<u64> {
+        self.data_block_count()?.checked_mul(BLOCK_SIZE as u64)
+    }
+
+    /// The length in bytes of the data unit following this header, including
+    /// padding, or `None` when it does not fit in a `usize`, i.e. when the data
+    /// unit can not be held in memory.
+    pub fn data_unit_size(&self) -> Option<usize> {
+        usize::try_from(self.data_unit_length()?).ok()
+    }
+
+    fn unpadded_data_unit_size(&self) -> usize {
+        self.checked_data_array_size()
+            .and_then(|size| usize::try_from(size / 8).ok())
+            .unwrap_or(usize::MAX)
+    }
+
+    // Sizes are computed in bits as a `u64`, so that they do not overflow on
+    // 32-bit targets for data units larger than 512 MiB.
+    fn checked_data_array_size(&self) -> Option<u64> {
+        if self.is_primary() {
+            self.primary_data_array_size()
+        } else {
+            self.extention_data_array_size()
+        }
+    }
+
+    fn is_primary(&self) -> bool {
+        self.has_keyword_record(&Keyword::SIMPLE)
+    }
+
+    fn has_keyword_record(&self, keyword: &Keyword) -> bool {
+        for keyword_record in &self.keyword_records {
+            if *keyword == keyword_record.keyword {
+                return true
+            }
+        }
+        false
+    }
+
+    /// The kind of HDU this header belongs to. The legacy extension names
+    /// `IUEIMAGE` and `A3DTABLE`, found in archival data, are the images and
+    /// binary tables that were later standardized as `IMAGE` and `BINTABLE`.
+    pub fn kind(&self) -> HduKind {
+        match self.get_trimmed_str(&Keyword::XTENSION) {
+            Err(_) if self.is_random_groups() => HduKind::Unknown,
+            Err(_) | Ok("IMAGE") | Ok("IUEIMAGE") => HduKind::Image,
+            Ok("BINTABLE") | Ok("A3DTABLE") => HduKind::BinTable,
+            Ok("TABLE") => HduKind::AsciiTable,
+            Ok(_) => HduKind::Unknown,
+        }
+    }
+
+    /// Whether the primary HDU uses the random groups convention, i.e.
+    /// `GROUPS = T` and `NAXIS1 = 0`.
+    pub fn is_random_groups(&self) -> bool {
+        self.is_primary()
+            && self.get_logical(&Keyword::GROUPS).unwrap_or(false)
+            && self.get_int(&Keyword::NAXISn(1)) == Ok(0i64)
+    }
+
+    fn primary_data_array_size(&self) -> Option<u64> {
+        if self.is_random_groups() {
+            self.random_groups_data_array_size()
+        } else {
+            self.bits_per_value().checked_mul(self.naxis_product()?)
+        }
+    }
+
+    fn extention_data_array_size(&self) -> Option<u64> {
+        self.grouped_data_array_size(self.naxis_product()?)
+    }
+
+    fn random_groups_data_array_size(&self) -> Option<u64> {
+        let axes = self.axes().ok()?;
+        let group_size = axes.iter().skip(1).try_fold(1u64, |product, axis| product.checked_mul(*axis as u64))?;
+        self.grouped_data_array_size(group_size)
+    }
+
+    fn grouped_data_array_size(&self, group_size: u64) -> Option<u64> {
+        let gcount = u64::try_from(self.get_int(&Keyword::GCOUNT).unwrap_or(1i64)).ok()?;
+        let pcount = u64::try_from(self.get_int(&Keyword::PCOUNT).unwrap_or(0i64)).ok()?;
+        self.bits_per_value()
+            .checked_mul(gcount)?
+            .checked_mul(pcount.checked_add(group_size)?)
+    }
+
+    fn bits_per_value(&self) -> u64 {
+        self.bitpix().map(|bitpix| bitpix.bits() as u64).unwrap_or(0)
+    }
+
+    /// The type of the values in the data array, i.e. the value of `BITPIX`.
+    pub fn bitpix(&self) -> Result<Bitpix, BitpixError> {
+        match self.get_int(&Keyword::BITPIX) {
+            Ok(value) => Bitpix::try_from(value),
+            Err(ValueRetrievalError::KeywordNotPresent) => Err(BitpixError::MissingBitpix),
+            Err(_) => Err(BitpixError::NotAnInteger),
+        }
+    }
+
+    /// How raw values of the data array map to physical values, as described
+    /// by `BSCALE`, `BZERO` and `BLANK`. Missing keywords take their defaults.
+    pub fn scaling(&self) -> Result<Scaling, ValueRetrievalError> {
+        Ok(Scaling {
+            bscale: optional(self.get_real(&Keyword::BSCALE))?.unwrap_or(1f64),
+            bzero: optional(self.get_real(&Keyword::BZERO))?.unwrap_or(0f64),
+            blank: optional(self.get_int(&Keyword::BLANK))?,
+        })
+    }
+
+    /// The value of the first record with this keyword.
+    pub fn get(&self, keyword: &Keyword) -> Option<&Value<'a>> {
+        self.record_of(keyword).map(|keyword_record| &keyword_record.value)
+    }
+
+    /// The first record with this keyword, with its value and comment.
+    pub fn record_of(&self, keyword: &Keyword) -> Option<&KeywordRecord<'a>> {
+        self.keyword_records.iter().find(|keyword_record| keyword_record.keyword == *keyword)
+    }
+
+    /// The index of the first record with this keyword in `keyword_records`.
+    pub fn position_of(&self, keyword: &Keyword) -> Option<usize> {
+        self.keyword_records.iter().position(|keyword_record| keyword_record.keyword == *keyword)
+    }
+
+    /// The index of the card that the record with index `record` starts at,
+    /// counting the `CONTINUE` cards of the records before it.
+    pub fn card_of(&self, record: usize) -> usize {
+        self.keyword_records.iter().take(record).map(KeywordRecord::cards).sum()
+    }
+
+    /// All records with this keyword, in the order they appear in the header.
+    pub fn records_of(&self, keyword: &Keyword) -> Vec<&KeywordRecord<'a>> {
+        self.keyword_records.iter()
+            .filter(|keyword_record| keyword_record.keyword == *keyword)
+            .collect()
+    }
+
+    /// The records whose keyword satisfies `predicate`, in the order they
+    /// appear in the header.
+    pub fn records_matching<'h, P>(&'h self, predicate: P) -> impl Iterator<Item = &'h KeywordRecord<'a>>
+        where P: Fn(&Keyword) -> bool + 'h {
+        self.keyword_records.iter().filter(move |keyword_record| predicate(&keyword_record.keyword))
+    }
+
+    /// The records of the indexed keywords that start with `prefix`, e.g. all
+    /// `TTYPEn` for `"TTYPE"`, with their index and in numeric order. This
+    /// does not need `TFIELDS` or `NAXIS` to know how many there are.
+    pub fn records_with_prefix(&self, prefix: &str) -> Vec<(u16, &KeywordRecord<'a>)> {
+        let mut records: Vec<(u16, &KeywordRecord<'a>)> = self.keyword_records.iter()
+            .filter_map(|keyword_record| {
+                let name = keyword_record.keyword.to_string();
+                let index = name.strip_prefix(prefix)
+                    .filter(|index| !index.is_empty() && index.bytes().all(|byte| byte.is_ascii_digit()))?;
+                u16::from_str(index).ok().map(|index| (index, keyword_record))
+            })
+            .collect();
+        records.sort_by_key(|&(index, _)| index);
+        records
+    }
+
+    /// The value of this keyword as a character string, as it is written
+    /// between the quotes, i.e. including trailing spaces.
+    pub fn get_str(&self, keyword: &Keyword) -> Result<&str, ValueRetrievalError> {
+        self.defined_value_of(keyword)?.as_str().ok_or(ValueRetrievalError::NotACharacterString)
+    }
+
+    /// The value of this keyword as a character string without its trailing
+    /// spaces, which are not significant. So `EXTNAME = 'PRIMARY '` gives
+    /// `"PRIMARY"`.
+    pub fn get_trimmed_str(&self, keyword: &Keyword) -> Result<&str, ValueRetrievalError> {
+        self.defined_value_of(keyword)?.as_trimmed_str().ok_or(ValueRetrievalError::NotACharacterString)
+    }
+
+    /// The value of this keyword as an integer.
+    pub fn get_int(&self, keyword: &Keyword) -> Result<i64, ValueRetrievalError> {
+        match *self.defined_value_of(keyword)? {
+            Value::Integer(n) => Ok(n),
+            _ => Err(ValueRetrievalError::NotAnInteger),
+        }
+    }
+
+    /// The value of this keyword as a real number. Integer values are converted
+    /// as well, because writers often omit the decimal point.
+    pub fn get_real(&self, keyword: &Keyword) -> Result<f64, ValueRetrievalError> {
+        match *self.defined_value_of(keyword)? {
+            Value::Real(r) => Ok(r),
+            Value::Integer(n) => Ok(n as f64),
+            _ => Err(ValueRetrievalError::NotAReal),
+        }
+    }
+
+    /// The value of this keyword as a logical constant.
+    pub fn get_logical(&self, keyword: &Keyword) -> Result<bool, ValueRetrievalError> {
+        match *self.defined_value_of(keyword)? {
+            Value::Logical(b) => Ok(b),
+            _ => Err(ValueRetrievalError::NotALogical),
+        }
+    }
+
+    /// The value of `DATASUM`, the checksum of the data unit. It is stored as
+    /// a character string holding an unsigned decimal number, which is reduced
+    /// modulo 2^32. Integer values are accepted as well.
+    pub fn datasum(&self) -> Result<u32, ValueRetrievalError> {
+        match *self.defined_value_of(&Keyword::DATASUM)? {
+            Value::CharacterString(ref s) => decimal_modulo_2_32(s.trim()).ok_or(ValueRetrievalError::NotADecimalNumber),
+            Value::Integer(n) if n >= 0 => Ok(n as u32),
+            _ => Err(ValueRetrievalError::NotADecimalNumber),
+        }
+    }
+
+    /// Render this header in aligned columns, optionally colored for display
+    /// in a terminal.
+    pub fn pretty(&self, options: &PrettyOptions) -> String {
+        pretty::render(self, options)
+    }
+
+    /// The text of all `COMMENT` records.
+    pub fn comments(&self) -> Vec<&str> {
+        self.commentary_of(&Keyword::COMMENT)
+    }
+
+    /// The text of all `HISTORY` records.
+    pub fn history(&self) -> Vec<&str> {
+        self.commentary_of(&Keyword::HISTORY)
+    }
+
+    /// Set the value of the first record with this keyword. A `comment`
+    /// replaces the comment of that record, `None` keeps it. Without such a
+    /// record, a new one is added before the `END` record.
+    pub fn set(&mut self, keyword: Keyword, value: Value<'a>, comment: Option<&'a str>) {
+        match self.keyword_records.iter_mut().find(|keyword_record| keyword_record.keyword == keyword) {
+            Some(keyword_record) => {
+                keyword_record.set_value(value);
+                if let Some(comment) = comment {
+                    keyword_record.comment = Some(Cow::Borrowed(comment));
+                }
+            },
+            None => {
+                let end = self.end_position();
+                self.keyword_records.insert(end, KeywordRecord::new(keyword, value, comment));
+            },
+        }
+    }
+
+    /// Remove all records with this keyword, returning them in order.
+    pub fn remove(&mut self, keyword: &Keyword) -> Vec<KeywordRecord<'a>> {
+        let (removed, kept) = self.keyword_records.drain(..)
+            .partition(|keyword_record| keyword_record.keyword == *keyword);
+        self.keyword_records = kept;
+        removed
+    }
+
+    /// Insert a record directly after the first record with keyword `after`.
+    pub fn insert_after(&mut self, after: &Keyword, record: KeywordRecord<'a>) -> Result<(), ValueRetrievalError> {
+        let position = self.keyword_records.iter()
+            .position(|keyword_record| keyword_record.keyword == *after)
+            .ok_or(ValueRetrievalError::KeywordNotPresent)?;
+        self.keyword_records.insert(position + 1, record);
+        Ok(())
+    }
+
+    // The position of the `END` record, or the end of the records without one.
+    fn end_position(&self) -> usize {
+        self.keyword_records.iter()
+            .position(|keyword_record| keyword_record.keyword == Keyword::END)
+            .unwrap_or(self.keyword_records.len())
+    }
+
+    /// Add a `COMMENT` record before the `END` record. Text that does not fit
+    /// in a card is wrapped over several cards when the header is written.
+    pub fn add_comment(&mut self, text: &str) {
+        self.add_commentary(Keyword::COMMENT, text)
+    }
+
+    /// Add a `HISTORY` record before the `END` record. Text that does not fit
+    /// in a card is wrapped over several cards when the header is written.
+    pub fn add_history(&mut self, text: &str) {
+        self.add_commentary(Keyword::HISTORY, text)
+    }
+
+    fn add_commentary(&mut self, keyword: Keyword, text: &str) {
+        let end = self.end_position();
+        let record = KeywordRecord::new(keyword, Value::CommentText(Cow::Owned(text.to_string())), Option::None);
+        self.keyword_records.insert(end, record);
+    }
+
+    fn commentary_of(&self, keyword: &Keyword) -> Vec<&str> {
+        self.keyword_records.iter()
+            .filter(|keyword_record| keyword_record.keyword == *keyword)
+            .filter_map(|keyword_record| {
+                match keyword_record.value {
+                    Value::CommentText(ref text) => Some(text.as_ref()),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    fn defined_value_of(&self, keyword: &Keyword) -> Result<&Value<'a>, ValueRetrievalError> {
+        match self.get(keyword) {
+            Some(&Value::Undefined) => Err(ValueRetrievalError::ValueUndefined),
+            Some(value) => Ok(value),
+            None => Err(ValueRetrievalError::KeywordNotPresent),
+        }
+    }
+
+    /// The length of each axis, `NAXIS1` first.
+    ///
+    /// Verifies that `NAXIS` is present and that exactly the keywords `NAXIS1`
+    /// up to and including `NAXISn`, where n is the value of `NAXIS`, occur in
+    /// this header, each with a non-negative integer value.
+    pub fn axes(&self) -> Result<Vec<usize>, AxisError> {
+        let naxis = match self.get(&Keyword::NAXIS) {
+            Some(&Value::Integer(n)) => n,
+            Some(_) => return Err(AxisError::InvalidNaxis),
+            None => return Err(AxisError::MissingNaxis),
+        };
+        if !(0..=999).contains(&naxis) {
+            return Err(AxisError::InvalidNaxis)
+        }
+        let mut axes: Vec<Option<usize>> = vec![None; naxis as usize];
+        for keyword_record in &self.keyword_records {
+            if let Keyword::NAXISn(n) = keyword_record.keyword {
+                let index = n as usize;
+                if index == 0 || index > axes.len() {
+                    return Err(AxisError::UnexpectedAxis(n))
+                }
+                if axes[index - 1].is_some() {
+                    return Err(AxisError::DuplicateAxis(n))
+                }
+                // A length that does not fit in a `usize` is refused, e.g. on 32-bit targets.
+                match keyword_record.value {
+                    Value::Integer(length) => match usize::try_from(length) {
+                        Ok(length) => axes[index - 1] = Some(length),
+                        Err(_) => return Err(AxisError::InvalidAxis(n)),
+                    },
+                    _ => return Err(AxisError::InvalidAxis(n)),
+                }
+            }
+        }
+        axes.into_iter()
+            .enumerate()
+            .map(|(index, axis)| axis.ok_or(AxisError::MissingAxis((index + 1) as u16)))
+            .collect()
+    }
+
+    fn naxis_product(&self) -> Option<u64> {
+        match self.axes() {
+            Ok(ref axes) if !axes.is_empty() => axes.iter().try_fold(1u64, |product, axis| product.checked_mul(*axis as u64)),
+            _ => Some(0),
+        }
+    }
+}
+
+/// A problem in a header that a lenient parse worked around instead of failing.
+#[derive(Debug, PartialEq, Clone)]
+pub struct HeaderWarning {
+    card: usize,
+    message: String,
+}
+
+impl HeaderWarning {
+    /// Create a warning about the card with index `card` in its header.
+    pub fn new<S: Into<String>>(card: usize, message: S) -> HeaderWarning {
+        HeaderWarning { card, message: message.into() }
+    }
+
+    /// The index of the card in its header, the first card having index 0.
+    pub fn card(&self) -> usize {
+        self.card
+    }
+
+    /// A description of the problem.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl Display for HeaderWarning {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        write!(f, "card {}: {}", self.card, self.message)
+    }
+}
+
+/// Problems with the `NAXIS` and `NAXISn` keywords of a header.
+#[derive(Debug, PartialEq)]
+pub enum AxisError {
+    /// The `NAXIS` keyword is not present.
+    MissingNaxis,
+    /// The value of `NAXIS` is not an integer between 0 and 999.
+    InvalidNaxis,
+    /// The keyword `NAXISn` is missing, while n is at most `NAXIS`.
+    MissingAxis(u16),
+    /// The keyword `NAXISn` is present, while n is zero or exceeds `NAXIS`.
+    UnexpectedAxis(u16),
+    /// The keyword `NAXISn` occurs more than once.
+    DuplicateAxis(u16),
+    /// The value of `NAXISn` is not a non-negative integer.
+    InvalidAxis(u16),
+}
+
+/// The type of the values in a data array, as signalled by the `BITPIX` keyword.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Bitpix {
+    /// Unsigned 8-bit integers, `BITPIX = 8`.
+    U8,
+    /// Signed 16-bit integers, `BITPIX = 16`.
+    I16,
+    /// Signed 32-bit integers, `BITPIX = 32`.
+    I32,
+    /// Signed 64-bit integers, `BITPIX = 64`.
+    I64,
+    /// Single precision floating point, `BITPIX = -32`.
+    F32,
+    /// Double precision floating point, `BITPIX = -64`.
+    F64,
+}
+
+impl Bitpix {
+    /// The value of the `BITPIX` keyword for this type.
+    pub fn value(self) -> i64 {
+        match self {
+            Bitpix::U8 => 8,
+            Bitpix::I16 => 16,
+            Bitpix::I32 => 32,
+            Bitpix::I64 => 64,
+            Bitpix::F32 => -32,
+            Bitpix::F64 => -64,
+        }
+    }
+
+    /// The number of bits of a single value.
+    pub fn bits(self) -> usize {
+        self.value().unsigned_abs() as usize
+    }
+
+    /// The number of bytes of a single value.
+    pub fn bytes(self) -> usize {
+        self.bits() / 8
+    }
+
+    /// Whether the values are floating point numbers.
+    pub fn is_floating_point(self) -> bool {
+        self.value() < 0
+    }
+}
+
+impl TryFrom<i64> for Bitpix {
+    type Error = BitpixError;
+
+    fn try_from(value: i64) -> Result<Self, Self::Error> {
+        match value {
+            8 => Ok(Bitpix::U8),
+            16 => Ok(Bitpix::I16),
+            32 => Ok(Bitpix::I32),
+            64 => Ok(Bitpix::I64),
+            -32 => Ok(Bitpix::F32),
+            -64 => Ok(Bitpix::F64),
+            _ => Err(BitpixError::UnknownBitpix(value)),
+        }
+    }
+}
+
+impl From<Bitpix> for i64 {
+    fn from(bitpix: Bitpix) -> Self {
+        bitpix.value()
+    }
+}
+
+impl Display for Bitpix {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        f.pad(&self.value().to_string())
+    }
+}
+
+impl Display for ValueRetrievalError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        match *self {
+            ValueRetrievalError::NotACharacterString => write!(f, "value is not a character string"),
+            ValueRetrievalError::NotAnInteger => write!(f, "value is not an integer"),
+            ValueRetrievalError::NotAReal => write!(f, "value is not a real number"),
+            ValueRetrievalError::NotALogical => write!(f, "value is not a logical constant"),
+            ValueRetrievalError::NotADecimalNumber => write!(f, "value is not an unsigned decimal number"),
+            ValueRetrievalError::ValueUndefined => write!(f, "value is undefined"),
+            ValueRetrievalError::KeywordNotPresent => write!(f, "keyword is not present"),
+        }
+    }
+}
+
+impl error::Error for ValueRetrievalError {}
+
+/// Problems with the `BITPIX` keyword of a header.
+#[derive(Debug, PartialEq)]
+pub enum BitpixError {
+    /// The `BITPIX` keyword is not present.
+    MissingBitpix,
+    /// The value of `BITPIX` is not an integer.
+    NotAnInteger,
+    /// The value of `BITPIX` is not one of 8, 16, 32, 64, -32 or -64.
+    UnknownBitpix(i64),
+}
+
+impl Display for BitpixError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        match *self {
+            BitpixError::MissingBitpix => write!(f, "BITPIX is missing"),
+            BitpixError::NotAnInteger => write!(f, "BITPIX is not an integer"),
+            BitpixError::UnknownBitpix(value) => write!(f, "BITPIX {} is not one of 8, 16, 32, 64, -32 or -64", value),
+        }
+    }
+}
+
+impl error::Error for BitpixError {}
+
+impl Display for AxisError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        match *self {
+            AxisError::MissingNaxis => write!(f, "NAXIS is missing"),
+            AxisError::InvalidNaxis => write!(f, "NAXIS is not an integer between 0 and 999"),
+            AxisError::MissingAxis(n) => write!(f, "NAXIS{} is missing", n),
+            AxisError::UnexpectedAxis(n) => write!(f, "NAXIS{} is present, but NAXIS is smaller", n),
+            AxisError::DuplicateAxis(n) => write!(f, "NAXIS{} occurs more than once", n),
+            AxisError::InvalidAxis(n) => write!(f, "NAXIS{} is not a non-negative integer", n),
+        }
+    }
+}
+
+impl error::Error for AxisError {}
+
+/// Problems with changing the extensions of a `Fits`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ExtensionError {
+    /// An extension can not be inserted at `index`, as there are only `count`
+    /// extensions.
+    OutOfRange {
+        /// The index, 0 being the first extension.
+        index: usize,
+        /// The number of extensions.
+        count: usize,
+    },
+}
+
+impl Display for ExtensionError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        match *self {
+            ExtensionError::OutOfRange { index, count } => write!(f, "an extension can not be inserted at {}, as there are only {} extensions", index, count),
+        }
+    }
+}
+
+impl error::Error for ExtensionError {}
+
+/// When asking for a value, these things can go wrong.
+#[derive(Debug, PartialEq)]
+pub enum ValueRetrievalError {
+    /// The value associated with this keyword is not a character string.
+    NotACharacterString,
+    /// The value associated with this keyword is not an integer.
+    NotAnInteger,
+    /// The value associated with this keyword is not a real number.
+    NotAReal,
+    /// The value associated with this keyword is not a logical constant.
+    NotALogical,
+    /// The value associated with this keyword is not an unsigned decimal
+    /// number, nor a character string holding one.
+    NotADecimalNumber,
+    /// There is no value associated with this keyword.
+    ValueUndefined,
+    /// The keyword is not present in the header.
+    KeywordNotPresent,
+}
+
+// Parse an unsigned decimal number, reduced modulo 2^32.
+fn decimal_modulo_2_32(s: &str) -> Option<u32> {
+    if s.is_empty() {
+        return None
+    }
+    s.chars().try_fold(0u64, |value, c| c.to_digit(10).map(|digit| (value * 10 + u64::from(digit)) % (1u64 << 32)))
+        .map(|value| value as u32)
+}
+
+// Turn a missing keyword into `None`, keeping the other problems.
+fn optional<T>(result: Result<T, ValueRetrievalError>) -> Result<Option<T>, ValueRetrievalError> {
+    match result {
+        Err(ValueRetrievalError::KeywordNotPresent) => Ok(Option::None),
+        result => result.map(Option::Some),
+    }
+}
+
+/// The linear map from raw values of a data array to physical values, i.e.
+/// `physical = BZERO + BSCALE * raw`. Raw integer values equal to `BLANK` are
+/// undefined.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Scaling {
+    /// The value of `BSCALE`, 1 by default.
+    pub bscale: f64,
+    /// The value of `BZERO`, 0 by default.
+    pub bzero: f64,
+    /// The value of `BLANK`, if present.
+    pub blank: Option<i64>,
+}
+
+impl Scaling {
+    /// The physical value of a raw integer value, `NaN` when it equals `BLANK`.
+    pub fn apply_to_integer(&self, raw: i64) -> f64 {
+        if self.blank == Some(raw) {
+            f64::NAN
+        } else {
+            self.apply(raw as f64)
+        }
+    }
+
+    /// The physical value of a raw value.
+    pub fn apply(&self, raw: f64) -> f64 {
+        self.bzero + self.bscale * raw
+    }
+}
+
+impl Default for Scaling {
+    fn default() -> Self {
+        Scaling { bscale: 1f64, bzero: 0f64, blank: Option::None }
+    }
+}
+
+/// The data array of an HDU. It holds the raw big-endian bytes of the data unit
+/// and knows how to interpret them according to `BITPIX` and `NAXISn`.
+#[derive(Debug, PartialEq)]
+pub struct DataArray<'a> {
+    /// The type of the values.
+    bitpix: Bitpix,
+    /// The length of each axis, i.e. the values of the `NAXISn` keywords.
+    axes: Vec<usize>,
+    /// The raw bytes of the data array, without padding.
+    data: Cow<'a, [u8]>,
+    /// How raw values map to physical values.
+    scaling: Scaling,
+}
+
+impl<'a> DataArray<'a> {
+    /// Create a `DataArray` from the raw bytes of a data unit.
+    pub fn new(bitpix: Bitpix, axes: Vec<usize>, data: &'a [u8]) -> DataArray<'a> {
+        DataArray { bitpix, axes, data: Cow::Borrowed(data), scaling: Scaling::default() }
+    }
+
+    /// Create a `DataArray` that owns its raw bytes, e.g. ones that are copied
+    /// from another data array.
+    pub fn from_vec(bitpix: Bitpix, axes: Vec<usize>, data: Vec<u8>) -> DataArray<'static> {
+        DataArray { bitpix, axes, data: Cow::Owned(data), scaling: Scaling::default() }
+    }
+
+    /// Use `scaling` to determine physical values.
+    pub fn with_scaling(self, scaling: Scaling) -> DataArray<'a> {
+        DataArray { scaling, ..self }
+    }
+
+    /// Convert into a `DataArray` that owns its bytes.
+    pub fn into_owned(self) -> DataArray<'static> {
+        DataArray { bitpix: self.bitpix, axes: self.axes, data: Cow::Owned(self.data.into_owned()), scaling: self.scaling }
+    }
+
+    /// A view of this `DataArray` that borrows its bytes from it.
+    pub fn view(&self) -> DataArray<'_> {
+        DataArray { bitpix: self.bitpix, axes: self.axes.clone(), data: Cow::Borrowed(&self.data), scaling: self.scaling }
+    }
+
+    /// The type of the values, as described by `BITPIX`.
+    pub fn bitpix(&self) -> Bitpix {
+        self.bitpix
+    }
+
+    /// How raw values map to physical values, as described by `BSCALE`,
+    /// `BZERO` and `BLANK`.
+    pub fn scaling(&self) -> Scaling {
+        self.scaling
+    }
+
+    /// The length of each axis, `NAXIS1` first.
+    pub fn axes(&self) -> &[usize] {
+        &self.axes
+    }
+
+    /// The raw big-endian bytes of the data array.
+    pub fn bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The values of a data array with `BITPIX = 8` as a slice, without copying.
+    pub fn as_u8(&self) -> Result<&[u8], DataArrayError> {
+        if self.bitpix == u8::BITPIX {
+            Ok(&self.data)
+        } else {
+            Err(DataArrayError::BitpixMismatch { expected: u8::BITPIX, actual: self.bitpix })
+        }
+    }
+
+    /// Decode the values of this data array. The type `T` should correspond
+    /// with `BITPIX`, e.g. `i16` for `BITPIX = 16` and `f32` for `BITPIX = -32`.
+    pub fn values<T: Pixel>(&self) -> Result<Vec<T>, DataArrayError> {
+        let mut values = vec!();
+        self.values_into(&mut values)?;
+        Ok(values)
+    }
+
+    /// Decode the values of this data array into `buffer`, replacing its
+    /// contents. Reading into the same buffer again reuses its allocation.
+    pub fn values_into<T: Pixel>(&self, buffer: &mut Vec<T>) -> Result<(), DataArrayError> {
+        if self.bitpix != T::BITPIX {
+            return Err(DataArrayError::BitpixMismatch { expected: T::BITPIX, actual: self.bitpix })
+        }
+        let size = T::BITPIX.bytes();
+        if !self.data.len().is_multiple_of(size) {
+            return Err(DataArrayError::IncompleteValue)
+        }
+        buffer.clear();
+        buffer.extend(self.data.chunks(size).map(T::from_big_endian));
+        Ok(())
+    }
+
+    /// Whether this data array follows the unsigned integer convention of `T`,
+    /// i.e. has the `BITPIX` of the stored type, `BSCALE = 1` and `BZERO` the
+    /// offset of `T`.
+    pub fn is_unsigned<T: Unsigned>(&self) -> bool {
+        self.bitpix == T::Stored::BITPIX && self.scaling.bscale == 1f64 && self.scaling.bzero == T::BZERO
+    }
+
+    /// Decode the values of a data array that follows the unsigned integer
+    /// convention, e.g. `u16` for `BITPIX = 16` and `BZERO = 32768`. Unlike
+    /// `physical_values`, this is exact for `u64`.
+    pub fn unsigned_values<T: Unsigned>(&self) -> Result<Vec<T>, DataArrayError> {
+        let mut values = vec!();
+        self.unsigned_values_into(&mut values)?;
+        Ok(values)
+    }
+
+    /// Decode the values of a data array that follows the unsigned integer
+    /// convention into `buffer`, replacing its contents.
+    pub fn unsigned_values_into<T: Unsigned>(&self, buffer: &mut Vec<T>) -> Result<(), DataArrayError> {
+        if !self.is_unsigned::<T>() {
+            return Err(DataArrayError::NotUnsigned)
+        }
+        let size = T::Stored::BITPIX.bytes();
+        if !self.data.len().is_multiple_of(size) {
+            return Err(DataArrayError::IncompleteValue)
+        }
+        buffer.clear();
+        buffer.extend(self.data.chunks(size).map(|bytes| T::from_stored(T::Stored::from_big_endian(bytes))));
+        Ok(())
+    }
+
+    /// Decode the values of this data array as `f64`, whatever the `BITPIX`.
+    pub fn to_f64(&self) -> Result<Vec<f64>, DataArrayError> {
+        match self.bitpix {
+            Bitpix::U8 => self.values_as_f64::<u8>(),
+            Bitpix::I16 => self.values_as_f64::<i16>(),
+            Bitpix::I32 => self.values_as_f64::<i32>(),
+            Bitpix::I64 => self.values_as_f64::<i64>(),
+            Bitpix::F32 => self.values_as_f64::<f32>(),
+            Bitpix::F64 => self.values_as_f64::<f64>(),
+        }
+    }
+
+    fn values_as_f64<T: Pixel>(&self) -> Result<Vec<f64>, DataArrayError> {
+        Ok(self.values::<T>()?.into_iter().map(Pixel::to_f64).collect())
+    }
+
+    /// Decode the physical values of this data array, i.e. apply `BSCALE` and
+    /// `BZERO`. Integer values equal to `BLANK` become `NaN`. This handles the
+    /// unsigned integer convention as well, e.g. `BITPIX = 16` with `BZERO = 32768`.
+    pub fn physical_values(&self) -> Result<Vec<f64>, DataArrayError> {
+        match self.bitpix {
+            Bitpix::U8 => self.scaled_integers::<u8>(),
+            Bitpix::I16 => self.scaled_integers::<i16>(),
+            Bitpix::I32 => self.scaled_integers::<i32>(),
+            Bitpix::I64 => self.scaled_integers::<i64>(),
+            Bitpix::F32 | Bitpix::F64 => Ok(self.to_f64()?.into_iter().map(|raw| self.scaling.apply(raw)).collect()),
         }
     }
+
+    fn scaled_integers<T: Pixel + Into<i64>>(&self) -> Result<Vec<f64>, DataArrayError> {
+        Ok(self.values::<T>()?.into_iter().map(|raw| self.scaling.apply_to_integer(raw.into())).collect())
+    }
 }
 
-/// Header Data Unit, combination of a header and an optional data array.
+/// When decoding a data array, these things can go wrong.
 #[derive(Debug, PartialEq)]
-pub struct HDU<'a> {
-    /// The header of this HDU.
-    pub header: Header<'a>,
-    /// The optional data array of this HDU.
-    data_array: Option<DataArray>,
+pub enum DataArrayError {
+    /// The requested type does not correspond with the `BITPIX` of the data array.
+    BitpixMismatch {
+        /// The `BITPIX` of the requested type.
+        expected: Bitpix,
+        /// The `BITPIX` of the data array.
+        actual: Bitpix,
+    },
+    /// The number of bytes is not a multiple of the size of a value.
+    IncompleteValue,
+    /// The data array does not follow the unsigned integer convention of the
+    /// requested type, i.e. `BSCALE = 1` and `BZERO` is the offset of the type.
+    NotUnsigned,
 }
 
-impl<'a> HDU<'a> {
-    /// Create an HDU with a header, setting the data_array to none.
-    pub fn new(header: Header<'a>) -> HDU<'a> {
-        HDU { header: header, data_array: Option::None }
+impl Display for DataArrayError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        match *self {
+            DataArrayError::BitpixMismatch { expected, actual } =>
+                write!(f, "values with BITPIX {} requested, but the data array has BITPIX {}", expected, actual),
+            DataArrayError::IncompleteValue => write!(f, "the data array ends in the middle of a value"),
+            DataArrayError::NotUnsigned => write!(f, "the data array does not hold unsigned integers"),
+        }
     }
 }
 
-/// The primary header of a FITS file.
-#[derive(Debug, PartialEq)]
-pub struct Header<'a> {
-    /// The keyword records of the primary header.
-    pub keyword_records: Vec<KeywordRecord<'a>>,
+impl error::Error for DataArrayError {}
+
+/// Types that can be stored in a data array.
+pub trait Pixel: Sized {
+    /// The value of `BITPIX` that signals this type.
+    const BITPIX: Bitpix;
+
+    /// Decode a value from its big-endian representation.
+    fn from_big_endian(bytes: &[u8]) -> Self;
+
+    /// Convert a value to `f64`, e.g. for arithmetic regardless of `BITPIX`.
+    fn to_f64(self) -> f64;
+
+    /// Append the big-endian representation of a value to `bytes`.
+    fn extend_big_endian(self, bytes: &mut Vec<u8>);
 }
 
-impl<'a> Header<'a> {
-    /// Create a Header with a given set of keyword_records
-    pub fn new(keyword_records: Vec<KeywordRecord<'a>>) -> Header<'a> {
-        Header { keyword_records: keyword_records }
-    }
+macro_rules! pixel {
+    ($t: ty, $bitpix: expr, $size: expr) => {
+        impl Pixel for $t {
+            const BITPIX: Bitpix = $bitpix;
 
-    /// Determines the size in bits of the data array following this header.
-    pub fn data_array_size(&self) -> usize {
-        if self.is_primary() {
-            lmle(self.primary_data_array_size(), 2880*8)
-        } else {
-            lmle(self.extention_data_array_size(), 2880*8)
-        }
-    }
+            fn from_big_endian(bytes: &[u8]) -> Self {
+                let mut buffer = [0u8; $size];
+                buffer.copy_from_slice(bytes);
+                <$t>::from_be_bytes(buffer)
+            }
 
-    fn is_primary(&self) -> bool {
-        self.has_keyword_record(&Keyword::SIMPLE)
-    }
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
 
-    fn has_keyword_record(&self, keyword: &Keyword) -> bool {
-        for keyword_record in &self.keyword_records {
-            if *keyword == keyword_record.keyword {
-                return true
+            fn extend_big_endian(self, bytes: &mut Vec<u8>) {
+                bytes.extend_from_slice(&self.to_be_bytes())
             }
         }
-        false
     }
+}
 
-    fn primary_data_array_size(&self) -> usize {
-        (self.integer_value_of(&Keyword::BITPIX).unwrap_or(0i64).abs() * self.naxis_product()) as usize
-    }
+pixel!(u8, Bitpix::U8, 1);
+pixel!(i16, Bitpix::I16, 2);
+pixel!(i32, Bitpix::I32, 4);
+pixel!(i64, Bitpix::I64, 8);
+pixel!(f32, Bitpix::F32, 4);
+pixel!(f64, Bitpix::F64, 8);
 
-    fn extention_data_array_size(&self) -> usize {
-        (self.integer_value_of(&Keyword::BITPIX).unwrap_or(0i64).abs() *
-         self.integer_value_of(&Keyword::GCOUNT).unwrap_or(1i64) *
-         (self.integer_value_of(&Keyword::PCOUNT).unwrap_or(0i64) + self.naxis_product())) as usize
-    }
+/// Unsigned integers, which FITS stores as signed integers of the same size
+/// with `BZERO` set to the offset, e.g. `BITPIX = 16` and `BZERO = 32768` for
+/// `u16`.
+pub trait Unsigned: Sized {
+    /// The signed type the values are stored as.
+    type Stored: Pixel + Copy;
 
-    fn integer_value_of(&self, keyword: &Keyword) -> Result<i64, ValueRetrievalError> {
-        self.value_of(keyword).and_then(|value| {
-            match value {
-                Value::Integer(n) => Ok(n),
-                _ => Err(ValueRetrievalError::NotAnInteger),
-            }
-        })
-    }
+    /// The value of `BZERO` that signals this type.
+    const BZERO: f64;
 
-    fn value_of(&self, keyword: &Keyword) -> Result<Value, ValueRetrievalError> {
-        if self.has_keyword_record(&keyword) {
-            for keyword_record in &self.keyword_records {
-                if keyword_record.keyword == *keyword {
-                    return Ok(keyword_record.value.clone())
-                }
+    /// The value that `raw` stores, i.e. `raw + BZERO`.
+    fn from_stored(raw: Self::Stored) -> Self;
+
+    /// The value to store for this value, i.e. `self - BZERO`.
+    fn to_stored(self) -> Self::Stored;
+}
+
+macro_rules! unsigned {
+    ($t: ty, $stored: ty) => {
+        impl Unsigned for $t {
+            type Stored = $stored;
+
+            const BZERO: f64 = -(<$stored>::MIN as f64);
+
+            // Adding the offset modulo 2^n flips the sign bit.
+            fn from_stored(raw: $stored) -> Self {
+                (raw as $t) ^ (1 << (<$t>::BITS - 1))
             }
-        }
-        Err(ValueRetrievalError::KeywordNotPresent)
-    }
 
-    fn naxis_product(&self) -> i64 {
-        let limit = self.integer_value_of(&Keyword::NAXIS).unwrap_or(0i64);
-        if limit > 0 {
-            let mut product = 1i64;
-            for n in 0..limit {
-                let naxisn = Keyword::NAXISn((n + 1i64) as u16);
-                product *= self.integer_value_of(&naxisn)
-                    .expect(format!("NAXIS{} should be defined", n).as_str());
+            fn to_stored(self) -> $stored {
+                (self ^ (1 << (<$t>::BITS - 1))) as $stored
             }
-            product
-        } else {
-            0i64
         }
     }
 }
 
-/// When asking for a value, these things can go wrong.
-#[derive(Debug)]
-pub enum ValueRetrievalError {
-    /// The value associated with this keyword is not an integer.
-    NotAnInteger,
-    /// There is no value associated with this keyword.
-    ValueUndefined,
-    /// The keyword is not present in the header.
-    KeywordNotPresent,
-}
-
-/// Placeholder for DataArray
-#[derive(Debug, PartialEq)]
-pub struct DataArray;
+unsigned!(u16, i16);
+unsigned!(u32, i32);
+unsigned!(u64, i64);
 
 /// A keyword record contains information about a FITS header. It consists of a
 /// keyword, the corresponding value and an optional comment.
-#[derive(Debug, PartialEq)]
+///
+/// A parsed record remembers the column of the `/` that starts its comment, so
+/// that it is written in the same place as long as the value is unchanged.
+/// The column does not take part in comparisons.
+#[derive(Debug, Clone)]
 pub struct KeywordRecord<'a> {
     /// The keyword of this record.
     keyword: Keyword,
     /// The value of this record.
     value: Value<'a>,
     /// The comment of this record.
-    comment: Option<&'a str>
+    comment: Option<Cow<'a, str>>,
+    /// The column of the `/` before the comment in the card this record was read from.
+    comment_column: Option<usize>,
+    /// The number of cards this record was read from.
+    cards: usize,
+}
+
+impl<'a, 'b> PartialEq<KeywordRecord<'b>> for KeywordRecord<'a> {
+    fn eq(&self, other: &KeywordRecord<'b>) -> bool {
+        self.keyword == other.keyword && self.value == other.value && self.comment == other.comment
+    }
 }
 
 impl<'a> KeywordRecord<'a> {
     /// Create a `KeywordRecord` from a specific `Keyword`.
     pub fn new(keyword: Keyword, value: Value<'a>, comment: Option<&'a str>) -> KeywordRecord<'a> {
-        KeywordRecord { keyword: keyword, value: value, comment: comment }
+        KeywordRecord { keyword, value, comment: comment.map(Cow::Borrowed), comment_column: Option::None, cards: 1 }
+    }
+
+    /// Place the `/` before the comment at this column, 0 being the first,
+    /// when writing this record. A column that the value reaches is ignored.
+    pub fn with_comment_column(self, column: usize) -> KeywordRecord<'a> {
+        KeywordRecord { comment_column: Option::Some(column), ..self }
+    }
+
+    /// The column of the `/` before the comment, if it is to be kept.
+    pub fn comment_column(&self) -> Option<usize> {
+        self.comment_column
+    }
+
+    /// The number of cards this record was read from: one, plus the
+    /// `CONTINUE` cards that were appended to it.
+    pub fn cards(&self) -> usize {
+        self.cards
+    }
+
+    /// Convert into a `KeywordRecord` that owns its value and comment.
+    pub fn into_owned(self) -> KeywordRecord<'static> {
+        KeywordRecord {
+            keyword: self.keyword,
+            value: self.value.into_owned(),
+            comment: self.comment.map(|comment| Cow::Owned(comment.into_owned())),
+            comment_column: self.comment_column,
+            cards: self.cards,
+        }
+    }
+
+    /// A view of this `KeywordRecord` that borrows its strings from it.
+    pub fn view(&self) -> KeywordRecord<'_> {
+        KeywordRecord {
+            keyword: self.keyword.clone(),
+            value: self.value.view(),
+            comment: self.comment.as_ref().map(|comment| Cow::Borrowed(&**comment)),
+            comment_column: self.comment_column,
+            cards: self.cards,
+        }
+    }
+
+    /// The keyword of this record.
+    pub fn keyword(&self) -> &Keyword {
+        &self.keyword
+    }
+
+    /// The value of this record.
+    pub fn value(&self) -> &Value<'a> {
+        &self.value
+    }
+
+    /// Replace the value of this record, keeping its keyword and comment.
+    pub fn set_value(&mut self, value: Value<'a>) {
+        if self.value != value {
+            self.comment_column = Option::None;
+        }
+        self.value = value;
+    }
+
+    /// The comment of this record.
+    pub fn comment(&self) -> Option<&str> {
+        self.comment.as_ref().map(|comment| comment.as_ref())
+    }
+
+    /// Whether the value is a character string that is continued in a
+    /// following `CONTINUE` record, i.e. a string that ends in `&`.
+    pub fn is_continued(&self) -> bool {
+        match self.value {
+            Value::CharacterString(ref s) => s.trim_end().ends_with('&'),
+            _ => false,
+        }
+    }
+
+    /// Append the character string of a `CONTINUE` record to the value of this
+    /// record, dropping the `&` that announced it. Comments are joined with a space.
+    pub fn append_continuation(&mut self, continuation: &KeywordRecord<'a>) {
+        if let (&mut Value::CharacterString(ref mut s), Value::CharacterString(rest)) = (&mut self.value, &continuation.value) {
+            let trimmed = s.trim_end();
+            let mut joined = trimmed.strip_suffix('&').unwrap_or(trimmed).to_string();
+            joined.push_str(rest);
+            *s = Cow::Owned(joined);
+        }
+        self.comment_column = Option::None;
+        self.cards += continuation.cards;
+        self.comment = match (self.comment.take(), continuation.comment.clone()) {
+            (Some(comment), Some(rest)) => Some(Cow::Owned(format!("{} {}", comment, rest))),
+            (comment, rest) => comment.or(rest),
+        };
     }
 }
 
+/// A keyword record is displayed as the card that holds it: 80 characters,
+/// with the keyword padded to 8 characters, followed by the value indicator
+/// `= `, the value in fixed format and ` / ` and the comment. Text that does
+/// not fit in the card is cut off.
 impl<'a> Display for KeywordRecord<'a> {
     fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
-        write!(f, "{:?}= {:?}/{}", self.keyword, self.value, self.comment.unwrap_or(""))
+        let mut card = match self.value {
+            Value::CommentText(ref text) => format!("{:<8}{}", self.keyword, text),
+            _ if self.keyword == Keyword::CONTINUE => format!("{:<8}  {}", self.keyword, self.value.fixed_format()),
+            _ => format!("{:<8}= {}", self.keyword, self.value.fixed_format()),
+        };
+        if let Some(comment) = self.comment() {
+            let width = match self.comment_column {
+                Some(column) if card.len() < column => column,
+                _ => card.len() + 1,
+            };
+            card = format!("{:<width$}/ {}", card, comment, width = width);
+        }
+        let card: String = card.chars().take(80).collect();
+        f.pad(&format!("{:<80}", card))
     }
 }
 
@@ -163,7 +1599,7 @@ impl<'a> Display for KeywordRecord<'a> {
 #[derive(Debug, PartialEq, Clone)]
 pub enum Value<'a> {
     /// A string enclosed in single quotes `'`.
-    CharacterString(&'a str),
+    CharacterString(Cow<'a, str>),
     /// A logical constant signified by either an uppercase `F` or an uppercase `T`.
     Logical(bool),
     /// An optionally signed decimal integer.
@@ -174,6 +1610,97 @@ pub enum Value<'a> {
     Complex((f64, f64)),
     /// When a value is not present
     Undefined,
+    /// The text of a commentary record, e.g. a `COMMENT` or `HISTORY` record.
+    CommentText(Cow<'a, str>),
+    /// The text after `= ` of a card that no value could be parsed from. Only
+    /// a lenient parse keeps such cards.
+    Unparsed(Cow<'a, str>),
+}
+
+impl<'a> Value<'a> {
+    /// Convert into a `Value` that owns its data.
+    pub fn into_owned(self) -> Value<'static> {
+        match self {
+            Value::CharacterString(s) => Value::CharacterString(Cow::Owned(s.into_owned())),
+            Value::Logical(b) => Value::Logical(b),
+            Value::Integer(n) => Value::Integer(n),
+            Value::Real(r) => Value::Real(r),
+            Value::Complex(c) => Value::Complex(c),
+            Value::Undefined => Value::Undefined,
+            Value::CommentText(s) => Value::CommentText(Cow::Owned(s.into_owned())),
+            Value::Unparsed(s) => Value::Unparsed(Cow::Owned(s.into_owned())),
+        }
+    }
+
+    /// A view of this `Value` that borrows its string from it.
+    pub fn view(&self) -> Value<'_> {
+        match *self {
+            Value::CharacterString(ref s) => Value::CharacterString(Cow::Borrowed(s)),
+            Value::Logical(b) => Value::Logical(b),
+            Value::Integer(n) => Value::Integer(n),
+            Value::Real(r) => Value::Real(r),
+            Value::Complex(c) => Value::Complex(c),
+            Value::Undefined => Value::Undefined,
+            Value::CommentText(ref s) => Value::CommentText(Cow::Borrowed(s)),
+            Value::Unparsed(ref s) => Value::Unparsed(Cow::Borrowed(s)),
+        }
+    }
+
+    /// The value as it is written in fixed format, i.e. in the 20 columns
+    /// after the value indicator. Character strings start in the first of
+    /// them, other values are right justified.
+    pub fn fixed_format(&self) -> String {
+        match *self {
+            Value::CharacterString(_) | Value::Undefined | Value::Unparsed(_) => format!("{:<20}", self),
+            _ => format!("{:>20}", self),
+        }
+    }
+
+    /// The text of a character string as it is written between the quotes,
+    /// with doubled quotes replaced by a single quote.
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Value::CharacterString(ref s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// The text of a character string without trailing spaces. Leading spaces
+    /// are significant, and a string of only spaces is a single space, which
+    /// differs from the empty string `''`.
+    pub fn as_trimmed_str(&self) -> Option<&str> {
+        self.as_str().map(|s| {
+            let trimmed = s.trim_end_matches(' ');
+            if trimmed.is_empty() && !s.is_empty() { &s[..1] } else { trimmed }
+        })
+    }
+}
+
+impl<'a> Display for Value<'a> {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        match *self {
+            Value::CharacterString(ref s) if s.is_empty() => f.pad("''"),
+            Value::CharacterString(ref s) => f.pad(&format!("'{:<8}'", s.replace('\'', "''"))),
+            Value::Logical(b) => f.pad(if b { "T" } else { "F" }),
+            Value::Integer(n) => f.pad(&n.to_string()),
+            Value::Real(r) => f.pad(&real(r)),
+            Value::Complex((re, im)) => f.pad(&format!("({}, {})", real(re), real(im))),
+            Value::Undefined => f.pad(""),
+            Value::CommentText(ref s) | Value::Unparsed(ref s) => f.pad(s),
+        }
+    }
+}
+
+/// Represent a real number in a way that is acceptable for FITS, i.e. with an
+/// uppercase exponent and a mantissa that includes a decimal point.
+fn real(r: f64) -> String {
+    let representation = format!("{:?}", r).to_uppercase();
+    match representation.find('E') {
+        Some(index) if !representation[..index].contains('.') => {
+            format!("{}.0{}", &representation[..index], &representation[index..])
+        },
+        _ => representation,
+    }
 }
 
 /// A unit struct that will act as a placeholder for blank records.
@@ -181,23 +1708,39 @@ pub enum Value<'a> {
 pub struct BlankRecord;
 
 /// The various keywords that can be found in headers.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 #[allow(non_camel_case_types, missing_docs)]
 pub enum Keyword {
+    AREASCAL,
     AV,
+    BACKSCAL,
+    BIASSEC,
     BITPIX,
+    BJDREFF,
+    BJDREFI,
+    BLANK,
+    BSCALE,
+    BZERO,
     CAMPAIGN,
+    CCDSEC,
     CHANNEL,
     CHECKSUM,
+    COMMENT,
+    CONTINUE,
     CREATOR,
+    DATASEC,
     DATASUM,
     DATA_REL,
     DATE,
     DEC_OBJ,
+    DETCHANS,
+    DETSEC,
     EBMINUSV,
     END,
     EQUINOX,
+    EXPOSURE,
     EXTEND,
+    EXTLEVEL,
     EXTNAME,
     EXTVER,
     FEH,
@@ -208,8 +1751,14 @@ pub enum Keyword {
     GLON,
     GMAG,
     GRCOLOR,
+    GRPIDn(u16),
+    GRPLCn(u16),
+    GROUPS,
+    GRPNAME,
+    HISTORY,
     HMAG,
     IMAG,
+    INHERIT,
     INSTRUME,
     JKCOLOR,
     JMAG,
@@ -218,6 +1767,9 @@ pub enum Keyword {
     KMAG,
     LOGG,
     MISSION,
+    MJDREF,
+    MJDREFF,
+    MJDREFI,
     MODULE,
     NAXIS,
     NAXISn(u16),
@@ -228,6 +1780,9 @@ pub enum Keyword {
     OUTPUT,
     PARALLAX,
     PCOUNT,
+    PSCALn(u16),
+    PTYPEn(u16),
+    PZEROn(u16),
     PMDEC,
     PMRA,
     PMTOTAL,
@@ -239,180 +1794,434 @@ pub enum Keyword {
     SIMPLE,
     TDIMn(u16),
     TDISPn(u16),
+    TASSIGN,
     TEFF,
     TELESCOP,
     TFIELDS,
     TFORMn(u16),
+    TIMEREF,
+    TIMESYS,
+    TIMEUNIT,
+    TIMSLICE,
     TIMVERSN,
     THEAP,
     TMINDEX,
     TNULLn(u16),
     TSCALn(u16),
+    TRIMSEC,
     TTABLEID,
     TTYPEn(u16),
     TUNITn(u16),
     TZEROn(u16),
     XTENSION,
     ZMAG,
-    Unprocessed, // TODO Remove the unprocessed keyword
+    /// The blank keyword, i.e. a keyword field of eight spaces. Like `COMMENT`
+    /// it is used for commentary text.
+    BlankField,
+    /// A keyword that is not known by name, e.g. a mission specific keyword.
+    /// The name is stored without trailing spaces.
+    Custom(String),
+}
+
+impl Display for Keyword {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        match *self {
+            Keyword::AREASCAL => f.pad("AREASCAL"),
+            Keyword::AV => f.pad("AV"),
+            Keyword::BACKSCAL => f.pad("BACKSCAL"),
+            Keyword::BIASSEC => f.pad("BIASSEC"),
+            Keyword::BITPIX => f.pad("BITPIX"),
+            Keyword::BJDREFF => f.pad("BJDREFF"),
+            Keyword::BJDREFI => f.pad("BJDREFI"),
+            Keyword::BLANK => f.pad("BLANK"),
+            Keyword::BSCALE => f.pad("BSCALE"),
+            Keyword::BZERO => f.pad("BZERO"),
+            Keyword::CAMPAIGN => f.pad("CAMPAIGN"),
+            Keyword::CCDSEC => f.pad("CCDSEC"),
+            Keyword::CHANNEL => f.pad("CHANNEL"),
+            Keyword::CHECKSUM => f.pad("CHECKSUM"),
+            Keyword::COMMENT => f.pad("COMMENT"),
+            Keyword::CONTINUE => f.pad("CONTINUE"),
+            Keyword::CREATOR => f.pad("CREATOR"),
+            Keyword::DATASEC => f.pad("DATASEC"),
+            Keyword::DATASUM => f.pad("DATASUM"),
+            Keyword::DATA_REL => f.pad("DATA_REL"),
+            Keyword::DATE => f.pad("DATE"),
+            Keyword::DEC_OBJ => f.pad("DEC_OBJ"),
+            Keyword::DETCHANS => f.pad("DETCHANS"),
+            Keyword::DETSEC => f.pad("DETSEC"),
+            Keyword::EBMINUSV => f.pad("EBMINUSV"),
+            Keyword::END => f.pad("END"),
+            Keyword::EQUINOX => f.pad("EQUINOX"),
+            Keyword::EXPOSURE => f.pad("EXPOSURE"),
+            Keyword::EXTEND => f.pad("EXTEND"),
+            Keyword::EXTLEVEL => f.pad("EXTLEVEL"),
+            Keyword::EXTNAME => f.pad("EXTNAME"),
+            Keyword::EXTVER => f.pad("EXTVER"),
+            Keyword::FEH => f.pad("FEH"),
+            Keyword::FILEVER => f.pad("FILEVER"),
+            Keyword::GCOUNT => f.pad("GCOUNT"),
+            Keyword::GKCOLOR => f.pad("GKCOLOR"),
+            Keyword::GLAT => f.pad("GLAT"),
+            Keyword::GLON => f.pad("GLON"),
+            Keyword::GMAG => f.pad("GMAG"),
+            Keyword::GRCOLOR => f.pad("GRCOLOR"),
+            Keyword::GROUPS => f.pad("GROUPS"),
+            Keyword::GRPNAME => f.pad("GRPNAME"),
+            Keyword::HISTORY => f.pad("HISTORY"),
+            Keyword::HMAG => f.pad("HMAG"),
+            Keyword::IMAG => f.pad("IMAG"),
+            Keyword::INHERIT => f.pad("INHERIT"),
+            Keyword::INSTRUME => f.pad("INSTRUME"),
+            Keyword::JKCOLOR => f.pad("JKCOLOR"),
+            Keyword::JMAG => f.pad("JMAG"),
+            Keyword::KEPLERID => f.pad("KEPLERID"),
+            Keyword::KEPMAG => f.pad("KEPMAG"),
+            Keyword::KMAG => f.pad("KMAG"),
+            Keyword::LOGG => f.pad("LOGG"),
+            Keyword::MISSION => f.pad("MISSION"),
+            Keyword::MJDREF => f.pad("MJDREF"),
+            Keyword::MJDREFF => f.pad("MJDREFF"),
+            Keyword::MJDREFI => f.pad("MJDREFI"),
+            Keyword::MODULE => f.pad("MODULE"),
+            Keyword::NAXIS => f.pad("NAXIS"),
+            Keyword::NEXTEND => f.pad("NEXTEND"),
+            Keyword::OBJECT => f.pad("OBJECT"),
+            Keyword::OBSMODE => f.pad("OBSMODE"),
+            Keyword::ORIGIN => f.pad("ORIGIN"),
+            Keyword::OUTPUT => f.pad("OUTPUT"),
+            Keyword::PARALLAX => f.pad("PARALLAX"),
+            Keyword::PCOUNT => f.pad("PCOUNT"),
+            Keyword::PMDEC => f.pad("PMDEC"),
+            Keyword::PMRA => f.pad("PMRA"),
+            Keyword::PMTOTAL => f.pad("PMTOTAL"),
+            Keyword::PROCVER => f.pad("PROCVER"),
+            Keyword::RADESYS => f.pad("RADESYS"),
+            Keyword::RADIUS => f.pad("RADIUS"),
+            Keyword::RA_OBJ => f.pad("RA_OBJ"),
+            Keyword::RMAG => f.pad("RMAG"),
+            Keyword::SIMPLE => f.pad("SIMPLE"),
+            Keyword::TASSIGN => f.pad("TASSIGN"),
+            Keyword::TEFF => f.pad("TEFF"),
+            Keyword::TELESCOP => f.pad("TELESCOP"),
+            Keyword::TFIELDS => f.pad("TFIELDS"),
+            Keyword::THEAP => f.pad("THEAP"),
+            Keyword::TIMEREF => f.pad("TIMEREF"),
+            Keyword::TIMESYS => f.pad("TIMESYS"),
+            Keyword::TIMEUNIT => f.pad("TIMEUNIT"),
+            Keyword::TIMSLICE => f.pad("TIMSLICE"),
+            Keyword::TIMVERSN => f.pad("TIMVERSN"),
+            Keyword::TMINDEX => f.pad("TMINDEX"),
+            Keyword::TRIMSEC => f.pad("TRIMSEC"),
+            Keyword::TTABLEID => f.pad("TTABLEID"),
+            Keyword::XTENSION => f.pad("XTENSION"),
+            Keyword::ZMAG => f.pad("ZMAG"),
+            Keyword::NAXISn(n) => f.pad(&format!("NAXIS{}", n)),
+            Keyword::GRPIDn(n) => f.pad(&format!("GRPID{}", n)),
+            Keyword::GRPLCn(n) => f.pad(&format!("GRPLC{}", n)),
+            Keyword::PSCALn(n) => f.pad(&format!("PSCAL{}", n)),
+            Keyword::PTYPEn(n) => f.pad(&format!("PTYPE{}", n)),
+            Keyword::PZEROn(n) => f.pad(&format!("PZERO{}", n)),
+            Keyword::TDIMn(n) => f.pad(&format!("TDIM{}", n)),
+            Keyword::TDISPn(n) => f.pad(&format!("TDISP{}", n)),
+            Keyword::TFORMn(n) => f.pad(&format!("TFORM{}", n)),
+            Keyword::TNULLn(n) => f.pad(&format!("TNULL{}", n)),
+            Keyword::TSCALn(n) => f.pad(&format!("TSCAL{}", n)),
+            Keyword::TTYPEn(n) => f.pad(&format!("TTYPE{}", n)),
+            Keyword::TUNITn(n) => f.pad(&format!("TUNIT{}", n)),
+            Keyword::TZEROn(n) => f.pad(&format!("TZERO{}", n)),
+            Keyword::BlankField => f.pad(""),
+            Keyword::Custom(ref name) => f.pad(name),
+        }
+    }
 }
 
 /// Problems that could occur when parsing a `str` for a Keyword are enumerated here.
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum ParseKeywordError {
     /// When a str can not be recognized as a keyword, this error will be returned.
     UnknownKeyword,
     /// When `NAXIS<number>` et. al. are parsed where `<number>` is not an actual number.
     NotANumber,
+    /// When the index of `NAXIS<number>` et. al. is 0 or larger than `MAXIMUM_INDEX`.
+    IndexOutOfRange(String),
+}
+
+impl Display for ParseKeywordError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        match *self {
+            ParseKeywordError::UnknownKeyword => write!(f, "unknown keyword"),
+            ParseKeywordError::NotANumber => write!(f, "the index of the keyword is not a number"),
+            ParseKeywordError::IndexOutOfRange(ref keyword) => write!(f, "the index of {} is not between 1 and {}", keyword, MAXIMUM_INDEX),
+        }
+    }
+}
+
+impl error::Error for ParseKeywordError {}
+
+/// The largest index of an indexed keyword, e.g. `NAXIS999`. Indices start at 1.
+pub const MAXIMUM_INDEX: u16 = 999;
+
+impl Keyword {
+    /// The indexed keyword with this prefix, e.g. `NAXIS` for `NAXISn`, and
+    /// index. Unlike `Keyword::NAXISn(n)` et. al. the index is checked.
+    pub fn indexed(prefix: &str, index: usize) -> Result<Keyword, ParseKeywordError> {
+        let &(_, constructor) = INDEXED_KEYWORDS.iter().find(|(candidate, _)| *candidate == prefix).ok_or(ParseKeywordError::UnknownKeyword)?;
+        match u16::try_from(index) {
+            Ok(index) if (1..=MAXIMUM_INDEX).contains(&index) => Ok(constructor(index)),
+            _ => Err(ParseKeywordError::IndexOutOfRange(format!("{}{}", prefix, index))),
+        }
+    }
+
+    /// Whether the name of this keyword parses back into the same keyword.
+    /// Every known keyword does. A `Custom` keyword does not when its name is
+    /// that of another keyword, e.g. `SIMPLE` or `NAXIS1`, or has trailing spaces.
+    pub fn round_trips(&self) -> bool {
+        Keyword::from_str(&self.to_string()).is_ok_and(|keyword| keyword == *self)
+    }
 }
 
 impl FromStr for Keyword {
     type Err = ParseKeywordError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.trim_right() {
-            "AV" => Ok(Keyword::AV),
-            "BITPIX" => Ok(Keyword::BITPIX),
-            "CAMPAIGN" => Ok(Keyword::CAMPAIGN),
-            "CHANNEL" => Ok(Keyword::CHANNEL),
-            "CHECKSUM" => Ok(Keyword::CHECKSUM),
-            "CREATOR" => Ok(Keyword::CREATOR),
-            "DATASUM" => Ok(Keyword::DATASUM),
-            "DATA_REL" => Ok(Keyword::DATA_REL),
-            "DATE" => Ok(Keyword::DATE),
-            "DEC_OBJ" => Ok(Keyword::DEC_OBJ),
-            "EBMINUSV" => Ok(Keyword::EBMINUSV),
-            "END" => Ok(Keyword::END),
-            "EQUINOX" => Ok(Keyword::EQUINOX),
-            "EXTEND" => Ok(Keyword::EXTEND),
-            "EXTNAME" => Ok(Keyword::EXTNAME),
-            "EXTVER" => Ok(Keyword::EXTVER),
-            "FEH" => Ok(Keyword::FEH),
-            "FILEVER" => Ok(Keyword::FILEVER),
-            "GCOUNT" => Ok(Keyword::GCOUNT),
-            "GKCOLOR" => Ok(Keyword::GKCOLOR),
-            "GLAT" => Ok(Keyword::GLAT),
-            "GLON" => Ok(Keyword::GLON),
-            "GMAG" => Ok(Keyword::GMAG),
-            "GRCOLOR" => Ok(Keyword::GRCOLOR),
-            "HMAG" => Ok(Keyword::HMAG),
-            "IMAG" => Ok(Keyword::IMAG),
-            "INSTRUME" => Ok(Keyword::INSTRUME),
-            "JKCOLOR" => Ok(Keyword::JKCOLOR),
-            "JMAG" => Ok(Keyword::JMAG),
-            "KEPLERID" => Ok(Keyword::KEPLERID),
-            "KEPMAG" => Ok(Keyword::KEPMAG),
-            "KMAG" => Ok(Keyword::KMAG),
-            "LOGG" => Ok(Keyword::LOGG),
-            "MISSION" => Ok(Keyword::MISSION),
-            "MODULE" => Ok(Keyword::MODULE),
-            "NAXIS" => Ok(Keyword::NAXIS),
-            "NEXTEND" => Ok(Keyword::NEXTEND),
-            "OBJECT" => Ok(Keyword::OBJECT),
-            "OBSMODE" => Ok(Keyword::OBSMODE),
-            "ORIGIN" => Ok(Keyword::ORIGIN),
-            "OUTPUT" => Ok(Keyword::OUTPUT),
-            "PARALLAX" => Ok(Keyword::PARALLAX),
-            "PCOUNT" => Ok(Keyword::PCOUNT),
-            "PMDEC" => Ok(Keyword::PMDEC),
-            "PMRA" => Ok(Keyword::PMRA),
-            "PMTOTAL" => Ok(Keyword::PMTOTAL),
-            "PROCVER" => Ok(Keyword::PROCVER),
-            "RADESYS" => Ok(Keyword::RADESYS),
-            "RADIUS" => Ok(Keyword::RADIUS),
-            "RA_OBJ" => Ok(Keyword::RA_OBJ),
-            "RMAG" => Ok(Keyword::RMAG),
-            "SIMPLE" => Ok(Keyword::SIMPLE),
-            "TEFF" => Ok(Keyword::TEFF),
-            "TELESCOP" => Ok(Keyword::TELESCOP),
-            "TFIELDS" => Ok(Keyword::TFIELDS),
-            "THEAP" => Ok(Keyword::THEAP),
-            "TIMVERSN" => Ok(Keyword::TIMVERSN),
-            "TMINDEX" => Ok(Keyword::TMINDEX),
-            "TTABLEID" => Ok(Keyword::TTABLEID),
-            "XTENSION" => Ok(Keyword::XTENSION),
-            "ZMAG" => Ok(Keyword::ZMAG),
-            input @ _ => {
-                let t_dim_constructor = Keyword::TDIMn;
-                let t_disp_constructor = Keyword::TDISPn;
-                let t_form_constructor = Keyword::TFORMn;
-                let naxis_constructor = Keyword::NAXISn;
-                let t_null_constructor = Keyword::TNULLn;
-                let t_scal_constructor = Keyword::TSCALn;
-                let t_type_constructor = Keyword::TTYPEn;
-                let t_unit_constructor = Keyword::TUNITn;
-                let t_zero_constructor = Keyword::TZEROn;
-                let tuples: Vec<(&str, &(Fn(u16) -> Keyword))> = vec!(
-                    ("TDIM", &t_dim_constructor),
-                    ("TDISP", &t_disp_constructor),
-                    ("TFORM", &t_form_constructor),
-                    ("NAXIS", &naxis_constructor),
-                    ("TNULL", &t_null_constructor),
-                    ("TSCAL", &t_scal_constructor),
-                    ("TTYPE", &t_type_constructor),
-                    ("TUNIT", &t_unit_constructor),
-                    ("TZERO", &t_zero_constructor),
-                );
-                let special_cases: Vec<PrefixedKeyword> =
-                    tuples
-                    .into_iter()
-                    .map(|(prefix, constructor)|{ PrefixedKeyword::new(prefix, constructor)})
-                    .collect();
-                for special_case in special_cases {
-                    if special_case.handles(input) {
-                        return special_case.transform(input)
-                    }
+        let input = s.trim_end();
+        if input.is_empty() {
+            return Ok(Keyword::BlankField)
+        }
+        let known = match input.as_bytes()[0] {
+            b'A' => match input {
+                "AREASCAL" => Some(Keyword::AREASCAL),
+                "AV" => Some(Keyword::AV),
+                _ => None,
+            },
+            b'B' => match input {
+                "BACKSCAL" => Some(Keyword::BACKSCAL),
+                "BIASSEC" => Some(Keyword::BIASSEC),
+                "BITPIX" => Some(Keyword::BITPIX),
+                "BJDREFF" => Some(Keyword::BJDREFF),
+                "BJDREFI" => Some(Keyword::BJDREFI),
+                "BLANK" => Some(Keyword::BLANK),
+                "BSCALE" => Some(Keyword::BSCALE),
+                "BZERO" => Some(Keyword::BZERO),
+                _ => None,
+            },
+            b'C' => match input {
+                "CAMPAIGN" => Some(Keyword::CAMPAIGN),
+                "CCDSEC" => Some(Keyword::CCDSEC),
+                "CHANNEL" => Some(Keyword::CHANNEL),
+                "CHECKSUM" => Some(Keyword::CHECKSUM),
+                "COMMENT" => Some(Keyword::COMMENT),
+                "CONTINUE" => Some(Keyword::CONTINUE),
+                "CREATOR" => Some(Keyword::CREATOR),
+                _ => None,
+            },
+            b'D' => match input {
+                "DATASEC" => Some(Keyword::DATASEC),
+                "DATASUM" => Some(Keyword::DATASUM),
+                "DATA_REL" => Some(Keyword::DATA_REL),
+                "DATE" => Some(Keyword::DATE),
+                "DEC_OBJ" => Some(Keyword::DEC_OBJ),
+                "DETCHANS" => Some(Keyword::DETCHANS),
+                "DETSEC" => Some(Keyword::DETSEC),
+                _ => None,
+            },
+            b'E' => match input {
+                "EBMINUSV" => Some(Keyword::EBMINUSV),
+                "END" => Some(Keyword::END),
+                "EQUINOX" => Some(Keyword::EQUINOX),
+                "EXPOSURE" => Some(Keyword::EXPOSURE),
+                "EXTEND" => Some(Keyword::EXTEND),
+                "EXTLEVEL" => Some(Keyword::EXTLEVEL),
+                "EXTNAME" => Some(Keyword::EXTNAME),
+                "EXTVER" => Some(Keyword::EXTVER),
+                _ => None,
+            },
+            b'F' => match input {
+                "FEH" => Some(Keyword::FEH),
+                "FILEVER" => Some(Keyword::FILEVER),
+                _ => None,
+            },
+            b'G' => match input {
+                "GCOUNT" => Some(Keyword::GCOUNT),
+                "GKCOLOR" => Some(Keyword::GKCOLOR),
+                "GLAT" => Some(Keyword::GLAT),
+                "GLON" => Some(Keyword::GLON),
+                "GMAG" => Some(Keyword::GMAG),
+                "GRCOLOR" => Some(Keyword::GRCOLOR),
+                "GROUPS" => Some(Keyword::GROUPS),
+                "GRPNAME" => Some(Keyword::GRPNAME),
+                _ => None,
+            },
+            b'H' => match input {
+                "HISTORY" => Some(Keyword::HISTORY),
+                "HMAG" => Some(Keyword::HMAG),
+                _ => None,
+            },
+            b'I' => match input {
+                "IMAG" => Some(Keyword::IMAG),
+                "INHERIT" => Some(Keyword::INHERIT),
+                "INSTRUME" => Some(Keyword::INSTRUME),
+                _ => None,
+            },
+            b'J' => match input {
+                "JKCOLOR" => Some(Keyword::JKCOLOR),
+                "JMAG" => Some(Keyword::JMAG),
+                _ => None,
+            },
+            b'K' => match input {
+                "KEPLERID" => Some(Keyword::KEPLERID),
+                "KEPMAG" => Some(Keyword::KEPMAG),
+                "KMAG" => Some(Keyword::KMAG),
+                _ => None,
+            },
+            b'L' => match input {
+                "LOGG" => Some(Keyword::LOGG),
+                _ => None,
+            },
+            b'M' => match input {
+                "MISSION" => Some(Keyword::MISSION),
+                "MJDREF" => Some(Keyword::MJDREF),
+                "MJDREFF" => Some(Keyword::MJDREFF),
+                "MJDREFI" => Some(Keyword::MJDREFI),
+                "MODULE" => Some(Keyword::MODULE),
+                _ => None,
+            },
+            b'N' => match input {
+                "NAXIS" => Some(Keyword::NAXIS),
+                "NEXTEND" => Some(Keyword::NEXTEND),
+                _ => None,
+            },
+            b'O' => match input {
+                "OBJECT" => Some(Keyword::OBJECT),
+                "OBSMODE" => Some(Keyword::OBSMODE),
+                "ORIGIN" => Some(Keyword::ORIGIN),
+                "OUTPUT" => Some(Keyword::OUTPUT),
+                _ => None,
+            },
+            b'P' => match input {
+                "PARALLAX" => Some(Keyword::PARALLAX),
+                "PCOUNT" => Some(Keyword::PCOUNT),
+                "PMDEC" => Some(Keyword::PMDEC),
+                "PMRA" => Some(Keyword::PMRA),
+                "PMTOTAL" => Some(Keyword::PMTOTAL),
+                "PROCVER" => Some(Keyword::PROCVER),
+                _ => None,
+            },
+            b'R' => match input {
+                "RADESYS" => Some(Keyword::RADESYS),
+                "RADIUS" => Some(Keyword::RADIUS),
+                "RA_OBJ" => Some(Keyword::RA_OBJ),
+                "RMAG" => Some(Keyword::RMAG),
+                _ => None,
+            },
+            b'S' => match input {
+                "SIMPLE" => Some(Keyword::SIMPLE),
+                _ => None,
+            },
+            b'T' => match input {
+                "TASSIGN" => Some(Keyword::TASSIGN),
+                "TEFF" => Some(Keyword::TEFF),
+                "TELESCOP" => Some(Keyword::TELESCOP),
+                "TFIELDS" => Some(Keyword::TFIELDS),
+                "THEAP" => Some(Keyword::THEAP),
+                "TIMEREF" => Some(Keyword::TIMEREF),
+                "TIMESYS" => Some(Keyword::TIMESYS),
+                "TIMEUNIT" => Some(Keyword::TIMEUNIT),
+                "TIMSLICE" => Some(Keyword::TIMSLICE),
+                "TIMVERSN" => Some(Keyword::TIMVERSN),
+                "TMINDEX" => Some(Keyword::TMINDEX),
+                "TRIMSEC" => Some(Keyword::TRIMSEC),
+                "TTABLEID" => Some(Keyword::TTABLEID),
+                _ => None,
+            },
+            b'X' => match input {
+                "XTENSION" => Some(Keyword::XTENSION),
+                _ => None,
+            },
+            b'Z' => match input {
+                "ZMAG" => Some(Keyword::ZMAG),
+                _ => None,
+            },
+            _ => None,
+        };
+        if let Some(keyword) = known {
+            return Ok(keyword)
+        }
+        // Only inputs that end in a digit can be indexed keywords, and only
+        // the prefixes that start alike need to be tried.
+        if input.ends_with(|c: char| c.is_ascii_digit()) {
+            let first = input.as_bytes()[0];
+            for &(prefix, constructor) in INDEXED_KEYWORDS.iter().filter(|(prefix, _)| prefix.as_bytes()[0] == first) {
+                if let Some(keyword) = indexed_keyword(input, prefix, constructor) {
+                    return keyword
                 }
-                Ok(Keyword::Unprocessed)
-                //Err(ParseKeywordError::UnknownKeyword)
             }
         }
+        Ok(Keyword::Custom(input.to_string()))
     }
 }
 
-trait KeywordSpecialCase {
-    fn handles(&self, input: &str) -> bool;
-    fn transform(&self, input: &str) -> Result<Keyword, ParseKeywordError>;
-}
-
-struct PrefixedKeyword<'a> {
-    prefix: &'a str,
-    constructor: &'a (Fn(u16) -> Keyword),
-}
+// Constructs an indexed keyword from its index.
+type IndexedConstructor = fn(u16) -> Keyword;
 
-impl<'a> PrefixedKeyword<'a> {
-    fn new(prefix: &'a str, constructor: &'a (Fn(u16) -> Keyword)) -> PrefixedKeyword<'a> {
-        PrefixedKeyword { prefix: prefix, constructor: constructor }
-    }
-}
+/// The prefixes of indexed keywords, e.g. `NAXIS` of `NAXIS1`, the most
+/// frequent first.
+const INDEXED_KEYWORDS: [(&str, IndexedConstructor); 14] = [
+    ("NAXIS", Keyword::NAXISn),
+    ("TTYPE", Keyword::TTYPEn),
+    ("TFORM", Keyword::TFORMn),
+    ("TUNIT", Keyword::TUNITn),
+    ("TDISP", Keyword::TDISPn),
+    ("TDIM", Keyword::TDIMn),
+    ("TNULL", Keyword::TNULLn),
+    ("TSCAL", Keyword::TSCALn),
+    ("TZERO", Keyword::TZEROn),
+    ("PTYPE", Keyword::PTYPEn),
+    ("PSCAL", Keyword::PSCALn),
+    ("PZERO", Keyword::PZEROn),
+    ("GRPID", Keyword::GRPIDn),
+    ("GRPLC", Keyword::GRPLCn),
+];
 
-impl<'a> KeywordSpecialCase for PrefixedKeyword<'a> {
-    fn handles(&self, input: &str) -> bool {
-        input.starts_with(self.prefix)
+// The indexed keyword for `input` when it consists of `prefix` followed by digits.
+fn indexed_keyword(input: &str, prefix: &str, constructor: IndexedConstructor) -> Option<Result<Keyword, ParseKeywordError>> {
+    let representation = input.strip_prefix(prefix)?;
+    if representation.is_empty() || !representation.bytes().all(|b| b.is_ascii_digit()) {
+        return None
     }
-
-    fn transform(&self, input: &str) -> Result<Keyword, ParseKeywordError> {
-        let (_, representation) = input.split_at(self.prefix.len());
-        match u16::from_str(representation) {
-            Ok(n) => Ok((self.constructor)(n)),
-            Err(_) => Err(ParseKeywordError::NotANumber)
-        }
+    match u16::from_str(representation) {
+        Ok(index) if (1..=MAXIMUM_INDEX).contains(&index) => Some(Ok(constructor(index))),
+        _ => Some(Err(ParseKeywordError::IndexOutOfRange(input.to_string()))),
     }
 }
 
-/// For input n and k, finds the least multiple of k such that n <= q*k and
-/// (q-1)*k < n
-fn lmle(n: usize, k: usize) -> usize {
-    let (q, r) = (n / k, n % k);
+/// The size in bytes of a FITS block. Headers and data units are padded to a
+/// multiple of this size.
+pub const BLOCK_SIZE: usize = 2880;
+
+/// For input n and k, finds the least multiple q*k of k such that n <= q*k and
+/// (q-1)*k < n. Returns `None` when k is zero or q*k does not fit in a `usize`.
+///
+/// E.g. `lmle(n, BLOCK_SIZE)` is the size of a unit of n bytes, including padding.
+pub fn lmle(n: usize, k: usize) -> Option<usize> {
+    let (q, r) = (n.checked_div(k)?, n % k);
     if r == 0 {
-        q * k
+        Some(q * k)
     } else {
-        (q + 1) * k
+        (q + 1).checked_mul(k)
     }
 }
 
+/// The number of blocks needed for a unit of `bytes` bytes.
+pub fn block_count(bytes: usize) -> usize {
+    bytes.div_ceil(BLOCK_SIZE)
+}
+
 #[cfg(test)]
+#[allow(clippy::identity_op)]
 mod tests {
+    use std::borrow::Cow;
     use std::str::FromStr;
     use super::*;
 
@@ -427,13 +2236,96 @@ mod tests {
         );
     }
 
+    #[test]
+    fn first_data_hdu_should_skip_hdus_without_data() {
+        let data = [0u8; 4];
+        let empty = || Header::new(vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(0i64), Option::None),
+        ));
+        let image = Header::new(vec!(
+            KeywordRecord::new(Keyword::XTENSION, Value::CharacterString(Cow::Borrowed("IMAGE")), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(32i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(1i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(1i64), Option::None),
+        ));
+        let fits = Fits::new(HDU::with_data(empty(), &data), vec!(HDU::with_data(image, &data)));
+
+        assert_eq!(fits.first_data_hdu(), Some(&fits.extensions[0]));
+        assert_eq!(Fits::new(HDU::new(empty()), vec!()).first_data_hdu(), None);
+    }
+
+    #[test]
+    fn adding_and_removing_extensions_should_maintain_extend_and_nextend() {
+        let primary = Header::new(vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(0i64), Option::None),
+            KeywordRecord::new(Keyword::NEXTEND, Value::Integer(7i64), Option::None),
+            KeywordRecord::new(Keyword::END, Value::Undefined, Option::None),
+        ));
+        let extension = || HDU::new(Header::new(vec!(
+            KeywordRecord::new(Keyword::XTENSION, Value::CharacterString(Cow::Borrowed("IMAGE")), Option::None),
+        )));
+        let mut fits = Fits::new(HDU::new(primary), vec!());
+
+        fits.push_extension(extension());
+        fits.insert_extension(0, extension()).unwrap();
+
+        assert_eq!(fits.primary_hdu.header.keyword_records[3].keyword, Keyword::EXTEND);
+        assert_eq!(fits.primary_hdu.header.get_logical(&Keyword::EXTEND), Ok(true));
+        assert_eq!(fits.primary_hdu.header.get_int(&Keyword::NEXTEND), Ok(2i64));
+
+        assert!(fits.remove_extension(1).is_some());
+        assert!(fits.remove_extension(1).is_none());
+
+        assert_eq!(fits.primary_hdu.header.get_int(&Keyword::NEXTEND), Ok(1i64));
+        assert_eq!(fits.primary_hdu.header.records_of(&Keyword::EXTEND).len(), 1);
+
+        fits.extensions.clear();
+
+        assert_eq!(fits.primary_hdu.header.get_int(&Keyword::NEXTEND), Ok(1i64));
+    }
+
+    #[test]
+    fn raw_extension_changes_should_leave_the_primary_header_alone() {
+        let primary = Header::new(vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(0i64), Option::None),
+            KeywordRecord::new(Keyword::NEXTEND, Value::Integer(7i64), Option::None),
+            KeywordRecord::new(Keyword::CHECKSUM, Value::CharacterString(Cow::Borrowed("hcHjjc9ghcEghc9g")), Option::None),
+            KeywordRecord::new(Keyword::END, Value::Undefined, Option::None),
+        ));
+        let extension = || HDU::new(Header::new(vec!(
+            KeywordRecord::new(Keyword::XTENSION, Value::CharacterString(Cow::Borrowed("IMAGE")), Option::None),
+        )));
+        let mut fits = Fits::new(HDU::new(primary.clone()), vec!());
+
+        fits.push_extension_raw(extension());
+        fits.insert_extension_raw(0, extension()).unwrap();
+
+        assert_eq!(fits.extensions.len(), 2);
+        assert_eq!(fits.insert_extension_raw(3, extension()), Err(ExtensionError::OutOfRange { index: 3, count: 2 }));
+        assert!(fits.remove_extension_raw(1).is_some());
+        assert!(fits.remove_extension_raw(1).is_none());
+        assert_eq!(fits.extensions.len(), 1);
+        assert_eq!(fits.primary_hdu.header, primary);
+
+        fits.update_extend();
+
+        assert_eq!(fits.primary_hdu.header.get_int(&Keyword::NEXTEND), Ok(1i64));
+        assert!(fits.primary_hdu.header.get(&Keyword::CHECKSUM).is_none());
+    }
+
     #[test]
     fn header_constructed_from_the_new_function_should_eq_hand_construction() {
         assert_eq!(
             Header { keyword_records: vec!(
                 KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
                 KeywordRecord::new(Keyword::NEXTEND, Value::Integer(0i64), Option::Some("no extensions")),
-            )},
+            ), warnings: vec!() },
             Header::new(vec!(
                 KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
                 KeywordRecord::new(Keyword::NEXTEND, Value::Integer(0i64), Option::Some("no extensions")),
@@ -442,29 +2334,267 @@ mod tests {
     }
 
     #[test]
-    fn keyword_record_constructed_from_the_new_function_should_eq_hand_construction() {
-        assert_eq!(
-            KeywordRecord { keyword: Keyword::ORIGIN, value: Value::Undefined, comment: Option::None },
-            KeywordRecord::new(Keyword::ORIGIN, Value::Undefined, Option::None));
+    fn keyword_records_should_be_displayed_in_fits_notation() {
+        let data = [
+            (KeywordRecord::new(Keyword::OBJECT, Value::CharacterString(Cow::Borrowed("TRAPPIST-1")), Option::Some("target")),
+             "OBJECT  = 'TRAPPIST-1'         / target"),
+            (KeywordRecord::new(Keyword::NAXIS, Value::Integer(0i64), Option::None),
+             "NAXIS   =                    0"),
+            (KeywordRecord::new(Keyword::COMMENT, Value::CommentText(Cow::Borrowed("a comment")), Option::None),
+             "COMMENT a comment"),
+            (KeywordRecord::new(Keyword::CONTINUE, Value::CharacterString(Cow::Borrowed("continued")), Option::None),
+             "CONTINUE  'continued'"),
+            (KeywordRecord::new(Keyword::HISTORY, Value::CommentText(Cow::Owned("x".repeat(90))), Option::None),
+             &*format!("HISTORY {}", "x".repeat(72))),
+        ];
+
+        for (record, expected) in data.iter() {
+            assert_eq!(record.to_string(), format!("{:<80}", expected));
+        }
+    }
+
+    #[test]
+    fn continuation_should_be_appended_to_a_continued_string() {
+        let mut record = KeywordRecord::new(Keyword::OBJECT, Value::CharacterString(Cow::Borrowed("a long &")), Option::Some("first"));
+        let continuation = KeywordRecord::new(Keyword::CONTINUE, Value::CharacterString(Cow::Borrowed("string")), Option::Some("second"));
+
+        assert!(record.is_continued());
+        record.append_continuation(&continuation);
+
+        assert!(!record.is_continued());
+        assert_eq!(record.value(), &Value::CharacterString(Cow::Borrowed("a long string")));
+        assert_eq!(record.comment(), Some("first second"));
+        assert_eq!(record.cards(), 2);
+    }
+
+    #[test]
+    fn keyword_record_constructed_from_the_new_function_should_eq_hand_construction() {
+        assert_eq!(
+            KeywordRecord { keyword: Keyword::ORIGIN, value: Value::Undefined, comment: Option::None, comment_column: Option::None, cards: 1 },
+            KeywordRecord::new(Keyword::ORIGIN, Value::Undefined, Option::None));
+    }
+
+    // One keyword of every variant, in the order of declaration. `variant` has
+    // no catch-all, so a new variant does not compile until it is numbered
+    // there, and the round trip test fails until it is listed here.
+    fn every_keyword() -> Vec<Keyword> {
+        vec!(
+            Keyword::AREASCAL, Keyword::AV, Keyword::BACKSCAL, Keyword::BIASSEC, Keyword::BITPIX,
+            Keyword::BJDREFF, Keyword::BJDREFI, Keyword::BLANK, Keyword::BSCALE, Keyword::BZERO,
+            Keyword::CAMPAIGN, Keyword::CCDSEC, Keyword::CHANNEL, Keyword::CHECKSUM, Keyword::COMMENT,
+            Keyword::CONTINUE, Keyword::CREATOR, Keyword::DATASEC, Keyword::DATASUM, Keyword::DATA_REL,
+            Keyword::DATE, Keyword::DEC_OBJ, Keyword::DETCHANS, Keyword::DETSEC, Keyword::EBMINUSV, Keyword::END,
+            Keyword::EQUINOX, Keyword::EXPOSURE, Keyword::EXTEND, Keyword::EXTLEVEL, Keyword::EXTNAME,
+            Keyword::EXTVER,
+            Keyword::FEH, Keyword::FILEVER, Keyword::GCOUNT, Keyword::GKCOLOR, Keyword::GLAT, Keyword::GLON,
+            Keyword::GMAG, Keyword::GRCOLOR, Keyword::GRPIDn(1u16), Keyword::GRPLCn(1u16), Keyword::GROUPS,
+            Keyword::GRPNAME,
+            Keyword::HISTORY, Keyword::HMAG, Keyword::IMAG,
+            Keyword::INHERIT, Keyword::INSTRUME, Keyword::JKCOLOR,
+            Keyword::JMAG, Keyword::KEPLERID, Keyword::KEPMAG, Keyword::KMAG, Keyword::LOGG,
+            Keyword::MISSION, Keyword::MJDREF, Keyword::MJDREFF, Keyword::MJDREFI,
+            Keyword::MODULE, Keyword::NAXIS, Keyword::NAXISn(1u16), Keyword::NEXTEND,
+            Keyword::OBJECT, Keyword::OBSMODE, Keyword::ORIGIN, Keyword::OUTPUT, Keyword::PARALLAX,
+            Keyword::PCOUNT, Keyword::PSCALn(1u16), Keyword::PTYPEn(1u16), Keyword::PZEROn(1u16),
+            Keyword::PMDEC, Keyword::PMRA, Keyword::PMTOTAL, Keyword::PROCVER,
+            Keyword::RADESYS, Keyword::RADIUS, Keyword::RA_OBJ, Keyword::RMAG, Keyword::SIMPLE,
+            Keyword::TDIMn(1u16), Keyword::TDISPn(1u16), Keyword::TASSIGN,
+            Keyword::TEFF, Keyword::TELESCOP, Keyword::TFIELDS,
+            Keyword::TFORMn(1u16), Keyword::TIMEREF, Keyword::TIMESYS, Keyword::TIMEUNIT,
+            Keyword::TIMSLICE, Keyword::TIMVERSN, Keyword::THEAP, Keyword::TMINDEX,
+            Keyword::TNULLn(1u16), Keyword::TSCALn(1u16), Keyword::TRIMSEC, Keyword::TTABLEID,
+            Keyword::TTYPEn(1u16), Keyword::TUNITn(1u16), Keyword::TZEROn(1u16), Keyword::XTENSION,
+            Keyword::ZMAG, Keyword::BlankField, Keyword::Custom("CTYPE1".to_string())
+        )
+    }
+
+    fn variant(keyword: &Keyword) -> usize {
+        match *keyword {
+            Keyword::AREASCAL => 0,
+            Keyword::AV => 1,
+            Keyword::BACKSCAL => 2,
+            Keyword::BIASSEC => 3,
+            Keyword::BITPIX => 4,
+            Keyword::BJDREFF => 5,
+            Keyword::BJDREFI => 6,
+            Keyword::BLANK => 7,
+            Keyword::BSCALE => 8,
+            Keyword::BZERO => 9,
+            Keyword::CAMPAIGN => 10,
+            Keyword::CCDSEC => 11,
+            Keyword::CHANNEL => 12,
+            Keyword::CHECKSUM => 13,
+            Keyword::COMMENT => 14,
+            Keyword::CONTINUE => 15,
+            Keyword::CREATOR => 16,
+            Keyword::DATASEC => 17,
+            Keyword::DATASUM => 18,
+            Keyword::DATA_REL => 19,
+            Keyword::DATE => 20,
+            Keyword::DEC_OBJ => 21,
+            Keyword::DETCHANS => 22,
+            Keyword::DETSEC => 23,
+            Keyword::EBMINUSV => 24,
+            Keyword::END => 25,
+            Keyword::EQUINOX => 26,
+            Keyword::EXPOSURE => 27,
+            Keyword::EXTEND => 28,
+            Keyword::EXTLEVEL => 29,
+            Keyword::EXTNAME => 30,
+            Keyword::EXTVER => 31,
+            Keyword::FEH => 32,
+            Keyword::FILEVER => 33,
+            Keyword::GCOUNT => 34,
+            Keyword::GKCOLOR => 35,
+            Keyword::GLAT => 36,
+            Keyword::GLON => 37,
+            Keyword::GMAG => 38,
+            Keyword::GRCOLOR => 39,
+            Keyword::GRPIDn(_) => 40,
+            Keyword::GRPLCn(_) => 41,
+            Keyword::GROUPS => 42,
+            Keyword::GRPNAME => 43,
+            Keyword::HISTORY => 44,
+            Keyword::HMAG => 45,
+            Keyword::IMAG => 46,
+            Keyword::INHERIT => 47,
+            Keyword::INSTRUME => 48,
+            Keyword::JKCOLOR => 49,
+            Keyword::JMAG => 50,
+            Keyword::KEPLERID => 51,
+            Keyword::KEPMAG => 52,
+            Keyword::KMAG => 53,
+            Keyword::LOGG => 54,
+            Keyword::MISSION => 55,
+            Keyword::MJDREF => 56,
+            Keyword::MJDREFF => 57,
+            Keyword::MJDREFI => 58,
+            Keyword::MODULE => 59,
+            Keyword::NAXIS => 60,
+            Keyword::NAXISn(_) => 61,
+            Keyword::NEXTEND => 62,
+            Keyword::OBJECT => 63,
+            Keyword::OBSMODE => 64,
+            Keyword::ORIGIN => 65,
+            Keyword::OUTPUT => 66,
+            Keyword::PARALLAX => 67,
+            Keyword::PCOUNT => 68,
+            Keyword::PSCALn(_) => 69,
+            Keyword::PTYPEn(_) => 70,
+            Keyword::PZEROn(_) => 71,
+            Keyword::PMDEC => 72,
+            Keyword::PMRA => 73,
+            Keyword::PMTOTAL => 74,
+            Keyword::PROCVER => 75,
+            Keyword::RADESYS => 76,
+            Keyword::RADIUS => 77,
+            Keyword::RA_OBJ => 78,
+            Keyword::RMAG => 79,
+            Keyword::SIMPLE => 80,
+            Keyword::TDIMn(_) => 81,
+            Keyword::TDISPn(_) => 82,
+            Keyword::TASSIGN => 83,
+            Keyword::TEFF => 84,
+            Keyword::TELESCOP => 85,
+            Keyword::TFIELDS => 86,
+            Keyword::TFORMn(_) => 87,
+            Keyword::TIMEREF => 88,
+            Keyword::TIMESYS => 89,
+            Keyword::TIMEUNIT => 90,
+            Keyword::TIMSLICE => 91,
+            Keyword::TIMVERSN => 92,
+            Keyword::THEAP => 93,
+            Keyword::TMINDEX => 94,
+            Keyword::TNULLn(_) => 95,
+            Keyword::TSCALn(_) => 96,
+            Keyword::TRIMSEC => 97,
+            Keyword::TTABLEID => 98,
+            Keyword::TTYPEn(_) => 99,
+            Keyword::TUNITn(_) => 100,
+            Keyword::TZEROn(_) => 101,
+            Keyword::XTENSION => 102,
+            Keyword::ZMAG => 103,
+            Keyword::BlankField => 104,
+            Keyword::Custom(_) => 105,
+        }
+    }
+
+    #[test]
+    fn every_keyword_should_round_trip_through_display_and_from_str() {
+        let keywords = every_keyword();
+
+        assert_eq!(keywords.iter().map(variant).collect::<Vec<_>>(), (0..106).collect::<Vec<_>>());
+        for keyword in keywords {
+            assert!(keyword.round_trips(), "{:?} does not round trip", keyword);
+            assert!(keyword.to_string().len() <= 8, "{:?} is longer than 8 characters", keyword);
+        }
+    }
+
+    #[test]
+    fn indexed_keywords_should_round_trip_for_every_index() {
+        let constructors: Vec<fn(u16) -> Keyword> = vec!(
+            Keyword::GRPIDn, Keyword::GRPLCn, Keyword::NAXISn, Keyword::PSCALn, Keyword::PTYPEn, Keyword::PZEROn,
+            Keyword::TDIMn, Keyword::TDISPn, Keyword::TFORMn, Keyword::TNULLn, Keyword::TSCALn, Keyword::TTYPEn, Keyword::TUNITn, Keyword::TZEROn,
+        );
+
+        for constructor in constructors {
+            for n in [1u16, 9u16, 10u16, 99u16, 100u16, 999u16] {
+                assert!(constructor(n).round_trips(), "{:?} does not round trip", constructor(n));
+            }
+        }
+    }
+
+    #[test]
+    fn indices_of_indexed_keywords_should_lie_between_1_and_999() {
+        for name in ["NAXIS0", "NAXIS1000", "NAXIS65535", "TTYPE0", "TFORM00"] {
+            assert_eq!(Keyword::from_str(name), Err(ParseKeywordError::IndexOutOfRange(name.to_string())));
+        }
+        assert_eq!(Keyword::indexed("TTYPE", 999), Ok(Keyword::TTYPEn(999u16)));
+        assert_eq!(Keyword::indexed("NAXIS", 65537).unwrap_err().to_string(), "the index of NAXIS65537 is not between 1 and 999");
+        assert_eq!(Keyword::indexed("CTYPE", 1), Err(ParseKeywordError::UnknownKeyword));
+    }
+
+    #[test]
+    fn custom_keywords_that_shadow_other_keywords_should_not_round_trip() {
+        for name in ["SIMPLE", "NAXIS1", "NAXIS01", "", "CTYPE1  "] {
+            assert!(!Keyword::Custom(name.to_string()).round_trips(), "{:?} should not round trip", name);
+        }
+        assert!(Keyword::Custom("CRVAL1".to_string()).round_trips());
     }
 
     #[test]
     fn keywords_could_be_constructed_from_str() {
         let data = vec!(
             ("AV", Keyword::AV),
+            ("AREASCAL", Keyword::AREASCAL),
+            ("BACKSCAL", Keyword::BACKSCAL),
+            ("BIASSEC", Keyword::BIASSEC),
             ("BITPIX", Keyword::BITPIX),
+            ("BJDREFF", Keyword::BJDREFF),
+            ("BJDREFI", Keyword::BJDREFI),
+            ("BLANK", Keyword::BLANK),
+            ("BSCALE", Keyword::BSCALE),
+            ("BZERO", Keyword::BZERO),
             ("CAMPAIGN", Keyword::CAMPAIGN),
+            ("CCDSEC", Keyword::CCDSEC),
             ("CHANNEL", Keyword::CHANNEL),
             ("CHECKSUM", Keyword::CHECKSUM),
+            ("COMMENT", Keyword::COMMENT),
+            ("CONTINUE", Keyword::CONTINUE),
             ("CREATOR", Keyword::CREATOR),
+            ("DATASEC", Keyword::DATASEC),
             ("DATASUM", Keyword::DATASUM),
             ("DATA_REL", Keyword::DATA_REL),
             ("DATE", Keyword::DATE),
             ("DEC_OBJ", Keyword::DEC_OBJ),
+            ("DETCHANS", Keyword::DETCHANS),
+            ("DETSEC", Keyword::DETSEC),
             ("EBMINUSV", Keyword::EBMINUSV),
             ("END", Keyword::END),
             ("EQUINOX", Keyword::EQUINOX),
+            ("EXPOSURE", Keyword::EXPOSURE),
             ("EXTEND", Keyword::EXTEND),
+            ("EXTLEVEL", Keyword::EXTLEVEL),
             ("EXTVER", Keyword::EXTVER),
             ("FEH", Keyword::FEH),
             ("FILEVER", Keyword::FILEVER),
@@ -474,8 +2604,12 @@ mod tests {
             ("GLON", Keyword::GLON),
             ("GMAG", Keyword::GMAG),
             ("GRCOLOR", Keyword::GRCOLOR),
+            ("GROUPS", Keyword::GROUPS),
+            ("GRPNAME", Keyword::GRPNAME),
+            ("HISTORY", Keyword::HISTORY),
             ("HMAG", Keyword::HMAG),
             ("IMAG", Keyword::IMAG),
+            ("INHERIT", Keyword::INHERIT),
             ("INSTRUME", Keyword::INSTRUME),
             ("JKCOLOR", Keyword::JKCOLOR),
             ("JMAG", Keyword::JMAG),
@@ -484,6 +2618,9 @@ mod tests {
             ("KMAG", Keyword::KMAG),
             ("LOGG", Keyword::LOGG),
             ("MISSION", Keyword::MISSION),
+            ("MJDREF", Keyword::MJDREF),
+            ("MJDREFF", Keyword::MJDREFF),
+            ("MJDREFI", Keyword::MJDREFI),
             ("MODULE", Keyword::MODULE),
             ("NAXIS", Keyword::NAXIS),
             ("NEXTEND", Keyword::NEXTEND),
@@ -502,12 +2639,18 @@ mod tests {
             ("RA_OBJ", Keyword::RA_OBJ),
             ("RMAG", Keyword::RMAG),
             ("SIMPLE", Keyword::SIMPLE),
+            ("TASSIGN", Keyword::TASSIGN),
             ("TEFF", Keyword::TEFF),
             ("TELESCOP", Keyword::TELESCOP),
             ("TFIELDS", Keyword::TFIELDS),
+            ("TIMEREF", Keyword::TIMEREF),
+            ("TIMESYS", Keyword::TIMESYS),
+            ("TIMEUNIT", Keyword::TIMEUNIT),
+            ("TIMSLICE", Keyword::TIMSLICE),
             ("TIMVERSN", Keyword::TIMVERSN),
             ("THEAP", Keyword::THEAP),
             ("TMINDEX", Keyword::TMINDEX),
+            ("TRIMSEC", Keyword::TRIMSEC),
             ("TTABLEID", Keyword::TTABLEID),
             ("XTENSION", Keyword::XTENSION),
             ("ZMAG", Keyword::ZMAG),
@@ -518,6 +2661,28 @@ mod tests {
         }
     }
 
+    #[allow(non_snake_case)]
+    #[test]
+    fn GRPIDn_should_be_parsed_from_str() {
+        for n in 1u16..1000u16 {
+            let keyword = Keyword::GRPIDn(n);
+            let representation = format!("GRPID{}", n);
+
+            assert_eq!(Keyword::from_str(&representation).unwrap(), keyword);
+        }
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn GRPLCn_should_be_parsed_from_str() {
+        for n in 1u16..1000u16 {
+            let keyword = Keyword::GRPLCn(n);
+            let representation = format!("GRPLC{}", n);
+
+            assert_eq!(Keyword::from_str(&representation).unwrap(), keyword);
+        }
+    }
+
     #[allow(non_snake_case)]
     #[test]
     fn TDIMn_should_be_parsed_from_str() {
@@ -618,6 +2783,51 @@ mod tests {
         }
     }
 
+    #[test]
+    fn values_should_be_displayed_in_fits_notation() {
+        let data = vec!(
+            (Value::CharacterString(Cow::Borrowed("K2")), "'K2      '"),
+            (Value::CharacterString(Cow::Borrowed("")), "''"),
+            (Value::CharacterString(Cow::Borrowed("O'HARA")), "'O''HARA '"),
+            (Value::Logical(false), "F"),
+            (Value::Integer(-37i64), "-37"),
+            (Value::Real(2000f64), "2000.0"),
+            (Value::Real(6.02e23f64), "6.02E23"),
+            (Value::Complex((1f64, -2f64)), "(1.0, -2.0)"),
+            (Value::Undefined, ""),
+        );
+
+        for (value, expected) in data {
+            assert_eq!(value.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn unknown_keywords_should_keep_their_name() {
+        for name in ["CRVAL1", "WCSNAMEP", "TFORM1P", "NAXISX"] {
+            let keyword = Keyword::from_str(name).unwrap();
+
+            assert_eq!(keyword, Keyword::Custom(name.to_string()));
+            assert_eq!(keyword.to_string(), name);
+        }
+        assert_eq!(Keyword::from_str("CTYPE1  ").unwrap(), Keyword::Custom("CTYPE1".to_string()));
+    }
+
+    #[test]
+    fn header_should_be_queryable_by_the_name_of_an_unknown_keyword() {
+        let header = Header::new(vec!(
+            KeywordRecord::new(Keyword::Custom("CRVAL1".to_string()), Value::Real(347.886643352957f64), Option::None),
+        ));
+
+        assert_eq!(header.get_real(&Keyword::from_str("CRVAL1").unwrap()), Ok(347.886643352957f64));
+    }
+
+    #[test]
+    fn blank_keyword_should_be_parsed_from_spaces() {
+        assert_eq!(Keyword::from_str("        ").unwrap(), Keyword::BlankField);
+        assert_eq!(format!("{:<8}", Keyword::BlankField), "        ");
+    }
+
     #[test]
     fn should_also_parse_whitespace_keywords() {
         assert_eq!(Keyword::from_str("SIMPLE  ").unwrap(), Keyword::SIMPLE);
@@ -640,16 +2850,571 @@ mod tests {
     #[test]
     fn extension_header_should_determine_correct_data_array_size() {
         let header = Header::new(vec!(
-            KeywordRecord::new(Keyword::XTENSION, Value::CharacterString("BINTABLE"), Option::None),
-            KeywordRecord::new(Keyword::BITPIX, Value::Integer(128i64), Option::None),
+            KeywordRecord::new(Keyword::XTENSION, Value::CharacterString(Cow::Borrowed("BINTABLE")), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(64i64), Option::None),
             KeywordRecord::new(Keyword::NAXIS, Value::Integer(2i64), Option::None),
             KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(3i64), Option::None),
             KeywordRecord::new(Keyword::NAXISn(2u16), Value::Integer(5i64), Option::None),
             KeywordRecord::new(Keyword::GCOUNT, Value::Integer(7i64), Option::None),
-            KeywordRecord::new(Keyword::PCOUNT, Value::Integer(11i64), Option::None),
+            KeywordRecord::new(Keyword::PCOUNT, Value::Integer(40i64), Option::None),
             KeywordRecord::new(Keyword::END, Value::Undefined, Option::None),
         ));
 
         assert_eq!(header.data_array_size(), 2*(2880*8) as usize);
     }
+
+    #[test]
+    fn random_groups_header_should_determine_correct_data_array_size() {
+        let header = Header::new(vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(-32i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(3i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(0i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(2u16), Value::Integer(3i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(3u16), Value::Integer(4i64), Option::None),
+            KeywordRecord::new(Keyword::GROUPS, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::PCOUNT, Value::Integer(6i64), Option::None),
+            KeywordRecord::new(Keyword::GCOUNT, Value::Integer(100i64), Option::None),
+            KeywordRecord::new(Keyword::END, Value::Undefined, Option::None),
+        ));
+
+        assert!(header.is_random_groups());
+        assert_eq!(header.data_block_count(), Some(3));
+    }
+
+    fn header_with_axes<'a>(naxis: i64, axes: Vec<(u16, i64)>) -> Header<'a> {
+        let mut records = vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(naxis), Option::None),
+        );
+        for (n, length) in axes {
+            records.push(KeywordRecord::new(Keyword::NAXISn(n), Value::Integer(length), Option::None));
+        }
+        Header::new(records)
+    }
+
+    #[test]
+    fn axes_should_be_determined_from_naxisn() {
+        let header = header_with_axes(2i64, vec!((1u16, 3i64), (2u16, 5i64)));
+
+        assert_eq!(header.axes(), Ok(vec!(3usize, 5usize)));
+    }
+
+    #[test]
+    fn axes_should_report_a_missing_axis() {
+        let header = header_with_axes(3i64, vec!((1u16, 3i64), (3u16, 5i64)));
+
+        assert_eq!(header.axes(), Err(AxisError::MissingAxis(2u16)));
+    }
+
+    #[test]
+    fn axes_should_report_an_unexpected_axis() {
+        let header = header_with_axes(1i64, vec!((1u16, 3i64), (2u16, 5i64)));
+
+        assert_eq!(header.axes(), Err(AxisError::UnexpectedAxis(2u16)));
+    }
+
+    #[test]
+    fn axes_should_report_a_duplicate_axis() {
+        let header = header_with_axes(1i64, vec!((1u16, 3i64), (1u16, 5i64)));
+
+        assert_eq!(header.axes(), Err(AxisError::DuplicateAxis(1u16)));
+    }
+
+    #[test]
+    fn axes_should_report_a_negative_axis() {
+        let header = header_with_axes(1i64, vec!((1u16, -3i64)));
+
+        assert_eq!(header.axes(), Err(AxisError::InvalidAxis(1u16)));
+    }
+
+    #[test]
+    fn axes_should_report_an_invalid_naxis() {
+        assert_eq!(header_with_axes(-1i64, vec!()).axes(), Err(AxisError::InvalidNaxis));
+        assert_eq!(header_with_axes(1000i64, vec!()).axes(), Err(AxisError::InvalidNaxis));
+        assert_eq!(Header::new(vec!()).axes(), Err(AxisError::MissingNaxis));
+    }
+
+    fn header_with_values<'a>() -> Header<'a> {
+        Header::new(vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::OBJECT, Value::CharacterString(Cow::Borrowed("EPIC 200164267")), Option::None),
+            KeywordRecord::new(Keyword::EQUINOX, Value::Real(2000f64), Option::None),
+            KeywordRecord::new(Keyword::PMRA, Value::Undefined, Option::None),
+            KeywordRecord::new(Keyword::TTYPEn(1u16), Value::CharacterString(Cow::Borrowed("TIME")), Option::None),
+            KeywordRecord::new(Keyword::TTYPEn(1u16), Value::CharacterString(Cow::Borrowed("FLUX")), Option::None),
+        ))
+    }
+
+    #[test]
+    fn get_should_return_the_value_of_the_first_record() {
+        let header = header_with_values();
+
+        assert_eq!(header.get(&Keyword::BITPIX), Some(&Value::Integer(8i64)));
+        assert_eq!(header.get(&Keyword::TTYPEn(1u16)), Some(&Value::CharacterString(Cow::Borrowed("TIME"))));
+        assert_eq!(header.get(&Keyword::NAXIS), None);
+    }
+
+    #[test]
+    fn records_of_should_return_all_records_of_a_keyword() {
+        let header = header_with_values();
+
+        let records = header.records_of(&Keyword::TTYPEn(1u16));
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].value(), &Value::CharacterString(Cow::Borrowed("FLUX")));
+    }
+
+    #[test]
+    fn record_of_should_return_the_first_record_of_a_keyword() {
+        let header = header_with_values();
+
+        let record = header.record_of(&Keyword::TTYPEn(1u16)).unwrap();
+
+        assert_eq!(record, &header.keyword_records[header.position_of(&Keyword::TTYPEn(1u16)).unwrap()]);
+        assert_eq!(record, header.records_of(&Keyword::TTYPEn(1u16))[0]);
+        assert_eq!(header.record_of(&Keyword::NAXIS), None);
+        assert_eq!(header.position_of(&Keyword::NAXIS), None);
+    }
+
+    #[test]
+    fn typed_getters_should_return_values_of_the_right_type() {
+        let header = header_with_values();
+
+        assert_eq!(header.get_str(&Keyword::OBJECT), Ok("EPIC 200164267"));
+        assert_eq!(header.get_int(&Keyword::BITPIX), Ok(8i64));
+        assert_eq!(header.get_real(&Keyword::EQUINOX), Ok(2000f64));
+        assert_eq!(header.get_real(&Keyword::BITPIX), Ok(8f64));
+        assert_eq!(header.get_logical(&Keyword::SIMPLE), Ok(true));
+    }
+
+    #[test]
+    fn character_strings_should_ignore_trailing_spaces_only_when_trimmed() {
+        let strings = [("PRIMARY ", "PRIMARY"), ("  leading", "  leading"), ("    ", " "), ("", "")];
+
+        for (raw, trimmed) in strings.iter() {
+            let value = Value::CharacterString(Cow::Borrowed(raw));
+            assert_eq!(value.as_str(), Some(*raw));
+            assert_eq!(value.as_trimmed_str(), Some(*trimmed));
+        }
+        assert_eq!(Value::Integer(1i64).as_trimmed_str(), None);
+    }
+
+    #[test]
+    fn typed_getters_should_report_problems() {
+        let header = header_with_values();
+
+        assert_eq!(header.get_str(&Keyword::BITPIX), Err(ValueRetrievalError::NotACharacterString));
+        assert_eq!(header.get_int(&Keyword::EQUINOX), Err(ValueRetrievalError::NotAnInteger));
+        assert_eq!(header.get_real(&Keyword::OBJECT), Err(ValueRetrievalError::NotAReal));
+        assert_eq!(header.get_logical(&Keyword::BITPIX), Err(ValueRetrievalError::NotALogical));
+        assert_eq!(header.get_real(&Keyword::PMRA), Err(ValueRetrievalError::ValueUndefined));
+        assert_eq!(header.get_int(&Keyword::NAXIS), Err(ValueRetrievalError::KeywordNotPresent));
+    }
+
+    #[test]
+    fn datasum_should_be_parsed_modulo_2_32() {
+        let header = |value| Header::new(vec!(KeywordRecord::new(Keyword::DATASUM, value, Option::None)));
+
+        assert_eq!(header(Value::CharacterString(Cow::Borrowed("0       "))).datasum(), Ok(0u32));
+        assert_eq!(header(Value::CharacterString(Cow::Borrowed("4294967295"))).datasum(), Ok(u32::MAX));
+        assert_eq!(header(Value::CharacterString(Cow::Borrowed("4294967298"))).datasum(), Ok(2u32));
+        assert_eq!(header(Value::Integer(1234i64)).datasum(), Ok(1234u32));
+        assert_eq!(header(Value::CharacterString(Cow::Borrowed("-1"))).datasum(), Err(ValueRetrievalError::NotADecimalNumber));
+        assert_eq!(header(Value::CharacterString(Cow::Borrowed(""))).datasum(), Err(ValueRetrievalError::NotADecimalNumber));
+        assert_eq!(Header::new(vec!()).datasum(), Err(ValueRetrievalError::KeywordNotPresent));
+    }
+
+    #[test]
+    fn commentary_should_be_collected_in_order() {
+        let header = Header::new(vec!(
+            KeywordRecord::new(Keyword::COMMENT, Value::CommentText(Cow::Borrowed("first")), Option::None),
+            KeywordRecord::new(Keyword::HISTORY, Value::CommentText(Cow::Borrowed("created")), Option::None),
+            KeywordRecord::new(Keyword::BlankField, Value::CommentText(Cow::Borrowed("")), Option::None),
+            KeywordRecord::new(Keyword::COMMENT, Value::CommentText(Cow::Borrowed("  second")), Option::None),
+        ));
+
+        assert_eq!(header.comments(), vec!("first", "  second"));
+        assert_eq!(header.history(), vec!("created"));
+    }
+
+    #[test]
+    fn records_should_be_set_removed_and_inserted_in_place() {
+        let mut header = Header::new(vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::OBJECT, Value::CharacterString(Cow::Borrowed("TRAPPIST")), Option::Some("target")),
+            KeywordRecord::new(Keyword::HISTORY, Value::CommentText(Cow::Borrowed("first")), Option::None),
+            KeywordRecord::new(Keyword::HISTORY, Value::CommentText(Cow::Borrowed("second")), Option::None),
+            KeywordRecord::new(Keyword::END, Value::Undefined, Option::None),
+        ));
+
+        header.set(Keyword::OBJECT, Value::CharacterString(Cow::Borrowed("TRAPPIST-1")), Option::None);
+        header.set(Keyword::EQUINOX, Value::Real(2000f64), Option::Some("equinox"));
+        let removed = header.remove(&Keyword::HISTORY);
+        let inserted = header.insert_after(&Keyword::SIMPLE, KeywordRecord::new(Keyword::EXTEND, Value::Logical(true), Option::None));
+
+        assert_eq!(removed.len(), 2);
+        assert_eq!(inserted, Ok(()));
+        assert_eq!(header.keyword_records, vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::EXTEND, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::OBJECT, Value::CharacterString(Cow::Borrowed("TRAPPIST-1")), Option::Some("target")),
+            KeywordRecord::new(Keyword::EQUINOX, Value::Real(2000f64), Option::Some("equinox")),
+            KeywordRecord::new(Keyword::END, Value::Undefined, Option::None),
+        ));
+        assert_eq!(header.insert_after(&Keyword::DATE, KeywordRecord::new(Keyword::ORIGIN, Value::Undefined, Option::None)),
+                   Err(ValueRetrievalError::KeywordNotPresent));
+    }
+
+    #[test]
+    fn commentary_should_be_added_before_the_end_record() {
+        let mut header = Header::new(vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::END, Value::Undefined, Option::None),
+        ));
+
+        header.add_history("  reduced");
+        header.add_comment("checked");
+
+        assert_eq!(header.history(), vec!("  reduced"));
+        assert_eq!(header.comments(), vec!("checked"));
+        assert_eq!(*header.keyword_records[3].keyword(), Keyword::END);
+    }
+
+    #[test]
+    fn hdu_with_data_should_only_keep_the_unpadded_data() {
+        let header = Header::new(vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(16i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(2i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(3i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(2u16), Value::Integer(2i64), Option::None),
+            KeywordRecord::new(Keyword::END, Value::Undefined, Option::None),
+        ));
+        let data = [0u8; 2880];
+
+        let hdu = HDU::with_data(header, &data);
+
+        let data_array = hdu.data_array().unwrap();
+        assert_eq!(data_array.bitpix(), Bitpix::I16);
+        assert_eq!(data_array.axes(), &[3usize, 2usize]);
+        assert_eq!(data_array.bytes().len(), 12usize);
+    }
+
+    #[test]
+    fn indexed_records_should_be_found_by_prefix_in_numeric_order() {
+        let header = Header::new(vec!(
+            KeywordRecord::new(Keyword::TFIELDS, Value::Integer(3i64), Option::None),
+            KeywordRecord::new(Keyword::TTYPEn(10u16), Value::CharacterString(Cow::Borrowed("FLUX")), Option::None),
+            KeywordRecord::new(Keyword::TFORMn(10u16), Value::CharacterString(Cow::Borrowed("E")), Option::None),
+            KeywordRecord::new(Keyword::TTYPEn(2u16), Value::CharacterString(Cow::Borrowed("TIME")), Option::None),
+            KeywordRecord::new(Keyword::Custom("TTYPEX".to_string()), Value::Integer(1i64), Option::None),
+        ));
+
+        let types: Vec<(u16, &str)> = header.records_with_prefix("TTYPE").into_iter()
+            .map(|(index, record)| (index, record.value().as_str().unwrap()))
+            .collect();
+
+        assert_eq!(types, vec!((2u16, "TIME"), (10u16, "FLUX")));
+        assert_eq!(header.records_with_prefix("T").len(), 0);
+        assert_eq!(header.records_matching(|keyword| keyword.to_string().starts_with("TF")).count(), 2);
+    }
+
+    #[test]
+    fn hdu_data_should_be_typed_by_simple_or_xtension() {
+        let data = include_bytes!("../../assets/images/k2-trappist1-unofficial-tpf-long-cadence.fits");
+        let fits = super::super::parser::parse(data).unwrap();
+        let extension = |xtension| Header::new(vec!(
+            KeywordRecord::new(Keyword::XTENSION, Value::CharacterString(Cow::Borrowed(xtension)), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(1i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(2i64), Option::None),
+        ));
+
+        assert!(matches!(fits.primary_hdu.data(), HduData::Image(data_array) if data_array.bytes().is_empty()));
+        assert!(matches!(fits.extensions[0].data(), HduData::BinTable(data) if data.len() == 2932 * 3599));
+        assert!(matches!(fits.extensions[1].data(), HduData::Image(data_array) if data_array.bitpix() == Bitpix::I32));
+        assert_eq!(HDU::with_data(extension("TABLE"), b"12").data(), &HduData::AsciiTable(Cow::Borrowed(b"12")));
+        assert_eq!(HDU::with_data(extension("FOREIGN"), b"12").data(), &HduData::Unknown(Cow::Borrowed(b"12")));
+        assert_eq!(HDU::with_data(extension("BINTABLE"), b"12").data_array(), None);
+    }
+
+    #[test]
+    fn data_shorter_than_the_header_describes_should_leave_the_data_unit_empty() {
+        let header = || Header::new(vec!(
+            KeywordRecord::new(Keyword::XTENSION, Value::CharacterString(Cow::Borrowed("TABLE")), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(1i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(3i64), Option::None),
+        ));
+
+        assert_eq!(HDU::with_data(header(), b"12").data(), &HduData::AsciiTable(Cow::Borrowed(b"")));
+        assert_eq!(HDU::with_owned_data(header(), b"12".to_vec()).data(), &HduData::AsciiTable(Cow::Borrowed(b"")));
+        assert_eq!(HDU::with_data(header(), b"1234").data(), &HduData::AsciiTable(Cow::Borrowed(b"123")));
+        assert_eq!(HDU::with_data(header(), b"12"), HDU::new(header()));
+    }
+
+    #[test]
+    fn legacy_extension_names_should_have_the_kind_they_were_standardized_as() {
+        let extension = |xtension| Header::new(vec!(
+            KeywordRecord::new(Keyword::XTENSION, Value::CharacterString(Cow::Borrowed(xtension)), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(1i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(2i64), Option::None),
+        ));
+
+        assert_eq!(extension("A3DTABLE").kind(), HduKind::BinTable);
+        assert_eq!(extension("IUEIMAGE").kind(), HduKind::Image);
+        assert_eq!(extension("IUEIMAGE ").kind(), HduKind::Image);
+        assert_eq!(extension("FOREIGN").kind(), HduKind::Unknown);
+        assert_eq!(HDU::with_data(extension("A3DTABLE"), b"12").data(), &HduData::BinTable(Cow::Borrowed(b"12")));
+    }
+
+    #[test]
+    fn data_array_should_decode_big_endian_values() {
+        let data = [0x00, 0x01, 0xff, 0xfe, 0x12, 0x34];
+        let data_array = DataArray::new(Bitpix::I16, vec!(3usize), &data);
+
+        assert_eq!(data_array.values::<i16>().unwrap(), vec!(1i16, -2i16, 0x1234i16));
+    }
+
+    #[test]
+    fn data_array_should_decode_floating_point_values() {
+        let data = [0x3f, 0x80, 0x00, 0x00, 0xc0, 0x00, 0x00, 0x00];
+        let data_array = DataArray::new(Bitpix::F32, vec!(2usize), &data);
+
+        assert_eq!(data_array.values::<f32>().unwrap(), vec!(1f32, -2f32));
+    }
+
+    #[test]
+    fn data_array_should_refuse_a_type_that_does_not_match_bitpix() {
+        let data = [0u8; 8];
+        let data_array = DataArray::new(Bitpix::I32, vec!(2usize), &data);
+
+        assert_eq!(data_array.values::<f32>(), Err(DataArrayError::BitpixMismatch { expected: Bitpix::F32, actual: Bitpix::I32 }));
+        assert_eq!(data_array.as_u8(), Err(DataArrayError::BitpixMismatch { expected: Bitpix::U8, actual: Bitpix::I32 }));
+    }
+
+    #[test]
+    fn data_array_should_convert_any_bitpix_to_f64() {
+        let data = [0x00, 0x01, 0xff, 0xfe];
+
+        assert_eq!(DataArray::new(Bitpix::I16, vec!(2usize), &data).to_f64().unwrap(), vec!(1f64, -2f64));
+        assert_eq!(DataArray::new(Bitpix::U8, vec!(4usize), &data).to_f64().unwrap(), vec!(0f64, 1f64, 255f64, 254f64));
+    }
+
+    #[test]
+    fn data_array_should_apply_scaling_to_physical_values() {
+        let data = [0x80, 0x00, 0xff, 0xff, 0x00, 0x01, 0x7f, 0xff];
+        let unsigned = Scaling { bzero: 32768f64, ..Scaling::default() };
+        let blank = Scaling { bscale: 2f64, bzero: 1f64, blank: Option::Some(-1i64) };
+
+        let values = DataArray::new(Bitpix::I16, vec!(4usize), &data).with_scaling(unsigned).physical_values().unwrap();
+        assert_eq!(values, vec!(0f64, 32767f64, 32769f64, 65535f64));
+        let values = DataArray::new(Bitpix::I16, vec!(4usize), &data).with_scaling(blank).physical_values().unwrap();
+        assert_eq!(values[0], -65535f64);
+        assert!(values[1].is_nan());
+        assert_eq!(values[2], 3f64);
+    }
+
+    #[test]
+    fn hdu_should_take_scaling_from_its_header() {
+        let header = Header::new(vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(16i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(1i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(2i64), Option::None),
+            KeywordRecord::new(Keyword::BZERO, Value::Integer(32768i64), Option::None),
+            KeywordRecord::new(Keyword::BLANK, Value::Integer(0i64), Option::None),
+        ));
+        let data = [0x80, 0x00, 0x00, 0x00];
+
+        assert_eq!(header.scaling(), Ok(Scaling { bscale: 1f64, bzero: 32768f64, blank: Option::Some(0i64) }));
+        let values = HDU::with_data(header, &data).data_array().unwrap().physical_values().unwrap();
+        assert_eq!(values[0], 0f64);
+        assert!(values[1].is_nan());
+        assert_eq!(Header::new(vec!()).scaling(), Ok(Scaling::default()));
+    }
+
+    #[test]
+    fn effective_header_should_inherit_from_the_primary_header_with_inherit() {
+        let string = |s| Value::CharacterString(Cow::Borrowed(s));
+        let primary = Header::new(vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(0i64), Option::None),
+            KeywordRecord::new(Keyword::EXTEND, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::TELESCOP, string("HST"), Option::None),
+            KeywordRecord::new(Keyword::OBJECT, string("M31"), Option::None),
+            KeywordRecord::new(Keyword::COMMENT, string("primary"), Option::None),
+        ));
+        let extension = |inherit| Header::new(vec!(
+            KeywordRecord::new(Keyword::XTENSION, string("IMAGE"), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(16i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(0i64), Option::None),
+            KeywordRecord::new(Keyword::INHERIT, Value::Logical(inherit), Option::None),
+            KeywordRecord::new(Keyword::OBJECT, string("M32"), Option::None),
+        ));
+        let fits = Fits::new(HDU::new(primary.clone()), vec!(HDU::new(extension(true)), HDU::new(extension(false))));
+
+        let inheriting = fits.effective_header(1).unwrap();
+
+        assert_eq!(inheriting.get_trimmed_str(&Keyword::TELESCOP), Ok("HST"));
+        assert_eq!(inheriting.get_trimmed_str(&Keyword::OBJECT), Ok("M32"));
+        assert_eq!(inheriting.get_int(&Keyword::BITPIX), Ok(16i64));
+        assert_eq!(inheriting.get(&Keyword::EXTEND), None);
+        assert!(inheriting.comments().is_empty());
+        assert_eq!(fits.effective_header(2), Some(extension(false)));
+        assert_eq!(fits.effective_header(0), Some(primary));
+        assert_eq!(fits.effective_header(3), None);
+    }
+
+    #[test]
+    fn hdus_should_be_found_by_name_and_version() {
+        let extension = |name, version: Option<i64>| {
+            let mut records = vec!(
+                KeywordRecord::new(Keyword::XTENSION, Value::CharacterString(Cow::Borrowed("BINTABLE")), Option::None),
+                KeywordRecord::new(Keyword::EXTNAME, Value::CharacterString(Cow::Borrowed(name)), Option::None),
+            );
+            if let Some(version) = version {
+                records.push(KeywordRecord::new(Keyword::EXTVER, Value::Integer(version), Option::None));
+            }
+            HDU::new(Header::new(records))
+        };
+        let fits = Fits::new(HDU::new(Header::new(vec!())), vec!(
+            extension("EVENTS  ", Option::None),
+            extension("GTI", Option::None),
+            extension("EVENTS", Option::Some(2i64)),
+        ));
+
+        assert_eq!(fits.iter_hdus().count(), 4);
+        assert_eq!(fits.hdu(2).and_then(HDU::name), Some("GTI"));
+        assert!(fits.hdu(4).is_none());
+        assert_eq!(fits.hdu_by_name("EVENTS").map(HDU::version), Some(1i64));
+        assert!(fits.hdu_by_name("events").is_none());
+        assert_eq!(fits.hdu_by_name_matching("events", NameMatching::CaseInsensitive), fits.hdu(1));
+        assert_eq!(fits.hdu_by_name_ver("EVENTS", 2i64), fits.hdu(3));
+        assert!(fits.hdu_by_name_ver("GTI", 2i64).is_none());
+        assert!(fits.hdu_by_name("STDGTI").is_none());
+    }
+
+    #[test]
+    fn inserted_extensions_should_resolve_version_collisions() {
+        let extension = |name, version: Option<i64>| {
+            let mut records = vec!(
+                KeywordRecord::new(Keyword::XTENSION, Value::CharacterString(Cow::Borrowed("BINTABLE")), Option::None),
+                KeywordRecord::new(Keyword::EXTNAME, Value::CharacterString(Cow::Borrowed(name)), Option::None),
+            );
+            if let Some(version) = version {
+                records.push(KeywordRecord::new(Keyword::EXTVER, Value::Integer(version), Option::None));
+            }
+            HDU::new(Header::new(records))
+        };
+        let versions = |fits: &Fits| fits.extensions.iter().map(HDU::version).collect::<Vec<i64>>();
+        let original = || Fits::new(HDU::new(Header::new(vec!())), vec!(
+            extension("EVENTS", Option::None),
+            extension("GTI", Option::None),
+            extension("EVENTS", Option::Some(2i64)),
+        ));
+        let (mut kept, mut next, mut shifted) = (original(), original(), original());
+
+        kept.insert_extension_versioned(1, extension("EVENTS", Option::None), VersionPolicy::Keep).unwrap();
+        next.insert_extension_versioned(1, extension("EVENTS", Option::None), VersionPolicy::Next).unwrap();
+        shifted.insert_extension_versioned(0, extension("EVENTS", Option::None), VersionPolicy::Shift).unwrap();
+        shifted.insert_extension_versioned(4, extension("GTI", Option::Some(2i64)), VersionPolicy::Shift).unwrap();
+
+        assert_eq!(versions(&kept), vec!(1i64, 1i64, 1i64, 2i64));
+        assert_eq!(versions(&next), vec!(1i64, 3i64, 1i64, 2i64));
+        assert_eq!(next.extensions[1].header.keyword_records[2].keyword, Keyword::EXTVER);
+        assert_eq!(versions(&shifted), vec!(1i64, 2i64, 1i64, 3i64, 2i64));
+        assert_eq!(shifted.hdu_by_name_ver("EVENTS", 1i64), shifted.hdu(1));
+    }
+
+    #[test]
+    fn extensions_should_not_be_inserted_past_the_last_one() {
+        let extension = |version| HDU::new(Header::new(vec!(
+            KeywordRecord::new(Keyword::XTENSION, Value::CharacterString(Cow::Borrowed("IMAGE")), Option::None),
+            KeywordRecord::new(Keyword::EXTNAME, Value::CharacterString(Cow::Borrowed("SCI")), Option::None),
+            KeywordRecord::new(Keyword::EXTVER, Value::Integer(version), Option::None),
+            KeywordRecord::new(Keyword::CHECKSUM, Value::CharacterString(Cow::Borrowed("hcHjjc9ghcEghc9g")), Option::None),
+        )));
+        let mut fits = Fits::new(HDU::new(Header::new(vec!())), vec!(extension(1i64), extension(2i64)));
+
+        assert_eq!(fits.insert_extension_versioned(3, extension(1i64), VersionPolicy::Shift), Err(ExtensionError::OutOfRange { index: 3, count: 2 }));
+        assert_eq!(fits.insert_extension(3, extension(3i64)), Err(ExtensionError::OutOfRange { index: 3, count: 2 }));
+        assert_eq!(fits.extensions.iter().map(HDU::version).collect::<Vec<i64>>(), vec!(1i64, 2i64));
+        assert!(fits.extensions[1].header.get(&Keyword::CHECKSUM).is_some());
+
+        fits.insert_extension_versioned(2, extension(2i64), VersionPolicy::Shift).unwrap();
+
+        assert!(fits.extensions[0].header.get(&Keyword::CHECKSUM).is_some());
+        assert!(fits.extensions[1].header.get(&Keyword::CHECKSUM).is_none());
+        assert!(fits.extensions[2].header.get(&Keyword::CHECKSUM).is_some());
+    }
+
+    #[test]
+    fn lmle_should_round_up_to_a_multiple() {
+        assert_eq!(lmle(0, BLOCK_SIZE), Some(0));
+        assert_eq!(lmle(1, BLOCK_SIZE), Some(2880));
+        assert_eq!(lmle(2880, BLOCK_SIZE), Some(2880));
+        assert_eq!(lmle(2881, BLOCK_SIZE), Some(5760));
+        assert_eq!(lmle(usize::MAX, BLOCK_SIZE), None);
+        assert_eq!(lmle(1, 0), None);
+        assert_eq!(block_count(2881), 2);
+    }
+
+    #[test]
+    fn data_block_count_should_detect_overflow() {
+        let header = |naxis1: i64| Header::new(vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(-64i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(2i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(naxis1), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(2u16), Value::Integer(naxis1), Option::None),
+        ));
+
+        assert_eq!(header(361).data_block_count(), Some(363));
+        assert_eq!(header(i64::MAX).data_block_count(), None);
+        assert_eq!(header(i64::MAX).data_array_size(), usize::MAX);
+    }
+
+    #[test]
+    fn data_unit_length_should_not_be_limited_by_the_address_space() {
+        let header = Header::new(vec!(
+            KeywordRecord::new(Keyword::XTENSION, Value::CharacterString(Cow::Borrowed("IMAGE")), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(-64i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(2i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(100000i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(2u16), Value::Integer(28800i64), Option::None),
+        ));
+
+        assert_eq!(header.data_unit_length(), Some(23040000000u64));
+        assert_eq!(header.data_block_count(), Some(8000000u64));
+        assert_eq!(header.data_unit_size(), usize::try_from(23040000000u64).ok());
+    }
+
+    #[test]
+    fn bitpix_should_convert_from_and_to_keyword_values() {
+        for value in [8i64, 16i64, 32i64, 64i64, -32i64, -64i64] {
+            let bitpix = Bitpix::try_from(value).unwrap();
+
+            assert_eq!(i64::from(bitpix), value);
+            assert_eq!(bitpix.to_string(), value.to_string());
+        }
+        assert_eq!(Bitpix::try_from(12i64), Err(BitpixError::UnknownBitpix(12i64)));
+        assert_eq!(Bitpix::F64.bytes(), 8);
+        assert!(Bitpix::F32.is_floating_point());
+    }
+
+    #[test]
+    fn header_bitpix_should_report_problems() {
+        let header = |value| Header::new(vec!(KeywordRecord::new(Keyword::BITPIX, value, Option::None)));
+
+        assert_eq!(header(Value::Integer(-32i64)).bitpix(), Ok(Bitpix::F32));
+        assert_eq!(header(Value::Integer(12i64)).bitpix(), Err(BitpixError::UnknownBitpix(12i64)));
+        assert_eq!(header(Value::Logical(true)).bitpix(), Err(BitpixError::NotAnInteger));
+        assert_eq!(Header::new(vec!()).bitpix(), Err(BitpixError::MissingBitpix));
+    }
 }
@@ -0,0 +1,181 @@
+//! The units module converts values between the units named by `TUNITn`,
+//! e.g. from `Jy` to `mJy` or from `d` to `s`.
+//!
+//! Units are looked up in a small registry of angles, times, count rates, flux
+//! densities and magnitudes. Times and flux densities accept an SI prefix, e.g.
+//! `ms` or `mJy`. Units are case sensitive, as the FITS standard demands.
+
+use std::error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use super::table::TableError;
+
+/// The kind of quantity a unit measures. Only units of the same dimension
+/// convert into each other.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Dimension {
+    /// An angle, in degrees.
+    Angle,
+    /// A time, in seconds.
+    Time,
+    /// A count rate, in electrons per second.
+    CountRate,
+    /// A spectral flux density, in Jansky.
+    FluxDensity,
+    /// A magnitude, which does not scale linearly.
+    Magnitude,
+}
+
+/// A unit, i.e. a multiple of the base unit of its dimension.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Unit {
+    /// What the unit measures.
+    pub dimension: Dimension,
+    /// The size of the unit in base units, e.g. 3600 for `h`.
+    pub factor: f64,
+}
+
+const REGISTRY: [(&str, Dimension, f64, bool); 15] = [
+    ("deg", Dimension::Angle, 1f64, false),
+    ("arcmin", Dimension::Angle, 1f64 / 60f64, false),
+    ("arcsec", Dimension::Angle, 1f64 / 3600f64, false),
+    ("mas", Dimension::Angle, 1f64 / 3600000f64, false),
+    ("rad", Dimension::Angle, 180f64 / std::f64::consts::PI, false),
+    ("s", Dimension::Time, 1f64, true),
+    ("min", Dimension::Time, 60f64, false),
+    ("h", Dimension::Time, 3600f64, false),
+    ("d", Dimension::Time, 86400f64, false),
+    ("a", Dimension::Time, 31557600f64, false),
+    ("yr", Dimension::Time, 31557600f64, false),
+    ("e-/s", Dimension::CountRate, 1f64, false),
+    ("electron/s", Dimension::CountRate, 1f64, false),
+    ("Jy", Dimension::FluxDensity, 1f64, true),
+    ("mag", Dimension::Magnitude, 1f64, false),
+];
+
+const PREFIXES: [(&str, f64); 6] = [
+    ("n", 1e-9f64),
+    ("u", 1e-6f64),
+    ("m", 1e-3f64),
+    ("k", 1e3f64),
+    ("M", 1e6f64),
+    ("G", 1e9f64),
+];
+
+impl FromStr for Unit {
+    type Err = UnitError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let symbol = s.trim();
+        let lookup = |symbol: &str| REGISTRY.iter().find(|entry| entry.0 == symbol);
+        if let Some(&(_, dimension, factor, _)) = lookup(symbol) {
+            return Ok(Unit { dimension, factor })
+        }
+        for &(prefix, multiple) in PREFIXES.iter() {
+            if let Some(&(_, dimension, factor, true)) = symbol.strip_prefix(prefix).and_then(lookup) {
+                return Ok(Unit { dimension, factor: factor * multiple })
+            }
+        }
+        Err(UnitError::UnknownUnit(symbol.to_string()))
+    }
+}
+
+impl Unit {
+    /// The factor that converts values in this unit to values in `other`.
+    pub fn factor_to(&self, other: &Unit) -> Option<f64> {
+        if self.dimension == other.dimension {
+            Some(self.factor / other.factor)
+        } else {
+            None
+        }
+    }
+}
+
+/// The factor that converts values in the unit `from` to values in the unit `to`.
+pub fn factor(from: &str, to: &str) -> Result<f64, UnitError> {
+    Unit::from_str(from)?
+        .factor_to(&Unit::from_str(to)?)
+        .ok_or_else(|| UnitError::Incompatible { from: from.trim().to_string(), to: to.trim().to_string() })
+}
+
+/// Floating point types that converted values can be returned as.
+pub trait Real {
+    /// Convert from an `f64`, possibly losing precision.
+    fn from_f64(value: f64) -> Self;
+}
+
+impl Real for f64 {
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+}
+
+impl Real for f32 {
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+}
+
+/// Problems that occur when converting units.
+#[derive(Debug, PartialEq)]
+pub enum UnitError {
+    /// The unit is not in the registry.
+    UnknownUnit(String),
+    /// The units measure different dimensions, e.g. `s` and `Jy`.
+    Incompatible {
+        /// The unit that is converted from.
+        from: String,
+        /// The unit that is converted to.
+        to: String,
+    },
+    /// The table does not have a column with this name.
+    MissingColumn(String),
+    /// The column with this name has no `TUNITn`.
+    MissingUnit(String),
+    /// The table could not be read.
+    Table(TableError),
+}
+
+impl Display for UnitError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            UnitError::UnknownUnit(ref unit) => write!(f, "unknown unit \"{}\"", unit),
+            UnitError::Incompatible { ref from, ref to } => write!(f, "{} can not be converted to {}", from, to),
+            UnitError::MissingColumn(ref name) => write!(f, "the column {} is missing", name),
+            UnitError::MissingUnit(ref name) => write!(f, "the column {} has no unit", name),
+            UnitError::Table(ref error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl error::Error for UnitError {}
+
+impl From<TableError> for UnitError {
+    fn from(error: TableError) -> Self {
+        UnitError::Table(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use super::{factor, Dimension, Unit, UnitError};
+
+    #[test]
+    fn units_should_be_found_with_and_without_prefix() {
+        assert_eq!(Unit::from_str("arcsec").map(|unit| unit.dimension), Ok(Dimension::Angle));
+        assert_eq!(Unit::from_str("mJy "), Ok(Unit { dimension: Dimension::FluxDensity, factor: 1e-3f64 }));
+        assert_eq!(Unit::from_str("min"), Ok(Unit { dimension: Dimension::Time, factor: 60f64 }));
+        assert_eq!(Unit::from_str("kmag"), Err(UnitError::UnknownUnit("kmag".to_string())));
+        assert_eq!(Unit::from_str("D"), Err(UnitError::UnknownUnit("D".to_string())));
+    }
+
+    #[test]
+    fn factors_should_convert_between_units_of_a_dimension() {
+        assert_eq!(factor("Jy", "mJy"), Ok(1000f64));
+        assert_eq!(factor("d", "s"), Ok(86400f64));
+        assert!((factor("deg", "arcsec").unwrap() - 3600f64).abs() < 1e-9);
+        assert_eq!(factor("e-/s", "Jy"), Err(UnitError::Incompatible { from: "e-/s".to_string(), to: "Jy".to_string() }));
+    }
+}
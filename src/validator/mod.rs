@@ -0,0 +1,615 @@
+//! The validator module checks a parsed `Fits` against the rules of the
+//! standard and reports what it finds.
+//!
+//! Every problem is a `Finding` with a severity, the rule that found it and,
+//! when a single record is at fault, the index of that record. Findings are
+//! collected per HDU in a `VerificationReport`, which decides whether a file
+//! passes for a given threshold.
+//!
+//! A `Validator` runs a list of rules. Besides the built-in rules, users can
+//! register their own, e.g. to enforce the header policy of an observatory.
+//! Most rules check a single HDU, file rules check the file as a whole and
+//! report their findings with the primary HDU.
+//!
+//! Some rules of the standard are about the bytes of a file rather than its
+//! contents, e.g. the order of the mandatory keywords, the fixed format of
+//! their values and the blank cards after `END`. `verify_strict` checks these
+//! as well, and gives every finding the byte offset of the problem.
+
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use super::checksum::{verify_datasum, ChecksumStatus};
+use super::error::Error;
+use super::parser::parse;
+use super::types::{Fits, HDU, HduKind, Header, Keyword, Value, BLOCK_SIZE};
+
+/// How serious a finding is. Severities are ordered, `Info` being the least serious.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub enum Severity {
+    /// Worth knowing, but not a problem.
+    Info,
+    /// Allowed by the standard, but likely to cause problems.
+    Warning,
+    /// Not allowed by the standard.
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Severity::Info => f.pad("info"),
+            Severity::Warning => f.pad("warning"),
+            Severity::Error => f.pad("error"),
+        }
+    }
+}
+
+/// A single problem found in an HDU.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Finding {
+    /// How serious the problem is.
+    pub severity: Severity,
+    /// The name of the rule that found the problem, e.g. `bitpix`.
+    pub rule: String,
+    /// The index of the keyword record at fault, if a single record is.
+    pub card: Option<usize>,
+    /// The byte offset of the problem in the file, if it is known.
+    pub offset: Option<usize>,
+    /// A description of the problem.
+    pub message: String,
+}
+
+impl Finding {
+    /// Create a `Finding` that is not about a single record.
+    pub fn new<S: Into<String>>(severity: Severity, rule: &str, message: S) -> Finding {
+        Finding { severity, rule: rule.to_string(), card: Option::None, offset: Option::None, message: message.into() }
+    }
+
+    /// The same finding, about the record with this index.
+    pub fn at(self, card: usize) -> Finding {
+        Finding { card: Option::Some(card), ..self }
+    }
+
+    /// The same finding, about the byte with this offset in the file.
+    pub fn at_offset(self, offset: usize) -> Finding {
+        Finding { offset: Option::Some(offset), ..self }
+    }
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} [{}]", self.severity, self.rule)?;
+        if let Some(card) = self.card {
+            write!(f, " card {}", card)?;
+        }
+        if let Some(offset) = self.offset {
+            write!(f, " byte {}", offset)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// The findings of a single HDU.
+#[derive(Debug, PartialEq, Clone)]
+pub struct HduReport {
+    /// The index of the HDU, 0 being the primary HDU.
+    pub index: usize,
+    /// The findings, in the order the rules found them.
+    pub findings: Vec<Finding>,
+}
+
+/// The findings of all HDUs of a file.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct VerificationReport {
+    /// A report per HDU, in the order of the file.
+    pub hdus: Vec<HduReport>,
+}
+
+impl VerificationReport {
+    /// All findings, together with the index of their HDU.
+    pub fn findings(&self) -> Vec<(usize, &Finding)> {
+        self.hdus.iter()
+            .flat_map(|hdu| hdu.findings.iter().map(move |finding| (hdu.index, finding)))
+            .collect()
+    }
+
+    /// The number of findings with this severity.
+    pub fn count(&self, severity: Severity) -> usize {
+        self.findings().iter().filter(|&&(_, finding)| finding.severity == severity).count()
+    }
+
+    /// The most serious severity found, if anything was found.
+    pub fn worst(&self) -> Option<Severity> {
+        self.findings().iter().map(|&(_, finding)| finding.severity).max()
+    }
+
+    /// Whether nothing was found that is at least as serious as `threshold`.
+    pub fn passes(&self, threshold: Severity) -> bool {
+        self.worst().is_none_or(|worst| worst < threshold)
+    }
+}
+
+/// A check of an HDU, given its index and the HDU itself.
+pub type Check = dyn Fn(usize, &HDU) -> Vec<Finding>;
+
+/// A check of a file as a whole.
+pub type FileCheck = dyn Fn(&Fits) -> Vec<Finding>;
+
+enum Scope {
+    Hdu(Box<Check>),
+    File(Box<FileCheck>),
+}
+
+struct Rule {
+    name: String,
+    check: Scope,
+}
+
+/// Runs a list of rules against the HDUs of a file.
+pub struct Validator {
+    rules: Vec<Rule>,
+}
+
+impl Validator {
+    /// A `Validator` with the built-in rules.
+    pub fn new() -> Validator {
+        Validator::empty()
+            .rule("first-keyword", first_keyword)
+            .rule("bitpix", bitpix)
+            .rule("axes", axes)
+            .rule("duplicate-keyword", duplicate_keywords)
+            .rule("datasum", datasum)
+            .file_rule("extend", extend)
+    }
+
+    /// A `Validator` without any rules.
+    pub fn empty() -> Validator {
+        Validator { rules: vec!() }
+    }
+
+    /// Add a rule that checks an HDU, given its index, 0 being the primary HDU.
+    /// The `rule` of its findings is set to `name`. A rule with the same name
+    /// is replaced.
+    pub fn rule<F>(mut self, name: &str, check: F) -> Validator where F: Fn(usize, &HDU) -> Vec<Finding> + 'static {
+        self.rules.retain(|rule| rule.name != name);
+        self.rules.push(Rule { name: name.to_string(), check: Scope::Hdu(Box::new(check)) });
+        self
+    }
+
+    /// Add a rule that checks a file as a whole, e.g. how the primary header
+    /// relates to the extensions. Its findings are reported with the primary
+    /// HDU. A rule with the same name is replaced.
+    pub fn file_rule<F>(mut self, name: &str, check: F) -> Validator where F: Fn(&Fits) -> Vec<Finding> + 'static {
+        self.rules.retain(|rule| rule.name != name);
+        self.rules.push(Rule { name: name.to_string(), check: Scope::File(Box::new(check)) });
+        self
+    }
+
+    /// Add a rule that only needs the header of an HDU.
+    pub fn header_rule<F>(self, name: &str, check: F) -> Validator where F: Fn(&Header) -> Vec<Finding> + 'static {
+        self.rule(name, move |_, hdu| check(&hdu.header))
+    }
+
+    /// Remove the rule with this name, e.g. a built-in rule that does not
+    /// apply to a certain kind of file.
+    pub fn without_rule(mut self, name: &str) -> Validator {
+        self.rules.retain(|rule| rule.name != name);
+        self
+    }
+
+    /// The names of the rules, in the order they run.
+    pub fn rules(&self) -> Vec<&str> {
+        self.rules.iter().map(|rule| rule.name.as_str()).collect()
+    }
+
+    /// Verify all HDUs of a file.
+    pub fn verify(&self, fits: &Fits) -> VerificationReport {
+        let mut hdus: Vec<HduReport> = fits.iter_hdus()
+            .enumerate()
+            .map(|(index, hdu)| HduReport { index, findings: self.verify_hdu(index, hdu) })
+            .collect();
+        hdus[0].findings.extend(self.verify_file(fits));
+        VerificationReport { hdus }
+    }
+
+    /// Verify a single HDU with index `index`, 0 being the primary HDU.
+    pub fn verify_hdu(&self, index: usize, hdu: &HDU) -> Vec<Finding> {
+        let mut findings = vec!();
+        for rule in &self.rules {
+            if let Scope::Hdu(ref check) = rule.check {
+                for finding in check(index, hdu) {
+                    findings.push(Finding { rule: rule.name.clone(), ..finding });
+                }
+            }
+        }
+        findings
+    }
+
+    // The findings of the file rules.
+    fn verify_file(&self, fits: &Fits) -> Vec<Finding> {
+        let mut findings = vec!();
+        for rule in &self.rules {
+            if let Scope::File(ref check) = rule.check {
+                for finding in check(fits) {
+                    findings.push(Finding { rule: rule.name.clone(), ..finding });
+                }
+            }
+        }
+        findings
+    }
+
+    /// Verify the bytes of a file. Besides the rules of this validator, the
+    /// strict rules run: `ascii`, `mandatory-order`, `fixed-format`, `end` and
+    /// `data-padding`. Findings about a record get the offset of its card.
+    /// A file that does not parse is not verified, the error tells why.
+    pub fn verify_strict(&self, data: &[u8]) -> Result<VerificationReport, Error> {
+        let fits = parse(data)?;
+        let mut offset = 0;
+        let mut hdus = vec!();
+        for (index, hdu) in fits.iter_hdus().enumerate() {
+            let cards: Vec<&[u8]> = data[offset..].chunks(CARD_SIZE).collect();
+            let end = cards.iter().position(|card| card.starts_with(b"END     ")).unwrap_or(cards.len() - 1);
+            let header_size = (end + 1).div_ceil(CARDS_PER_BLOCK) * BLOCK_SIZE;
+            let mut hdu_findings = self.verify_hdu(index, hdu);
+            if index == 0 {
+                hdu_findings.extend(self.verify_file(&fits));
+            }
+            let mut findings: Vec<Finding> = hdu_findings.into_iter()
+                .map(|finding| match finding.card {
+                    Some(card) => finding.at_offset(offset + hdu.header.card_of(card) * CARD_SIZE),
+                    None => finding,
+                })
+                .collect();
+            let header = RawHeader { cards: &cards[0..(header_size / CARD_SIZE)], offset };
+            findings.extend(ascii(&header));
+            findings.extend(mandatory_order(index, hdu, &header));
+            findings.extend(fixed_format(index, hdu, &header));
+            findings.extend(end_card(&header, end));
+            findings.extend(data_padding(hdu, &data[(offset + header_size)..], offset + header_size));
+            hdus.push(HduReport { index, findings });
+            offset += header_size + hdu.header.data_unit_size().unwrap_or(0);
+        }
+        Ok(VerificationReport { hdus })
+    }
+}
+
+impl Default for Validator {
+    fn default() -> Self {
+        Validator::new()
+    }
+}
+
+/// Verify all HDUs of a file with the built-in rules.
+pub fn verify(fits: &Fits) -> VerificationReport {
+    Validator::new().verify(fits)
+}
+
+/// Verify a single HDU with index `index` with the built-in rules.
+pub fn verify_hdu(index: usize, hdu: &HDU) -> Vec<Finding> {
+    Validator::new().verify_hdu(index, hdu)
+}
+
+/// Verify the bytes of a file with the built-in and the strict rules.
+pub fn verify_strict(data: &[u8]) -> Result<VerificationReport, Error> {
+    Validator::new().verify_strict(data)
+}
+
+// The finding about the first record with this keyword, if there is one.
+fn at_keyword(finding: Finding, hdu: &HDU, keyword: &Keyword) -> Finding {
+    match hdu.header.position_of(keyword) {
+        Some(card) => finding.at(card),
+        None => finding,
+    }
+}
+
+fn first_keyword(index: usize, hdu: &HDU) -> Vec<Finding> {
+    let expected = if index == 0 { Keyword::SIMPLE } else { Keyword::XTENSION };
+    match hdu.header.keyword_records.first() {
+        Some(record) if *record.keyword() == expected => vec!(),
+        _ => vec!(Finding::new(Severity::Error, "first-keyword", format!("the header does not start with {}", expected)).at(0)),
+    }
+}
+
+// Extensions may follow when `EXTEND = T`, none following is allowed.
+fn extend(fits: &Fits) -> Vec<Finding> {
+    let header = &fits.primary_hdu.header;
+    if fits.extensions.is_empty() && header.get_logical(&Keyword::EXTEND) == Ok(true) {
+        let finding = Finding::new(Severity::Info, "extend", "EXTEND = T, but no extensions follow");
+        vec!(at_keyword(finding, &fits.primary_hdu, &Keyword::EXTEND))
+    } else {
+        vec!()
+    }
+}
+
+fn bitpix(_: usize, hdu: &HDU) -> Vec<Finding> {
+    match hdu.header.bitpix() {
+        Ok(_) => vec!(),
+        Err(error) => vec!(at_keyword(Finding::new(Severity::Error, "bitpix", error.to_string()), hdu, &Keyword::BITPIX)),
+    }
+}
+
+fn axes(_: usize, hdu: &HDU) -> Vec<Finding> {
+    match hdu.header.axes() {
+        Ok(_) => vec!(),
+        Err(error) => vec!(Finding::new(Severity::Error, "axes", error.to_string())),
+    }
+}
+
+fn duplicate_keywords(_: usize, hdu: &HDU) -> Vec<Finding> {
+    let records = &hdu.header.keyword_records;
+    records.iter()
+        .enumerate()
+        .filter(|&(_, record)| !matches!(*record.value(), Value::CommentText(_)) && *record.keyword() != Keyword::CONTINUE)
+        .filter(|&(card, record)| records[..card].iter().any(|earlier| earlier.keyword() == record.keyword()))
+        .map(|(card, record)| Finding::new(Severity::Warning, "duplicate-keyword", format!("{} occurs more than once", record.keyword())).at(card))
+        .collect()
+}
+
+fn datasum(_: usize, hdu: &HDU) -> Vec<Finding> {
+    match verify_datasum(hdu) {
+        ChecksumStatus::Valid => vec!(),
+        ChecksumStatus::Missing => vec!(Finding::new(Severity::Info, "datasum", "DATASUM is not present")),
+        ChecksumStatus::Invalid => vec!(at_keyword(
+            Finding::new(Severity::Error, "datasum", "DATASUM is not the sum of the data unit"), hdu, &Keyword::DATASUM)),
+    }
+}
+
+const CARD_SIZE: usize = 80;
+const CARDS_PER_BLOCK: usize = BLOCK_SIZE / CARD_SIZE;
+
+// The cards of a header, including the blank cards after `END`, and the
+// offset of the header in the file.
+struct RawHeader<'a> {
+    cards: &'a [&'a [u8]],
+    offset: usize,
+}
+
+impl<'a> RawHeader<'a> {
+    fn finding(&self, severity: Severity, rule: &str, card: usize, column: usize, message: String) -> Finding {
+        Finding::new(severity, rule, message).at(card).at_offset(self.offset + card * CARD_SIZE + column)
+    }
+}
+
+fn ascii(header: &RawHeader) -> Vec<Finding> {
+    header.cards.iter().enumerate()
+        .filter_map(|(card, bytes)| bytes.iter().position(|byte| !(0x20..=0x7e).contains(byte)).map(|column| (card, column, bytes[column])))
+        .map(|(card, column, byte)| header.finding(Severity::Error, "ascii", card, column, format!("byte 0x{:02x} is not a printable ASCII character", byte)))
+        .collect()
+}
+
+// The keywords that should start a header, in order.
+fn mandatory_keywords(index: usize, hdu: &HDU) -> Vec<Keyword> {
+    let naxis = hdu.header.get_int(&Keyword::NAXIS).unwrap_or(0).clamp(0, 999) as u16;
+    let mut keywords = vec!(if index == 0 { Keyword::SIMPLE } else { Keyword::XTENSION }, Keyword::BITPIX, Keyword::NAXIS);
+    keywords.extend((1..(naxis + 1)).map(Keyword::NAXISn));
+    if index > 0 {
+        keywords.push(Keyword::PCOUNT);
+        keywords.push(Keyword::GCOUNT);
+        if let HduKind::AsciiTable | HduKind::BinTable = hdu.header.kind() {
+            keywords.push(Keyword::TFIELDS);
+        }
+    }
+    keywords
+}
+
+fn mandatory_order(index: usize, hdu: &HDU, header: &RawHeader) -> Vec<Finding> {
+    let records = &hdu.header.keyword_records;
+    mandatory_keywords(index, hdu).into_iter().enumerate()
+        .find(|(card, keyword)| records.get(*card).map(|record| record.keyword()) != Some(keyword))
+        .map(|(card, keyword)| {
+            let found = records.get(card).map(|record| record.keyword().to_string()).unwrap_or_else(|| "nothing".to_string());
+            header.finding(Severity::Error, "mandatory-order", card, 0, format!("expected {}, found {}", keyword, found))
+        })
+        .into_iter()
+        .collect()
+}
+
+fn fixed_format(index: usize, hdu: &HDU, header: &RawHeader) -> Vec<Finding> {
+    let records = &hdu.header.keyword_records;
+    let mut findings = vec!();
+    for (card, keyword) in mandatory_keywords(index, hdu).into_iter().enumerate() {
+        let (record, bytes) = match (records.get(card), header.cards.get(card)) {
+            (Some(record), Some(bytes)) if *record.keyword() == keyword => (record, bytes),
+            _ => break,
+        };
+        let fixed = match *record.value() {
+            Value::Logical(_) => bytes[10..29].iter().all(|byte| *byte == b' ') && (bytes[29] == b'T' || bytes[29] == b'F'),
+            Value::Integer(_) => bytes[29].is_ascii_digit() && bytes[10..30].iter().skip_while(|byte| **byte == b' ').all(|byte| *byte == b'-' || *byte == b'+' || byte.is_ascii_digit()),
+            Value::CharacterString(_) => bytes[10] == b'\'',
+            _ => false,
+        };
+        if &bytes[8..10] != b"= " || !fixed {
+            findings.push(header.finding(Severity::Error, "fixed-format", card, 10, format!("the value of {} is not in fixed format", keyword)));
+        }
+    }
+    findings
+}
+
+fn end_card(header: &RawHeader, end: usize) -> Vec<Finding> {
+    match header.cards[end][3..].iter().position(|byte| *byte != b' ') {
+        Some(column) => vec!(header.finding(Severity::Error, "end", end, column + 3, "END is followed by text".to_string())),
+        None => vec!(),
+    }
+}
+
+fn data_padding(hdu: &HDU, data: &[u8], offset: usize) -> Vec<Finding> {
+    let used = hdu.data().bytes().len();
+    let size = hdu.header.data_unit_size().unwrap_or(0);
+    let fill = hdu.header.kind().fill();
+    match data[used.min(size)..size].iter().position(|byte| *byte != fill) {
+        Some(position) => vec!(Finding::new(Severity::Error, "data-padding", "the data unit is not padded correctly").at_offset(offset + used + position)),
+        None => vec!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use super::super::parser::parse;
+    use super::super::types::{Fits, HDU, Header, KeywordRecord, Keyword, Value};
+    use super::{verify, verify_hdu, verify_strict, Finding, Severity, Validator, VerificationReport};
+
+    #[test]
+    fn k2_file_should_pass() {
+        let data = include_bytes!("../../assets/images/k2-trappist1-unofficial-tpf-long-cadence.fits");
+        let fits = parse(data).unwrap();
+
+        let report = verify(&fits);
+
+        assert_eq!(report.hdus.len(), 3);
+        assert!(report.passes(Severity::Warning), "{:?}", report.findings());
+    }
+
+    #[test]
+    fn findings_should_point_at_the_record_at_fault() {
+        let hdu = HDU::new(Header::new(vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(12i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(0i64), Option::None),
+            KeywordRecord::new(Keyword::OBJECT, Value::CharacterString(Cow::Borrowed("A")), Option::None),
+            KeywordRecord::new(Keyword::OBJECT, Value::CharacterString(Cow::Borrowed("B")), Option::None),
+        )));
+
+        let findings = verify_hdu(0, &hdu);
+
+        assert_eq!(findings, vec!(
+            Finding::new(Severity::Error, "bitpix", "BITPIX 12 is not one of 8, 16, 32, 64, -32 or -64").at(1),
+            Finding::new(Severity::Warning, "duplicate-keyword", "OBJECT occurs more than once").at(4),
+            Finding::new(Severity::Info, "datasum", "DATASUM is not present"),
+        ));
+        assert_eq!(findings[0].to_string(), "error [bitpix] card 1: BITPIX 12 is not one of 8, 16, 32, 64, -32 or -64");
+    }
+
+    #[test]
+    fn report_should_pass_below_its_worst_severity() {
+        let hdu = HDU::new(Header::new(vec!(
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(0i64), Option::None),
+        )));
+        let fits = Fits::new(hdu, vec!());
+
+        let report = verify(&fits);
+
+        assert_eq!(report.worst(), Some(Severity::Error));
+        assert_eq!(report.count(Severity::Error), 1);
+        assert!(!report.passes(Severity::Error));
+        assert!(VerificationReport::default().passes(Severity::Info));
+    }
+
+    #[test]
+    fn custom_rules_should_run_alongside_the_built_in_rules() {
+        let data = include_bytes!("../../assets/images/k2-trappist1-unofficial-tpf-long-cadence.fits");
+        let fits = parse(data).unwrap();
+        let validator = Validator::new()
+            .without_rule("datasum")
+            .header_rule("observer", |header| {
+                match header.get(&Keyword::Custom("OBSERVER".to_string())) {
+                    Some(_) => vec!(),
+                    None => vec!(Finding::new(Severity::Warning, "", "OBSERVER is not present")),
+                }
+            });
+
+        let report = validator.verify(&fits);
+
+        assert_eq!(validator.rules(), vec!("first-keyword", "bitpix", "axes", "duplicate-keyword", "extend", "observer"));
+        assert_eq!(report.count(Severity::Warning), 3);
+        assert_eq!(report.findings()[0], (0, &Finding::new(Severity::Warning, "observer", "OBSERVER is not present")));
+    }
+
+    fn file(cards: &[&str], data: &[u8]) -> Vec<u8> {
+        let mut bytes: Vec<u8> = cards.iter().flat_map(|card| format!("{:80}", card).into_bytes()).collect();
+        bytes.resize(bytes.len().div_ceil(2880) * 2880, b' ');
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    #[test]
+    fn k2_file_should_pass_strict_verification() {
+        let data = include_bytes!("../../assets/images/k2-trappist1-unofficial-tpf-long-cadence.fits");
+
+        let report = verify_strict(data).unwrap();
+
+        assert_eq!(report.hdus.len(), 3);
+        assert!(report.passes(Severity::Warning), "{:?}", report.findings());
+    }
+
+    #[test]
+    fn strict_verification_should_report_byte_offsets() {
+        let mut data = file(&[
+            "SIMPLE  = T",
+            "NAXIS   =                    1",
+            "BITPIX  =                    8",
+            "NAXIS1  =                    2",
+            "END     here",
+        ], &[1, 2]);
+        data.resize(2 * 2880, 0);
+        data[2880 + 100] = 7;
+
+        let report = Validator::empty().verify_strict(&data).unwrap();
+
+        assert_eq!(report.findings().into_iter().map(|(_, finding)| finding.clone()).collect::<Vec<_>>(), vec!(
+            Finding::new(Severity::Error, "mandatory-order", "expected BITPIX, found NAXIS").at(1).at_offset(80),
+            Finding::new(Severity::Error, "fixed-format", "the value of SIMPLE is not in fixed format").at(0).at_offset(10),
+            Finding::new(Severity::Error, "end", "END is followed by text").at(4).at_offset(328),
+            Finding::new(Severity::Error, "data-padding", "the data unit is not padded correctly").at_offset(2980),
+        ));
+    }
+
+    #[test]
+    fn strict_verification_should_count_continue_cards_in_offsets() {
+        let object = format!("OBJECT  = '{}&'", "a".repeat(67));
+        let first = format!("CONTINUE  '{}&'", "b".repeat(67));
+        let second = format!("CONTINUE  '{}'", "c".repeat(66));
+        let data = file(&[
+            "SIMPLE  =                    T",
+            "BITPIX  =                    8",
+            "NAXIS   =                    0",
+            &object,
+            &first,
+            &second,
+            "TELESCOP= 'Kepler'",
+            "TELESCOP= 'K2'",
+            "END",
+        ], &[]);
+        let fits = parse(&data).unwrap();
+
+        let report = verify_strict(&data).unwrap();
+
+        assert_eq!(fits.primary_hdu.header.get_str(&Keyword::OBJECT).map(str::len), Ok(200));
+        assert_eq!(report.findings()[0], (0, &Finding::new(Severity::Warning, "duplicate-keyword", "TELESCOP occurs more than once").at(5).at_offset(560)));
+    }
+
+    #[test]
+    fn extend_without_extensions_should_be_noted() {
+        let data = file(&[
+            "SIMPLE  =                    T",
+            "BITPIX  =                    8",
+            "NAXIS   =                    0",
+            "EXTEND  =                    T",
+            "END",
+        ], &[]);
+        let fits = parse(&data).unwrap();
+
+        let report = Validator::empty().file_rule("extend", super::extend).verify(&fits);
+
+        assert!(fits.extensions.is_empty());
+        assert_eq!(report.findings(), vec!((0, &Finding::new(Severity::Info, "extend", "EXTEND = T, but no extensions follow").at(3))));
+        assert!(verify_strict(&data).unwrap().passes(Severity::Warning));
+    }
+
+    #[test]
+    fn strict_verification_should_report_non_ascii_bytes() {
+        let mut data = file(&[
+            "SIMPLE  =                    T / conforms",
+            "BITPIX  =                    8",
+            "NAXIS   =                    0",
+            "END",
+        ], &[]);
+        data[35] = 0x7f;
+
+        let report = Validator::empty().verify_strict(&data).unwrap();
+
+        assert_eq!(report.findings()[0].1.to_string(), "error [ascii] card 0 byte 35: byte 0x7f is not a printable ASCII character");
+        assert!(verify_strict(&data[0..100]).is_err());
+    }
+}
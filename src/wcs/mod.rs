@@ -0,0 +1,340 @@
+//! The wcs module maps pixel coordinates to world coordinates, following the
+//! World Coordinate System keywords of a header.
+//!
+//! Pixel coordinates are counted from 1, like `CRPIXn`. A pixel `p` is first
+//! mapped linearly onto intermediate coordinates
+//!
+//! > x<sub>i</sub> = Σ<sub>j</sub> M<sub>ij</sub> (p<sub>j</sub> - CRPIX<sub>j</sub>)
+//!
+//! where `M` is `CDi_j` when present, `CDELTi` times `PCi_j` otherwise, with
+//! `CROTA2` as the rotation of old headers. Linear axes add `CRVALi`. A pair
+//! of celestial axes with the `TAN` projection, e.g. `RA---TAN` and
+//! `DEC--TAN`, is projected gnomonically around `CRVALi`, with the native pole
+//! at the default `LONPOLE` of 180°. World coordinates are in degrees.
+//!
+//! The WCS keywords are read as `Keyword::Custom`.
+
+use super::types::{Header, Keyword, ValueRetrievalError};
+
+/// The World Coordinate System of an HDU.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Wcs {
+    /// The type of every axis, `CTYPEn`. Empty for an axis without type.
+    pub ctype: Vec<String>,
+    /// The world coordinate of the reference point, `CRVALn`.
+    pub crval: Vec<f64>,
+    /// The pixel coordinate of the reference point, `CRPIXn`.
+    pub crpix: Vec<f64>,
+    /// The linear transformation from pixel offsets to intermediate
+    /// coordinates, by row, i.e. `matrix[i][j]` is `CDi_j` counting from 0.
+    pub matrix: Vec<Vec<f64>>,
+}
+
+/// Problems that occur when reading or applying a WCS.
+#[derive(Debug, PartialEq)]
+pub enum WcsError {
+    /// A WCS keyword has a value of the wrong type.
+    InvalidValue {
+        /// The name of the keyword.
+        keyword: String,
+        /// What is wrong with the value.
+        error: ValueRetrievalError,
+    },
+    /// The number of coordinates does not match the number of axes.
+    DimensionMismatch {
+        /// The number of axes.
+        expected: usize,
+        /// The number of coordinates given.
+        actual: usize,
+    },
+    /// The linear transformation can not be inverted.
+    SingularMatrix,
+    /// A celestial axis uses a projection other than `TAN`.
+    UnsupportedProjection(String),
+    /// A world coordinate lies on the hemisphere that does not project.
+    OutsideProjection,
+}
+
+impl Wcs {
+    /// Read the WCS of a header. The number of axes is `WCSAXES`, or `NAXIS`
+    /// when it is not present. Missing keywords get their default value.
+    pub fn from_header(header: &Header) -> Result<Wcs, WcsError> {
+        let axes = match optional(header, "WCSAXES", Header::get_int)? {
+            Some(axes) => axes,
+            None => header.get_int(&Keyword::NAXIS).unwrap_or(0),
+        }.max(0) as usize;
+        let mut ctype = vec!();
+        let mut crval = vec!();
+        let mut crpix = vec!();
+        let mut cdelt = vec!();
+        for i in 1..(axes + 1) {
+            ctype.push(optional(header, &format!("CTYPE{}", i), Header::get_str)?.unwrap_or("").trim_end().to_string());
+            crval.push(real(header, &format!("CRVAL{}", i), 0f64)?);
+            crpix.push(real(header, &format!("CRPIX{}", i), 0f64)?);
+            cdelt.push(real(header, &format!("CDELT{}", i), 1f64)?);
+        }
+        let matrix = if has_any(header, axes, "CD") {
+            element_matrix(header, axes, "CD", 0f64)?
+        } else if has_any(header, axes, "PC") || axes < 2 || optional(header, "CROTA2", Header::get_real)?.is_none() {
+            let pc = element_matrix(header, axes, "PC", 1f64)?;
+            pc.iter().enumerate().map(|(i, row)| row.iter().map(|pc_ij| cdelt[i] * pc_ij).collect()).collect()
+        } else {
+            let rotation = real(header, "CROTA2", 0f64)?.to_radians();
+            let mut matrix = identity(axes);
+            for (i, row) in matrix.iter_mut().enumerate() {
+                row[i] = cdelt[i];
+            }
+            matrix[0][0] = cdelt[0] * rotation.cos();
+            matrix[0][1] = -cdelt[1] * rotation.sin();
+            matrix[1][0] = cdelt[0] * rotation.sin();
+            matrix[1][1] = cdelt[1] * rotation.cos();
+            matrix
+        };
+        Ok(Wcs { ctype, crval, crpix, matrix })
+    }
+
+    /// The number of axes.
+    pub fn axes(&self) -> usize {
+        self.ctype.len()
+    }
+
+    /// The indices of the longitude and latitude axes, when both are present.
+    pub fn celestial_axes(&self) -> Option<(usize, usize)> {
+        let longitude = self.ctype.iter().position(|ctype| is_longitude(ctype))?;
+        let latitude = self.ctype.iter().position(|ctype| is_latitude(ctype))?;
+        Some((longitude, latitude))
+    }
+
+    /// The world coordinates of a pixel.
+    pub fn pixel_to_world(&self, pixel: &[f64]) -> Result<Vec<f64>, WcsError> {
+        self.check_dimension(pixel)?;
+        let offset: Vec<f64> = pixel.iter().zip(self.crpix.iter()).map(|(p, r)| p - r).collect();
+        let mut world: Vec<f64> = self.matrix.iter()
+            .map(|row| row.iter().zip(offset.iter()).map(|(m, o)| m * o).sum::<f64>())
+            .collect();
+        match self.projection()? {
+            Some((lon, lat)) => {
+                let (alpha, delta) = deproject(world[lon], world[lat], self.crval[lon], self.crval[lat]);
+                for (i, value) in world.iter_mut().enumerate() {
+                    *value += self.crval[i];
+                }
+                world[lon] = alpha;
+                world[lat] = delta;
+            }
+            None => for (i, value) in world.iter_mut().enumerate() {
+                *value += self.crval[i];
+            },
+        }
+        Ok(world)
+    }
+
+    /// The pixel coordinates of a world coordinate.
+    pub fn world_to_pixel(&self, world: &[f64]) -> Result<Vec<f64>, WcsError> {
+        self.check_dimension(world)?;
+        let mut intermediate: Vec<f64> = world.iter().zip(self.crval.iter()).map(|(w, r)| w - r).collect();
+        if let Some((lon, lat)) = self.projection()? {
+            let (x, y) = project(world[lon], world[lat], self.crval[lon], self.crval[lat])
+                .ok_or(WcsError::OutsideProjection)?;
+            intermediate[lon] = x;
+            intermediate[lat] = y;
+        }
+        let offset = solve(self.matrix.clone(), intermediate).ok_or(WcsError::SingularMatrix)?;
+        Ok(offset.iter().zip(self.crpix.iter()).map(|(o, r)| o + r).collect())
+    }
+
+    fn check_dimension(&self, coordinates: &[f64]) -> Result<(), WcsError> {
+        if coordinates.len() != self.axes() {
+            return Err(WcsError::DimensionMismatch { expected: self.axes(), actual: coordinates.len() })
+        }
+        Ok(())
+    }
+
+    // The celestial axes, when they are projected.
+    fn projection(&self) -> Result<Option<(usize, usize)>, WcsError> {
+        match self.celestial_axes() {
+            Some((lon, lat)) => {
+                for i in [lon, lat] {
+                    let code = projection_code(&self.ctype[i]);
+                    if code != "TAN" {
+                        return Err(WcsError::UnsupportedProjection(code.to_string()))
+                    }
+                }
+                Ok(Some((lon, lat)))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+fn is_longitude(ctype: &str) -> bool {
+    ctype.starts_with("RA--") || ctype.get(1..4) == Some("LON")
+}
+
+fn is_latitude(ctype: &str) -> bool {
+    ctype.starts_with("DEC-") || ctype.get(1..4) == Some("LAT")
+}
+
+fn projection_code(ctype: &str) -> &str {
+    ctype.get(5..).unwrap_or("")
+}
+
+// Gnomonic projection of `(alpha, delta)` around `(alpha0, delta0)`, in degrees.
+fn project(alpha: f64, delta: f64, alpha0: f64, delta0: f64) -> Option<(f64, f64)> {
+    let (alpha, delta, alpha0, delta0) = (alpha.to_radians(), delta.to_radians(), alpha0.to_radians(), delta0.to_radians());
+    let d = delta.sin() * delta0.sin() + delta.cos() * delta0.cos() * (alpha - alpha0).cos();
+    if d <= 0f64 {
+        return None
+    }
+    let xi = delta.cos() * (alpha - alpha0).sin() / d;
+    let eta = (delta.sin() * delta0.cos() - delta.cos() * delta0.sin() * (alpha - alpha0).cos()) / d;
+    Some((xi.to_degrees(), eta.to_degrees()))
+}
+
+// The inverse of `project`.
+fn deproject(xi: f64, eta: f64, alpha0: f64, delta0: f64) -> (f64, f64) {
+    let (xi, eta, delta0) = (xi.to_radians(), eta.to_radians(), delta0.to_radians());
+    let denominator = delta0.cos() - eta * delta0.sin();
+    let alpha = alpha0 + xi.atan2(denominator).to_degrees();
+    let delta = (delta0.sin() + eta * delta0.cos()).atan2(xi.hypot(denominator)).to_degrees();
+    (alpha.rem_euclid(360f64), delta)
+}
+
+// Solve `matrix * x = b` by Gaussian elimination with partial pivoting.
+fn solve(mut matrix: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    for column in 0..n {
+        let pivot = (column..n).max_by(|&i, &j| matrix[i][column].abs().total_cmp(&matrix[j][column].abs()))?;
+        if matrix[pivot][column] == 0f64 {
+            return None
+        }
+        matrix.swap(column, pivot);
+        b.swap(column, pivot);
+        let pivot_row = matrix[column].clone();
+        for row in (column + 1)..n {
+            let factor = matrix[row][column] / pivot_row[column];
+            for (value, pivot_value) in matrix[row].iter_mut().zip(pivot_row.iter()).skip(column) {
+                *value -= factor * pivot_value;
+            }
+            b[row] -= factor * b[column];
+        }
+    }
+    let mut x = vec!(0f64; n);
+    for row in (0..n).rev() {
+        let sum: f64 = ((row + 1)..n).map(|k| matrix[row][k] * x[k]).sum();
+        x[row] = (b[row] - sum) / matrix[row][row];
+    }
+    Some(x)
+}
+
+fn identity(n: usize) -> Vec<Vec<f64>> {
+    (0..n).map(|i| (0..n).map(|j| if i == j { 1f64 } else { 0f64 }).collect()).collect()
+}
+
+fn has_any(header: &Header, axes: usize, prefix: &str) -> bool {
+    (1..(axes + 1)).any(|i| (1..(axes + 1)).any(|j| header.get(&element(prefix, i, j)).is_some()))
+}
+
+// The matrix of `PREFIXi_j` keywords, with `diagonal` as default on the diagonal.
+fn element_matrix(header: &Header, axes: usize, prefix: &str, diagonal: f64) -> Result<Vec<Vec<f64>>, WcsError> {
+    (1..(axes + 1)).map(|i| {
+        (1..(axes + 1)).map(|j| {
+            let default = if i == j { diagonal } else { 0f64 };
+            real(header, &format!("{}{}_{}", prefix, i, j), default)
+        }).collect()
+    }).collect()
+}
+
+fn element(prefix: &str, i: usize, j: usize) -> Keyword {
+    Keyword::Custom(format!("{}{}_{}", prefix, i, j))
+}
+
+fn real(header: &Header, name: &str, default: f64) -> Result<f64, WcsError> {
+    optional(header, name, Header::get_real).map(|value| value.unwrap_or(default))
+}
+
+// The value of the keyword `name`, or `None` when it is not present.
+fn optional<'h, 'a, T, F>(header: &'h Header<'a>, name: &str, get: F) -> Result<Option<T>, WcsError>
+    where F: Fn(&'h Header<'a>, &Keyword) -> Result<T, ValueRetrievalError> {
+    let keyword = name.parse::<Keyword>().unwrap_or_else(|_| Keyword::Custom(name.to_string()));
+    match get(header, &keyword) {
+        Ok(value) => Ok(Some(value)),
+        Err(ValueRetrievalError::KeywordNotPresent) => Ok(None),
+        Err(error) => Err(WcsError::InvalidValue { keyword: name.to_string(), error }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use super::super::types::{Header, KeywordRecord, Keyword, Value, ValueRetrievalError};
+    use super::{Wcs, WcsError};
+
+    fn header(records: Vec<(&str, Value<'static>)>) -> Header<'static> {
+        let mut keyword_records = vec!(KeywordRecord::new(Keyword::NAXIS, Value::Integer(2i64), Option::None));
+        for (name, value) in records {
+            keyword_records.push(KeywordRecord::new(Keyword::Custom(name.to_string()), value, Option::None));
+        }
+        Header::new(keyword_records)
+    }
+
+    fn assert_close(actual: &[f64], expected: &[f64]) {
+        for (a, e) in actual.iter().zip(expected.iter()) {
+            assert!((a - e).abs() < 1e-9, "{:?} is not close to {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn linear_axes_should_use_cdelt_and_pc() {
+        let wcs = Wcs::from_header(&header(vec!(
+            ("CRPIX1", Value::Real(10f64)), ("CRVAL1", Value::Real(100f64)), ("CDELT1", Value::Real(2f64)),
+            ("CRPIX2", Value::Real(1f64)), ("CRVAL2", Value::Real(0f64)), ("CDELT2", Value::Real(0.5f64)),
+            ("PC1_2", Value::Real(1f64)),
+        ))).unwrap();
+
+        assert_eq!(wcs.matrix, vec!(vec!(2f64, 2f64), vec!(0f64, 0.5f64)));
+        assert_close(&wcs.pixel_to_world(&[11f64, 3f64]).unwrap(), &[106f64, 1f64]);
+        assert_close(&wcs.world_to_pixel(&[106f64, 1f64]).unwrap(), &[11f64, 3f64]);
+    }
+
+    #[test]
+    fn crota2_should_rotate_the_axes() {
+        let wcs = Wcs::from_header(&header(vec!(
+            ("CDELT1", Value::Real(1f64)), ("CDELT2", Value::Real(1f64)), ("CROTA2", Value::Real(90f64)),
+        ))).unwrap();
+
+        assert_close(&wcs.pixel_to_world(&[1f64, 0f64]).unwrap(), &[0f64, 1f64]);
+    }
+
+    #[test]
+    fn tan_projection_should_round_trip() {
+        let wcs = Wcs::from_header(&header(vec!(
+            ("CTYPE1", Value::CharacterString(Cow::Borrowed("RA---TAN"))),
+            ("CTYPE2", Value::CharacterString(Cow::Borrowed("DEC--TAN"))),
+            ("CRPIX1", Value::Real(50f64)), ("CRPIX2", Value::Real(50f64)),
+            ("CRVAL1", Value::Real(346.6f64)), ("CRVAL2", Value::Real(-5.04f64)),
+            ("CD1_1", Value::Real(-0.001f64)), ("CD2_2", Value::Real(0.001f64)),
+        ))).unwrap();
+
+        assert_eq!(wcs.celestial_axes(), Some((0, 1)));
+        assert_close(&wcs.pixel_to_world(&[50f64, 50f64]).unwrap(), &[346.6f64, -5.04f64]);
+        let world = wcs.pixel_to_world(&[10f64, 80f64]).unwrap();
+        assert!(world[0] > 346.6f64 && world[1] > -5.04f64);
+        assert_close(&wcs.world_to_pixel(&world).unwrap(), &[10f64, 80f64]);
+        assert_eq!(wcs.world_to_pixel(&[166.6f64, 5.04f64]), Err(WcsError::OutsideProjection));
+    }
+
+    #[test]
+    fn problems_should_be_reported() {
+        let sin = Wcs::from_header(&header(vec!(
+            ("CTYPE1", Value::CharacterString(Cow::Borrowed("RA---SIN"))),
+            ("CTYPE2", Value::CharacterString(Cow::Borrowed("DEC--SIN"))),
+        ))).unwrap();
+        let singular = Wcs::from_header(&header(vec!(("CDELT1", Value::Real(0f64))))).unwrap();
+
+        assert_eq!(sin.pixel_to_world(&[1f64, 1f64]), Err(WcsError::UnsupportedProjection("SIN".to_string())));
+        assert_eq!(sin.pixel_to_world(&[1f64]), Err(WcsError::DimensionMismatch { expected: 2, actual: 1 }));
+        assert_eq!(singular.world_to_pixel(&[1f64, 1f64]), Err(WcsError::SingularMatrix));
+        assert_eq!(Wcs::from_header(&header(vec!(("CRVAL1", Value::Logical(true))))),
+                   Err(WcsError::InvalidValue { keyword: "CRVAL1".to_string(), error: ValueRetrievalError::NotAReal }));
+    }
+}
@@ -0,0 +1,823 @@
+//! The writer module is responsible for writing FITS files.
+//!
+//! Headers are written as 80 character cards, values in fixed format, and both
+//! headers and data arrays are padded to a multiple of 2880 bytes.
+//!
+//! `WriterOptions` changes the layout of cards, e.g. the column of comments or
+//! the lowercase `e` of exponents, for tools that are picky about it.
+//!
+//! `rewrite` replaces a file with an edited `Fits`, e.g. after adding or
+//! removing extensions with `Fits::push_extension` or `Fits::remove_extension`.
+//! `write_inserted` inserts an extension into the bytes of a file, copying the
+//! HDUs it does not touch as they are.
+
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use super::checksum::compute_checksum;
+use super::error::Error;
+use super::parser::parse_with_info;
+use super::types::{Fits, HDU, Header, KeywordRecord, Keyword, Value, VersionPolicy};
+
+/// The size in bytes of a FITS block.
+const BLOCK_SIZE: usize = 2880;
+/// The size in bytes of a card, i.e. a single keyword record.
+const CARD_SIZE: usize = 80;
+/// The number of characters of a long string that fit in a card, leaving room
+/// for the keyword, the quotes and the `&` that announces a continuation.
+const CHUNK_SIZE: usize = 67;
+/// The number of characters of commentary text that fit in a card, i.e.
+/// columns 9 through 80.
+const COMMENTARY_SIZE: usize = 72;
+
+/// Problems that could occur when writing a FITS structure.
+#[derive(Debug)]
+pub enum WriteError {
+    /// The underlying writer failed.
+    Io(io::Error),
+    /// The name of the keyword of a record is longer than 8 characters.
+    KeywordTooLong(String),
+    /// The name of the keyword of a record would be read back as a different
+    /// keyword, e.g. a custom keyword named `SIMPLE`.
+    AmbiguousKeyword(String),
+    /// A mandatory keyword is placed where the standard does not allow it.
+    MisplacedKeyword(Keyword),
+    /// The keyword and value of a record do not fit in a single card.
+    CardTooLong(String),
+    /// The real or complex value of a record with this keyword is not a
+    /// finite number, which FITS can not express.
+    NonFiniteValue(Keyword),
+    /// The data unit of the HDU with this index, 0 being the primary HDU or an
+    /// HDU written by itself, does not have the size that its header describes.
+    DataSizeMismatch(usize),
+}
+
+impl Display for WriteError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            WriteError::Io(ref error) => write!(f, "{}", error),
+            WriteError::KeywordTooLong(ref name) => write!(f, "keyword {} is longer than 8 characters", name),
+            WriteError::MisplacedKeyword(ref keyword) => write!(f, "keyword {} is mandatory and can not be placed freely", keyword),
+            WriteError::AmbiguousKeyword(ref name) => write!(f, "keyword \"{}\" would be read back as a different keyword", name),
+            WriteError::CardTooLong(ref card) => write!(f, "record does not fit in a card: \"{}\"", card.trim_end()),
+            WriteError::NonFiniteValue(ref keyword) => write!(f, "the value of {} is not a finite number", keyword),
+            WriteError::DataSizeMismatch(index) => write!(f, "the data unit of HDU {} does not have the size its header describes", index),
+        }
+    }
+}
+
+impl error::Error for WriteError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            WriteError::Io(ref error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for WriteError {
+    fn from(error: io::Error) -> Self {
+        WriteError::Io(error)
+    }
+}
+
+/// Options that determine the layout of the cards that are written. The
+/// default writes values in fixed format with uppercase exponents, and
+/// comments where they were read from or right after the value.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct WriterOptions {
+    /// The offset in the card of the `/` that starts a comment, e.g. 31 for
+    /// the column after a fixed-format value. A value that does not leave room
+    /// is followed by a single space instead. `None` keeps the column a record
+    /// was read with.
+    pub comment_column: Option<usize>,
+    /// Right justify numbers and logicals to column 30, as the fixed format
+    /// prescribes. Otherwise they directly follow `= `, in free format.
+    pub fixed_format: bool,
+    /// Write the exponents of real numbers with an uppercase `E`.
+    pub uppercase_exponent: bool,
+}
+
+impl Default for WriterOptions {
+    fn default() -> Self {
+        WriterOptions { comment_column: Option::None, fixed_format: true, uppercase_exponent: true }
+    }
+}
+
+impl WriterOptions {
+    /// Write a `Fits` structure, the primary HDU followed by all extensions.
+    pub fn write_fits<W: Write>(&self, writer: &mut W, fits: &Fits) -> Result<(), WriteError> {
+        for (index, hdu) in fits.iter_hdus().enumerate() {
+            self.write_hdu_at(writer, hdu, index)?;
+        }
+        Ok(())
+    }
+
+    /// Write a single HDU, its header followed by its data array. The data
+    /// unit is padded as the kind of HDU prescribes, see `HduKind::fill`. An
+    /// HDU whose data unit does not have the size its header describes is
+    /// refused with `DataSizeMismatch(0)`, rather than written as a truncated
+    /// file.
+    pub fn write_hdu<W: Write>(&self, writer: &mut W, hdu: &HDU) -> Result<(), WriteError> {
+        self.write_hdu_at(writer, hdu, 0)
+    }
+
+    // Write the HDU with this index, which is reported when its data unit has the wrong size.
+    fn write_hdu_at<W: Write>(&self, writer: &mut W, hdu: &HDU, index: usize) -> Result<(), WriteError> {
+        let data = hdu.data().bytes();
+        if hdu.header.data_unit_size().is_some_and(|size| size != data.len() + padding(data.len())) {
+            return Err(WriteError::DataSizeMismatch(index))
+        }
+        self.write_header(writer, &hdu.header)?;
+        if !data.is_empty() {
+            writer.write_all(data)?;
+            writer.write_all(&vec![hdu.header.kind().fill(); padding(data.len())])?;
+        }
+        Ok(())
+    }
+
+    /// Write a header, terminated by an `END` card and padded with blank records.
+    pub fn write_header<W: Write>(&self, writer: &mut W, header: &Header) -> Result<(), WriteError> {
+        let mut size = 0;
+        for record in &header.keyword_records {
+            if *record.keyword() == Keyword::END {
+                continue
+            }
+            for card in self.cards(record)? {
+                writer.write_all(card.as_bytes())?;
+                size += CARD_SIZE;
+            }
+        }
+        writer.write_all(format!("{:<80}", Keyword::END).as_bytes())?;
+        size += CARD_SIZE;
+        writer.write_all(&vec![b' '; padding(size)])?;
+        Ok(())
+    }
+
+    /// Format a keyword record as an 80 character card, see `card`.
+    pub fn card(&self, record: &KeywordRecord) -> Result<String, WriteError> {
+        let name = keyword_name(record.keyword())?;
+        let finite = match *record.value() {
+            Value::Real(r) => r.is_finite(),
+            Value::Complex((re, im)) => re.is_finite() && im.is_finite(),
+            _ => true,
+        };
+        if !finite {
+            return Err(WriteError::NonFiniteValue(record.keyword().clone()))
+        }
+        let text = match *record.value() {
+            Value::CommentText(ref text) => format!("{:<8}{}", name, text),
+            _ if *record.keyword() == Keyword::CONTINUE => format!("{:<8}  {}", name, self.value(record.value())),
+            ref value => format!("{:<8}= {}", name, self.value(value)),
+        };
+        if text.trim_end().len() > CARD_SIZE {
+            return Err(WriteError::CardTooLong(text))
+        }
+        Ok(self.finish(text, record))
+    }
+
+    /// Format a keyword record as one or more 80 character cards, see `cards`.
+    pub fn cards(&self, record: &KeywordRecord) -> Result<Vec<String>, WriteError> {
+        if let Value::CommentText(ref text) = *record.value() {
+            return wrap(text, COMMENTARY_SIZE).into_iter()
+                .map(|line| self.card(&KeywordRecord::new(record.keyword().clone(), Value::CommentText(Cow::Borrowed(line)), Option::None)))
+                .collect()
+        }
+        let chunks = match *record.value() {
+            Value::CharacterString(ref s) if escaped_length(s) > CHUNK_SIZE + 1 => chunks(s),
+            _ => return self.card(record).map(|card| vec!(card)),
+        };
+        let name = keyword_name(record.keyword())?;
+        let last = chunks.len() - 1;
+        Ok(chunks.iter()
+            .enumerate()
+            .map(|(index, chunk)| {
+                let mut card = if index == 0 {
+                    format!("{:<8}= '{}", name, chunk)
+                } else {
+                    format!("{:<8}  '{}", Keyword::CONTINUE, chunk)
+                };
+                if index < last {
+                    card.push_str("&'");
+                    format!("{:<80}", card)
+                } else {
+                    card.push('\'');
+                    self.finish(card, record)
+                }
+            })
+            .collect())
+    }
+
+    fn value(&self, value: &Value) -> String {
+        let text = if self.fixed_format { value.fixed_format() } else { value.to_string() };
+        match *value {
+            Value::Real(_) | Value::Complex(_) if !self.uppercase_exponent => text.replace('E', "e"),
+            _ => text,
+        }
+    }
+
+    // Add the comment of `record` to `card`, truncate it to a single card and pad it.
+    fn finish(&self, mut card: String, record: &KeywordRecord) -> String {
+        if let Some(comment) = record.comment() {
+            let width = match self.comment_column.or_else(|| record.comment_column()) {
+                Some(column) if card.len() < column => column,
+                _ => card.len() + 1,
+            };
+            card = format!("{:<width$}/ {}", card, comment, width = width);
+        }
+        let card: String = card.chars().take(CARD_SIZE).collect();
+        format!("{:<80}", card)
+    }
+}
+
+/// Write a `Fits` structure, the primary HDU followed by all extensions.
+///
+/// ```
+/// use std::borrow::Cow;
+/// use fits_rs::builder::HeaderBuilder;
+/// use fits_rs::parser::parse;
+/// use fits_rs::types::{Bitpix, Fits, HDU, Keyword, Value};
+/// use fits_rs::writer::write_fits;
+///
+/// let header = HeaderBuilder::primary(Bitpix::U8, &[4, 2])
+///     .record(Keyword::OBJECT, Value::CharacterString(Cow::Borrowed("TRAPPIST-1")), Option::None)
+///     .build()?;
+/// let fits = Fits::new(HDU::with_owned_data(header, vec!(0, 1, 2, 3, 4, 5, 6, 7)), vec!());
+///
+/// let mut bytes = vec!();
+/// write_fits(&mut bytes, &fits)?;
+///
+/// assert_eq!(bytes.len(), 2 * 2880);
+/// let written = parse(&bytes)?;
+/// assert_eq!(written.primary_hdu.header.get_str(&Keyword::OBJECT), Ok("TRAPPIST-1"));
+/// assert_eq!(written.primary_hdu.data().bytes(), &[0, 1, 2, 3, 4, 5, 6, 7]);
+/// # Ok::<(), fits_rs::error::Error>(())
+/// ```
+pub fn write_fits<W: Write>(writer: &mut W, fits: &Fits) -> Result<(), WriteError> {
+    WriterOptions::default().write_fits(writer, fits)
+}
+
+/// Replace the file at `path` with `fits`. The file is written next to it and
+/// then renamed, so that the original is left intact when writing fails. An
+/// HDU whose data unit does not have the size its header describes is
+/// refused, so that the file consists of the blocks its headers describe.
+pub fn rewrite<P: AsRef<Path>>(path: P, fits: &Fits) -> Result<(), WriteError> {
+    for (index, hdu) in fits.iter_hdus().enumerate() {
+        let size = hdu.data().bytes().len();
+        if hdu.header.data_unit_size() != Some(size + padding(size)) {
+            return Err(WriteError::DataSizeMismatch(index))
+        }
+    }
+    let path = path.as_ref();
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let result = write_file(Path::new(&temporary), fits)
+        .and_then(|_| Ok(fs::rename(&temporary, path)?));
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    result
+}
+
+fn write_file(path: &Path, fits: &Fits) -> Result<(), WriteError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_fits(&mut writer, fits)?;
+    writer.into_inner().map_err(|error| error.into_error())?.sync_all()?;
+    Ok(())
+}
+
+/// Write `data`, the bytes of a file, with `hdu` inserted as extension
+/// `index`, 0 being the first extension, like
+/// `Fits::insert_extension_versioned` does. The bytes of an HDU whose header
+/// does not change are copied as they are, as are the data units of the
+/// others, so that only the inserted HDU and the changed headers are written
+/// anew. A changed header that held a `CHECKSUM` gets a new one.
+pub fn write_inserted<'a, W: Write>(writer: &mut W, data: &'a [u8], index: usize, hdu: HDU<'a>, policy: VersionPolicy) -> Result<(), Error> {
+    let (mut fits, infos) = parse_with_info(data)?;
+    let originals: Vec<Header> = fits.iter_hdus().map(|hdu| hdu.header.clone()).collect();
+    fits.insert_extension_versioned(index, hdu, policy)?;
+    let hdus = Some(&mut fits.primary_hdu).into_iter().chain(fits.extensions.iter_mut());
+    for (position, edited) in hdus.enumerate() {
+        let original = match position.cmp(&(index + 1)) {
+            Ordering::Less => position,
+            Ordering::Equal => {
+                WriterOptions::default().write_hdu_at(writer, edited, position)?;
+                continue
+            },
+            Ordering::Greater => position - 1,
+        };
+        let info = &infos[original];
+        if edited.header == originals[original] {
+            writer.write_all(&data[info.header_range.clone()])?;
+        } else {
+            if originals[original].get(&Keyword::CHECKSUM).is_some() {
+                compute_checksum(edited)?;
+            }
+            write_header(writer, &edited.header)?;
+        }
+        writer.write_all(&data[info.data_range.clone()])?;
+    }
+    Ok(())
+}
+
+/// Write a single HDU, its header followed by its data array.
+pub fn write_hdu<W: Write>(writer: &mut W, hdu: &HDU) -> Result<(), WriteError> {
+    WriterOptions::default().write_hdu(writer, hdu)
+}
+
+/// Write a header, terminated by an `END` card and padded with blank records.
+pub fn write_header<W: Write>(writer: &mut W, header: &Header) -> Result<(), WriteError> {
+    WriterOptions::default().write_header(writer, header)
+}
+
+/// Format a keyword record as an 80 character card, see the `Display` of
+/// `KeywordRecord`.
+///
+/// The value is written in fixed format, i.e. strings start in column 11 and
+/// other values are right justified to column 30. A comment that does not fit
+/// is truncated. The text of commentary records starts in column 9. A value or
+/// commentary text that does not fit is refused, as is a real or complex value
+/// that is not finite, e.g. `NaN`.
+pub fn card(record: &KeywordRecord) -> Result<String, WriteError> {
+    WriterOptions::default().card(record)
+}
+
+// The name of a keyword, as long as the parser reads it back as the same keyword.
+fn keyword_name(keyword: &Keyword) -> Result<String, WriteError> {
+    let name = keyword.to_string();
+    if name.len() > 8 {
+        return Err(WriteError::KeywordTooLong(name))
+    }
+    if !keyword.round_trips() {
+        return Err(WriteError::AmbiguousKeyword(name))
+    }
+    Ok(name)
+}
+
+/// Format a keyword record as one or more 80 character cards.
+///
+/// A character string that does not fit in a single card is split over
+/// `CONTINUE` cards. Every part but the last ends in `&`, and the comment is
+/// written on the last card. Commentary text that does not fit is wrapped
+/// over several cards with the same keyword.
+pub fn cards(record: &KeywordRecord) -> Result<Vec<String>, WriteError> {
+    WriterOptions::default().cards(record)
+}
+
+/// Split text into lines of at most `width` characters. A line is broken at
+/// the last space that fits, which is dropped, or inside a word that is longer
+/// than a line. Spaces at the start of the text are kept.
+pub fn wrap(text: &str, width: usize) -> Vec<&str> {
+    let mut lines = vec!();
+    let mut rest = text;
+    while let Some((limit, _)) = rest.char_indices().nth(width) {
+        let start = rest.len() - rest.trim_start().len();
+        let (line, next) = if rest[limit..].starts_with(' ') {
+            (&rest[..limit], &rest[(limit + 1)..])
+        } else {
+            match rest[..limit].rfind(' ') {
+                Some(space) if space > start => (&rest[..space], &rest[(space + 1)..]),
+                _ => (&rest[..limit], &rest[limit..]),
+            }
+        };
+        lines.push(line);
+        rest = next;
+    }
+    lines.push(rest);
+    lines
+}
+
+fn escaped_length(s: &str) -> usize {
+    s.chars().map(|c| if c == '\'' { 2 } else { c.len_utf8() }).sum()
+}
+
+fn chunks(s: &str) -> Vec<String> {
+    let mut chunks = vec!();
+    let mut chunk = String::new();
+    for c in s.chars() {
+        let escaped = if c == '\'' { "''".to_string() } else { c.to_string() };
+        if chunk.len() + escaped.len() > CHUNK_SIZE {
+            chunks.push(chunk);
+            chunk = String::new();
+        }
+        chunk.push_str(&escaped);
+    }
+    chunks.push(chunk);
+    chunks
+}
+
+fn padding(size: usize) -> usize {
+    (BLOCK_SIZE - size % BLOCK_SIZE) % BLOCK_SIZE
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use nom::IResult;
+    use super::super::parser::fits;
+    use super::super::error::Error;
+    use super::super::types::{Bitpix, ExtensionError, Fits, HDU, Header, KeywordRecord, Keyword, Value, VersionPolicy};
+    use std::env;
+    use std::fs;
+    use super::super::parser::{parse, parse_with_info};
+    use super::super::checksum::{compute_checksum, verify_all, ChecksumReport, ChecksumStatus};
+    use super::super::validator::{verify_strict, Severity};
+    use super::super::testgen;
+    use super::{rewrite, write_fits, write_hdu, write_header, write_inserted, card, cards, wrap, WriteError, WriterOptions};
+
+    #[test]
+    fn card_should_format_a_character_string() {
+        let record = KeywordRecord::new(Keyword::OBJECT, Value::CharacterString(Cow::Borrowed("EPIC 200164267")), Option::Some("string version of target id"));
+
+        assert_eq!(card(&record).unwrap(),
+                   "OBJECT  = 'EPIC 200164267'     / string version of target id                    ");
+    }
+
+    #[test]
+    fn card_should_pad_short_character_strings() {
+        let record = KeywordRecord::new(Keyword::MISSION, Value::CharacterString(Cow::Borrowed("K2")), Option::None);
+
+        assert_eq!(card(&record).unwrap(),
+                   "MISSION = 'K2      '                                                            ");
+    }
+
+    #[test]
+    fn card_should_escape_quotes_in_character_strings() {
+        let record = KeywordRecord::new(Keyword::OBJECT, Value::CharacterString(Cow::Borrowed("O'HARA")), Option::None);
+
+        assert_eq!(card(&record).unwrap(),
+                   "OBJECT  = 'O''HARA '                                                            ");
+    }
+
+    #[test]
+    fn card_should_right_justify_numbers_and_logicals() {
+        let data = [
+            (KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+             "SIMPLE  =                    T                                                  "),
+            (KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(2932i64), Option::None),
+             "NAXIS1  =                 2932                                                  "),
+            (KeywordRecord::new(Keyword::EQUINOX, Value::Real(2000f64), Option::None),
+             "EQUINOX =               2000.0                                                  "),
+            (KeywordRecord::new(Keyword::EQUINOX, Value::Real(1e-5f64), Option::None),
+             "EQUINOX =               1.0E-5                                                  "),
+        ];
+
+        for (record, expected) in data.iter() {
+            assert_eq!(card(record).unwrap(), *expected);
+        }
+    }
+
+    #[test]
+    fn options_should_change_the_layout_of_cards() {
+        let record = KeywordRecord::new(Keyword::EQUINOX, Value::Real(1e-5f64), Option::Some("small"));
+        let free = WriterOptions { comment_column: Option::Some(40), fixed_format: false, uppercase_exponent: false };
+
+        assert_eq!(free.card(&record).unwrap(),
+                   "EQUINOX = 1.0e-5                        / small                                 ");
+        assert_eq!(WriterOptions { comment_column: Option::Some(31), ..WriterOptions::default() }.card(&record).unwrap(),
+                   "EQUINOX =               1.0E-5 / small                                          ");
+        assert_eq!(card(&record).unwrap(), WriterOptions::default().card(&record).unwrap());
+    }
+
+    #[test]
+    fn card_should_write_commentary_from_column_nine() {
+        let data = [
+            (KeywordRecord::new(Keyword::COMMENT, Value::CommentText(Cow::Borrowed("  indented")), Option::None),
+             "COMMENT   indented                                                              "),
+            (KeywordRecord::new(Keyword::HISTORY, Value::CommentText(Cow::Borrowed("created")), Option::None),
+             "HISTORY created                                                                 "),
+            (KeywordRecord::new(Keyword::BlankField, Value::CommentText(Cow::Borrowed("")), Option::None),
+             "                                                                                "),
+        ];
+
+        for (record, expected) in data.iter() {
+            assert_eq!(card(record).unwrap(), *expected);
+        }
+    }
+
+    #[test]
+    fn cards_should_split_long_strings_over_continue_cards() {
+        let long = "a string that is far too long to fit in a single card, so it is continued on the next card";
+        let record = KeywordRecord::new(Keyword::OBJECT, Value::CharacterString(Cow::Borrowed(long)), Option::Some("comment"));
+
+        let result = cards(&record).unwrap();
+
+        assert_eq!(result, vec!(
+            "OBJECT  = 'a string that is far too long to fit in a single card, so it is con&'".to_string(),
+            "CONTINUE  'tinued on the next card' / comment                                   ".to_string(),
+        ));
+    }
+
+    #[test]
+    fn wrap_should_break_lines_at_spaces() {
+        assert_eq!(wrap("the quick brown fox", 9), vec!("the quick", "brown fox"));
+        assert_eq!(wrap("  indented text", 10), vec!("  indented", "text"));
+        assert_eq!(wrap("unbreakable words", 5), vec!("unbre", "akabl", "e", "words"));
+        assert_eq!(wrap("short", 72), vec!("short"));
+    }
+
+    #[test]
+    fn cards_should_wrap_long_commentary_text() {
+        let long = "  this history is indented and far too long to fit in a single card, so it is wrapped";
+        let header = Header::new(vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::HISTORY, Value::CommentText(Cow::Borrowed(long)), Option::None),
+        ));
+        let mut buffer: Vec<u8> = vec!();
+
+        write_header(&mut buffer, &header).unwrap();
+
+        match super::super::parser::header(&buffer) {
+            IResult::Done(_, h) => assert_eq!(h.history(), vec!(
+                "  this history is indented and far too long to fit in a single card, so",
+                "it is wrapped")),
+            _ => panic!("Did not expect the written header to fail"),
+        }
+    }
+
+    #[test]
+    fn continued_strings_should_parse_to_the_same_record() {
+        let long = "a string with 'quotes' that is far too long to fit in a single card, so it is continued";
+        let header = Header::new(vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::OBJECT, Value::CharacterString(Cow::Borrowed(long)), Option::None),
+        ));
+        let mut buffer: Vec<u8> = vec!();
+
+        write_header(&mut buffer, &header).unwrap();
+
+        match super::super::parser::header(&buffer) {
+            IResult::Done(_, h) => assert_eq!(h.get_str(&Keyword::OBJECT), Ok(long)),
+            _ => panic!("Did not expect the written header to fail"),
+        }
+    }
+
+    #[test]
+    fn card_should_truncate_long_comments() {
+        let record = KeywordRecord::new(Keyword::DATE, Value::CharacterString(Cow::Borrowed("2017-03-08")), Option::Some("a comment that is far too long to fit in the remainder of the card"));
+
+        let result = card(&record).unwrap();
+
+        assert_eq!(result.len(), 80);
+        assert!(result.starts_with("DATE    = '2017-03-08'         / a comment that is far too long"));
+    }
+
+    #[test]
+    fn card_should_refuse_keywords_longer_than_eight_characters() {
+        let record = KeywordRecord::new(Keyword::Custom("LONGKEYWORD".to_string()), Value::Undefined, Option::None);
+
+        match card(&record) {
+            Err(WriteError::KeywordTooLong(name)) => assert_eq!(name, "LONGKEYWORD"),
+            _ => panic!("Expected a keyword too long error"),
+        }
+    }
+
+    #[test]
+    fn card_should_refuse_values_that_are_not_finite() {
+        let nan = KeywordRecord::new(Keyword::EQUINOX, Value::Real(f64::NAN), Option::None);
+        let infinite = KeywordRecord::new(Keyword::OBJECT, Value::Complex((1f64, f64::INFINITY)), Option::None);
+
+        assert!(matches!(card(&nan), Err(WriteError::NonFiniteValue(Keyword::EQUINOX))));
+        assert!(matches!(cards(&infinite), Err(WriteError::NonFiniteValue(Keyword::OBJECT))));
+    }
+
+    #[test]
+    fn card_should_refuse_keywords_that_are_read_back_differently() {
+        let record = KeywordRecord::new(Keyword::Custom("NAXIS1".to_string()), Value::Integer(3i64), Option::None);
+
+        match card(&record) {
+            Err(WriteError::AmbiguousKeyword(name)) => assert_eq!(name, "NAXIS1"),
+            _ => panic!("Expected an ambiguous keyword error"),
+        }
+    }
+
+    #[test]
+    fn card_should_write_unknown_keywords_by_name() {
+        let record = KeywordRecord::new(Keyword::Custom("CTYPE1".to_string()), Value::CharacterString(Cow::Borrowed("RA---TAN")), Option::None);
+
+        assert_eq!(card(&record).unwrap(),
+                   "CTYPE1  = 'RA---TAN'                                                            ");
+    }
+
+    #[test]
+    fn write_header_should_end_with_an_end_card_and_pad_to_a_block() {
+        let header = Header::new(vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(0i64), Option::None),
+            KeywordRecord::new(Keyword::END, Value::Undefined, Option::None),
+        ));
+        let mut buffer: Vec<u8> = vec!();
+
+        write_header(&mut buffer, &header).unwrap();
+
+        assert_eq!(buffer.len(), 2880);
+        assert_eq!(&buffer[240..243], b"END");
+        assert!(buffer[243..].iter().all(|b| *b == b' '));
+    }
+
+    #[test]
+    fn written_fits_should_parse_to_the_same_structure() {
+        let data = include_bytes!("../../assets/images/k2-trappist1-unofficial-tpf-long-cadence.fits");
+        let original = match fits(data) {
+            IResult::Done(_, f) => f,
+            _ => panic!("Did not expect the test file to fail"),
+        };
+        let primary = Fits::new(original.primary_hdu, vec!());
+        let mut buffer: Vec<u8> = vec!();
+
+        write_fits(&mut buffer, &primary).unwrap();
+
+        assert_eq!(buffer.len() % 2880, 0);
+        match fits(&buffer) {
+            IResult::Done(tail, f) => {
+                assert_eq!(f, primary);
+                assert_eq!(tail.len(), 0);
+            },
+            _ => panic!("Did not expect the written file to fail"),
+        }
+    }
+
+    #[test]
+    fn written_file_with_all_extensions_should_parse_to_the_same_structure() {
+        let data = include_bytes!("../../assets/images/k2-trappist1-unofficial-tpf-long-cadence.fits");
+        let original = match fits(data) {
+            IResult::Done(_, f) => f,
+            _ => panic!("Did not expect the test file to fail"),
+        };
+        let mut buffer: Vec<u8> = vec!();
+
+        write_fits(&mut buffer, &original).unwrap();
+
+        match fits(&buffer) {
+            IResult::Done(tail, f) => {
+                assert_eq!(f, original);
+                assert_eq!(tail.len(), 0);
+            },
+            _ => panic!("Did not expect the written file to fail"),
+        }
+    }
+
+    #[test]
+    fn ascii_tables_should_be_padded_with_blanks() {
+        let header = Header::new(vec!(
+            KeywordRecord::new(Keyword::XTENSION, Value::CharacterString(Cow::Borrowed("TABLE")), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(2i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(5i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(2u16), Value::Integer(1i64), Option::None),
+            KeywordRecord::new(Keyword::PCOUNT, Value::Integer(0i64), Option::None),
+            KeywordRecord::new(Keyword::GCOUNT, Value::Integer(1i64), Option::None),
+            KeywordRecord::new(Keyword::TFIELDS, Value::Integer(1i64), Option::None),
+            KeywordRecord::new(Keyword::TFORMn(1u16), Value::CharacterString(Cow::Borrowed("A5")), Option::None),
+        ));
+        let mut table = HDU::with_data(header, b"hello");
+        compute_checksum(&mut table).unwrap();
+        let mut buffer: Vec<u8> = vec!();
+
+        write_fits(&mut buffer, &Fits::new(HDU::empty_primary(), vec!(table))).unwrap();
+
+        assert_eq!(buffer.len(), 3*2880);
+        assert_eq!(&buffer[(2*2880)..(2*2880 + 7)], b"hello  ");
+        assert_eq!(buffer[buffer.len() - 1], b' ');
+        assert!(verify_strict(&buffer).unwrap().passes(Severity::Warning));
+        assert_eq!(verify_all(&buffer).unwrap()[1], ChecksumReport { datasum: ChecksumStatus::Valid, checksum: ChecksumStatus::Valid });
+    }
+
+    #[test]
+    fn written_data_array_should_be_padded_to_a_block() {
+        let data = [1u8, 2u8, 3u8];
+        let header = Header::new(vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(1i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(3i64), Option::None),
+        ));
+        let original = Fits::new(HDU::with_data(header, &data), vec!());
+        let mut buffer: Vec<u8> = vec!();
+
+        write_fits(&mut buffer, &original).unwrap();
+
+        assert_eq!(buffer.len(), 2*2880);
+        assert_eq!(&buffer[2880..2884], &[1u8, 2u8, 3u8, 0u8]);
+        match fits(&buffer) {
+            IResult::Done(_, f) => assert_eq!(f, original),
+            _ => panic!("Did not expect the written file to fail"),
+        }
+    }
+
+    #[test]
+    fn hdus_without_the_data_their_header_describes_should_be_refused() {
+        let header = Header::new(vec!(
+            KeywordRecord::new(Keyword::SIMPLE, Value::Logical(true), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(1i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(3i64), Option::None),
+        ));
+        let image = Header::new(vec!(
+            KeywordRecord::new(Keyword::XTENSION, Value::CharacterString(Cow::Borrowed("IMAGE")), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(1i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(3i64), Option::None),
+            KeywordRecord::new(Keyword::PCOUNT, Value::Integer(0i64), Option::None),
+            KeywordRecord::new(Keyword::GCOUNT, Value::Integer(1i64), Option::None),
+        ));
+        let fits = Fits::new(HDU::with_data(header.clone(), &[1u8, 2u8, 3u8]), vec!(HDU::with_data(image, &[1u8, 2u8])));
+        let mut buffer: Vec<u8> = vec!();
+
+        assert!(matches!(write_hdu(&mut buffer, &HDU::new(header)), Err(WriteError::DataSizeMismatch(0))));
+        assert!(buffer.is_empty());
+        assert!(matches!(write_fits(&mut buffer, &fits), Err(WriteError::DataSizeMismatch(1))));
+    }
+
+    #[test]
+    fn rewrite_should_replace_a_file_with_an_edited_fits() {
+        let data = include_bytes!("../../assets/images/k2-trappist1-unofficial-tpf-long-cadence.fits");
+        let path = env::temp_dir().join(format!("fits-rs-rewrite-{}.fits", std::process::id()));
+        fs::write(&path, &data[..]).unwrap();
+        let original = fs::read(&path).unwrap();
+        let mut edited = parse(&original).unwrap();
+
+        let removed = edited.remove_extension_by_name("TARGETTABLES");
+        edited.push_extension(HDU::new(Header::new(vec!(
+            KeywordRecord::new(Keyword::XTENSION, Value::CharacterString(Cow::Borrowed("IMAGE")), Option::None),
+            KeywordRecord::new(Keyword::BITPIX, Value::Integer(8i64), Option::None),
+            KeywordRecord::new(Keyword::NAXIS, Value::Integer(1i64), Option::None),
+            KeywordRecord::new(Keyword::NAXISn(1u16), Value::Integer(10i64), Option::None),
+        ))));
+        let refused = rewrite(&path, &edited);
+        edited.remove_extension(1);
+        rewrite(&path, &edited).unwrap();
+
+        assert!(removed.is_some());
+        assert!(matches!(refused, Err(WriteError::DataSizeMismatch(2))));
+        let rewritten = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(rewritten.len() % 2880, 0);
+        assert_eq!(parse(&rewritten).unwrap(), edited);
+    }
+
+    #[test]
+    fn inserted_extensions_should_leave_the_other_hdus_as_they_are() {
+        let named = |name, version| vec!(
+            KeywordRecord::new(Keyword::EXTNAME, Value::CharacterString(Cow::Borrowed(name)), Option::None),
+            KeywordRecord::new(Keyword::EXTVER, Value::Integer(version), Option::None),
+        );
+        let data = testgen::file(testgen::image(Bitpix::I16, &[10], vec!()).unwrap(), vec!(
+            testgen::image_extension(Bitpix::I16, &[3], named("SCI", 1i64)).unwrap(),
+            testgen::image_extension(Bitpix::F32, &[4], named("ERR", 1i64)).unwrap(),
+            testgen::image_extension(Bitpix::I16, &[5], named("SCI", 2i64)).unwrap(),
+        )).unwrap();
+        let inserted = || testgen::image_extension(Bitpix::U8, &[2], named("SCI", 2i64)).unwrap();
+        let (_, infos) = parse_with_info(&data).unwrap();
+
+        let mut written = vec!();
+        write_inserted(&mut written, &data, 2, inserted(), VersionPolicy::Shift).unwrap();
+
+        let mut expected = parse(&data).unwrap();
+        expected.insert_extension_versioned(2, inserted(), VersionPolicy::Shift).unwrap();
+        let mut rewritten = vec!();
+        write_fits(&mut rewritten, &expected).unwrap();
+        assert_eq!(written, rewritten);
+        assert_eq!(written.len(), data.len() + 2 * 2880);
+        assert_eq!(&written[..infos[2].data_range.end], &data[..infos[2].data_range.end]);
+        assert_eq!(expected.hdu(3).map(HDU::version), Some(2i64));
+        assert_eq!(expected.hdu(4).map(HDU::version), Some(3i64));
+    }
+
+    #[test]
+    fn inserted_extensions_should_keep_checksums_valid() {
+        let named = |name, version| vec!(
+            KeywordRecord::new(Keyword::EXTNAME, Value::CharacterString(Cow::Borrowed(name)), Option::None),
+            KeywordRecord::new(Keyword::EXTVER, Value::Integer(version), Option::None),
+        );
+        let mut fits = Fits::new(testgen::image(Bitpix::I16, &[10], vec!()).unwrap(), vec!());
+        fits.push_extension(testgen::image_extension(Bitpix::I16, &[3], named("SCI", 1i64)).unwrap());
+        fits.push_extension(testgen::image_extension(Bitpix::I16, &[5], named("SCI", 2i64)).unwrap());
+        compute_checksum(&mut fits.primary_hdu).unwrap();
+        for hdu in fits.extensions.iter_mut() {
+            compute_checksum(hdu).unwrap();
+        }
+        let mut data = vec!();
+        write_fits(&mut data, &fits).unwrap();
+        let inserted = || testgen::image_extension(Bitpix::U8, &[2], named("SCI", 1i64)).unwrap();
+
+        let mut written = vec!();
+        write_inserted(&mut written, &data, 0, inserted(), VersionPolicy::Shift).unwrap();
+        let refused = write_inserted(&mut vec!(), &data, 3, inserted(), VersionPolicy::Shift);
+
+        let reports = verify_all(&written).unwrap();
+        assert_eq!(reports.len(), 4);
+        assert!(reports.iter().all(ChecksumReport::is_valid));
+        assert_eq!(reports[1].checksum, ChecksumStatus::Missing);
+        assert!(reports[2..].iter().all(|report| report.checksum == ChecksumStatus::Valid));
+        assert!(matches!(refused, Err(Error::Extension(ExtensionError::OutOfRange { index: 3, count: 2 }))));
+    }
+}
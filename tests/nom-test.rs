@@ -1,3 +1,5 @@
+#![allow(clippy::char_lit_as_u8)]
+
 #[macro_use]
 extern crate nom;
 